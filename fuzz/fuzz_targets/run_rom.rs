@@ -0,0 +1,32 @@
+//! Arbitrary code from 0x0100 on, run for a few frames: no opcode stream
+//! or IO write it makes may panic the core. The whole input is also parsed
+//! as a header first.
+
+#![no_main]
+
+use gameboy::GameBoy;
+use gameboy::cartridge::{Cartridge, CartridgeHeader};
+use libfuzzer_sys::fuzz_target;
+
+/// Enough for games' init code to reach the PPU, APU and timer
+const FRAMES: usize = 4;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = CartridgeHeader::from_rom(data);
+
+    let mut rom = vec![0; 0x8000];
+    let len = data.len().min(rom.len() - 0x0100);
+    rom[0x0100..0x0100 + len].copy_from_slice(&data[..len]);
+    // Whatever the code, a header that loads: ROM only or MBC1, 32 KB, 8 KB RAM
+    rom[0x0147] &= 0x03;
+    rom[0x0148] = 0x00;
+    rom[0x0149] = 0x02;
+    let Ok(cartridge) = Cartridge::from_rom(rom) else {
+        return;
+    };
+    let mut game = GameBoy::new();
+    game.memory.load_cartridge(cartridge);
+    for _ in 0..FRAMES {
+        game.finish_frame();
+    }
+});
@@ -0,0 +1,181 @@
+use crate::audio;
+use crate::playtime::CYCLES_PER_SECOND;
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+/// Output samples one step is spread across
+const WIDTH: usize = 16;
+/// Kernels per output sample, for steps landing between two samples
+const PHASES: usize = 32;
+/// Passband as a fraction of the output's Nyquist frequency, leaving room
+/// for the window's roll-off before anything can alias
+const CUTOFF: f64 = 0.9;
+
+/// Band-limited resampling in the style of `blip_buf`: the APU's output only
+/// changes in steps, so rather than pick one level per output sample (which
+/// aliases every harmonic above half the sample rate back into the audible
+/// range), each step is drawn as a band-limited step of `WIDTH` samples and
+/// the output is their running sum. This delays the output by `WIDTH / 2`
+/// samples, a third of a millisecond.
+pub(super) struct BlipBuffer {
+    sample_rate: u32,
+    /// Cycles times the sample rate since the last sample, so the rate need
+    /// not divide the CPU clock
+    clock: u64,
+    /// The level the last step left the output at
+    level: audio::Frame,
+    /// Change in level from each output sample to the next, starting with
+    /// the next one
+    deltas: VecDeque<[f64; 2]>,
+    /// Output level as of the last sample produced
+    sum: [f64; 2],
+}
+
+impl BlipBuffer {
+    pub(super) fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1),
+            clock: 0,
+            level: [0.0; 2],
+            deltas: VecDeque::from([[0.0; 2]; WIDTH]),
+            sum: [0.0; 2],
+        }
+    }
+
+    pub(super) fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The output is `level` from now on
+    #[allow(clippy::float_cmp)] // Any change at all is a step
+    pub(super) fn set_level(&mut self, level: audio::Frame) {
+        if level == self.level {
+            return;
+        }
+        let step = [
+            f64::from(level[0] - self.level[0]),
+            f64::from(level[1] - self.level[1]),
+        ];
+        self.level = level;
+        let phase = usize::try_from(self.clock * PHASES as u64 / CYCLES_PER_SECOND)
+            .map_or(0, |phase| phase.min(PHASES - 1));
+        for (delta, tap) in self.deltas.iter_mut().zip(kernel()[phase]) {
+            delta[0] += step[0] * tap;
+            delta[1] += step[1] * tap;
+        }
+    }
+
+    /// Let `cycles` pass at the current level, appending the samples that
+    /// fall due to `out`
+    #[allow(clippy::cast_possible_truncation)] // Samples are f32 for the host
+    pub(super) fn advance(&mut self, cycles: u8, out: &mut Vec<audio::Frame>) {
+        self.clock += u64::from(cycles) * u64::from(self.sample_rate);
+        while self.clock >= CYCLES_PER_SECOND {
+            self.clock -= CYCLES_PER_SECOND;
+            let delta = self.deltas.pop_front().unwrap_or_default();
+            self.deltas.push_back([0.0; 2]);
+            self.sum[0] += delta[0];
+            self.sum[1] += delta[1];
+            out.push([self.sum[0] as f32, self.sum[1] as f32]);
+        }
+    }
+
+    /// Drop any steps still to be heard and start again from silence
+    pub(super) fn clear(&mut self) {
+        *self = Self::new(self.sample_rate);
+    }
+}
+
+/// For each phase, how a step of 1 landing that far into an output sample
+/// is spread over the next `WIDTH` samples: a Blackman-windowed sinc,
+/// normalised so the taps sum to exactly the step
+#[allow(clippy::cast_precision_loss)] // Both are small
+fn kernel() -> &'static [[f64; WIDTH]; PHASES] {
+    static KERNEL: OnceLock<[[f64; WIDTH]; PHASES]> = OnceLock::new();
+    KERNEL.get_or_init(|| {
+        let half = WIDTH as f64 / 2.0;
+        let mut kernel = [[0.0; WIDTH]; PHASES];
+        let mut offset = 0.0;
+        for taps in &mut kernel {
+            // Distance from the step to the middle of each sample's interval
+            let mut x = 0.5 - half - offset;
+            for tap in taps.iter_mut() {
+                *tap = if x.abs() < half {
+                    let phase = std::f64::consts::PI * x / half;
+                    let window = 0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                    sinc(CUTOFF * x) * window
+                } else {
+                    0.0
+                };
+                x += 1.0;
+            }
+            let total: f64 = taps.iter().sum();
+            for tap in taps.iter_mut() {
+                *tap /= total;
+            }
+            offset += 1.0 / PHASES as f64;
+        }
+        kernel
+    })
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Toggle between -0.5 and 0.5 every `half_period` cycles for one
+    /// emulated second
+    fn square(half_period: u64) -> Vec<audio::Frame> {
+        let mut blip = BlipBuffer::new(48_000);
+        let mut out = Vec::new();
+        let mut high = false;
+        for cycle in (0..CYCLES_PER_SECOND).step_by(4) {
+            if cycle % half_period < 4 {
+                high = !high;
+                blip.set_level(if high { [0.5; 2] } else { [-0.5; 2] });
+            }
+            blip.advance(4, &mut out);
+        }
+        out
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn rms(samples: &[audio::Frame]) -> f32 {
+        let power: f32 = samples.iter().map(|s| s[0] * s[0]).sum();
+        (power / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn settles_on_each_level() {
+        let mut blip = BlipBuffer::new(44_100);
+        let mut out = Vec::new();
+        blip.set_level([0.25, -1.0]);
+        for _ in 0..1000 {
+            blip.advance(4, &mut out);
+        }
+        assert_eq!(out.len(), 42);
+        assert!(out[0][0].abs() < 1e-3, "Delayed by half the kernel");
+        let last = out[out.len() - 1];
+        assert!((last[0] - 0.25).abs() < 1e-6 && (last[1] + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tones_above_nyquist_do_not_alias() {
+        // 2048 Hz, well inside the passband, comes through at full strength
+        let audible = square(1024);
+        assert!(rms(&audible[WIDTH..]) > 0.45);
+        // ~30 kHz has nothing below 24 kHz, where decimating it would
+        // fold it back down at close to full strength
+        let inaudible = square(70);
+        assert!(rms(&inaudible[WIDTH..]) < 0.1);
+    }
+}
@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+/// One of the APU's four sound channels, numbered as in the register names:
+/// channel 1 is NR10-NR14 and channel 4 is NR41-NR44
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Square1,
+    Square2,
+    Wave,
+    Noise,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 4] = [
+        Channel::Square1,
+        Channel::Square2,
+        Channel::Wave,
+        Channel::Noise,
+    ];
+
+    /// 1 to 4
+    pub fn number(self) -> u8 {
+        match self {
+            Channel::Square1 => 1,
+            Channel::Square2 => 2,
+            Channel::Wave => 3,
+            Channel::Noise => 4,
+        }
+    }
+
+    /// Lowercase name, as accepted by `from_str` along with the number
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Square1 => "square1",
+            Channel::Square2 => "square2",
+            Channel::Wave => "wave",
+            Channel::Noise => "noise",
+        }
+    }
+
+    /// The channel after this one, or `None` after the last
+    pub fn next(self) -> Option<Channel> {
+        match self {
+            Channel::Square1 => Some(Channel::Square2),
+            Channel::Square2 => Some(Channel::Wave),
+            Channel::Wave => Some(Channel::Noise),
+            Channel::Noise => None,
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Channel::ALL
+            .into_iter()
+            .find(|channel| {
+                channel.name().eq_ignore_ascii_case(name) || channel.number().to_string() == name
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Unknown channel: {name} (expected 1 to 4, square1, square2, wave or noise)"
+                )
+            })
+    }
+}
+
+/// Which channels the mixer lets through, for listening to one part of a
+/// tune or debugging one channel's emulation. A channel left out still runs
+/// and reports itself playing in NR52; only its output is dropped. Like the
+/// host volume, this is not the game's and is not saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channels {
+    heard: [bool; 4],
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self { heard: [true; 4] }
+    }
+}
+
+impl Channels {
+    pub fn is_enabled(self, channel: Channel) -> bool {
+        self.heard[usize::from(channel.number() - 1)]
+    }
+
+    pub fn set_enabled(&mut self, channel: Channel, enabled: bool) {
+        self.heard[usize::from(channel.number() - 1)] = enabled;
+    }
+
+    /// Flip a channel, returning whether it is now heard
+    pub fn toggle(&mut self, channel: Channel) -> bool {
+        let enabled = !self.is_enabled(channel);
+        self.set_enabled(channel, enabled);
+        enabled
+    }
+
+    /// Hear only `channel`, or every channel for `None`
+    pub fn solo(&mut self, channel: Option<Channel>) {
+        for other in Channel::ALL {
+            self.set_enabled(other, channel.is_none_or(|channel| channel == other));
+        }
+    }
+
+    /// The one channel heard, if only one is
+    pub fn soloed(self) -> Option<Channel> {
+        let mut heard = Channel::ALL
+            .into_iter()
+            .filter(|&channel| self.is_enabled(channel));
+        match (heard.next(), heard.next()) {
+            (Some(channel), None) => Some(channel),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solo_hears_one_channel_until_cleared() {
+        let mut channels = Channels::default();
+        assert_eq!(channels.soloed(), None);
+        channels.solo(Some(Channel::Wave));
+        assert_eq!(channels.soloed(), Some(Channel::Wave));
+        assert!(!channels.is_enabled(Channel::Square1));
+
+        assert!(channels.toggle(Channel::Noise));
+        assert_eq!(channels.soloed(), None);
+        channels.solo(None);
+        assert_eq!(channels, Channels::default());
+    }
+
+    #[test]
+    fn parses_numbers_and_names() {
+        assert_eq!("2".parse(), Ok(Channel::Square2));
+        assert_eq!("Noise".parse(), Ok(Channel::Noise));
+        assert!("5".parse::<Channel>().is_err());
+    }
+}
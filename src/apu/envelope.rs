@@ -0,0 +1,74 @@
+use crate::savestate::{StateReader, StateWriter};
+
+/// Volume envelope (`NRx2`): the volume a channel starts at when triggered
+/// and how it fades in or out from there.
+///
+/// Byte format VVVV DPPP: starting volume, direction (1 = louder) and pace
+/// in 64 Hz ticks per step, 0 for a constant volume.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Envelope {
+    /// `NRx2` as written
+    register: u8,
+    /// Current volume, 0 to 15
+    pub(super) volume: u8,
+    /// Ticks left until the next step
+    timer: u8,
+}
+
+impl Envelope {
+    pub(super) fn read(&self) -> u8 {
+        self.register
+    }
+
+    /// Takes effect on the next trigger
+    pub(super) fn write(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    /// Whether the channel's DAC is on: it is off while the top five bits
+    /// of `NRx2` are all clear, which also silences the channel
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    pub(super) fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.pace();
+    }
+
+    /// Frame sequencer step 7, 64 times a second
+    pub(super) fn clock(&mut self) {
+        let pace = self.pace();
+        if pace == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = pace;
+        if self.register & 0x08 != 0 {
+            self.volume = (self.volume + 1).min(15);
+        } else {
+            self.volume = self.volume.saturating_sub(1);
+        }
+    }
+
+    fn pace(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    pub(super) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.register);
+        state.write_u8(self.volume);
+        state.write_u8(self.timer);
+    }
+
+    /// Out-of-range fields, as in a corrupt state, are brought back in range
+    pub(super) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.register = state.read_u8()?;
+        self.volume = state.read_u8()? & 0x0F;
+        self.timer = state.read_u8()?.min(self.pace());
+        Ok(())
+    }
+}
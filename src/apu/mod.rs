@@ -0,0 +1,536 @@
+mod blip;
+mod channels;
+mod envelope;
+mod square;
+mod sweep;
+
+use crate::audio::{self, Volume};
+use crate::interrupts::Interrupts;
+use crate::memory::Memory;
+use crate::savestate::{StateReader, StateWriter};
+use crate::tick::{self, Tickable};
+use blip::BlipBuffer;
+use square::Square;
+
+pub use channels::{Channel, Channels};
+
+/// Samples per second produced unless the frontend asks for another rate
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+/// Cycles between frame sequencer steps, 512 times a second
+// TODO: Step on the falling edge of DIV bit 12, so DIV writes move it
+const SEQUENCER_CYCLES: u16 = 8192;
+
+/// Output level of one channel at full volume, so all four at once reach
+/// full scale
+const CHANNEL_LEVEL: f32 = 0.25;
+
+/// NR50 and NR51 as the boot ROM leaves them: full volume on both sides,
+/// channels 1 and 2 on both and 3 and 4 on the left
+const BOOT_NR50: u8 = 0x77;
+const BOOT_NR51: u8 = 0xF3;
+
+/// The audio processing unit: the channels, the frame sequencer clocking
+/// them, and the mixer (NR50-NR52) that pans them to the left and right
+/// and sets the master volume. Only the two square wave channels are
+/// emulated so far: channel 1 (NR10-NR14), with a frequency sweep, and
+/// channel 2 (NR21-NR24).
+pub struct Apu {
+    channel1: Square,
+    channel2: Square,
+    /// NR52 bit 7. While off, the registers read as cleared and ignore
+    /// writes.
+    powered: bool,
+    /// NR50: bits 4-6 the left volume, 0-2 the right. The cartridge VIN
+    /// input (bits 3 and 7) has no cartridge using it to play.
+    master_volume: u8,
+    /// NR51: bit `n + 4` sends channel `n + 1` left, bit `n` right
+    panning: u8,
+    /// The host's volume and mute, applied after NR50
+    volume: Volume,
+    /// The channels the host wants to hear
+    channels: Channels,
+    /// Cycles into the current frame sequencer step
+    sequencer_cycles: u16,
+    /// Frame sequencer step, 0 to 7, which clocks the length counters,
+    /// sweep and envelopes
+    sequencer_step: u8,
+    /// Turns the mixer's output into samples at the host's rate
+    blip: BlipBuffer,
+    /// Produced since the last `take_samples`
+    samples: Vec<audio::Frame>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            channel1: Square::new(true),
+            channel2: Square::new(false),
+            powered: true,
+            master_volume: BOOT_NR50,
+            panning: BOOT_NR51,
+            volume: Volume::default(),
+            channels: Channels::default(),
+            sequencer_cycles: 0,
+            sequencer_step: 0,
+            blip: BlipBuffer::new(DEFAULT_SAMPLE_RATE),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.blip.sample_rate()
+    }
+
+    /// Produce `rate` samples per emulated second from now on, to match the
+    /// host audio device
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.blip = BlipBuffer::new(rate);
+    }
+
+    pub fn volume(&self) -> Volume {
+        self.volume
+    }
+
+    /// Master volume and mute, applied as samples are mixed so every sink
+    /// hears the same level
+    pub fn set_volume(&mut self, volume: Volume) {
+        self.volume = volume;
+    }
+
+    pub fn volume_mut(&mut self) -> &mut Volume {
+        &mut self.volume
+    }
+
+    /// Which channels are mixed in (see `Channels`)
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    pub fn set_channels(&mut self, channels: Channels) {
+        self.channels = channels;
+    }
+
+    /// Mix `channel` in or leave it out, from the next sample. It keeps
+    /// running either way, so NR52 and `channel1_enabled` are unaffected.
+    pub fn set_channel_enabled(&mut self, channel: Channel, enabled: bool) {
+        self.channels.set_enabled(channel, enabled);
+    }
+
+    pub fn channel_enabled(&self, channel: Channel) -> bool {
+        self.channels.is_enabled(channel)
+    }
+
+    /// Samples produced since the last call
+    pub fn take_samples(&mut self) -> Vec<audio::Frame> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        if self.powered {
+            self.channel1.tick(cycles);
+            self.channel2.tick(cycles);
+
+            self.sequencer_cycles += u16::from(cycles);
+            if self.sequencer_cycles >= SEQUENCER_CYCLES {
+                self.sequencer_cycles -= SEQUENCER_CYCLES;
+                self.step_sequencer();
+            }
+        }
+
+        self.blip.advance(cycles, &mut self.samples);
+        let level = self.mix();
+        self.blip.set_level(level);
+    }
+
+    fn step_sequencer(&mut self) {
+        if self.sequencer_step.is_multiple_of(2) {
+            self.channel1.clock_length();
+            self.channel2.clock_length();
+        }
+        if self.sequencer_step == 2 || self.sequencer_step == 6 {
+            self.channel1.clock_sweep();
+        }
+        if self.sequencer_step == 7 {
+            self.channel1.clock_envelope();
+            self.channel2.clock_envelope();
+        }
+        self.sequencer_step = (self.sequencer_step + 1) % 8;
+    }
+
+    /// One stereo sample: each channel through its DAC, where level 0 to 15
+    /// maps to 1.0 down to -1.0, summed on the sides NR51 sends it to and
+    /// scaled by that side's NR50 volume, then the host volume. Channels the
+    /// host has left out are skipped.
+    fn mix(&self) -> audio::Frame {
+        let mut mixed = [0.0; 2];
+        for (channel, output) in [self.channel1.output(), self.channel2.output()]
+            .into_iter()
+            .enumerate()
+        {
+            if !self.channels.is_enabled(Channel::ALL[channel]) {
+                continue;
+            }
+            let Some(level) = output else {
+                continue;
+            };
+            let analog = (1.0 - f32::from(level) / 7.5) * CHANNEL_LEVEL;
+            if self.panning & 0x10 << channel != 0 {
+                mixed[0] += analog;
+            }
+            if self.panning & 1 << channel != 0 {
+                mixed[1] += analog;
+            }
+        }
+        let gain = self.volume.gain();
+        let side = |volume: u8| f32::from(volume + 1) / 8.0 * gain;
+        [
+            mixed[0] * side(self.master_volume >> 4 & 0x07),
+            mixed[1] * side(self.master_volume & 0x07),
+        ]
+    }
+
+    /// Whether channel 1 is playing, as NR52 bit 0 will report it
+    pub fn channel1_enabled(&self) -> bool {
+        self.channel1.enabled()
+    }
+
+    /// Whether channel 2 is playing, as NR52 bit 1 will report it
+    pub fn channel2_enabled(&self) -> bool {
+        self.channel2.enabled()
+    }
+
+    /// NR52: power, and which channels are playing
+    fn read_nr52(&self) -> u8 {
+        0x70 | u8::from(self.powered) << 7
+            | u8::from(self.channel2.enabled()) << 1
+            | u8::from(self.channel1.enabled())
+    }
+
+    fn write_nr52(&mut self, value: u8) {
+        let powered = value & 0x80 != 0;
+        if powered == self.powered {
+            return;
+        }
+        self.powered = powered;
+        if powered {
+            // The frame sequencer starts over from step 0
+            self.sequencer_cycles = 0;
+            self.sequencer_step = 0;
+        } else {
+            self.channel1.power_off();
+            self.channel2.power_off();
+            self.master_volume = 0;
+            self.panning = 0;
+        }
+    }
+
+    /// Read NR10-NR52 at its address; any other address reads 0xFF
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            #[allow(clippy::cast_possible_truncation)]
+            0xFF10..=0xFF14 => self.channel1.read((address - 0xFF10) as u8),
+            // NR20 does not exist; channel 2 has no sweep so reads it as 0xFF
+            #[allow(clippy::cast_possible_truncation)]
+            0xFF15..=0xFF19 => self.channel2.read((address - 0xFF15) as u8),
+            0xFF24 => self.master_volume,
+            0xFF25 => self.panning,
+            0xFF26 => self.read_nr52(),
+            _ => 0xFF,
+        }
+    }
+
+    /// Write NR10-NR52 at its address; only NR52 and the length counters
+    /// while powered off. Any other address is ignored.
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        if !self.powered && address != 0xFF26 {
+            // Only the length counters still load, on the DMG
+            match address {
+                0xFF11 => self.channel1.write_length(value),
+                0xFF16 => self.channel2.write_length(value),
+                _ => {}
+            }
+            return;
+        }
+        match address {
+            #[allow(clippy::cast_possible_truncation)]
+            0xFF10..=0xFF14 => self.channel1.write((address - 0xFF10) as u8, value),
+            #[allow(clippy::cast_possible_truncation)]
+            0xFF15..=0xFF19 => self.channel2.write((address - 0xFF15) as u8, value),
+            0xFF24 => self.master_volume = value,
+            0xFF25 => self.panning = value,
+            0xFF26 => self.write_nr52(value),
+            _ => {}
+        }
+    }
+
+    /// Samples not yet taken are not saved: the frontend has them already
+    /// or will miss one frame's worth. The host volume and channel
+    /// selection are not the game's and are not saved either.
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        self.channel1.save_state(state);
+        self.channel2.save_state(state);
+        state.write_bool(self.powered);
+        state.write_u8(self.master_volume);
+        state.write_u8(self.panning);
+        state.write_u16(self.sequencer_cycles);
+        state.write_u8(self.sequencer_step);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.channel1.load_state(state)?;
+        self.channel2.load_state(state)?;
+        self.powered = state.read_bool()?;
+        self.master_volume = state.read_u8()?;
+        self.panning = state.read_u8()?;
+        self.sequencer_cycles = state.read_u16()? % SEQUENCER_CYCLES;
+        self.sequencer_step = state.read_u8()? % 8;
+        self.blip.clear();
+        self.samples.clear();
+        Ok(())
+    }
+}
+
+/// The APU requests no interrupts; samples pile up for `take_samples`
+impl Tickable for Apu {
+    fn advance(&mut self, cycles: u32, _memory: &Memory, _interrupts: &mut Interrupts) {
+        for step in tick::in_steps(cycles, u8::MAX) {
+            self.tick(step);
+        }
+    }
+
+    /// Until the frame sequencer next clocks the length counters, sweep or
+    /// envelopes, which can switch channels off in NR52
+    fn next_event(&self) -> Option<u32> {
+        self.powered
+            .then(|| u32::from(SEQUENCER_CYCLES - self.sequencer_cycles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playtime::CYCLES_PER_SECOND;
+    use crate::savestate::StateWriter;
+
+    /// Run for `ms` milliseconds of emulated time
+    fn run(apu: &mut Apu, ms: u64) {
+        for _ in 0..ms * CYCLES_PER_SECOND / 4000 {
+            apu.tick(4);
+        }
+    }
+
+    /// Full volume, no envelope; 50% duty; a period of 1792 (2048 Hz)
+    fn play(apu: &mut Apu, sweep: u8, length: Option<u8>) {
+        apu.write_register(0xFF10, sweep);
+        apu.write_register(0xFF11, 0x80 | length.unwrap_or(0));
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF13, 0x00);
+        let length_enable = if length.is_some() { 0x40 } else { 0 };
+        apu.write_register(0xFF14, 0x80 | length_enable | 0x07);
+    }
+
+    #[test]
+    fn produces_samples_at_the_sample_rate() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(44_100);
+        run(&mut apu, 1000);
+        assert_eq!(apu.take_samples().len(), 44_100);
+        assert!(apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn plays_the_duty_cycle() {
+        let mut apu = Apu::new();
+        play(&mut apu, 0, None);
+        run(&mut apu, 10);
+        let samples = apu.take_samples();
+        let high = samples.iter().filter(|s| s[0] < 0.0).count();
+        let low = samples.iter().filter(|s| s[0] > 0.0).count();
+        assert!(
+            high.abs_diff(low) < samples.len() / 20,
+            "{high} high, {low} low"
+        );
+        assert_eq!(apu.read_register(0xFF11), 0xBF);
+        assert_eq!(apu.read_register(0xFF14), 0xBF);
+    }
+
+    #[test]
+    fn length_counter_stops_the_channel() {
+        let mut apu = Apu::new();
+        // 64 - 32 = 32 ticks at 256 Hz, 1/8 s
+        play(&mut apu, 0, Some(32));
+        run(&mut apu, 120);
+        assert!(apu.channel1_enabled());
+        run(&mut apu, 10);
+        assert!(!apu.channel1_enabled());
+    }
+
+    #[test]
+    fn envelope_fades_out() {
+        let mut apu = Apu::new();
+        play(&mut apu, 0, None);
+        // Start at 2, one step down every 1/64 s
+        apu.write_register(0xFF12, 0x21);
+        apu.write_register(0xFF14, 0x87);
+        run(&mut apu, 40);
+        apu.take_samples();
+        run(&mut apu, 10);
+        assert!(apu.take_samples().iter().all(|s| s[0] > 0.0));
+        // The DAC stays on at volume 0
+        assert!(apu.channel1_enabled());
+    }
+
+    #[test]
+    fn sweep_raises_the_period_until_it_overflows() {
+        let mut apu = Apu::new();
+        // Up by period >> 1 every 1/128 s from 1024: 1536, then 2304 overflows
+        play(&mut apu, 0x11, None);
+        apu.write_register(0xFF14, 0x84);
+        assert!(apu.channel1_enabled());
+        run(&mut apu, 10);
+        assert!(!apu.channel1_enabled());
+
+        // Down: 1792 - 224 each step, never overflowing
+        play(&mut apu, 0x1B, None);
+        run(&mut apu, 100);
+        assert!(apu.channel1_enabled());
+        assert_eq!(apu.read_register(0xFF10), 0x9B);
+    }
+
+    #[test]
+    fn out_of_range_state_is_brought_back_in_range() {
+        let mut state = StateWriter::new();
+        for sweep in [true, false] {
+            if sweep {
+                state.write_u8(0x71); // Pace 7, adding period >> 1
+                state.write_bool(true);
+                state.write_u16(0xFFFF); // Shadow period
+                state.write_u8(0xFF);
+            }
+            state.write_u8(0xFF); // Duty
+            state.write_u8(0xFF); // Duty step
+            state.write_u16(0);
+            state.write_u16(0xFFFF); // Period
+            state.write_u8(0xFF); // Length
+            state.write_bool(false);
+            state.write_u8(0xF9); // Envelope getting louder every tick
+            state.write_u8(0xFF); // Volume
+            state.write_u8(0xFF);
+            state.write_bool(true);
+        }
+        state.write_bool(true);
+        state.write_u8(0x77);
+        state.write_u8(0xFF);
+        state.write_u16(0);
+        state.write_u8(0);
+
+        let mut apu = Apu::new();
+        apu.load_state(&mut state.finish().reader()).unwrap();
+        // Long enough for the unclamped timers to run out too
+        run(&mut apu, 5000);
+        assert_eq!(apu.read_register(0xFF10), 0xF1);
+    }
+
+    #[test]
+    fn channel2_plays_without_a_sweep() {
+        let mut apu = Apu::new();
+        // 25% duty, length 64 - 48 = 16 ticks (1/16 s), volume 15 fading out
+        apu.write_register(0xFF15, 0x7F);
+        apu.write_register(0xFF16, 0x70);
+        apu.write_register(0xFF17, 0xF3);
+        apu.write_register(0xFF18, 0x00);
+        apu.write_register(0xFF19, 0xC7);
+        assert!(apu.channel2_enabled() && !apu.channel1_enabled());
+        assert_eq!(apu.read_register(0xFF15), 0xFF);
+        assert_eq!(apu.read_register(0xFF16), 0x7F);
+
+        run(&mut apu, 10);
+        let samples = apu.take_samples();
+        let high = samples.iter().filter(|s| s[0] < 0.0).count();
+        let low = samples.iter().filter(|s| s[0] > 0.0).count();
+        assert!(high * 2 < low, "{high} high, {low} low");
+
+        run(&mut apu, 60);
+        assert!(!apu.channel2_enabled());
+    }
+
+    /// The mixer's output after each of `ms` milliseconds' worth of ticks,
+    /// before resampling softens its edges
+    fn mixed(apu: &mut Apu, ms: u64) -> Vec<audio::Frame> {
+        (0..ms * CYCLES_PER_SECOND / 4000)
+            .map(|_| {
+                apu.tick(4);
+                apu.mix()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mixer_pans_and_scales_each_side() {
+        let mut apu = Apu::new();
+        play(&mut apu, 0, None);
+        apu.write_register(0xFF25, 0x10); // Channel 1 left only
+        apu.write_register(0xFF24, 0x73); // Left 7, right 3
+        let samples = mixed(&mut apu, 10);
+        assert!(samples.iter().all(|s| s[1] == 0.0));
+        let loudest = samples.iter().map(|s| s[0].abs()).fold(0.0, f32::max);
+        assert!((loudest - CHANNEL_LEVEL).abs() < 1e-6);
+        assert!(apu.take_samples().iter().all(|s| s[1] == 0.0));
+
+        apu.write_register(0xFF25, 0x01);
+        apu.volume_mut().set_percent(50);
+        let samples = mixed(&mut apu, 10);
+        assert!(samples.iter().all(|s| s[0] == 0.0));
+        let loudest = samples.iter().map(|s| s[1].abs()).fold(0.0, f32::max);
+        assert!((loudest - CHANNEL_LEVEL * 0.5 * 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn disabled_channels_are_silent_but_keep_playing() {
+        let mut apu = Apu::new();
+        play(&mut apu, 0, None);
+        apu.set_channel_enabled(Channel::Square1, false);
+        assert!(mixed(&mut apu, 5).iter().all(|s| *s == [0.0, 0.0]));
+        assert!(apu.channel1_enabled());
+        assert_eq!(apu.read_register(0xFF26), 0xF1);
+
+        apu.set_channel_enabled(Channel::Square1, true);
+        assert!(mixed(&mut apu, 5).iter().any(|s| s[0] != 0.0));
+        apu.channels.solo(Some(Channel::Square2));
+        assert!(mixed(&mut apu, 5).iter().all(|s| *s == [0.0, 0.0]));
+    }
+
+    #[test]
+    fn powering_off_clears_registers_and_ignores_writes() {
+        let mut apu = Apu::new();
+        play(&mut apu, 0, None);
+        assert_eq!(apu.read_register(0xFF26), 0xF1);
+
+        apu.write_register(0xFF26, 0x00);
+        assert_eq!(apu.read_register(0xFF26), 0x70);
+        assert_eq!(apu.read_register(0xFF12), 0x00);
+        assert_eq!(apu.read_register(0xFF24), 0x00);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF25, 0xFF);
+        assert_eq!(apu.read_register(0xFF12), 0x00);
+        assert_eq!(apu.read_register(0xFF25), 0x00);
+        run(&mut apu, 5);
+        assert!(apu.take_samples().iter().all(|s| *s == [0.0, 0.0]));
+
+        // The DMG still loads length counters while off: 64 - 60 = 4 ticks
+        apu.write_register(0xFF11, 0x3C);
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF14, 0xC7);
+        assert_eq!(apu.read_register(0xFF26), 0xF1);
+        run(&mut apu, 20);
+        assert_eq!(apu.read_register(0xFF26), 0xF0);
+    }
+}
@@ -0,0 +1,214 @@
+use super::envelope::Envelope;
+use super::sweep::{Sweep, SweepStep};
+use crate::savestate::{StateReader, StateWriter};
+
+/// Waveforms for the four duty settings of `NRx1`, 12.5%, 25%, 50% and 75%
+/// high, played from the top bit down
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+/// Length counter start when `NRx1` asks for 0: the longest note, 1/4 s
+const MAX_LENGTH: u8 = 64;
+
+/// A square wave channel: an 8-step duty waveform at an 11-bit period,
+/// with a length counter and volume envelope. Channel 1 adds a frequency
+/// sweep.
+///
+/// Registers are addressed by index: 0 is `NRx0` (the sweep) to 4, `NRx4`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Square {
+    sweep: Option<Sweep>,
+    /// `NRx1` bits 6-7, an index into `DUTY_PATTERNS`
+    duty: u8,
+    /// Position in the waveform, 0 to 7
+    duty_step: u8,
+    /// Cycles to the next duty step
+    timer: u16,
+    /// From `NRx3` and the low bits of `NRx4`
+    period: u16,
+    /// Length counter ticks left, counting down at 256 Hz while enabled
+    length: u8,
+    length_enabled: bool,
+    envelope: Envelope,
+    /// Playing; cleared by the length counter, a sweep overflow or the DAC
+    /// turning off
+    enabled: bool,
+}
+
+impl Square {
+    pub(super) fn new(sweep: bool) -> Self {
+        Self {
+            sweep: sweep.then(Sweep::default),
+            duty: 0,
+            duty_step: 0,
+            timer: 0,
+            period: 0,
+            length: 0,
+            length_enabled: false,
+            envelope: Envelope::default(),
+            enabled: false,
+        }
+    }
+
+    /// Whether the channel is playing, as NR52 reports it
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Cycles per duty step: the period counts up from itself to 2048,
+    /// once every 4 cycles
+    fn step_cycles(&self) -> u16 {
+        (2048 - self.period) * 4
+    }
+
+    pub(super) fn tick(&mut self, cycles: u8) {
+        let mut cycles = u16::from(cycles);
+        while cycles > 0 {
+            if self.timer == 0 {
+                self.timer = self.step_cycles();
+                self.duty_step = (self.duty_step + 1) % 8;
+            }
+            let run = cycles.min(self.timer);
+            self.timer -= run;
+            cycles -= run;
+        }
+    }
+
+    /// Current output level, 0 to 15, or `None` while the DAC is off
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+        let high = DUTY_PATTERNS[usize::from(self.duty)] >> (7 - self.duty_step) & 1 != 0;
+        Some(if self.enabled && high {
+            self.envelope.volume
+        } else {
+            0
+        })
+    }
+
+    pub(super) fn read(&self, register: u8) -> u8 {
+        match register {
+            0 => self.sweep.as_ref().map_or(0xFF, Sweep::read),
+            // Only the duty and length enable read back
+            1 => self.duty << 6 | 0x3F,
+            2 => self.envelope.read(),
+            3 => 0xFF,
+            _ => 0xBF | u8::from(self.length_enabled) << 6,
+        }
+    }
+
+    pub(super) fn write(&mut self, register: u8, value: u8) {
+        match register {
+            0 => {
+                if let Some(ref mut sweep) = self.sweep {
+                    sweep.write(value);
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length = MAX_LENGTH - (value & 0x3F);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.period = self.period & 0x700 | u16::from(value),
+            _ => {
+                self.period = self.period & 0xFF | u16::from(value & 0x07) << 8;
+                self.length_enabled = value & 0x40 != 0;
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+        }
+    }
+
+    /// `NRx1` written while the APU is off, which on the DMG still loads
+    /// the length counter
+    pub(super) fn write_length(&mut self, value: u8) {
+        self.length = MAX_LENGTH - (value & 0x3F);
+    }
+
+    /// Clear every register as powering the APU off does; the DMG keeps
+    /// the length counter
+    pub(super) fn power_off(&mut self) {
+        *self = Self {
+            length: self.length,
+            ..Self::new(self.sweep.is_some())
+        };
+    }
+
+    /// Restart the note (`NRx4` bit 7)
+    // TODO: The extra length clock when enabling length in the first half
+    // of a frame sequencer period, and the duty step carrying over from
+    // before the trigger
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        if self.length == 0 {
+            self.length = MAX_LENGTH;
+        }
+        self.timer = self.step_cycles();
+        self.envelope.trigger();
+        if let Some(ref mut sweep) = self.sweep
+            && !sweep.trigger(self.period)
+        {
+            self.enabled = false;
+        }
+    }
+
+    /// Frame sequencer steps 0, 2, 4 and 6
+    pub(super) fn clock_length(&mut self) {
+        if self.length_enabled && self.length > 0 {
+            self.length -= 1;
+            if self.length == 0 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub(super) fn clock_sweep(&mut self) {
+        let Some(ref mut sweep) = self.sweep else {
+            return;
+        };
+        match sweep.clock() {
+            SweepStep::Unchanged => {}
+            SweepStep::Period(period) => self.period = period,
+            SweepStep::Overflow => self.enabled = false,
+        }
+    }
+
+    pub(super) fn save_state(&self, state: &mut StateWriter) {
+        if let Some(ref sweep) = self.sweep {
+            sweep.save_state(state);
+        }
+        state.write_u8(self.duty);
+        state.write_u8(self.duty_step);
+        state.write_u16(self.timer);
+        state.write_u16(self.period);
+        state.write_u8(self.length);
+        state.write_bool(self.length_enabled);
+        self.envelope.save_state(state);
+        state.write_bool(self.enabled);
+    }
+
+    pub(super) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        if let Some(ref mut sweep) = self.sweep {
+            sweep.load_state(state)?;
+        }
+        self.duty = state.read_u8()? & 0x03;
+        self.duty_step = state.read_u8()? & 0x07;
+        self.timer = state.read_u16()?;
+        self.period = state.read_u16()? & 0x7FF;
+        self.length = state.read_u8()?.min(MAX_LENGTH);
+        self.length_enabled = state.read_bool()?;
+        self.envelope.load_state(state)?;
+        self.enabled = state.read_bool()?;
+        Ok(())
+    }
+}
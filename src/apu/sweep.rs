@@ -0,0 +1,116 @@
+use crate::savestate::{StateReader, StateWriter};
+
+/// Largest 11-bit period; a sweep past it silences the channel
+const MAX_PERIOD: u16 = 0x7FF;
+
+/// What a sweep tick did to the channel's period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SweepStep {
+    Unchanged,
+    Period(u16),
+    /// The period went past `MAX_PERIOD`: the channel turns off
+    Overflow,
+}
+
+/// Channel 1's frequency sweep (NR10), which moves the period up or down
+/// by a fraction of itself at a fixed pace, for rising and falling tones.
+///
+/// Byte format -PPP DSSS: pace in 128 Hz ticks per step (0 for none),
+/// direction (1 = subtract, lowering the pitch) and shift: each step adds
+/// or subtracts period >> shift.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Sweep {
+    register: u8,
+    enabled: bool,
+    /// Copy of the period the sweep works from, set on trigger
+    shadow: u16,
+    /// Ticks left until the next step
+    timer: u8,
+}
+
+impl Sweep {
+    pub(super) fn read(&self) -> u8 {
+        self.register | 0x80
+    }
+
+    pub(super) fn write(&mut self, value: u8) {
+        self.register = value & 0x7F;
+    }
+
+    /// Start sweeping from `period`, returning false if the first step
+    /// would already overflow and the channel must stay off
+    pub(super) fn trigger(&mut self, period: u16) -> bool {
+        self.shadow = period;
+        self.timer = self.reload();
+        self.enabled = self.pace() != 0 || self.shift() != 0;
+        self.shift() == 0 || self.next_period().is_some()
+    }
+
+    /// Frame sequencer steps 2 and 6, 128 times a second
+    pub(super) fn clock(&mut self) -> SweepStep {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return SweepStep::Unchanged;
+        }
+        self.timer = self.reload();
+        if !self.enabled || self.pace() == 0 {
+            return SweepStep::Unchanged;
+        }
+        match self.next_period() {
+            None => SweepStep::Overflow,
+            Some(next) if self.shift() > 0 => {
+                self.shadow = next;
+                // The new period is checked again straight away
+                if self.next_period().is_some() {
+                    SweepStep::Period(next)
+                } else {
+                    SweepStep::Overflow
+                }
+            }
+            Some(_) => SweepStep::Unchanged,
+        }
+    }
+
+    /// The period one step on, or `None` past `MAX_PERIOD`
+    fn next_period(&self) -> Option<u16> {
+        let delta = self.shadow >> self.shift();
+        let next = if self.register & 0x08 != 0 {
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        };
+        (next <= MAX_PERIOD).then_some(next)
+    }
+
+    /// A pace of 0 still counts down from 8
+    fn reload(&self) -> u8 {
+        match self.pace() {
+            0 => 8,
+            pace => pace,
+        }
+    }
+
+    fn pace(&self) -> u8 {
+        (self.register >> 4) & 0x07
+    }
+
+    fn shift(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    pub(super) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.register);
+        state.write_bool(self.enabled);
+        state.write_u16(self.shadow);
+        state.write_u8(self.timer);
+    }
+
+    /// Out-of-range fields, as in a corrupt state, are brought back in range
+    pub(super) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.register = state.read_u8()? & 0x7F;
+        self.enabled = state.read_bool()?;
+        self.shadow = state.read_u16()? & MAX_PERIOD;
+        self.timer = state.read_u8()?.min(self.reload());
+        Ok(())
+    }
+}
@@ -3,6 +3,16 @@ use clap::{
     Parser,
     Subcommand,
 };
+use gameboy::apu::Channel;
+use gameboy::audio::Volume;
+use gameboy::autosplit::LIVESPLIT_DEFAULT_ADDRESS;
+use gameboy::eventlog::EventLogFormat;
+use gameboy::frame::MAX_PERSISTENCE;
+use gameboy::frontend::{ColorMode, Filter, Scale, WindowBackend};
+use gameboy::shader::PostShader;
+use gameboy::quirks::QuirkPolicy;
+use gameboy::rtc::Clock;
+use gameboy::savestate::StateCheck;
 
 
 #[derive(Parser, Debug)]
@@ -13,18 +23,266 @@ pub struct GameboyArgs {
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)] // Parsed once at startup
 pub enum RunType {
     /// Run the Game Boy with the supplied ROM (.gb) file
     Run(RunCommand),
 
     /// Run the Game Boy in test mode.
     Test(TestCommand),
+
+    /// Run the Game Boy headlessly under control of a Rhai script
+    Script(ScriptCommand),
+
+    /// Copy a .sav file from another emulator into the game's data directory
+    ImportSave(SaveTransferCommand),
+
+    /// Copy the game's battery save out to a .sav file for another emulator
+    ExportSave(SaveTransferCommand),
+
+    /// Show a ROM's header, data directory and play time
+    Info(InfoCommand),
+
+    /// Inspect save state files
+    State(StateCommand),
+
+    /// Check ROM headers, checksums and mapper/size consistency
+    Verify(VerifyCommand),
+
+    /// Run a ROM and export an RGBDS listing with executed code disassembled
+    Disasm(DisasmCommand),
+
+    /// Find the first instruction where a ROM's run departs from a reference
+    /// gameboy-doctor log, using save states instead of logging everything
+    Bisect(BisectCommand),
+
+    /// Compare two captured frames and count the pixels that differ
+    Framediff(FramediffCommand),
+
+    /// Run every test ROM in a directory in parallel and print a scoreboard
+    Harness(HarnessCommand),
+
+    /// Boot every ROM in a directory with random input and report panics,
+    /// illegal opcodes and hangs
+    Soak(SoakCommand),
+
+    /// Export the instruction set (mnemonics, cycles, flags, measured
+    /// cycles) as JSON
+    Opcodes(OpcodesCommand),
+
+    /// Show the core version, supported models and mappers, and which
+    /// accuracy features are emulated, for bug reports
+    Capabilities(CapabilitiesCommand),
 }
 
 #[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct RunCommand {
-    /// Path to the rom (.gb) file you wish to load
-    pub rom: String,
+    /// Path to the rom (.gb) file you wish to load. Without one, the built-in
+    /// splash screen is shown instead.
+    pub rom: Option<String>,
+
+    /// Base directory for per-game data (saves, states, screenshots, cheats)
+    #[clap(long)]
+    pub data_dir: Option<String>,
+
+    /// Memory-map the ROM file instead of reading it, for large ROMs
+    #[clap(long)]
+    pub mmap: bool,
+
+    /// Start from a cached state taken after the boot sequence and intro
+    #[clap(long)]
+    pub fast_boot: bool,
+
+    /// Number of intro frames to skip when using --fast-boot
+    #[clap(long, default_value_t = 180)]
+    pub fast_boot_frames: u32,
+
+    /// Scroll the logo from the cartridge header down the screen before the
+    /// game starts, as the boot ROM does. Ignored with --fast-boot.
+    #[clap(long)]
+    pub boot_logo: bool,
+
+    /// DMG colorization: a built-in name (grayscale, dmg, up, up+a, left+b, ...),
+    /// `cgb` for the palette the CGB boot ROM picks for this game, or a palette
+    /// file. Defaults to the game's palette.txt if present.
+    #[clap(long)]
+    pub palette: Option<String>,
+
+    /// How to report edge-case behavior: silent, warn or trap, optionally per
+    /// quirk (rom-write, vram-mode3, echo-ram, illegal-opcode), e.g.
+    /// `warn,illegal-opcode=trap`
+    #[clap(long, default_value = "silent")]
+    pub quirks: QuirkPolicy,
+
+    /// Master volume, 0 to 100%. Audio is muted while turbo is held, and the
+    /// mute hotkey (M) toggles it.
+    #[clap(long, default_value = "100")]
+    pub volume: Volume,
+
+    /// Sound channels to leave out of the mix, by number (1-4) or name
+    /// (square1, square2, wave, noise), e.g. `--mute 3,4`. Hotkeys 1 to 4
+    /// toggle them in a window.
+    #[clap(long, value_delimiter = ',')]
+    pub mute: Vec<Channel>,
+
+    /// Hear only this sound channel. The solo hotkey (S) moves on to the
+    /// next one.
+    #[clap(long, conflicts_with = "mute")]
+    pub solo: Option<Channel>,
+
+    /// Where the cartridge clock gets the time: system for the host's
+    /// clock, emulated[:SECONDS] to count emulated time from a UNIX time
+    /// (for replays that must not drift), or fixed:SECONDS
+    #[clap(long, default_value = "system")]
+    pub clock: Clock,
+
+    /// Check loaded save states for corruption (PC, SP, PPU position,
+    /// mapped banks): off, refuse, or repair what can be repaired
+    #[clap(long, default_value = "off")]
+    pub state_check: StateCheck,
+
+    /// Serial port of a USB link cable adapter to link with a real Game Boy
+    /// (e.g. /dev/ttyACM0 or COM3)
+    #[clap(long)]
+    pub link_cable: Option<String>,
+
+    /// Frames without cartridge RAM writes before the battery save is flushed
+    #[clap(long, default_value_t = 60)]
+    pub autosave_debounce: u64,
+
+    /// Longest a changed battery save goes unflushed while the game keeps
+    /// writing, in frames
+    #[clap(long, default_value_t = 600)]
+    pub autosave_max_interval: u64,
+
+    /// Record interrupts, PPU mode changes and bank switches with cycle
+    /// timestamps and write them here on exit
+    #[clap(long)]
+    pub event_log: Option<String>,
+
+    /// Event log format: json, or perfetto for a trace ui.perfetto.dev can open
+    #[clap(long, default_value = "json")]
+    pub event_log_format: EventLogFormat,
+
+    /// Debug check: stop with an error if the timer, PPU or serial port ever
+    /// falls behind the CPU by more than one event window
+    #[clap(long)]
+    pub clock_trace: bool,
+
+    /// Write rendered frames to this directory as PNGs numbered by frame
+    #[clap(long)]
+    pub dump_frames: Option<String>,
+
+    /// With --dump-frames, write only every Nth frame
+    #[clap(long, default_value_t = 1)]
+    pub every: u64,
+
+    /// Write every audio sample to this WAV file (16-bit stereo at the
+    /// output sample rate)
+    #[clap(long)]
+    pub dump_audio: Option<String>,
+
+    /// Run headless at full speed until this many frames have completed, then
+    /// stop there instead of running the usual instruction budget
+    #[clap(long)]
+    pub pause_at_frame: Option<u64>,
+
+    /// Serve JSON-RPC over HTTP on this address (e.g. 127.0.0.1:8765) and
+    /// run in real time until a client calls quit
+    #[clap(long)]
+    pub rpc: Option<String>,
+
+    /// Play in a window: sdl, or pixels where SDL2 is not installed. Each
+    /// needs a build with `--features` of its name. Arrows are the D-pad, X
+    /// and Z are A and B, Enter is Start and Space is Select.
+    #[clap(long)]
+    pub frontend: Option<WindowBackend>,
+
+    /// Hotkey file remapping the window's emulator keys over the defaults
+    #[clap(long)]
+    pub hotkeys: Option<String>,
+
+    /// Post-process the window on the GPU with a WGSL shader: a preset
+    /// (scanlines, lcd-grid, curvature, smooth) or a .wgsl file. Needs
+    /// --frontend pixels; without it the frame is scaled as is.
+    #[clap(long, value_parser = PostShader::from_name_or_path)]
+    pub shader: Option<PostShader>,
+
+    /// Window scale: 1 to 6 times the Game Boy screen, or fit for the
+    /// largest whole multiple that fits the window. The screen keeps its
+    /// aspect ratio, with black borders around it.
+    #[clap(long, default_value = "fit")]
+    pub scale: Scale,
+
+    /// How the window stretches pixels: nearest for sharp pixels or
+    /// bilinear for smoothed ones
+    #[clap(long, default_value = "nearest")]
+    pub filter: Filter,
+
+    /// Blend this percent of each window picture into the next, 0 to 90,
+    /// like the DMG LCD's slow pixels: flickering sprites then look
+    /// see-through, as games meant them to
+    #[clap(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=i64::from(MAX_PERSISTENCE)))]
+    pub ghosting: u8,
+
+    /// Draw the screen in this terminal with half blocks: truecolor, or 256
+    /// where 24-bit color is missing. Without a window, --rpc or
+    /// --pause-at-frame, plays in real time until the CPU halts; with
+    /// --pause-at-frame, draws the frame it stops at.
+    #[clap(long)]
+    pub terminal: Option<ColorMode>,
+
+    /// For long headless runs (--pause-at-frame, --rpc): print a status line
+    /// every this many seconds, and write a save state to the states
+    /// directory when sent SIGUSR1
+    #[clap(long)]
+    pub watch: Option<u64>,
+
+    /// Use at most this percent of one host CPU, 1 to 100, by sleeping
+    /// between frames: for long headless runs on shared machines
+    #[clap(long, conflicts_with_all = ["frontend", "rpc"], value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub max_host_cpu: Option<u8>,
+
+    /// Speedrun autosplitter: a file of memory conditions (kind, address,
+    /// value before and after, name per line) checked every frame. Starts,
+    /// splits and resets are printed with their emulated time.
+    #[clap(long)]
+    pub autosplit: Option<String>,
+
+    /// With --autosplit, also drive LiveSplit's timer through its server
+    /// component, at this address or 127.0.0.1:16834
+    #[allow(clippy::doc_markdown)] // Backticks would show in --help
+    #[clap(long, requires = "autosplit", num_args = 0..=1, default_missing_value = LIVESPLIT_DEFAULT_ADDRESS)]
+    pub livesplit: Option<String>,
+
+    /// RAM map (address, type, name per line) whose fields are printed when
+    /// emulation stops. Defaults to the RAM map in the game's data directory.
+    #[clap(long)]
+    pub ram_map: Option<String>,
+
+    /// Count reads, writes and switches per cartridge bank and print a
+    /// histogram of them, with the mapper writes, when emulation stops
+    #[clap(long)]
+    pub bank_stats: bool,
+
+    /// Validate ROM banking: record every bank and address read or
+    /// executed, and when emulation stops list those outside the header's
+    /// ROM size or the file, which read as 0xFF. Exits with status 1 if
+    /// there are any or the file size is off.
+    #[clap(long)]
+    pub bank_audit: bool,
+
+    /// Light up every address as it is read, written or executed, fading
+    /// each frame, and write the 64K heat map here as a 256x256 PNG on exit:
+    /// writes red, reads green, executes blue, one row per 256 bytes
+    #[clap(long)]
+    pub heat_map: Option<String>,
+
+    /// Record the last writer of every address from the first instruction,
+    /// so --rpc clients can ask who last wrote an address
+    #[clap(long)]
+    pub track_writes: bool,
 }
 
 #[derive(Args, Debug)]
@@ -34,4 +292,195 @@ pub struct TestCommand {
 
     /// Log CPU state to file
     pub log: String,
-}
\ No newline at end of file
+
+    /// Base directory for per-game data (saves, states, screenshots, cheats)
+    #[clap(long)]
+    pub data_dir: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ScriptCommand {
+    /// Path to the rom (.gb) file you wish to load
+    pub rom: String,
+
+    /// Path to the Rhai (.rhai) script that drives the emulator
+    pub script: String,
+
+    /// RAM map whose fields the script can read by name. Defaults to the RAM
+    /// map in the game's data directory.
+    #[clap(long)]
+    pub ram_map: Option<String>,
+
+    /// Use at most this percent of one host CPU, 1 to 100, by sleeping
+    /// between frames
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub max_host_cpu: Option<u8>,
+}
+
+#[derive(Args, Debug)]
+pub struct InfoCommand {
+    /// Path to the rom (.gb) file you wish to inspect
+    pub rom: String,
+
+    /// Base directory for per-game data (saves, states, screenshots, cheats)
+    #[clap(long)]
+    pub data_dir: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct SaveTransferCommand {
+    /// Path to the rom (.gb) file the save belongs to
+    pub rom: String,
+
+    /// The .sav file to import from or export to
+    pub file: String,
+
+    /// Base directory for per-game data (saves, states, screenshots, cheats)
+    #[clap(long)]
+    pub data_dir: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct StateCommand {
+    #[clap(subcommand)]
+    pub action: StateAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StateAction {
+    /// Show the title, ROM hash, save time and play time stored in a state
+    Info(StateInfoCommand),
+}
+
+#[derive(Args, Debug)]
+pub struct StateInfoCommand {
+    /// Path to the save state file
+    pub file: String,
+
+    /// Also write the state's thumbnail to this PNG file
+    #[clap(long)]
+    pub thumbnail: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyCommand {
+    /// ROM (.gb) files to check
+    #[clap(required = true)]
+    pub roms: Vec<String>,
+
+    /// Print one JSON object per ROM instead of a readable report
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DisasmCommand {
+    /// Path to the rom (.gb) file you wish to load
+    pub rom: String,
+
+    /// Where to write the listing (.asm)
+    pub output: String,
+
+    /// Frames to run while recording which bytes are code
+    #[clap(long, default_value_t = 600)]
+    pub frames: u32,
+}
+
+#[derive(Args, Debug)]
+pub struct BisectCommand {
+    /// Path to the rom (.gb) file you wish to load
+    pub rom: String,
+
+    /// Reference log from a known-good emulator, one line per instruction
+    pub reference: String,
+
+    /// Instructions between save states; smaller uses more memory but
+    /// compares fewer lines at the end
+    #[clap(long, default_value_t = 100_000)]
+    pub interval: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct FramediffCommand {
+    /// Expected frame (.png)
+    pub a: String,
+
+    /// Actual frame (.png)
+    pub b: String,
+
+    /// Write an image with the differing pixels highlighted (.png)
+    #[clap(long)]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct HarnessCommand {
+    /// Directory searched recursively for test ROMs (.gb, .gbc)
+    pub dir: String,
+
+    /// Number of ROMs to run at once. Defaults to the number of CPUs.
+    #[clap(long)]
+    pub jobs: Option<usize>,
+
+    /// Emulated frames before a ROM without a result times out
+    #[clap(long, default_value_t = 3600)]
+    pub frames: u64,
+
+    /// Wall-clock seconds before a ROM without a result times out
+    #[clap(long, default_value_t = 60)]
+    pub timeout: u64,
+
+    /// Results cache, reused until the emulator binary changes
+    #[clap(long, default_value = "target/harness-cache.json")]
+    pub cache: String,
+
+    /// Run every ROM even if a cached result exists
+    #[clap(long)]
+    pub no_cache: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SoakCommand {
+    /// Directory searched recursively for ROMs (.gb, .gbc)
+    pub dir: String,
+
+    /// Emulated seconds to run each ROM for
+    #[clap(long, default_value_t = 60)]
+    pub seconds_per_rom: u64,
+
+    /// Seed for the random input; the same seed replays the same presses
+    #[clap(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Emulated seconds with a still picture and PC stuck in one place
+    /// before a ROM counts as hung
+    #[clap(long, default_value_t = 10)]
+    pub hang_seconds: u64,
+
+    /// Number of ROMs to run at once. Defaults to the number of CPUs.
+    #[clap(long)]
+    pub jobs: Option<usize>,
+
+    /// Use at most this percent of one host CPU per job, 1 to 100, by
+    /// sleeping between frames
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub max_host_cpu: Option<u8>,
+
+    /// Print one JSON object per ROM instead of a readable report
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct OpcodesCommand {
+    /// Write the JSON here instead of to standard output
+    #[clap(long)]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct CapabilitiesCommand {
+    /// Print JSON, for frontends and test harnesses
+    #[clap(long)]
+    pub json: bool,
+}
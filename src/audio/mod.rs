@@ -0,0 +1,234 @@
+mod null;
+mod volume;
+
+use std::collections::VecDeque;
+
+pub use null::NullDevice;
+pub use volume::Volume;
+
+/// One stereo sample, left then right
+pub type Frame = [f32; 2];
+
+/// Counters a frontend can show or log to diagnose crackling audio
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioStats {
+    /// Host callbacks that found too few samples and padded with silence
+    pub underruns: u64,
+    /// Samples dropped because the buffer was full
+    pub overruns: u64,
+    /// Current fill level, 0.0 (empty) to 1.0 (full)
+    pub fill: f32,
+}
+
+/// Fixed-capacity buffer between the emulator (producer) and the host audio
+/// callback (consumer). Frontends share it behind a mutex. There is no host
+/// audio device yet: `NullDevice` is its only consumer outside tests, and it
+/// and `RateControl` and `Resampler` are the building blocks for one.
+pub struct AudioRing {
+    samples: VecDeque<Frame>,
+    capacity: usize,
+    underruns: u64,
+    overruns: u64,
+}
+
+impl AudioRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            underruns: 0,
+            overruns: 0,
+        }
+    }
+
+    /// Queue samples, dropping (and counting) any that do not fit
+    pub fn push(&mut self, frames: &[Frame]) {
+        let room = self.capacity - self.samples.len();
+        let (queued, dropped) = frames.split_at(frames.len().min(room));
+        self.samples.extend(queued);
+        self.overruns += dropped.len() as u64;
+    }
+
+    /// Fill `out` for the host, padding with silence on underrun
+    pub fn pop(&mut self, out: &mut [Frame]) {
+        let available = out.len().min(self.samples.len());
+        for (slot, frame) in out.iter_mut().zip(self.samples.drain(..available)) {
+            *slot = frame;
+        }
+        if available < out.len() {
+            out[available..].fill([0.0; 2]);
+            self.underruns += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fill(&self) -> f32 {
+        self.samples.len() as f32 / self.capacity as f32
+    }
+
+    pub fn stats(&self) -> AudioStats {
+        AudioStats {
+            underruns: self.underruns,
+            overruns: self.overruns,
+            fill: self.fill(),
+        }
+    }
+}
+
+/// Dynamic rate control: the emulated and host audio clocks never match
+/// exactly, so the buffer slowly drains or fills until it clicks. Nudging
+/// the resampling ratio by a fraction of a percent towards a half-full
+/// buffer keeps it centered without an audible pitch change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateControl {
+    /// Largest adjustment either way, as a fraction (0.005 = ±0.5%)
+    pub max_adjust: f64,
+}
+
+impl Default for RateControl {
+    fn default() -> Self {
+        Self { max_adjust: 0.005 }
+    }
+}
+
+impl RateControl {
+    /// Output/input ratio to resample with at the given fill level. Above 1.0
+    /// produces more samples (buffer running low), below 1.0 fewer.
+    pub fn ratio(&self, fill: f32) -> f64 {
+        let error = 1.0 - 2.0 * f64::from(fill.clamp(0.0, 1.0));
+        1.0 + error * self.max_adjust
+    }
+}
+
+/// Linear-interpolating resampler from the emulator's sample rate to the host's.
+/// Nothing feeds it yet; see `AudioRing`.
+pub struct Resampler {
+    input_rate: f64,
+    output_rate: f64,
+    /// Position of the next output sample between `previous` and the next input
+    position: f64,
+    previous: Frame,
+}
+
+impl Resampler {
+    /// A zero rate is taken as 1 Hz, as `process` would never finish with it
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        Self {
+            input_rate: f64::from(input_rate.max(1)),
+            output_rate: f64::from(output_rate.max(1)),
+            position: 0.0,
+            previous: [0.0; 2],
+        }
+    }
+
+    /// Resample `input`, appending to `out`. `ratio` comes from `RateControl`.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn process(&mut self, input: &[Frame], ratio: f64, out: &mut Vec<Frame>) {
+        let step = self.input_rate / (self.output_rate * ratio);
+        for &next in input {
+            while self.position < 1.0 {
+                let t = self.position as f32;
+                out.push([
+                    self.previous[0] + (next[0] - self.previous[0]) * t,
+                    self.previous[1] + (next[1] - self.previous[1]) * t,
+                ]);
+                self.position += step;
+            }
+            self.position -= 1.0;
+            self.previous = next;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)] // Expected samples are exactly representable
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_counts_overruns_and_underruns() {
+        let mut ring = AudioRing::new(4);
+        ring.push(&[[1.0, 1.0]; 6]);
+        assert_eq!(ring.len(), 4);
+
+        let mut out = [[9.0; 2]; 6];
+        ring.pop(&mut out);
+        assert_eq!(out[3], [1.0, 1.0]);
+        assert_eq!(out[4], [0.0, 0.0], "Underrun is padded with silence");
+
+        let stats = ring.stats();
+        assert_eq!(stats.overruns, 2);
+        assert_eq!(stats.underruns, 1);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn rate_is_nudged_towards_half_full() {
+        let control = RateControl::default();
+        assert!((control.ratio(0.5) - 1.0).abs() < 1e-12);
+        assert!((control.ratio(0.0) - 1.005).abs() < 1e-12);
+        assert!((control.ratio(1.0) - 0.995).abs() < 1e-12);
+        assert!((control.ratio(7.0) - 0.995).abs() < 1e-12);
+    }
+
+    #[test]
+    fn resampler_interpolates_and_converts_rate() {
+        let mut resampler = Resampler::new(2, 4);
+        let mut out = Vec::new();
+        resampler.process(&[[1.0, -1.0], [1.0, -1.0]], 1.0, &mut out);
+
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[1], [0.5, -0.5]);
+        assert_eq!(out[3], [1.0, -1.0]);
+    }
+
+    #[test]
+    fn resampler_finishes_with_a_zero_rate() {
+        let mut out = Vec::new();
+        Resampler::new(0, 48_000).process(&[[1.0, -1.0]; 2], 1.0, &mut out);
+        assert_eq!(out.len(), 96_000);
+
+        out.clear();
+        Resampler::new(48_000, 0).process(&[[1.0, -1.0]; 2], 1.0, &mut out);
+        assert_eq!(out.len(), 1);
+    }
+
+    #[test]
+    fn rate_control_keeps_drifting_buffer_centered() {
+        // Host consumes 0.3% faster than the emulator nominally produces
+        let control = RateControl::default();
+        let mut resampler = Resampler::new(48_000, 48_000);
+        let mut ring = AudioRing::new(4096);
+        ring.push(&[[0.0; 2]; 2048]);
+
+        let mut output = Vec::new();
+        let mut out = [[0.0; 2]; 802];
+        for _ in 0..2000 {
+            output.clear();
+            resampler.process(&[[0.0; 2]; 800], control.ratio(ring.fill()), &mut output);
+            ring.push(&output);
+            ring.pop(&mut out);
+        }
+
+        let stats = ring.stats();
+        assert_eq!(stats.underruns, 0);
+        assert_eq!(stats.overruns, 0);
+        assert!(
+            stats.fill > 0.2 && stats.fill < 0.8,
+            "fill drifted to {}",
+            stats.fill
+        );
+    }
+}
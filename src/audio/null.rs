@@ -0,0 +1,99 @@
+use super::{AudioRing, Frame};
+use std::time::Duration;
+
+/// Stand-in for the host audio device when there is none (CI, SSH): it
+/// takes samples out of the ring at `sample_rate` per second of elapsed time
+/// and discards them, so pacing that waits on the audio buffer runs at the
+/// same speed as with real hardware.
+pub struct NullDevice {
+    sample_rate: u32,
+    /// Time played since creation, and the samples that took
+    played: Duration,
+    consumed: u64,
+    scratch: Vec<Frame>,
+}
+
+impl NullDevice {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            played: Duration::ZERO,
+            consumed: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Samples taken from the ring since creation, underruns included
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /// Play `elapsed` worth of audio from `ring`, as the host callback would
+    /// over that time. Sample counts are worked out from the total time
+    /// played, so splitting the same time into more calls never drifts.
+    pub fn play(&mut self, ring: &mut AudioRing, elapsed: Duration) {
+        self.played += elapsed;
+        let due = self.played.as_nanos() * u128::from(self.sample_rate) / 1_000_000_000;
+        let due = u64::try_from(due).unwrap_or(u64::MAX);
+        let count = usize::try_from(due - self.consumed).unwrap_or(usize::MAX);
+        self.consumed = due;
+
+        self.scratch.resize(count, [0.0; 2]);
+        ring.pop(&mut self.scratch);
+    }
+
+    /// How long the producer should wait before queueing more so that the
+    /// ring drains to `target_fill` (0.0 to 1.0), as audio-synced pacing
+    /// would block on a real device
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn pacing_delay(&self, ring: &AudioRing, target_fill: f32) -> Duration {
+        let target = (f64::from(target_fill.clamp(0.0, 1.0)) * ring.capacity() as f64) as usize;
+        let excess = ring.len().saturating_sub(target) as u64;
+        Duration::from_nanos(excess * 1_000_000_000 / u64::from(self.sample_rate.max(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consumes_at_the_sample_rate_without_drift() {
+        let mut ring = AudioRing::new(48_000);
+        let mut device = NullDevice::new(48_000);
+
+        // A 60 Hz frame does not hold a whole number of 48 kHz periods
+        for _ in 0..60 {
+            ring.push(&[[0.5; 2]; 800]);
+            device.play(&mut ring, Duration::from_nanos(16_666_667));
+        }
+        assert_eq!(device.consumed(), 48_000);
+        assert!(ring.is_empty());
+        assert_eq!(ring.stats().underruns, 0);
+
+        device.play(&mut ring, Duration::from_millis(1));
+        assert_eq!(ring.stats().underruns, 1, "Nothing queued");
+    }
+
+    #[test]
+    fn pacing_waits_for_the_ring_to_drain_to_target() {
+        let mut ring = AudioRing::new(4800);
+        let device = NullDevice::new(48_000);
+        ring.push(&vec![[0.0; 2]; 3600]);
+
+        assert_eq!(
+            device.pacing_delay(&ring, 0.5),
+            Duration::from_millis(25),
+            "1200 samples over half full"
+        );
+        assert_eq!(device.pacing_delay(&ring, 1.0), Duration::ZERO);
+    }
+}
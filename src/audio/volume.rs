@@ -0,0 +1,121 @@
+use super::Frame;
+use std::str::FromStr;
+
+/// Master volume, applied where the APU mixes its channels so every audio
+/// sink (speakers, WAV capture) hears the same level.
+///
+/// Fast-forwarded audio is a high-pitched smear, so by default it is
+/// silenced for as long as the frontend reports fast-forwarding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Volume {
+    /// 0 to 100
+    percent: u8,
+    pub muted: bool,
+    /// Silence audio while `fast_forward` is set
+    pub mute_fast_forward: bool,
+    /// Set by the frontend while turbo or fast-forward is on
+    pub fast_forward: bool,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+impl Volume {
+    /// `percent` above 100 is taken as 100
+    pub fn new(percent: u8) -> Self {
+        Self {
+            percent: percent.min(100),
+            muted: false,
+            mute_fast_forward: true,
+            fast_forward: false,
+        }
+    }
+
+    pub fn percent(self) -> u8 {
+        self.percent
+    }
+
+    /// `percent` above 100 is taken as 100
+    pub fn set_percent(&mut self, percent: u8) {
+        self.percent = percent.min(100);
+    }
+
+    /// Mute or unmute, returning whether now muted
+    pub fn toggle_mute(&mut self) -> bool {
+        self.muted = !self.muted;
+        self.muted
+    }
+
+    /// Whether nothing is heard, whatever the level
+    pub fn is_silent(self) -> bool {
+        self.muted || self.percent == 0 || (self.fast_forward && self.mute_fast_forward)
+    }
+
+    /// Factor samples are scaled by, 0.0 to 1.0
+    pub fn gain(self) -> f32 {
+        if self.is_silent() {
+            0.0
+        } else {
+            f32::from(self.percent) / 100.0
+        }
+    }
+
+    /// Scale mixed samples by `gain`
+    pub fn apply(self, samples: &mut [Frame]) {
+        let gain = self.gain();
+        for sample in samples {
+            sample[0] *= gain;
+            sample[1] *= gain;
+        }
+    }
+}
+
+impl FromStr for Volume {
+    type Err = String;
+
+    /// A percentage, with or without the `%`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_suffix('%')
+            .unwrap_or(s)
+            .parse::<u8>()
+            .ok()
+            .filter(|&percent| percent <= 100)
+            .map(Self::new)
+            .ok_or_else(|| format!("Volume must be 0 to 100%: {s}"))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)] // Expected samples are exactly representable
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mute_and_fast_forward_silence_the_mix() {
+        let mut volume = Volume::new(50);
+        let mut samples = [[1.0, -0.5]; 2];
+        volume.apply(&mut samples);
+        assert_eq!(samples[1], [0.5, -0.25]);
+
+        volume.fast_forward = true;
+        assert_eq!(volume.gain(), 0.0);
+        volume.mute_fast_forward = false;
+        assert_eq!(volume.gain(), 0.5);
+
+        assert!(volume.toggle_mute());
+        volume.apply(&mut samples);
+        assert_eq!(samples[0], [0.0, 0.0]);
+        assert_eq!(Volume::new(250).percent(), 100);
+    }
+
+    #[test]
+    fn parses_percentages() {
+        assert_eq!("80%".parse::<Volume>().map(Volume::percent), Ok(80));
+        assert_eq!("0".parse::<Volume>().map(Volume::percent), Ok(0));
+        assert!("101".parse::<Volume>().is_err());
+        assert!("loud".parse::<Volume>().is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::audio;
+use crate::frame::Frame;
+use crate::frontend::FrameSink;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes in one stereo frame of 16-bit samples
+const BYTES_PER_FRAME: u32 = 4;
+/// RIFF header up to the first sample
+const HEADER_LEN: u32 = 44;
+
+/// Writes every sample the APU produces to a 16-bit stereo WAV file, for
+/// regression-testing audio on machines without a sound card. The header's
+/// sizes are brought up to date after every write, so the file plays even
+/// if the run is cut short.
+#[derive(Debug)]
+pub struct WavDumper {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// Stereo frames written so far
+    frames: u32,
+}
+
+impl WavDumper {
+    /// Create `path`, replacing any file there, for samples at `sample_rate`
+    pub fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(b"RIFF")?;
+        file.write_all(&(HEADER_LEN - 8).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?; // fmt chunk size
+        file.write_all(&1u16.to_le_bytes())?; // PCM
+        file.write_all(&2u16.to_le_bytes())?; // Channels
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&sample_rate.saturating_mul(BYTES_PER_FRAME).to_le_bytes())?;
+        file.write_all(&4u16.to_le_bytes())?; // Block align
+        file.write_all(&16u16.to_le_bytes())?; // Bits per sample
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.flush()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            frames: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stereo frames written so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Append `samples`, clipped to -1.0..=1.0, and update the header
+    #[allow(clippy::cast_possible_truncation)] // Clamped to the i16 range
+    pub fn write(&mut self, samples: &[audio::Frame]) -> io::Result<()> {
+        let frames = u32::try_from(samples.len())
+            .ok()
+            .and_then(|count| self.frames.checked_add(count))
+            .filter(|&frames| frames <= (u32::MAX - HEADER_LEN) / BYTES_PER_FRAME)
+            .ok_or_else(|| io::Error::other("WAV file is full (4 GB)"))?;
+        for sample in samples.iter().flatten() {
+            let pcm = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16;
+            self.file.write_all(&pcm.to_le_bytes())?;
+        }
+        self.frames = frames;
+
+        let data_len = frames * BYTES_PER_FRAME;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(data_len + HEADER_LEN - 8).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(u64::from(HEADER_LEN) - 4))?;
+        self.file.write_all(&data_len.to_le_bytes())?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+impl FrameSink for WavDumper {
+    fn wants_frame(&self, _frame: u64) -> bool {
+        false
+    }
+
+    fn present(&mut self, _frame: u64, _image: &Frame) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn audio(&mut self, samples: &[audio::Frame]) -> Result<(), String> {
+        self.write(samples)
+            .map_err(|e| format!("{}: {e}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn writes_a_playable_wav_after_every_chunk() {
+        let path =
+            std::env::temp_dir().join(format!("gameboy-audiodump-{}.wav", std::process::id()));
+        let mut dumper = WavDumper::create(&path, 48_000).unwrap();
+        dumper.write(&[[0.0, 1.0], [-1.0, 2.0]]).unwrap();
+        dumper.write(&[[0.5, -0.5]]).unwrap();
+        let bytes = fs::read(&path);
+        fs::remove_file(&path).unwrap();
+
+        let bytes = bytes.unwrap();
+        assert_eq!(dumper.frames(), 3);
+        assert_eq!(bytes.len(), 44 + 3 * 4);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(bytes[4..8], (36u32 + 12).to_le_bytes());
+        assert_eq!(bytes[24..28], 48_000u32.to_le_bytes());
+        assert_eq!(bytes[40..44], 12u32.to_le_bytes());
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(samples, [0, 32767, -32767, 32767, 16384, -16384]);
+    }
+}
@@ -0,0 +1,88 @@
+/// Decides when to flush the battery save while a game is running.
+///
+/// Games write a save in a burst of external RAM writes. Flushing once the
+/// writes have gone quiet for `debounce` frames catches the finished save
+/// without writing half of one; `max_interval` bounds how long a game that
+/// never stops writing can go unsaved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Autosave {
+    debounce: u64,
+    max_interval: u64,
+    ram_writes: u64,          // Cartridge RAM write count at the last poll
+    last_write: u64,          // Frame the count last changed
+    dirty_since: Option<u64>, // Frame of the first write not yet flushed
+}
+
+impl Autosave {
+    /// Intervals are in frames (about 60 per second)
+    pub fn new(debounce: u64, max_interval: u64) -> Self {
+        Self {
+            debounce,
+            max_interval,
+            ram_writes: 0,
+            last_write: 0,
+            dirty_since: None,
+        }
+    }
+
+    /// Call once per frame with the cartridge's RAM write count. Returns
+    /// true when the save should be flushed now.
+    pub fn poll(&mut self, frame: u64, ram_writes: u64) -> bool {
+        if ram_writes != self.ram_writes {
+            self.ram_writes = ram_writes;
+            self.last_write = frame;
+            self.dirty_since.get_or_insert(frame);
+        }
+
+        let Some(dirty_since) = self.dirty_since else {
+            return false;
+        };
+        let quiet = frame - self.last_write >= self.debounce;
+        let overdue = frame - dirty_since >= self.max_interval;
+        if quiet || overdue {
+            self.dirty_since = None;
+        }
+        quiet || overdue
+    }
+
+    /// Treat `ram_writes` as already saved, e.g. right after loading a save
+    pub fn mark_saved(&mut self, ram_writes: u64) {
+        self.ram_writes = ram_writes;
+        self.dirty_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames at which `poll` asks for a flush, given the write count per frame
+    fn flushes(autosave: &mut Autosave, writes: &[u64]) -> Vec<u64> {
+        (0..)
+            .zip(writes)
+            .filter(|&(frame, &count)| autosave.poll(frame, count))
+            .map(|(frame, _)| frame)
+            .collect()
+    }
+
+    #[test]
+    fn flushes_once_writes_go_quiet() {
+        let mut autosave = Autosave::new(3, 100);
+        let writes = [0, 5, 9, 12, 12, 12, 12, 12, 12];
+        assert_eq!(flushes(&mut autosave, &writes), vec![6]);
+    }
+
+    #[test]
+    fn constant_writes_still_flush_at_max_interval() {
+        let mut autosave = Autosave::new(3, 4);
+        let writes: Vec<u64> = (1..=10).collect();
+        assert_eq!(flushes(&mut autosave, &writes), vec![4, 9]);
+    }
+
+    #[test]
+    fn nothing_to_flush_without_writes() {
+        let mut autosave = Autosave::new(0, 0);
+        autosave.mark_saved(7);
+        assert!(flushes(&mut autosave, &[7; 10]).is_empty());
+    }
+}
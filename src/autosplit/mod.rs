@@ -0,0 +1,373 @@
+use crate::memory::Memory;
+use crate::playtime::{emulated_time, format_duration};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Where `LiveSplit`'s server component listens unless told otherwise
+pub const LIVESPLIT_DEFAULT_ADDRESS: &str = "127.0.0.1:16834";
+
+/// What a condition does to the run when it triggers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitKind {
+    Start,
+    Split,
+    Reset,
+}
+
+impl SplitKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SplitKind::Start => "start",
+            SplitKind::Split => "split",
+            SplitKind::Reset => "reset",
+        }
+    }
+}
+
+impl FromStr for SplitKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "start" => Ok(SplitKind::Start),
+            "split" => Ok(SplitKind::Split),
+            "reset" => Ok(SplitKind::Reset),
+            _ => Err(format!(
+                "Unknown split kind: {s} (expected start, split or reset)"
+            )),
+        }
+    }
+}
+
+/// A byte of memory changing to `to`, from `from` or from anything else
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitCondition {
+    pub kind: SplitKind,
+    pub address: u16,
+    pub from: Option<u8>,
+    pub to: u8,
+    pub name: String,
+}
+
+impl SplitCondition {
+    /// Whether the byte going from `previous` to `current` over a frame
+    /// matches
+    fn triggered(&self, previous: u8, current: u8) -> bool {
+        current == self.to && previous != current && self.from.is_none_or(|from| from == previous)
+    }
+}
+
+/// A condition that triggered, timestamped with the frame it was seen at
+/// and the emulated time since the run started
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitEvent {
+    pub kind: SplitKind,
+    pub name: String,
+    pub frame: u64,
+    pub time: Duration,
+}
+
+impl SplitEvent {
+    /// The `LiveSplit` Server commands for this event, each ending in CRLF.
+    /// Game time is set from emulated time, so the timer shows the same
+    /// splits however fast the emulator ran.
+    pub fn livesplit_commands(&self) -> String {
+        match self.kind {
+            SplitKind::Start => {
+                "starttimer\r\ninitgametime\r\npausegametime\r\nsetgametime 0:00:00.000\r\n"
+                    .to_string()
+            }
+            SplitKind::Split => format!("setgametime {}\r\nsplit\r\n", game_time(self.time)),
+            SplitKind::Reset => "reset\r\n".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SplitEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "frame {}  {}  {} {}",
+            self.frame,
+            game_time(self.time),
+            self.kind.name(),
+            self.name
+        )
+    }
+}
+
+/// `H:MM:SS.mmm`, as `LiveSplit`'s `setgametime` takes it
+pub fn game_time(time: Duration) -> String {
+    format!("{}.{:03}", format_duration(time), time.subsec_millis())
+}
+
+/// Speedrun autosplitter: watches memory once per frame for the value
+/// changes a file of conditions describes, and reports run starts, splits
+/// and resets. Checking only at frame boundaries keeps the result the same
+/// on every replay of the same inputs.
+///
+/// Split conditions are taken in file order, one per segment: only the
+/// next one is watched, and a reset goes back to the first. Start
+/// conditions only count while no run is going, resets only during one.
+///
+/// The file has one condition per line: a kind, a hex address, the value
+/// before (`*` for any) and after, and an optional name. Blank lines and
+/// `#` comments are ignored.
+///
+/// ```text
+/// # kind  address  from  to  name
+/// start   C0A0     00    01  new game
+/// split   D05C     *     03  first badge
+/// reset   C0A0     01    00
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Autosplitter {
+    conditions: Vec<SplitCondition>,
+    /// Each condition's byte at the last poll; none before the first
+    previous: Option<Vec<u8>>,
+    /// Frame the current run started at
+    started: Option<u64>,
+    /// Split conditions passed in the current run
+    splits_done: usize,
+}
+
+fn parse_hex(text: &str) -> Option<u16> {
+    let digits = text
+        .trim_start_matches("0x")
+        .trim_start_matches("0X")
+        .trim_start_matches('$');
+    u16::from_str_radix(digits, 16).ok()
+}
+
+impl Autosplitter {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut conditions = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {message}", index + 1);
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [kind, address, from, to, ref name @ ..] = parts[..] else {
+                return Err(error("expected: kind address from to [name]".to_string()));
+            };
+            let kind: SplitKind = kind.parse().map_err(error)?;
+            let address =
+                parse_hex(address).ok_or_else(|| error(format!("invalid address: {address}")))?;
+            let value = |text: &str| {
+                parse_hex(text)
+                    .and_then(|value| u8::try_from(value).ok())
+                    .ok_or_else(|| error(format!("invalid byte: {text}")))
+            };
+            let from = if from == "*" {
+                None
+            } else {
+                Some(value(from)?)
+            };
+            let to = value(to)?;
+            let name = if name.is_empty() {
+                kind.name().to_string()
+            } else {
+                name.join(" ")
+            };
+            conditions.push(SplitCondition {
+                kind,
+                address,
+                from,
+                to,
+                name,
+            });
+        }
+        Ok(Self {
+            conditions,
+            ..Self::default()
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Conditions in file order
+    pub fn conditions(&self) -> &[SplitCondition] {
+        &self.conditions
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Call once per frame with the frame number. Returns the events the
+    /// memory changes since the last call triggered, in file order; the
+    /// first call only records the starting values.
+    pub fn poll(&mut self, memory: &Memory, frame: u64) -> Vec<SplitEvent> {
+        let current: Vec<u8> = self
+            .conditions
+            .iter()
+            .map(|condition| memory.peek(condition.address))
+            .collect();
+        let Some(previous) = self.previous.replace(current.clone()) else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        // Split conditions seen so far this poll, to find the next one
+        let mut splits = 0;
+        for (condition, (&before, &after)) in
+            self.conditions.iter().zip(previous.iter().zip(&current))
+        {
+            let applies = match condition.kind {
+                SplitKind::Start => self.started.is_none(),
+                SplitKind::Split => {
+                    splits += 1;
+                    self.started.is_some() && splits == self.splits_done + 1
+                }
+                SplitKind::Reset => self.started.is_some(),
+            };
+            if !applies || !condition.triggered(before, after) {
+                continue;
+            }
+            match condition.kind {
+                SplitKind::Start => {
+                    self.started = Some(frame);
+                    self.splits_done = 0;
+                }
+                SplitKind::Split => self.splits_done += 1,
+                SplitKind::Reset => self.started = None,
+            }
+            events.push(SplitEvent {
+                kind: condition.kind,
+                name: condition.name.clone(),
+                frame,
+                time: emulated_time(frame.saturating_sub(self.started.unwrap_or(frame))),
+            });
+        }
+        events
+    }
+}
+
+/// Client for `LiveSplit`'s server component, which drives the timer from
+/// commands sent over TCP
+pub struct LiveSplit {
+    stream: TcpStream,
+}
+
+impl LiveSplit {
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream })
+    }
+
+    pub fn send(&mut self, event: &SplitEvent) -> io::Result<()> {
+        self.stream.write_all(event.livesplit_commands().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    const SPLITS: &str = "\
+        # kind address from to name\n\
+        start C000 00 01 new game\n\
+        split $C001 * 02 first boss\n\
+        split C001 02 03\n\
+        reset C000 01 00\n";
+
+    /// Poll after setting each (address, value), one frame apiece from 1
+    fn run(splitter: &mut Autosplitter, writes: &[(u16, u8)]) -> Vec<(u64, SplitKind, String)> {
+        let mut memory = Memory::default();
+        splitter.poll(&memory, 0);
+        let mut events = Vec::new();
+        for (frame, &(address, value)) in (1..).zip(writes) {
+            memory.write_byte(address, value);
+            let polled = splitter.poll(&memory, frame);
+            events.extend(
+                polled
+                    .into_iter()
+                    .map(|event| (event.frame, event.kind, event.name)),
+            );
+        }
+        events
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let splitter = Autosplitter::parse(SPLITS).unwrap();
+        let conditions = splitter.conditions();
+        assert_eq!(conditions.len(), 4);
+        assert_eq!(conditions[1].address, 0xC001);
+        assert_eq!((conditions[1].from, conditions[1].to), (None, 0x02));
+        assert_eq!(conditions[1].name, "first boss");
+        assert_eq!(conditions[2].name, "split");
+        assert!(Autosplitter::parse("split C000 100 01").is_err());
+        assert!(Autosplitter::parse("pause C000 00 01").is_err());
+        assert!(Autosplitter::parse("start C000 00").is_err());
+    }
+
+    #[test]
+    fn splits_in_order_within_a_run() {
+        let mut splitter = Autosplitter::parse(SPLITS).unwrap();
+        let events = run(
+            &mut splitter,
+            &[
+                // Before the run starts, splits do not count
+                (0xC001, 0x02),
+                (0xC001, 0x00),
+                (0xC000, 0x01),
+                (0xC001, 0x03),
+                (0xC001, 0x02),
+                (0xC001, 0x03),
+                (0xC000, 0x00),
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                (3, SplitKind::Start, "new game".to_string()),
+                (5, SplitKind::Split, "first boss".to_string()),
+                (6, SplitKind::Split, "split".to_string()),
+                (7, SplitKind::Reset, "reset".to_string()),
+            ]
+        );
+        assert!(!splitter.is_running());
+    }
+
+    #[test]
+    fn sends_livesplit_commands_with_game_time() {
+        let event = SplitEvent {
+            kind: SplitKind::Split,
+            name: "first boss".to_string(),
+            frame: 4000,
+            time: Duration::from_millis(62_075),
+        };
+        assert_eq!(
+            event.to_string(),
+            "frame 4000  0:01:02.075  split first boss"
+        );
+
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = LiveSplit::connect(&server.local_addr().unwrap().to_string()).unwrap();
+        client.send(&event).unwrap();
+        drop(client);
+        let mut received = String::new();
+        server
+            .accept()
+            .unwrap()
+            .0
+            .read_to_string(&mut received)
+            .unwrap();
+        assert_eq!(received, "setgametime 0:01:02.075\r\nsplit\r\n");
+    }
+}
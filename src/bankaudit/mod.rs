@@ -0,0 +1,290 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Bytes in one ROM bank
+const BANK_SIZE: usize = 0x4000;
+
+/// Out-of-range addresses listed in the report; the rest are counted
+const MAX_LISTED: usize = 16;
+
+/// How the CPU touched a ROM byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AccessKind {
+    Read,
+    /// Fetched as the first byte of an instruction
+    Execute,
+}
+
+impl AccessKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            AccessKind::Read => "read",
+            AccessKind::Execute => "execute",
+        }
+    }
+}
+
+/// A ROM address accessed in a bank the ROM does not have, which the
+/// cartridge answers with 0xFF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange {
+    pub bank: usize,
+    /// CPU address, 0x0000-0x7FFF
+    pub address: u16,
+    pub kind: AccessKind,
+    /// The instruction that first made the access
+    pub pc: u16,
+    pub count: u64,
+}
+
+/// Bytes of one bank seen, one bit per byte
+#[derive(Debug, Clone)]
+struct Touched {
+    read: Vec<u64>,
+    executed: Vec<u64>,
+}
+
+impl Touched {
+    fn new() -> Self {
+        Self {
+            read: vec![0; BANK_SIZE / 64],
+            executed: vec![0; BANK_SIZE / 64],
+        }
+    }
+
+    fn mark(&mut self, offset: usize, kind: AccessKind) {
+        let bits = match kind {
+            AccessKind::Read => &mut self.read,
+            AccessKind::Execute => &mut self.executed,
+        };
+        bits[offset / 64] |= 1 << (offset % 64);
+    }
+}
+
+/// Bytes of one ROM bank the CPU read or executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankCoverage {
+    pub bank: usize,
+    pub read: u32,
+    pub executed: u32,
+}
+
+/// Validation of ROM banking: records every (bank, address) pair the CPU
+/// reads or executes, and flags those outside the ROM the header declares
+/// or past the end of the file. The cartridge quietly returns 0xFF for
+/// these, so a game doing it points at a banking bug in the emulator or a
+/// truncated or overdumped ROM.
+///
+/// The audit only watches: it is not part of save states and survives
+/// resets and state loads, so rewinding, run-ahead and netplay play out
+/// exactly as without it. Reads come in through `&Memory`, hence the cells.
+#[derive(Debug, Clone)]
+pub struct BankAudit {
+    declared_banks: usize,
+    file_size: usize,
+    touched: RefCell<BTreeMap<usize, Touched>>,
+    out_of_range: RefCell<BTreeMap<(usize, u16, AccessKind), OutOfRange>>,
+    /// PC of the instruction executing, to blame reads on
+    pc: Cell<u16>,
+}
+
+impl BankAudit {
+    /// Audit against `declared_banks` 16KB banks, as the header says, and a
+    /// ROM file of `file_size` bytes
+    pub fn new(declared_banks: usize, file_size: usize) -> Self {
+        Self {
+            declared_banks,
+            file_size,
+            touched: RefCell::new(BTreeMap::new()),
+            out_of_range: RefCell::new(BTreeMap::new()),
+            pc: Cell::new(0),
+        }
+    }
+
+    /// A CPU read of 0x0000-0x7FFF with ROM bank `rom_bank` mapped at
+    /// 0x4000. Other addresses are ignored.
+    pub fn record_read(&self, address: u16, rom_bank: usize) {
+        self.record(address, rom_bank, AccessKind::Read);
+    }
+
+    /// The CPU starting the instruction at `pc`, with ROM bank `rom_bank`
+    /// mapped at 0x4000
+    pub fn record_execute(&self, pc: u16, rom_bank: usize) {
+        self.pc.set(pc);
+        self.record(pc, rom_bank, AccessKind::Execute);
+    }
+
+    fn record(&self, address: u16, rom_bank: usize, kind: AccessKind) {
+        let (bank, offset) = match address {
+            0x0000..=0x3FFF => (0, usize::from(address)),
+            0x4000..=0x7FFF => (rom_bank, usize::from(address - 0x4000)),
+            _ => return,
+        };
+        self.touched
+            .borrow_mut()
+            .entry(bank)
+            .or_insert_with(Touched::new)
+            .mark(offset, kind);
+        if bank < self.declared_banks && bank * BANK_SIZE + offset < self.file_size {
+            return;
+        }
+        self.out_of_range
+            .borrow_mut()
+            .entry((bank, address, kind))
+            .or_insert(OutOfRange {
+                bank,
+                address,
+                kind,
+                pc: self.pc.get(),
+                count: 0,
+            })
+            .count += 1;
+    }
+
+    /// Banks the header declares
+    pub fn declared_banks(&self) -> usize {
+        self.declared_banks
+    }
+
+    pub fn file_size(&self) -> usize {
+        self.file_size
+    }
+
+    /// Bytes read and executed in each bank touched, lowest bank first
+    pub fn coverage(&self) -> Vec<BankCoverage> {
+        let count = |bits: &[u64]| bits.iter().map(|word| word.count_ones()).sum();
+        self.touched
+            .borrow()
+            .iter()
+            .map(|(&bank, touched)| BankCoverage {
+                bank,
+                read: count(&touched.read),
+                executed: count(&touched.executed),
+            })
+            .collect()
+    }
+
+    /// Every distinct out-of-range access, by bank then address
+    pub fn out_of_range(&self) -> Vec<OutOfRange> {
+        self.out_of_range.borrow().values().copied().collect()
+    }
+
+    /// No out-of-range accesses, and a file the size the header declares
+    pub fn is_clean(&self) -> bool {
+        self.out_of_range.borrow().is_empty() && self.file_size == self.declared_size()
+    }
+
+    fn declared_size(&self) -> usize {
+        self.declared_banks * BANK_SIZE
+    }
+
+    /// Why an access to `bank` and `address` is out of range
+    fn reason(&self, bank: usize, address: u16) -> &'static str {
+        if bank >= self.declared_banks {
+            "bank past the header's ROM size"
+        } else if address < 0x4000 || bank * BANK_SIZE < self.file_size {
+            "past the end of the file"
+        } else {
+            "bank past the end of the file"
+        }
+    }
+}
+
+/// The ROM size check, bytes touched per bank, then the out-of-range
+/// accesses
+impl fmt::Display for BankAudit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ROM: header declares {} banks ({} KB), file is {} KB",
+            self.declared_banks,
+            self.declared_size() / 1024,
+            self.file_size / 1024
+        )?;
+        if self.file_size < self.declared_size() {
+            write!(f, " (truncated?)")?;
+        } else if self.file_size > self.declared_size() {
+            write!(f, " (overdump?)")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "    bank {:>10} {:>10}", "read", "executed")?;
+        for usage in self.coverage() {
+            writeln!(
+                f,
+                "{:>8} {:>10} {:>10}",
+                usage.bank, usage.read, usage.executed
+            )?;
+        }
+
+        let out_of_range = self.out_of_range();
+        if out_of_range.is_empty() {
+            return write!(f, "No out-of-range accesses");
+        }
+        write!(
+            f,
+            "{} out-of-range addresses (read as 0xFF):",
+            out_of_range.len()
+        )?;
+        for access in out_of_range.iter().take(MAX_LISTED) {
+            write!(
+                f,
+                "\n  bank {:>3} {:04X} {:<7} x{:<6} first from PC {:04X}, {}",
+                access.bank,
+                access.address,
+                access.kind.name(),
+                access.count,
+                access.pc,
+                self.reason(access.bank, access.address)
+            )?;
+        }
+        if out_of_range.len() > MAX_LISTED {
+            write!(f, "\n  ... and {} more", out_of_range.len() - MAX_LISTED)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_banks_past_the_header_and_the_file() {
+        // Header says 4 banks, the file holds 3 and a half
+        let audit = BankAudit::new(4, 3 * BANK_SIZE + 0x2000);
+        audit.record_execute(0x0150, 1);
+        audit.record_read(0x4000, 2);
+        audit.record_read(0x7000, 3); // Past the end of the file
+        audit.record_execute(0x4100, 5); // Past the header's size
+        audit.record_read(0x4101, 5);
+        audit.record_read(0x4101, 5);
+        audit.record_read(0xC000, 9); // Not ROM
+
+        let banks: Vec<usize> = audit.coverage().iter().map(|usage| usage.bank).collect();
+        assert_eq!(banks, [0, 2, 3, 5]);
+        assert_eq!(audit.coverage()[3].executed, 1);
+
+        let flagged = audit.out_of_range();
+        assert_eq!(flagged.len(), 3);
+        assert_eq!((flagged[0].bank, flagged[0].address), (3, 0x7000));
+        assert_eq!(flagged[0].pc, 0x0150);
+        assert_eq!(flagged[1].kind, AccessKind::Execute);
+        assert_eq!((flagged[2].count, flagged[2].pc), (2, 0x4100));
+        assert!(!audit.is_clean());
+
+        let report = audit.to_string();
+        assert!(report.contains("(truncated?)"));
+        assert!(report.contains("bank   3 7000 read    x1      first from PC 0150, past the end"));
+        assert!(report.contains("bank past the header's ROM size"));
+    }
+
+    #[test]
+    fn in_range_accesses_are_clean() {
+        let audit = BankAudit::new(2, 2 * BANK_SIZE);
+        audit.record_execute(0x0100, 1);
+        audit.record_read(0x7FFF, 1);
+        assert!(audit.is_clean());
+        assert!(audit.to_string().ends_with("No out-of-range accesses"));
+    }
+}
@@ -0,0 +1,227 @@
+use std::cell::Cell;
+use std::fmt;
+
+/// Width of the longest histogram bar, in characters
+const BAR_WIDTH: u64 = 32;
+
+/// How much one bank was used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BankUsage {
+    /// Times the mapper switched to this bank
+    pub selects: u64,
+    pub reads: u64,
+    /// Always 0 for ROM banks
+    pub writes: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Counts {
+    selects: u64,
+    reads: Cell<u64>,
+    writes: u64,
+}
+
+impl Counts {
+    fn usage(&self) -> BankUsage {
+        BankUsage {
+            selects: self.selects,
+            reads: self.reads.get(),
+            writes: self.writes,
+        }
+    }
+}
+
+/// Per-bank usage of the cartridge, for checking a mapper implementation
+/// against what a game expects and for homebrew authors laying out banks.
+///
+/// Counts CPU reads of each ROM bank, reads and writes of each RAM bank,
+/// and how often the mapper switched to each. Mapper activity is counted by
+/// register range (0x0000, 0x2000, 0x4000 and 0x6000, 0x2000 bytes each,
+/// whatever the registers mean on a given MBC), along with bank writes that
+/// selected the bank already mapped, which a game could leave out.
+///
+/// Reads come in through `&Memory`, hence the cells.
+#[derive(Debug, Clone)]
+pub struct BankStats {
+    rom: Vec<Counts>,
+    ram: Vec<Counts>,
+    mapper_writes: [u64; 4],
+    reselects: u64,
+}
+
+impl BankStats {
+    #[allow(clippy::similar_names)]
+    pub fn new(rom_banks: usize, ram_banks: usize) -> Self {
+        Self {
+            rom: vec![Counts::default(); rom_banks],
+            ram: vec![Counts::default(); ram_banks],
+            mapper_writes: [0; 4],
+            reselects: 0,
+        }
+    }
+
+    /// A CPU read of cartridge space with `banks` (ROM, RAM) mapped. Banks
+    /// past the end of the cartridge are not counted.
+    #[allow(clippy::similar_names)]
+    pub fn record_read(&self, address: u16, (rom_bank, ram_bank): (usize, usize)) {
+        let counts = match address {
+            0x0000..=0x3FFF => self.rom.first(),
+            0x4000..=0x7FFF => self.rom.get(rom_bank),
+            0xA000..=0xBFFF => self.ram.get(ram_bank),
+            _ => None,
+        };
+        if let Some(counts) = counts {
+            counts.reads.set(counts.reads.get() + 1);
+        }
+    }
+
+    /// A CPU write to external RAM with RAM bank `bank` mapped
+    pub fn record_ram_write(&mut self, bank: usize) {
+        if let Some(counts) = self.ram.get_mut(bank) {
+            counts.writes += 1;
+        }
+    }
+
+    /// A write to the mapper registers (0x0000-0x7FFF) that changed the
+    /// mapped (ROM, RAM) banks from `before` to `after`
+    pub fn record_mapper_write(
+        &mut self,
+        address: u16,
+        before: (usize, usize),
+        after: (usize, usize),
+    ) {
+        self.mapper_writes[usize::from(address >> 13) & 0x03] += 1;
+        if after.0 != before.0
+            && let Some(counts) = self.rom.get_mut(after.0)
+        {
+            counts.selects += 1;
+        }
+        if after.1 != before.1
+            && let Some(counts) = self.ram.get_mut(after.1)
+        {
+            counts.selects += 1;
+        }
+        if (0x2000..0x6000).contains(&address) && before == after {
+            self.reselects += 1;
+        }
+    }
+
+    /// Usage of each ROM bank, bank 0 first
+    pub fn rom_usage(&self) -> Vec<BankUsage> {
+        self.rom.iter().map(Counts::usage).collect()
+    }
+
+    /// Usage of each RAM bank, bank 0 first
+    pub fn ram_usage(&self) -> Vec<BankUsage> {
+        self.ram.iter().map(Counts::usage).collect()
+    }
+
+    /// Writes to each mapper register range, 0x0000-0x1FFF first
+    pub fn mapper_writes(&self) -> [u64; 4] {
+        self.mapper_writes
+    }
+
+    /// Writes to the bank registers (0x2000-0x5FFF) that left the mapped
+    /// banks as they were
+    pub fn reselects(&self) -> u64 {
+        self.reselects
+    }
+}
+
+/// One table with a bar per bank, scaled to the busiest bank
+fn write_table(f: &mut fmt::Formatter, kind: &str, banks: &[BankUsage]) -> fmt::Result {
+    let accesses = |usage: &BankUsage| usage.reads + usage.writes;
+    let busiest = banks.iter().map(accesses).max().unwrap_or(0).max(1);
+    writeln!(
+        f,
+        "{kind} bank {:>10} {:>12} {:>12}",
+        "selects", "reads", "writes"
+    )?;
+    for (bank, usage) in banks.iter().enumerate() {
+        let bar = (accesses(usage) * BAR_WIDTH).div_ceil(busiest);
+        let line = format!(
+            "{bank:>8} {:>10} {:>12} {:>12} {}",
+            usage.selects,
+            usage.reads,
+            usage.writes,
+            "#".repeat(usize::try_from(bar).unwrap_or(0))
+        );
+        writeln!(f, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Histograms of the ROM and RAM banks, then the mapper writes
+impl fmt::Display for BankStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_table(f, "ROM", &self.rom_usage())?;
+        if !self.ram.is_empty() {
+            write_table(f, "RAM", &self.ram_usage())?;
+        }
+        let [enable, low, high, mode] = self.mapper_writes;
+        write!(
+            f,
+            "Mapper writes: 0000-1FFF {enable}, 2000-3FFF {low}, 4000-5FFF {high}, 6000-7FFF {mode} ({} reselected the mapped bank)",
+            self.reselects
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_count_against_the_mapped_bank() {
+        let stats = BankStats::new(4, 1);
+        stats.record_read(0x0150, (2, 0));
+        stats.record_read(0x4000, (2, 0));
+        stats.record_read(0x7FFF, (2, 0));
+        stats.record_read(0xA000, (2, 0));
+        stats.record_read(0x4000, (9, 0)); // Past the end of the ROM
+        stats.record_read(0xC000, (2, 0));
+
+        let reads: Vec<u64> = stats.rom_usage().iter().map(|usage| usage.reads).collect();
+        assert_eq!(reads, [1, 0, 2, 0]);
+        assert_eq!(stats.ram_usage()[0].reads, 1);
+    }
+
+    #[test]
+    fn mapper_writes_count_switches_and_reselects() {
+        let mut stats = BankStats::new(4, 4);
+        stats.record_mapper_write(0x0000, (1, 0), (1, 0)); // RAM enable
+        stats.record_mapper_write(0x2000, (1, 0), (3, 0));
+        stats.record_mapper_write(0x2100, (3, 0), (3, 0));
+        stats.record_mapper_write(0x4000, (3, 0), (3, 2));
+        stats.record_ram_write(2);
+
+        assert_eq!(stats.mapper_writes(), [1, 2, 1, 0]);
+        assert_eq!(stats.reselects(), 1, "RAM enable is not a bank write");
+        assert_eq!(stats.rom_usage()[3].selects, 1);
+        assert_eq!(
+            stats.ram_usage()[2],
+            BankUsage {
+                selects: 1,
+                reads: 0,
+                writes: 1
+            }
+        );
+    }
+
+    #[test]
+    fn histogram_scales_to_the_busiest_bank() {
+        let stats = BankStats::new(2, 0);
+        for _ in 0..4 {
+            stats.record_read(0x0000, (1, 0));
+        }
+        stats.record_read(0x4000, (1, 0));
+
+        let report = stats.to_string();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4, "No RAM table without RAM");
+        assert!(lines[1].ends_with(&format!(" {}", "#".repeat(32))));
+        assert!(lines[2].ends_with(" 1            0 ########"));
+        assert!(!report.contains(" \n"), "No trailing spaces");
+        assert!(lines[3].starts_with("Mapper writes: 0000-1FFF 0,"));
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+
+/// RTC footer length written by BGB, VBA-M and `SameBoy` (64-bit timestamp)
+pub const RTC_FOOTER_LEN: usize = 48;
+/// RTC footer length written by older VBA builds (32-bit timestamp)
+pub const RTC_FOOTER_LEN_32: usize = 44;
+
+/// MBC3 clock registers in the common `.sav` footer layout: seconds,
+/// minutes, hours, day low, day high/flags, each stored as a little-endian
+/// u32, first the live values then the latched ones, then the UNIX time
+/// the save was written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RtcFooter {
+    pub current: [u8; 5],
+    pub latched: [u8; 5],
+    pub timestamp: u64,
+}
+
+impl RtcFooter {
+    fn parse(data: &[u8]) -> Self {
+        let register = |index: usize| data[index * 4];
+        let timestamp = if data.len() == RTC_FOOTER_LEN {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&data[40..48]);
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from(u32::from_le_bytes([data[40], data[41], data[42], data[43]]))
+        };
+        Self {
+            current: std::array::from_fn(register),
+            latched: std::array::from_fn(|index| register(index + 5)),
+            timestamp,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        for &register in self.current.iter().chain(&self.latched) {
+            out.extend_from_slice(&u32::from(register).to_le_bytes());
+        }
+        out.extend_from_slice(&self.timestamp.to_le_bytes());
+    }
+}
+
+/// Battery-backed cartridge RAM in the `.sav` layout shared by most
+/// emulators: the raw RAM image, optionally followed by an RTC footer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatterySave {
+    pub ram: Vec<u8>,
+    pub rtc: Option<RtcFooter>,
+}
+
+impl BatterySave {
+    /// Parse a save for a cartridge with `ram_size` bytes of RAM. Saves with
+    /// either footer size are accepted; anything else must match exactly.
+    pub fn parse(data: &[u8], ram_size: usize) -> Result<Self, String> {
+        if data.len() < ram_size {
+            return Err(format!(
+                "Save is {} bytes, cartridge has {ram_size} bytes of RAM",
+                data.len()
+            ));
+        }
+        let (ram, footer) = data.split_at(ram_size);
+        let rtc = match footer.len() {
+            0 => None,
+            RTC_FOOTER_LEN | RTC_FOOTER_LEN_32 => Some(RtcFooter::parse(footer)),
+            extra => {
+                return Err(format!(
+                    "Save has {extra} unexpected bytes after {ram_size} bytes of RAM"
+                ));
+            }
+        };
+        Ok(Self {
+            ram: ram.to_vec(),
+            rtc,
+        })
+    }
+
+    /// Serialize, always using the 48-byte footer when there is a clock
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.ram.clone();
+        if let Some(rtc) = &self.rtc {
+            rtc.write(&mut out);
+        }
+        out
+    }
+
+    pub fn load(path: &Path, ram_size: usize) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&data, ram_size).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn footer(timestamp_len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for register in [30, 15, 12, 200, 1, 29, 15, 12, 200, 1] {
+            data.extend_from_slice(&u32::to_le_bytes(register));
+        }
+        data.extend_from_slice(&0x6543_2100_u64.to_le_bytes()[..timestamp_len]);
+        data
+    }
+
+    #[test]
+    fn plain_ram_round_trips() {
+        let save = BatterySave::parse(&[1, 2, 3, 4], 4).unwrap();
+        assert_eq!(save.rtc, None);
+        assert_eq!(save.to_bytes(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn parses_bgb_rtc_footer() {
+        let mut data = vec![0xAA; 8];
+        data.extend(footer(8));
+        let save = BatterySave::parse(&data, 8).unwrap();
+
+        let rtc = save.rtc.unwrap();
+        assert_eq!(rtc.current, [30, 15, 12, 200, 1]);
+        assert_eq!(rtc.latched, [29, 15, 12, 200, 1]);
+        assert_eq!(rtc.timestamp, 0x6543_2100);
+        assert_eq!(save.to_bytes(), data);
+    }
+
+    #[test]
+    fn old_vba_footer_is_upgraded_to_64_bit_timestamp() {
+        let mut data = vec![0xAA; 8];
+        data.extend(footer(4));
+        let save = BatterySave::parse(&data, 8).unwrap();
+
+        assert_eq!(save.rtc.unwrap().timestamp, 0x6543_2100);
+        assert_eq!(save.to_bytes().len(), 8 + RTC_FOOTER_LEN);
+    }
+
+    #[test]
+    fn wrong_size_is_an_error() {
+        assert!(BatterySave::parse(&[0; 4], 8).is_err());
+        assert!(BatterySave::parse(&[0; 10], 8).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::GameBoy;
+use crate::savestate::SaveState;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// A gameboy-doctor style reference log, one line per instruction, read on
+/// demand so traces of long-running games need not fit in memory
+pub struct Trace<R> {
+    reader: R,
+    offsets: Vec<u64>, // Start of each line
+}
+
+impl Trace<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead + Seek> Trace<R> {
+    /// Index the lines of `reader`
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut offsets = Vec::new();
+        let mut offset = reader.seek(SeekFrom::Start(0))?;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            if !line.trim_ascii().is_empty() {
+                offsets.push(offset);
+            }
+            offset += read as u64;
+        }
+        Ok(Self { reader, offsets })
+    }
+
+    /// Number of instructions in the trace
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// State before instruction `index`, trimmed
+    pub fn line(&mut self, index: usize) -> io::Result<String> {
+        self.reader.seek(SeekFrom::Start(self.offsets[index]))?;
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        Ok(line.trim().to_string())
+    }
+}
+
+/// The first instruction whose CPU state differs from the reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Instructions executed since power on
+    pub instruction: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BisectReport {
+    pub divergence: Option<Divergence>,
+    /// Save states taken on the way through the trace
+    pub checkpoints: usize,
+    /// Trace lines compared, far fewer than the trace's length
+    pub compared: usize,
+}
+
+/// Find where `game` first departs from `trace` without comparing every
+/// instruction: run through the trace taking a save state every `interval`
+/// instructions, binary-search the checkpoints for the first one that
+/// disagrees, then step through the region before it line by line.
+///
+/// Assumes a divergence persists once it happens, as a wrong register or
+/// flag almost always does. `game` must be powered on at the trace's first
+/// line and is left at the divergent instruction (or the end of the trace).
+pub fn bisect<R: BufRead + Seek>(
+    game: &mut GameBoy,
+    trace: &mut Trace<R>,
+    interval: usize,
+) -> io::Result<BisectReport> {
+    let interval = interval.max(1);
+    let mut checkpoints: Vec<SaveState> = Vec::new();
+    for index in 0..trace.len() {
+        if index.is_multiple_of(interval) {
+            checkpoints.push(game.save_state());
+        }
+        game.step();
+    }
+
+    let mut report = BisectReport {
+        divergence: None,
+        checkpoints: checkpoints.len(),
+        compared: 0,
+    };
+    // First checkpoint that disagrees with the trace, or past the last one
+    let (mut low, mut high) = (0, checkpoints.len());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        restore(game, &checkpoints[middle]);
+        let expected = trace.line(middle * interval)?;
+        report.compared += 1;
+        if game.trace_line() == expected {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    if checkpoints.is_empty() {
+        return Ok(report);
+    }
+
+    // Step from the last agreeing checkpoint up to and including the first
+    // disagreeing one
+    let start = low.saturating_sub(1) * interval;
+    let end = if low < checkpoints.len() {
+        low * interval + 1
+    } else {
+        trace.len()
+    };
+    restore(game, &checkpoints[low.saturating_sub(1)]);
+    for index in start..end {
+        let expected = trace.line(index)?;
+        report.compared += 1;
+        let actual = game.trace_line();
+        if actual != expected {
+            report.divergence = Some(Divergence {
+                instruction: index,
+                expected,
+                actual,
+            });
+            break;
+        }
+        game.step();
+    }
+    Ok(report)
+}
+
+fn restore(game: &mut GameBoy, state: &SaveState) {
+    game.load_state(state)
+        .expect("a state saved this run loads back");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::{Cartridge, test_rom};
+    use std::io::Cursor;
+
+    /// Game running INC A; JR -3 forever, powered on
+    fn game() -> GameBoy {
+        let mut game = GameBoy::new();
+        game.memory
+            .load_cartridge(Cartridge::from_rom(test_rom(&[0x3C, 0x18, 0xFD])).unwrap());
+        game.power_on();
+        game
+    }
+
+    fn reference(instructions: usize) -> Vec<String> {
+        let mut game = game();
+        (0..instructions)
+            .map(|_| {
+                let line = game.trace_line();
+                game.step();
+                line
+            })
+            .collect()
+    }
+
+    fn trace(lines: &[String]) -> Trace<Cursor<Vec<u8>>> {
+        Trace::new(Cursor::new(lines.join("\n").into_bytes())).unwrap()
+    }
+
+    #[test]
+    fn finds_the_first_divergent_instruction() {
+        let mut lines = reference(1000);
+        for line in &mut lines[384..] {
+            *line = line.replacen("SP:", "SP:F", 1);
+        }
+
+        let mut game = game();
+        let report = bisect(&mut game, &mut trace(&lines), 64).unwrap();
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.instruction, 384);
+        assert_eq!(divergence.expected, lines[384]);
+        assert_eq!(game.trace_line(), divergence.actual);
+        assert_eq!(report.checkpoints, 16);
+        assert!(report.compared < 100, "compared {}", report.compared);
+    }
+
+    #[test]
+    fn matching_run_has_no_divergence() {
+        let lines = reference(200);
+        let report = bisect(&mut game(), &mut trace(&lines), 50).unwrap();
+        assert_eq!(report.divergence, None);
+
+        let lines: Vec<String> = lines
+            .iter()
+            .map(|line| line.replacen("A:", "A:F", 1))
+            .collect();
+        let report = bisect(&mut game(), &mut trace(&lines), 50).unwrap();
+        assert_eq!(report.divergence.unwrap().instruction, 0);
+    }
+}
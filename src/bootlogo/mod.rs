@@ -0,0 +1,148 @@
+use crate::ioregs::Io;
+use crate::memory::Memory;
+use crate::savestate::{StateReader, StateWriter};
+
+/// Where the cartridge header keeps the logo the boot ROM scrolls
+pub const LOGO_START: usize = 0x0104;
+pub const LOGO_LEN: usize = 48;
+
+/// SCY when the logo starts: 100 lines below the screen, wrapped around to
+/// above it
+const START_SCY: u8 = 0x64;
+/// Frames the finished logo is held before the game starts
+const HOLD_FRAMES: u16 = 64;
+/// Frames from the first scroll to the game starting
+pub const LOGO_FRAMES: u16 = START_SCY as u16 + HOLD_FRAMES;
+
+/// The ® drawn to the right of the logo, one byte per row
+const REGISTERED: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+/// Tiles 1-24 hold the logo and tile 25 the ®
+const FIRST_TILE: u16 = 0x8010;
+const REGISTERED_TILE: u8 = 25;
+/// The logo's two tile map rows, 12 tiles each, and the ® after the first
+const TOP_ROW: u16 = 0x9904;
+const BOTTOM_ROW: u16 = 0x9924;
+const REGISTERED_MAP: u16 = 0x9910;
+
+/// Each bit of a nibble twice, so a 4-pixel logo row fills a tile row
+fn widen(nibble: u8) -> u8 {
+    (0..4).fold(0, |byte, bit| {
+        let pixel = (nibble >> bit) & 1;
+        byte | (pixel * 0b11) << (bit * 2)
+    })
+}
+
+/// The classic logo scroll, played in place of a boot ROM. The logo is
+/// read from the cartridge header and drawn by the PPU from VRAM, the way
+/// the DMG boot ROM does it: each logo pixel doubled into an 8x8 tile
+/// pixel block, tiles 1-24 in two rows at the middle of the map, and SCY
+/// counting down until the logo lands at line 64. The logo and ® are left
+/// in VRAM when the game starts, as some games expect.
+///
+/// The CPU does not run during the scroll; `GameBoy::step` only advances
+/// the rest of the hardware. Unlike the boot ROM, a bad logo or header
+/// checksum does not lock up.
+// TODO: Play the two-note chime once the APU exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogoScroll {
+    /// Frames since the scroll started
+    frame: u16,
+}
+
+impl LogoScroll {
+    /// Copy the logo in `logo` (the 48 header bytes) into VRAM and set up
+    /// the LCD for the first frame of the scroll
+    pub fn start(logo: &[u8; LOGO_LEN], memory: &mut Memory) -> Self {
+        memory.data[0x8000..=0x9FFF].fill(0);
+        // Each byte is two 4-pixel rows, each pair of bytes a tile
+        let rows = logo.iter().flat_map(|&byte| [byte >> 4, byte & 0x0F]);
+        let mut address = usize::from(FIRST_TILE);
+        for row in rows {
+            let pixels = widen(row);
+            // Two tile rows per logo row, colour 1 (low bit plane only)
+            memory.data[address] = pixels;
+            memory.data[address + 2] = pixels;
+            address += 4;
+        }
+        for row in REGISTERED {
+            memory.data[address] = row;
+            address += 2;
+        }
+
+        for tile in 0..12u8 {
+            memory.data[usize::from(TOP_ROW) + usize::from(tile)] = tile + 1;
+            memory.data[usize::from(BOTTOM_ROW) + usize::from(tile)] = tile + 13;
+        }
+        memory.data[usize::from(REGISTERED_MAP)] = REGISTERED_TILE;
+
+        let mut io = Io::new(memory);
+        io.set_scy(START_SCY);
+        // Black on white, whatever the game's palette will be
+        io.set_bgp(0xFC);
+        io.set_lcdc(0x91);
+        Self { frame: 0 }
+    }
+
+    /// Move to the next frame, returning false once the scroll is over and
+    /// the game should start
+    pub fn advance(&mut self, memory: &mut Memory) -> bool {
+        self.frame += 1;
+        let scy = START_SCY.saturating_sub(u8::try_from(self.frame).unwrap_or(u8::MAX));
+        Io::new(memory).set_scy(scy);
+        self.frame < LOGO_FRAMES
+    }
+
+    pub(crate) fn save_state(scroll: Option<Self>, state: &mut StateWriter) {
+        state.write_bool(scroll.is_some());
+        state.write_u16(scroll.map_or(0, |scroll| scroll.frame));
+    }
+
+    pub(crate) fn load_state(state: &mut StateReader) -> Result<Option<Self>, String> {
+        let playing = state.read_bool()?;
+        let frame = state.read_u16()?;
+        if frame >= LOGO_FRAMES {
+            return Err(format!(
+                "Save state logo scroll frame out of range: {frame}"
+            ));
+        }
+        Ok(playing.then_some(Self { frame }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logo_nibbles_become_doubled_tile_rows() {
+        assert_eq!(widen(0b1000), 0b1100_0000);
+        assert_eq!(widen(0b0101), 0b0011_0011);
+
+        let mut logo = [0; LOGO_LEN];
+        logo[0] = 0xF0;
+        logo[47] = 0x09;
+        let mut memory = Memory::new();
+        LogoScroll::start(&logo, &mut memory);
+        // Tile 1, rows 0 and 1 from the high nibble of the first byte
+        assert_eq!(memory.data[0x8010..0x8014], [0xFF, 0, 0xFF, 0]);
+        assert_eq!(memory.data[0x8014], 0);
+        // Tile 24's last two rows from the low nibble of the last byte
+        assert_eq!(memory.data[0x818C..0x8190], [0xC3, 0, 0xC3, 0]);
+        assert_eq!(memory.data[0x8190..0x8194], [0x3C, 0, 0x42, 0]);
+        assert_eq!(memory.data[0x9904], 1);
+        assert_eq!(memory.data[0x992F], 24);
+        assert_eq!(memory.data[0x9910], 25);
+    }
+
+    #[test]
+    fn scrolls_then_holds() {
+        let mut memory = Memory::new();
+        let mut scroll = LogoScroll::start(&[0; LOGO_LEN], &mut memory);
+        assert_eq!(Io::new(&mut memory).scy(), 0x64);
+        let frames = std::iter::from_fn(|| Some(scroll.advance(&mut memory)))
+            .take_while(|&playing| playing)
+            .count();
+        assert_eq!(frames + 1, usize::from(LOGO_FRAMES));
+        assert_eq!(Io::new(&mut memory).scy(), 0);
+    }
+}
@@ -1,8 +1,25 @@
+mod rom;
+
+use crate::memory::RamInit;
+use crate::savestate::{StateReader, StateWriter};
 use std::fs;
 use std::io;
 use std::path::Path;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+pub use rom::Rom;
+
+/// Logo bitmap the boot ROM compares against 0x0104-0x0133; real hardware
+/// locks up if it differs
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// External RAM sizes by header code (0x0149)
+const RAM_SIZES: [usize; 6] = [0, 2048, 8192, 32_768, 131_072, 65_536];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CartridgeType {
     RomOnly,
     Mbc1,
@@ -23,12 +40,45 @@ impl From<u8> for CartridgeType {
     }
 }
 
+impl CartridgeType {
+    /// Header code (0x0147)
+    pub fn code(self) -> u8 {
+        match self {
+            CartridgeType::RomOnly => 0x00,
+            CartridgeType::Mbc1 => 0x01,
+            CartridgeType::Mbc1Ram => 0x02,
+            CartridgeType::Mbc1RamBattery => 0x03,
+            CartridgeType::Unknown(code) => code,
+        }
+    }
+
+    /// The types this core emulates, in header order
+    pub const SUPPORTED: [CartridgeType; 4] = [
+        CartridgeType::RomOnly,
+        CartridgeType::Mbc1,
+        CartridgeType::Mbc1Ram,
+        CartridgeType::Mbc1RamBattery,
+    ];
+
+    /// Name as in the Pan Docs header table
+    pub fn name(self) -> &'static str {
+        match self {
+            CartridgeType::RomOnly => "ROM ONLY",
+            CartridgeType::Mbc1 => "MBC1",
+            CartridgeType::Mbc1Ram => "MBC1+RAM",
+            CartridgeType::Mbc1RamBattery => "MBC1+RAM+BATTERY",
+            CartridgeType::Unknown(_) => "unknown",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CartridgeHeader {
     pub title: String,
     pub cartridge_type: CartridgeType,
     pub rom_size: usize,
     pub ram_size: usize,
+    pub global_checksum: u16,
 }
 
 impl CartridgeHeader {
@@ -63,43 +113,120 @@ impl CartridgeHeader {
         let rom_size = rom_banks * 16384; // 16KB per bank
 
         // RAM size at 0x0149
-        let ram_size = match rom[0x0149] {
-            0x00 => 0,       // No RAM
-            0x01 => 2048,    // 2KB (unused)
-            0x02 => 8192,    // 8KB (1 bank)
-            0x03 => 32_768,  // 32KB (4 banks)
-            0x04 => 131_072, // 128KB (16 banks)
-            0x05 => 65_536,  // 64KB (8 banks)
-            _ => return Err(format!("Invalid RAM size: 0x{:02X}", rom[0x0149])),
-        };
+        // 0: none, 1: 2KB (unused), 2: 8KB (1 bank), 3: 32KB (4 banks),
+        // 4: 128KB (16 banks), 5: 64KB (8 banks)
+        let ram_size = *RAM_SIZES
+            .get(usize::from(rom[0x0149]))
+            .ok_or_else(|| format!("Invalid RAM size: 0x{:02X}", rom[0x0149]))?;
+
+        // Global checksum at 0x014E-0x014F (big-endian)
+        let global_checksum = u16::from_be_bytes([rom[0x014E], rom[0x014F]]);
 
         Ok(CartridgeHeader {
             title,
             cartridge_type,
             rom_size,
             ram_size,
+            global_checksum,
         })
     }
+
+    /// Write this header into a ROM image: the logo, title, cartridge type
+    /// and size codes, then both checksums. The global checksum is computed
+    /// over `rom` rather than taken from `global_checksum`, so write the
+    /// header last or call `fix_checksums` after further changes. The entry
+    /// point and other header bytes are left as they are.
+    #[allow(clippy::similar_names)]
+    pub fn write_into(&self, rom: &mut [u8]) -> Result<(), String> {
+        if rom.len() < 0x0150 {
+            return Err("ROM too small to contain valid header".to_string());
+        }
+        let title = self.title.as_bytes();
+        if title.len() > 16 {
+            return Err(format!("Title longer than 16 bytes: {:?}", self.title));
+        }
+        let rom_code = (0..=8_u8)
+            .find(|&code| 0x8000 << code == self.rom_size)
+            .ok_or_else(|| format!("Invalid ROM size: {} bytes", self.rom_size))?;
+        let ram_code = (0_u8..)
+            .zip(RAM_SIZES)
+            .find_map(|(code, size)| (size == self.ram_size).then_some(code))
+            .ok_or_else(|| format!("Invalid RAM size: {} bytes", self.ram_size))?;
+
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x0134..=0x0143].fill(0);
+        rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+        rom[0x0147] = self.cartridge_type.code();
+        rom[0x0148] = rom_code;
+        rom[0x0149] = ram_code;
+        fix_checksums(rom);
+        Ok(())
+    }
+}
+
+/// Header checksum of 0x0134-0x014C as the boot ROM computes it, to match
+/// 0x014D. `rom` must hold a header.
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[0x0134..=0x014C]
+        .iter()
+        .fold(0_u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1))
+}
+
+/// Sum of every byte but the global checksum itself (0x014E-0x014F)
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|&(address, _)| address != 0x014E && address != 0x014F)
+        .fold(0_u16, |sum, (_, &byte)| sum.wrapping_add(u16::from(byte)))
+}
+
+/// Store both checksums for the ROM's current contents, after patching it.
+/// `rom` must hold a header.
+pub fn fix_checksums(rom: &mut [u8]) {
+    rom[0x014D] = header_checksum(rom);
+    let sum = global_checksum(rom);
+    rom[0x014E..0x0150].copy_from_slice(&sum.to_be_bytes());
+}
+
+/// CRC-32 (IEEE) as used by zip/png and most ROM databases
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
 }
 
 pub struct Cartridge {
-    rom: Vec<u8>,
+    rom: Rom,
     ram: Vec<u8>,
     header: CartridgeHeader,
     rom_bank: usize, // Current ROM bank (for MBC)
     ram_bank: usize, // Current RAM bank (for MBC)
     ram_enabled: bool,
     banking_mode: u8, // 0 = ROM banking, 1 = RAM banking (MBC1)
+    ram_writes: u64,  // Changes to external RAM since load, for autosave
 }
 
 impl Cartridge {
-    #[allow(clippy::similar_names)]
     pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let rom = fs::read(path)?;
+        Self::load_from(fs::read(path)?)
+    }
 
-        let header = CartridgeHeader::from_rom(&rom)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    /// Like `load`, but maps the file rather than reading it (see `Rom::map`)
+    pub fn load_mapped<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::load_from(Rom::map(path.as_ref())?)
+    }
 
+    fn load_from(rom: impl Into<Rom>) -> io::Result<Self> {
+        let cartridge =
+            Self::from_rom(rom).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let header = &cartridge.header;
         println!("Loaded ROM: {}", header.title);
         println!("Type: {:?}", header.cartridge_type);
         println!(
@@ -109,6 +236,15 @@ impl Cartridge {
         );
         println!("RAM size: {} bytes", header.ram_size);
 
+        Ok(cartridge)
+    }
+
+    /// Build a cartridge from ROM bytes already in memory, without logging.
+    /// Passing a clone of another cartridge's `Rom` shares its bytes.
+    #[allow(clippy::similar_names)]
+    pub fn from_rom(rom: impl Into<Rom>) -> Result<Self, String> {
+        let rom = rom.into();
+        let header = CartridgeHeader::from_rom(&rom)?;
         let ram = vec![0; header.ram_size];
 
         Ok(Cartridge {
@@ -119,13 +255,15 @@ impl Cartridge {
             ram_bank: 0,
             ram_enabled: false,
             banking_mode: 0,
+            ram_writes: 0,
         })
     }
 
+    #[allow(clippy::collapsible_match)]
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
-            // ROM Bank 0 (0x0000-0x3FFF)
-            0x0000..=0x3FFF => self.rom[addr as usize],
+            // ROM Bank 0 (0x0000-0x3FFF), open bus past the end of a short ROM
+            0x0000..=0x3FFF => self.rom.get(addr as usize).copied().unwrap_or(0xFF),
 
             // ROM Bank 1-N (0x4000-0x7FFF) - switchable
             0x4000..=0x7FFF => {
@@ -157,18 +295,16 @@ impl Cartridge {
 
     pub fn write_byte(&mut self, addr: u16, value: u8) {
         match self.header.cartridge_type {
-            CartridgeType::RomOnly => {
-                todo!()
-            } // No writes for ROM-only
-
             CartridgeType::Mbc1 | CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery => {
                 self.write_mbc1(addr, value);
             }
 
-            CartridgeType::Unknown(_) => {} // Other MBC types not implemented yet
+            // No writes for ROM-only; other MBC types not implemented yet
+            CartridgeType::RomOnly | CartridgeType::Unknown(_) => {}
         }
     }
 
+    #[allow(clippy::collapsible_match)]
     fn write_mbc1(&mut self, addr: u16, value: u8) {
         match addr {
             // RAM Enable (0x0000-0x1FFF)
@@ -204,8 +340,9 @@ impl Cartridge {
             0xA000..=0xBFFF => {
                 if self.ram_enabled && !self.ram.is_empty() {
                     let offset = (self.ram_bank * 0x2000) + (addr as usize - 0xA000);
-                    if offset < self.ram.len() {
+                    if offset < self.ram.len() && self.ram[offset] != value {
                         self.ram[offset] = value;
+                        self.ram_writes += 1;
                     }
                 }
             }
@@ -217,4 +354,182 @@ impl Cartridge {
     pub fn header(&self) -> &CartridgeHeader {
         &self.header
     }
+
+    /// Return the mapper to its power-on state. External RAM is left intact
+    /// unless `ram` is given, in which case it is reinitialised.
+    pub fn reset(&mut self, ram: Option<RamInit>) {
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.ram_enabled = false;
+        self.banking_mode = 0;
+        if let Some(init) = ram {
+            init.fill(&mut self.ram);
+        }
+    }
+
+    /// The full ROM image
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Offset into the ROM image that `addr` (0x0000-0x7FFF) currently maps to
+    pub fn rom_offset(&self, addr: u16) -> Option<usize> {
+        let offset = match addr {
+            0x0000..=0x3FFF => usize::from(addr),
+            0x4000..=0x7FFF => self.rom_bank * 0x4000 + usize::from(addr - 0x4000),
+            _ => return None,
+        };
+        (offset < self.rom.len()).then_some(offset)
+    }
+
+    /// Currently mapped (ROM, RAM) banks
+    pub fn banks(&self) -> (usize, usize) {
+        (self.rom_bank, self.ram_bank)
+    }
+
+    /// Number of (ROM, RAM) banks, 16KB and 8KB each
+    #[allow(clippy::similar_names)]
+    pub fn bank_counts(&self) -> (usize, usize) {
+        (
+            self.header.rom_size / 0x4000,
+            self.header.ram_size.div_ceil(0x2000),
+        )
+    }
+
+    /// Bring the mapped banks back within the cartridge, as the mapper
+    /// masks bank numbers to the chips' size on hardware
+    #[allow(clippy::similar_names)]
+    pub(crate) fn wrap_banks(&mut self) {
+        let (rom_banks, ram_banks) = self.bank_counts();
+        self.rom_bank %= rom_banks.max(1);
+        self.ram_bank %= ram_banks.max(1);
+    }
+
+    /// Whether writes to the ROM area reach a memory bank controller
+    pub fn has_mbc(&self) -> bool {
+        self.header.cartridge_type != CartridgeType::RomOnly
+    }
+
+    /// Whether external RAM survives power off and belongs in a `.sav` file
+    pub fn has_battery(&self) -> bool {
+        matches!(self.header.cartridge_type, CartridgeType::Mbc1RamBattery)
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// Number of writes that changed external RAM. Only ever increases, so
+    /// callers can compare it against an earlier reading.
+    pub fn ram_writes(&self) -> u64 {
+        self.ram_writes
+    }
+
+    /// Replace external RAM with a battery save of the same size
+    pub fn load_ram(&mut self, ram: &[u8]) -> Result<(), String> {
+        if ram.len() != self.ram.len() {
+            return Err(format!(
+                "Save has {} bytes of RAM, cartridge has {}",
+                ram.len(),
+                self.ram.len()
+            ));
+        }
+        self.ram.copy_from_slice(ram);
+        Ok(())
+    }
+
+    /// CRC-32 of the full ROM image, used to key caches to an exact dump
+    pub fn rom_hash(&self) -> u32 {
+        crc32(&self.rom)
+    }
+
+    /// Save banking registers and external RAM (the ROM is not included)
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(u32::try_from(self.rom_bank).unwrap_or(u32::MAX));
+        state.write_u32(u32::try_from(self.ram_bank).unwrap_or(u32::MAX));
+        state.write_bool(self.ram_enabled);
+        state.write_u8(self.banking_mode);
+        state.write_bytes(&self.ram);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank = state.read_u32()? as usize;
+        self.ram_bank = state.read_u32()? as usize;
+        self.ram_enabled = state.read_bool()?;
+        self.banking_mode = state.read_u8()?;
+        state.read_into(&mut self.ram)?;
+        // The restored RAM no longer matches what is on disk
+        self.ram_writes += 1;
+        Ok(())
+    }
+}
+
+/// 32 KB ROM-only image running `code` from the entry point
+#[cfg(test)]
+pub(crate) fn test_rom(code: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0100 + code.len()].copy_from_slice(code);
+    rom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_headers_parse_and_verify() {
+        let header = CartridgeHeader {
+            title: "HOMEBREW".to_string(),
+            cartridge_type: CartridgeType::Mbc1RamBattery,
+            rom_size: 0x1_0000,
+            ram_size: 8192,
+            global_checksum: 0,
+        };
+        let mut rom = vec![0; 0x1_0000];
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        rom[0x0134..0x0144].fill(b'X');
+        header.write_into(&mut rom).unwrap();
+
+        let parsed = CartridgeHeader::from_rom(&rom).unwrap();
+        assert_eq!(parsed.title, "HOMEBREW");
+        assert_eq!(parsed.cartridge_type, CartridgeType::Mbc1RamBattery);
+        assert_eq!((parsed.rom_size, parsed.ram_size), (0x1_0000, 8192));
+        assert_eq!(parsed.global_checksum, global_checksum(&rom));
+        assert!(crate::verify::verify_rom(&rom).passed());
+
+        rom[0x4000] = 0xAA;
+        fix_checksums(&mut rom);
+        assert!(crate::verify::verify_rom(&rom).passed());
+    }
+
+    #[test]
+    fn header_writes_reject_what_cannot_be_encoded() {
+        let header = |title: &str, rom_size, ram_size| CartridgeHeader {
+            title: title.to_string(),
+            cartridge_type: CartridgeType::RomOnly,
+            rom_size,
+            ram_size,
+            global_checksum: 0,
+        };
+        let mut rom = vec![0; 0x8000];
+        assert!(
+            header("A TITLE TOO LONG!", 0x8000, 0)
+                .write_into(&mut rom)
+                .is_err()
+        );
+        assert!(header("TEST", 0x6000, 0).write_into(&mut rom).is_err());
+        assert!(header("TEST", 0x8000, 4096).write_into(&mut rom).is_err());
+        assert!(
+            header("TEST", 0x8000, 0)
+                .write_into(&mut rom[..0x0100])
+                .is_err()
+        );
+        assert!(rom.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn crc32_matches_reference_values() {
+        assert_eq!(crc32(b""), 0x0000_0000);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
 }
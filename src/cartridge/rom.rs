@@ -0,0 +1,76 @@
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+enum Storage {
+    Owned(Arc<[u8]>),
+    Mapped(Arc<Mmap>),
+}
+
+/// Read-only ROM contents, either read into memory or mapped from the file.
+///
+/// Clones share the same bytes, so many emulator instances running one game
+/// (AI training, netplay tests) only hold a single copy.
+#[derive(Debug, Clone)]
+pub struct Rom(Storage);
+
+impl Rom {
+    /// Map the file instead of reading it, so startup does not wait for an
+    /// 8 MB ROM to load and the OS pages in only the banks actually used
+    pub fn map(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: The mapping is only ever read. Changing the ROM file while it
+        // is mapped is not supported, as with every program that maps files.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self(Storage::Mapped(Arc::new(mmap))))
+    }
+
+    /// Whether the bytes come from a file mapping
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.0, Storage::Mapped(_))
+    }
+}
+
+impl From<Vec<u8>> for Rom {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(Storage::Owned(bytes.into()))
+    }
+}
+
+impl Deref for Rom {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            Storage::Owned(bytes) => bytes,
+            Storage::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn mapped_rom_matches_file_and_clones_share_it() {
+        let path = std::env::temp_dir().join(format!("gameboy-rom-{}.gb", std::process::id()));
+        let bytes: Vec<u8> = (0..=255).cycle().take(0x8000).collect();
+        fs::write(&path, &bytes).unwrap();
+
+        let rom = Rom::map(&path).unwrap();
+        let shared = rom.clone();
+        assert!(rom.is_mapped());
+        assert_eq!(&rom[..], &bytes[..]);
+        assert_eq!(shared.as_ptr(), rom.as_ptr());
+        assert!(!Rom::from(bytes).is_mapped());
+
+        drop((rom, shared)); // Windows refuses to delete mapped files
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use crate::savestate::{StateReader, StateWriter};
+
+/// CPU cycles the CPU sits stalled after STOP starts a speed switch
+/// (2050 M-cycles), whichever way it switches
+pub const SPEED_SWITCH_STALL: u32 = 8200;
+
+/// Whether `address` is an I/O register only the CGB has: KEY0, KEY1, VBK,
+/// the HDMA registers, RP, the color palettes, OPRI and SVBK. In DMG mode
+/// they read 0xFF and ignore writes, which games probe to detect a CGB.
+pub fn is_cgb_register(address: u16) -> bool {
+    matches!(
+        address,
+        0xFF4C | 0xFF4D | 0xFF4F | 0xFF51..=0xFF56 | 0xFF68..=0xFF6C | 0xFF70
+    )
+}
+
+/// KEY1 (0xFF4D): the pending and current CPU speed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpeedSwitch {
+    double_speed: bool,
+    armed: bool,
+    /// Cycles of the current switch's stall still to run
+    stall: u32,
+}
+
+impl SpeedSwitch {
+    pub fn is_double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    pub fn read_register(&self) -> u8 {
+        u8::from(self.double_speed) << 7 | 0x7E | u8::from(self.armed)
+    }
+
+    /// Bit 0 arms a switch for the next STOP
+    pub fn write_register(&mut self, value: u8) {
+        self.armed = value & 0x01 != 0;
+    }
+
+    /// STOP was executed. Returns whether it switched speed instead of
+    /// entering low power mode.
+    pub fn stop(&mut self) -> bool {
+        if !self.armed {
+            return false;
+        }
+        self.armed = false;
+        self.double_speed = !self.double_speed;
+        self.stall = SPEED_SWITCH_STALL;
+        true
+    }
+
+    /// Take `cycles` off a stall in progress, returning whether the CPU is
+    /// stalled
+    pub fn stall(&mut self, cycles: u32) -> bool {
+        if self.stall == 0 {
+            return false;
+        }
+        self.stall = self.stall.saturating_sub(cycles);
+        true
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.double_speed);
+        state.write_bool(self.armed);
+        state.write_u32(self.stall);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.double_speed = state.read_bool()?;
+        self.armed = state.read_bool()?;
+        self.stall = state.read_u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn armed_stop_switches_speed_and_stalls() {
+        let mut speed = SpeedSwitch::default();
+        assert!(!speed.stop(), "Nothing armed");
+        assert_eq!(speed.read_register(), 0x7E);
+
+        speed.write_register(0x01);
+        assert_eq!(speed.read_register(), 0x7F);
+        assert!(speed.stop());
+        assert_eq!(speed.read_register(), 0xFE);
+
+        let mut stalled = 0;
+        while speed.stall(4) {
+            stalled += 4;
+        }
+        assert_eq!(stalled, SPEED_SWITCH_STALL);
+        assert!(speed.is_double_speed());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::memory::Memory;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// What a cheat code does, decoded from its text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    /// `TTVVLLHH`: keep writing `value` to RAM at `address` every frame
+    GameShark { bank: u8, value: u8, address: u16 },
+    /// `ABC-DEF` or `ABC-DEF-GHI`: return `value` for reads of ROM `address`,
+    /// only while the ROM holds `compare` there if given
+    GameGenie {
+        address: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+}
+
+impl FromStr for CheatCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let digits: Vec<u8> = code
+            .chars()
+            .filter(|&c| c != '-')
+            .map(|c| c.to_digit(16).and_then(|digit| u8::try_from(digit).ok()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("Invalid cheat code: {code}"))?;
+        let byte = |index: usize| digits[index] << 4 | digits[index + 1];
+        match (digits.len(), code.contains('-')) {
+            (8, false) => Ok(CheatCode::GameShark {
+                bank: byte(0),
+                value: byte(2),
+                address: u16::from_le_bytes([byte(4), byte(6)]),
+            }),
+            (6 | 9, _) => {
+                let address = u16::from(digits[5]) << 12
+                    | u16::from(digits[2]) << 8
+                    | u16::from(digits[3]) << 4
+                    | u16::from(digits[4]);
+                let address = address ^ 0xF000;
+                if address >= 0x8000 {
+                    return Err(format!("Game Genie code {code} patches outside ROM"));
+                }
+                // Digit H is a checksum and not needed
+                let compare = (digits.len() == 9)
+                    .then(|| (digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA);
+                Ok(CheatCode::GameGenie {
+                    address,
+                    value: byte(0),
+                    compare,
+                })
+            }
+            _ => Err(format!(
+                "Invalid cheat code: {code} (expected GameShark TTVVLLHH or Game Genie ABC-DEF-GHI)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    pub enabled: bool,
+    /// Code as the user wrote it, kept for saving back
+    text: String,
+    code: CheatCode,
+}
+
+impl Cheat {
+    pub fn new(name: &str, code: &str, enabled: bool) -> Result<Self, String> {
+        if name.trim().is_empty() || name.contains(['#', '\n']) {
+            return Err(format!("Invalid cheat name: {name:?}"));
+        }
+        Ok(Self {
+            name: name.trim().to_string(),
+            enabled,
+            text: code.to_ascii_uppercase(),
+            code: code.parse()?,
+        })
+    }
+
+    pub fn code(&self) -> CheatCode {
+        self.code
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// The cheats for one game, kept in `cheats.txt` in its game directory.
+///
+/// One cheat per line: `on` or `off`, the code and the name, which runs to
+/// the end of the line. Blank lines and `#` comments are ignored.
+///
+/// ```text
+/// # Found with the RAM search
+/// on   010138CD     Infinite lives
+/// off  00A-17B-C49  Skip the intro
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheatList {
+    cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut list = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {message}", index + 1);
+
+            let Some((state, code, name)) =
+                line.split_once(char::is_whitespace)
+                    .and_then(|(state, rest)| {
+                        let (code, name) = rest.trim_start().split_once(char::is_whitespace)?;
+                        Some((state, code, name))
+                    })
+            else {
+                return Err(error("expected: on/off code name".to_string()));
+            };
+            let enabled = match state.to_ascii_lowercase().as_str() {
+                "on" => true,
+                "off" => false,
+                _ => return Err(error(format!("expected on or off, not {state}"))),
+            };
+            list.add(Cheat::new(name, code, enabled).map_err(error)?)
+                .map_err(error)?;
+        }
+        Ok(list)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// The list in the file format
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for cheat in &self.cheats {
+            let state = if cheat.enabled { "on" } else { "off" };
+            writeln!(text, "{state:<4} {:<12} {}", cheat.text, cheat.name)
+                .expect("writing to a String");
+        }
+        text
+    }
+
+    /// Cheats in file order
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Cheat> {
+        self.cheats.iter().find(|cheat| cheat.name == name)
+    }
+
+    pub fn add(&mut self, cheat: Cheat) -> Result<(), String> {
+        if self.get(&cheat.name).is_some() {
+            return Err(format!("Duplicate cheat name: {}", cheat.name));
+        }
+        self.cheats.push(cheat);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Cheat> {
+        let index = self.cheats.iter().position(|cheat| cheat.name == name)?;
+        Some(self.cheats.remove(index))
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        let cheat = self
+            .cheats
+            .iter_mut()
+            .find(|cheat| cheat.name == name)
+            .ok_or_else(|| format!("Unknown cheat: {name}"))?;
+        cheat.enabled = enabled;
+        Ok(())
+    }
+
+    /// ROM patches from the enabled Game Genie codes
+    pub(crate) fn rom_patches(&self) -> Vec<RomPatch> {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| match cheat.code {
+                CheatCode::GameGenie {
+                    address,
+                    value,
+                    compare,
+                } => Some(RomPatch {
+                    address,
+                    value,
+                    compare,
+                }),
+                CheatCode::GameShark { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Write the enabled `GameShark` values, as the device does once a frame.
+    /// Values already in place are not rewritten, so cartridge RAM cheats do
+    /// not keep triggering battery autosaves.
+    // TODO: Honour the bank byte once CGB work RAM banking exists
+    pub(crate) fn apply_ram(&self, memory: &mut Memory) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            if let CheatCode::GameShark { value, address, .. } = cheat.code
+                && memory.peek(address) != value
+            {
+                memory.write_byte(address, value);
+            }
+        }
+    }
+}
+
+/// A Game Genie substitution applied to ROM reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RomPatch {
+    address: u16,
+    value: u8,
+    compare: Option<u8>,
+}
+
+impl RomPatch {
+    /// The byte the CPU sees at `address` where the ROM holds `byte`
+    pub(crate) fn apply(self, address: u16, byte: u8) -> u8 {
+        if address == self.address && self.compare.is_none_or(|compare| compare == byte) {
+            self.value
+        } else {
+            byte
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_gameshark_and_game_genie_codes() {
+        assert_eq!(
+            "010138CD".parse(),
+            Ok(CheatCode::GameShark {
+                bank: 0x01,
+                value: 0x01,
+                address: 0xCD38
+            })
+        );
+        assert_eq!(
+            "00a-17b-c49".parse(),
+            Ok(CheatCode::GameGenie {
+                address: 0x4A17,
+                value: 0x00,
+                compare: Some(0xC8)
+            })
+        );
+        assert_eq!(
+            "3E0-15F"
+                .parse::<CheatCode>()
+                .map(|code| matches!(code, CheatCode::GameGenie { compare: None, .. })),
+            Ok(true)
+        );
+        assert!("00A-17B-C4".parse::<CheatCode>().is_err());
+        assert!("00A-170-C49".parse::<CheatCode>().is_err(), "Patches VRAM");
+        assert!("XYZ".parse::<CheatCode>().is_err());
+    }
+
+    #[test]
+    fn file_round_trips_toggles() {
+        let mut list = CheatList::parse(
+            "# Mine\n\
+             on  010138CD  Infinite lives\n\
+             \n\
+             OFF 00A-17B-C49 Skip the intro  # Breaks the save menu\n",
+        )
+        .unwrap();
+        assert_eq!(list.cheats().len(), 2);
+        assert_eq!(
+            list.get("Skip the intro").map(|cheat| cheat.enabled),
+            Some(false)
+        );
+
+        list.set_enabled("Skip the intro", true).unwrap();
+        assert!(list.set_enabled("Walk through walls", true).is_err());
+        let reloaded = CheatList::parse(&list.to_text()).unwrap();
+        assert_eq!(reloaded, list);
+        assert_eq!(reloaded.rom_patches().len(), 1);
+
+        assert!(CheatList::parse("on 010138CD A\non 010139CD A").is_err());
+        assert!(CheatList::parse("maybe 010138CD A").is_err());
+        assert!(CheatList::parse("on 010138CD").is_err());
+    }
+
+    #[test]
+    fn game_genie_patch_checks_the_original_byte() {
+        let patch = RomPatch {
+            address: 0x4A17,
+            value: 0x00,
+            compare: Some(0xC8),
+        };
+        assert_eq!(patch.apply(0x4A17, 0xC8), 0x00);
+        assert_eq!(patch.apply(0x4A17, 0x12), 0x12, "Other ROM bank");
+        assert_eq!(patch.apply(0x4A18, 0xC8), 0xC8);
+    }
+}
@@ -0,0 +1,131 @@
+/// Longest instruction (CALL, RST and friends), the window the step loop
+/// advances every component within
+pub const DEFAULT_WINDOW: u64 = 24;
+
+/// Hardware that runs on the CPU's clock
+// TODO: Add OAM DMA once it is emulated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Timer,
+    Ppu,
+    Serial,
+    Apu,
+}
+
+impl Component {
+    pub const ALL: [Component; 4] = [
+        Component::Timer,
+        Component::Ppu,
+        Component::Serial,
+        Component::Apu,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Component::Timer => "timer",
+            Component::Ppu => "ppu",
+            Component::Serial => "serial",
+            Component::Apu => "apu",
+        }
+    }
+}
+
+/// Debug record of when each component was last brought up to date, for
+/// checking that no component falls behind the CPU by more than one event
+/// window. A scheduler that defers component updates must keep this passing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockTrace {
+    window: u64,
+    cpu: u64,
+    last: [u64; 4],    // Cycle each component was last advanced to
+    max_lag: [u64; 4], // Worst lag seen per component
+    failure: Option<String>,
+}
+
+impl ClockTrace {
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            cpu: 0,
+            last: [0; 4],
+            max_lag: [0; 4],
+            failure: None,
+        }
+    }
+
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// The CPU has run up to `cycle`
+    pub fn advance_cpu(&mut self, cycle: u64) {
+        self.cpu = cycle;
+    }
+
+    /// `component` has been brought up to `cycle`
+    pub fn advance(&mut self, component: Component, cycle: u64) {
+        self.last[component as usize] = cycle;
+    }
+
+    /// Cycle `component` was last advanced to
+    pub fn last_advanced(&self, component: Component) -> u64 {
+        self.last[component as usize]
+    }
+
+    /// The first check that failed
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+
+    /// Worst lag behind the CPU seen by `check`, in cycles
+    pub fn max_lag(&self, component: Component) -> u64 {
+        self.max_lag[component as usize]
+    }
+
+    /// Record each component's lag behind the CPU, failing if any is more
+    /// than the window. The first failure is also kept for `failure`.
+    pub fn check(&mut self) -> Result<(), String> {
+        for component in Component::ALL {
+            let index = component as usize;
+            let lag = self.cpu.saturating_sub(self.last[index]);
+            self.max_lag[index] = self.max_lag[index].max(lag);
+            if lag > self.window {
+                let message = format!(
+                    "{} lags the CPU by {lag} cycles at cycle {} (window {})",
+                    component.name(),
+                    self.cpu,
+                    self.window
+                );
+                self.failure.get_or_insert_with(|| message.clone());
+                return Err(message);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lagging_component_fails_the_check() {
+        let mut trace = ClockTrace::new(24);
+        trace.advance_cpu(100);
+        for component in Component::ALL {
+            trace.advance(component, 100);
+        }
+        trace.advance_cpu(120);
+        trace.advance(Component::Timer, 120);
+        assert_eq!(trace.check(), Ok(()));
+        assert_eq!(trace.max_lag(Component::Ppu), 20);
+
+        trace.advance_cpu(130);
+        assert_eq!(
+            trace.check(),
+            Err("ppu lags the CPU by 30 cycles at cycle 130 (window 24)".to_string())
+        );
+        assert_eq!(trace.last_advanced(Component::Timer), 120);
+        assert!(trace.failure().unwrap().starts_with("ppu lags"));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::cpu::registers::Flags;
+
+// Arithmetic as pure functions of their inputs, returning the result and
+// the new flags. The Cpu methods only move values in and out of
+// registers and memory.
+
+/// ADD: Z if the result is 0, N=0, H if carry from bit 3, C if carry from bit 7
+pub fn add(a: u8, value: u8) -> (u8, Flags) {
+    adc(a, value, false)
+}
+
+/// ADC: as ADD, with the carry flag added in
+pub fn adc(a: u8, value: u8, carry: bool) -> (u8, Flags) {
+    let carry = u8::from(carry);
+    let result = a.wrapping_add(value).wrapping_add(carry);
+    let flags = Flags {
+        z: result == 0,
+        n: false,
+        h: (a & 0x0F) + (value & 0x0F) + carry > 0x0F,
+        c: u16::from(a) + u16::from(value) + u16::from(carry) > 0xFF,
+    };
+    (result, flags)
+}
+
+/// SUB: Z if the result is 0, N=1, H if borrow from bit 4, C if borrow
+pub fn sub(a: u8, value: u8) -> (u8, Flags) {
+    sbc(a, value, false)
+}
+
+/// SBC: as SUB, with the carry flag subtracted too
+pub fn sbc(a: u8, value: u8, carry: bool) -> (u8, Flags) {
+    let carry = u8::from(carry);
+    let result = a.wrapping_sub(value).wrapping_sub(carry);
+    let flags = Flags {
+        z: result == 0,
+        n: true,
+        h: (a & 0x0F) < (value & 0x0F) + carry,
+        c: u16::from(a) < u16::from(value) + u16::from(carry),
+    };
+    (result, flags)
+}
+
+/// CP: the flags of SUB, without the result
+pub fn cp(a: u8, value: u8) -> Flags {
+    sub(a, value).1
+}
+
+/// INC: Z if the result is 0, N=0, H if carry from bit 3, C unchanged
+pub fn inc(value: u8, flags: Flags) -> (u8, Flags) {
+    let result = value.wrapping_add(1);
+    let flags = Flags {
+        z: result == 0,
+        n: false,
+        h: (value & 0x0F) == 0x0F,
+        c: flags.c,
+    };
+    (result, flags)
+}
+
+/// DEC: Z if the result is 0, N=1, H if borrow from bit 4, C unchanged
+pub fn dec(value: u8, flags: Flags) -> (u8, Flags) {
+    let result = value.wrapping_sub(1);
+    let flags = Flags {
+        z: result == 0,
+        n: true,
+        h: (value & 0x0F) == 0x00,
+        c: flags.c,
+    };
+    (result, flags)
+}
+
+/// ADD HL,rr: Z unchanged, N=0, H if carry from bit 11, C if carry from
+/// bit 15
+pub fn add16(hl: u16, value: u16, flags: Flags) -> (u16, Flags) {
+    let result = hl.wrapping_add(value);
+    let flags = Flags {
+        z: flags.z,
+        n: false,
+        h: (hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF,
+        c: u32::from(hl) + u32::from(value) > 0xFFFF,
+    };
+    (result, flags)
+}
+
+/// SP plus a signed 8-bit offset, for ADD SP,e and LD HL,SP+e. Z=0, N=0,
+/// and H and C as for adding the offset byte to SP's low byte.
+pub fn add_sp(sp: u16, offset: u8) -> (u16, Flags) {
+    let extended = if offset & 0x80 == 0 {
+        u16::from(offset)
+    } else {
+        0xFF00 | u16::from(offset)
+    };
+    let low = sp.to_le_bytes()[0];
+    let flags = Flags {
+        z: false,
+        n: false,
+        h: (low & 0x0F) + (offset & 0x0F) > 0x0F,
+        c: u16::from(low) + u16::from(offset) > 0xFF,
+    };
+    (sp.wrapping_add(extended), flags)
+}
+
+/// DAA: adjust A back to BCD after an addition or subtraction of two BCD
+/// numbers, going by N, H and C from that instruction. Z if the result is 0,
+/// N unchanged, H=0, C set if the adjustment carried (never cleared).
+pub fn daa(a: u8, flags: Flags) -> (u8, Flags) {
+    let mut adjust = 0u8;
+    let mut carry = flags.c;
+    if flags.h || (!flags.n && (a & 0x0F) > 0x09) {
+        adjust |= 0x06;
+    }
+    if flags.c || (!flags.n && a > 0x99) {
+        adjust |= 0x60;
+        carry = true;
+    }
+
+    let result = if flags.n {
+        a.wrapping_sub(adjust)
+    } else {
+        a.wrapping_add(adjust)
+    };
+    let flags = Flags {
+        z: result == 0,
+        n: flags.n,
+        h: false,
+        c: carry,
+    };
+    (result, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flags from a byte, as F holds them
+    fn flags(value: u8) -> Flags {
+        let mut flags = Flags::new();
+        flags.set_from_u8(value);
+        flags
+    }
+
+    /// Every combination of two operands and a carry in, with the result
+    /// and F worked out independently of the code under test: from a wide
+    /// result, taking half-carry from bit 4 of `a ^ value ^ result`
+    fn truth_table(subtract: bool) -> Vec<(u8, u8, bool, u8, u8)> {
+        let mut table = Vec::with_capacity(256 * 256 * 2);
+        for a in 0..=255u8 {
+            for value in 0..=255u8 {
+                for carry in [false, true] {
+                    let wide = if subtract {
+                        i32::from(a) - i32::from(value) - i32::from(carry)
+                    } else {
+                        i32::from(a) + i32::from(value) + i32::from(carry)
+                    };
+                    let result = wide.to_le_bytes()[0];
+                    let mut f = 0;
+                    if result == 0 {
+                        f |= 0x80;
+                    }
+                    if subtract {
+                        f |= 0x40;
+                    }
+                    if (i32::from(a) ^ i32::from(value) ^ wide) & 0x10 != 0 {
+                        f |= 0x20;
+                    }
+                    if !(0..=0xFF).contains(&wide) {
+                        f |= 0x10;
+                    }
+                    table.push((a, value, carry, result, f));
+                }
+            }
+        }
+        table
+    }
+
+    #[test]
+    fn add_and_adc_match_the_truth_table() {
+        for (a, value, carry, result, f) in truth_table(false) {
+            assert_eq!(
+                adc(a, value, carry),
+                (result, flags(f)),
+                "ADC {a:02X} {value:02X} {carry}"
+            );
+            if !carry {
+                assert_eq!(add(a, value), (result, flags(f)), "ADD {a:02X} {value:02X}");
+            }
+        }
+    }
+
+    #[test]
+    fn sub_sbc_and_cp_match_the_truth_table() {
+        for (a, value, carry, result, f) in truth_table(true) {
+            assert_eq!(
+                sbc(a, value, carry),
+                (result, flags(f)),
+                "SBC {a:02X} {value:02X} {carry}"
+            );
+            if !carry {
+                assert_eq!(sub(a, value), (result, flags(f)), "SUB {a:02X} {value:02X}");
+                assert_eq!(cp(a, value), flags(f), "CP {a:02X} {value:02X}");
+            }
+        }
+    }
+
+    #[test]
+    fn inc_and_dec_keep_carry() {
+        for value in 0..=255u8 {
+            for f in [0x00, 0xF0] {
+                let carry = f & 0x10;
+                let (result, got) = inc(value, flags(f));
+                let h = if value & 0x0F == 0x0F { 0x20 } else { 0 };
+                let z = if result == 0 { 0x80 } else { 0 };
+                assert_eq!((result, got), (value.wrapping_add(1), flags(z | h | carry)));
+
+                let (result, got) = dec(value, flags(f));
+                let h = if value & 0x0F == 0 { 0x20 } else { 0 };
+                let z = if result == 0 { 0x80 } else { 0 };
+                assert_eq!(
+                    (result, got),
+                    (value.wrapping_sub(1), flags(z | 0x40 | h | carry))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn add16_carries_from_bits_11_and_15() {
+        assert_eq!(add16(0x0FFF, 0x0001, flags(0x80)), (0x1000, flags(0xA0)));
+        assert_eq!(add16(0xF000, 0x1000, flags(0x40)), (0x0000, flags(0x10)));
+        assert_eq!(add16(0x8FFF, 0x8001, flags(0x00)), (0x1000, flags(0x30)));
+        assert_eq!(add16(0x1234, 0x0100, flags(0xF0)), (0x1334, flags(0x80)));
+    }
+
+    #[test]
+    fn add_sp_sign_extends_and_carries_from_the_low_byte() {
+        assert_eq!(add_sp(0xFFF8, 0x08), (0x0000, flags(0x30)));
+        assert_eq!(add_sp(0x0000, 0xFF), (0xFFFF, flags(0x00)));
+        assert_eq!(add_sp(0x0001, 0xFF), (0x0000, flags(0x30)));
+        assert_eq!(add_sp(0xC00F, 0x01), (0xC010, flags(0x20)));
+    }
+
+    #[test]
+    fn daa_matches_the_truth_table() {
+        // Every A with every N, H and C, against the adjustment written
+        // carry-first on a wide value
+        for a in 0..=255u8 {
+            for f in (0x00..=0x70).step_by(0x10) {
+                let (n, h, c) = (f & 0x40 != 0, f & 0x20 != 0, f & 0x10 != 0);
+                let mut wide = i32::from(a);
+                let mut carry = c;
+                if n {
+                    if c {
+                        wide -= 0x60;
+                    }
+                    if h {
+                        wide -= 0x06;
+                    }
+                } else {
+                    if c || wide > 0x99 {
+                        wide += 0x60;
+                        carry = true;
+                    }
+                    if h || wide & 0x0F > 0x09 {
+                        wide += 0x06;
+                    }
+                }
+                let result = wide.to_le_bytes()[0];
+                let z = if result == 0 { 0x80 } else { 0 };
+                let expected = z | (f & 0x40) | if carry { 0x10 } else { 0 };
+                assert_eq!(
+                    daa(a, flags(f)),
+                    (result, flags(expected)),
+                    "DAA {a:02X} F={f:02X}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn daa_gives_bcd_sums_and_differences() {
+        let bcd = |n: u8| ((n / 10) << 4) | (n % 10);
+        for x in 0..100u8 {
+            for y in 0..100u8 {
+                for carry in [false, true] {
+                    let (sum, f) = adc(bcd(x), bcd(y), carry);
+                    let (sum, f) = daa(sum, f);
+                    let total = x + y + u8::from(carry);
+                    assert_eq!((sum, f.c), (bcd(total % 100), total >= 100), "{x} + {y}");
+
+                    let (difference, f) = sbc(bcd(x), bcd(y), carry);
+                    let (difference, f) = daa(difference, f);
+                    let borrow = y + u8::from(carry) > x;
+                    let expected = (100 + x - y - u8::from(carry)) % 100;
+                    assert_eq!((difference, f.c), (bcd(expected), borrow), "{x} - {y}");
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use super::Cpu;
 use crate::memory::Memory;
+use crate::quirks::Quirk;
 
 impl Cpu {
     /// Fetch the next byte and increment PC
@@ -10,7 +11,7 @@ impl Cpu {
     }
 
     /// Fetch the next word (16-bit) and increment PC by 2
-    pub fn fetch_word(&mut self, memory: &Memory) -> u16 {
+    pub fn fetch_word(&mut self, memory: &mut Memory) -> u16 {
         let word = memory.read_word(self.pc);
         self.pc = self.pc.wrapping_add(2);
         word
@@ -18,6 +19,9 @@ impl Cpu {
 
     /// Execute one instruction and return cycles taken
     pub fn execute(&mut self, memory: &mut Memory) -> u8 {
+        if std::mem::take(&mut self.ime_pending) {
+            self.interrupts_enabled = true;
+        }
         let opcode = self.fetch_byte(memory);
         self.execute_opcode(opcode, memory)
     }
@@ -148,9 +152,11 @@ impl Cpu {
             0x08 => self.ld_nn_sp(memory),
             0xF9 => self.ld_sp_hl(),
             0xF8 => self.ld_hl_sp_n(memory),
+            0xE8 => self.add_sp_n(memory),
 
-            // HALT
+            // HALT and STOP
             0x76 => self.halt(),
+            0x10 => self.stop(memory),
 
             // Rotate/shift instructions
             0x07 => self.rlca(),
@@ -211,8 +217,15 @@ impl Cpu {
             0x2B => self.dec_hl_16(),
             0x3B => self.dec_sp(),
 
+            // ADD 16-bit
+            0x09 => self.add_hl_bc(),
+            0x19 => self.add_hl_de(),
+            0x29 => self.add_hl_hl(),
+            0x39 => self.add_hl_sp(),
+
             // Jump instructions
             0xC3 => self.jp_nn(memory),
+            0xE9 => self.jp_hl(),
             0x18 => self.jr_n(memory),
 
             // Conditional relative jumps
@@ -347,11 +360,14 @@ impl Cpu {
                 self.execute_cb_opcode(cb_opcode, memory)
             }
 
-            _ => panic!(
-                "Unimplemented opcode: 0x{:02X} at PC: 0x{:04X}",
-                opcode,
-                self.pc.wrapping_sub(1)
-            ),
+            // Unused opcodes lock up real hardware
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+                memory
+                    .quirks
+                    .report(Quirk::IllegalOpcode, self.pc.wrapping_sub(1));
+                self.locked = true;
+                4
+            }
         }
     }
 
@@ -450,3 +466,107 @@ impl Cpu {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::opcodes::{CB_OPCODES, OPCODES, Opcode};
+
+    /// Where instructions run: work RAM on a bus without a cartridge, so
+    /// operands read as zero and nothing is banked
+    const START: u16 = 0xC000;
+
+    /// For a conditional jump, call or return, the flags (as F) that make
+    /// the branch go each way: (taken, not taken)
+    fn branch_flags(opcode: &Opcode) -> Option<(u8, u8)> {
+        let (operation, operands) = opcode.mnemonic.split_once(' ')?;
+        if !["jr", "jp", "call", "ret"].contains(&operation) {
+            return None;
+        }
+        match operands.split(',').next()? {
+            "nz" => Some((0x00, 0x80)),
+            "z" => Some((0x80, 0x00)),
+            "nc" => Some((0x00, 0x10)),
+            "c" => Some((0x10, 0x00)),
+            _ => None,
+        }
+    }
+
+    /// Cycles `execute` reports for the instruction starting with `bytes`,
+    /// run alone from `START` with F set to `flags`
+    fn cycles(bytes: [u8; 2], flags: u8) -> u8 {
+        let mut memory = Memory::default();
+        memory.write_byte(START, bytes[0]);
+        memory.write_byte(START + 1, bytes[1]);
+        let mut cpu = Cpu::new();
+        cpu.pc = START;
+        cpu.registers.f.set_from_u8(flags);
+        cpu.execute(&mut memory)
+    }
+
+    /// Every implemented opcode against the table, with conditional
+    /// branches run both ways. Failures are collected so one run lists them
+    /// all.
+    fn check(table: &[Opcode; 256], prefix: Option<u8>) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        for (code, opcode) in (0..=u8::MAX).zip(table) {
+            if opcode.is_illegal() || (prefix.is_none() && code == 0xCB) {
+                continue;
+            }
+            let bytes = prefix.map_or([code, 0x00], |prefix| [prefix, code]);
+            let runs = if let Some((taken, not_taken)) = branch_flags(opcode) {
+                vec![
+                    ("taken", taken, opcode.branch_cycles),
+                    ("not taken", not_taken, opcode.cycles),
+                ]
+            } else {
+                assert!(
+                    !opcode.is_conditional(),
+                    "{}: no condition found",
+                    opcode.mnemonic
+                );
+                vec![("", 0x00, opcode.cycles), ("", 0xF0, opcode.cycles)]
+            };
+            for (variant, flags, expected) in runs {
+                let actual = cycles(bytes, flags);
+                if actual != expected {
+                    mismatches.push(format!(
+                        "{:02X} {} {variant}: {actual} cycles, table says {expected}",
+                        code, opcode.mnemonic
+                    ));
+                }
+            }
+        }
+        mismatches
+    }
+
+    #[test]
+    fn base_opcodes_take_the_cycles_in_the_table() {
+        let mismatches = check(&OPCODES, None);
+        assert!(mismatches.is_empty(), "{mismatches:#?}");
+    }
+
+    #[test]
+    fn cb_opcodes_take_the_cycles_in_the_table() {
+        let mismatches = check(&CB_OPCODES, Some(0xCB));
+        assert!(mismatches.is_empty(), "{mismatches:#?}");
+    }
+
+    #[test]
+    fn every_condition_is_recognised() {
+        let conditional: Vec<&str> = OPCODES
+            .iter()
+            .filter(|opcode| branch_flags(opcode).is_some())
+            .map(|opcode| opcode.mnemonic)
+            .collect();
+        // JR, JP, CALL and RET, each with NZ, Z, NC and C
+        assert_eq!(conditional.len(), 16, "{conditional:?}");
+        assert_eq!(
+            OPCODES
+                .iter()
+                .filter(|opcode| opcode.is_conditional())
+                .count(),
+            16
+        );
+    }
+}
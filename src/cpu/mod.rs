@@ -1,15 +1,28 @@
 use crate::cpu::registers::Registers;
 use crate::memory::Memory;
+use crate::savestate::{StateReader, StateWriter};
 
+pub mod alu;
 mod instructions;
+pub mod opcodes;
 pub mod registers;
 
+#[allow(clippy::struct_excessive_bools)] // Flags the CPU keeps, as hardware does
 pub struct Cpu {
     pub registers: Registers,
     pub pc: u16,
     pub sp: u16,
+    /// In HALT: no instructions run until an enabled interrupt is requested
     pub halted: bool,
+    /// In STOP: the CPU and DIV wait for a press on a selected joypad line
+    pub stopped: bool,
+    /// An unused opcode hung the CPU, as on hardware; only a reset ends it
+    pub locked: bool,
     pub interrupts_enabled: bool,
+    /// EI ran as the last instruction. IME is set as the next one starts, so
+    /// no interrupt can be taken until that instruction has run, and a DI
+    /// straight after EI leaves interrupts disabled.
+    pub ime_pending: bool,
 }
 
 impl Cpu {
@@ -19,21 +32,67 @@ impl Cpu {
             pc: 0x0100, // Start after boot ROM
             sp: 0xFFFE,
             halted: false,
+            stopped: false,
+            locked: false,
             interrupts_enabled: false,
+            ime_pending: false,
         }
     }
 
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.registers.af());
+        state.write_u16(self.registers.bc());
+        state.write_u16(self.registers.de());
+        state.write_u16(self.registers.hl());
+        state.write_u16(self.pc);
+        state.write_u16(self.sp);
+        state.write_bool(self.halted);
+        state.write_bool(self.stopped);
+        state.write_bool(self.locked);
+        state.write_bool(self.interrupts_enabled);
+        state.write_bool(self.ime_pending);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.registers.set_af(state.read_u16()?);
+        self.registers.set_bc(state.read_u16()?);
+        self.registers.set_de(state.read_u16()?);
+        self.registers.set_hl(state.read_u16()?);
+        self.pc = state.read_u16()?;
+        self.sp = state.read_u16()?;
+        self.halted = state.read_bool()?;
+        self.stopped = state.read_bool()?;
+        self.locked = state.read_bool()?;
+        self.interrupts_enabled = state.read_bool()?;
+        self.ime_pending = state.read_bool()?;
+        Ok(())
+    }
+
     // NOP - No operation
     fn nop(&mut self) -> u8 {
         4 // 4 cycles
     }
 
-    // HALT - Halt CPU until interrupt
+    // HALT - Halt CPU until an enabled interrupt is requested, which
+    // `GameBoy::step` checks before each instruction
     fn halt(&mut self) -> u8 {
         self.halted = true;
         4
     }
 
+    // STOP - Enter very low power mode, resetting DIV, until a selected
+    // joypad line goes low (see `GameBoy::step`). The byte after the opcode
+    // is skipped. With a CGB speed switch armed in KEY1 it switches speed
+    // instead, and the CPU stalls while the new speed settles.
+    fn stop(&mut self, memory: &mut Memory) -> u8 {
+        self.fetch_byte(memory);
+        memory.timer.reset_div();
+        if !memory.speed.stop() {
+            self.stopped = true;
+        }
+        4
+    }
+
     // LD r, r' - Load register to register (all take 4 cycles)
     fn ld_a_a(&mut self) -> u8 {
         4
@@ -361,25 +420,25 @@ impl Cpu {
     }
 
     // 16-bit loads (12 cycles each)
-    fn ld_bc_nn(&mut self, memory: &Memory) -> u8 {
+    fn ld_bc_nn(&mut self, memory: &mut Memory) -> u8 {
         let value = self.fetch_word(memory);
         self.registers.set_bc(value);
         12
     }
 
-    fn ld_de_nn(&mut self, memory: &Memory) -> u8 {
+    fn ld_de_nn(&mut self, memory: &mut Memory) -> u8 {
         let value = self.fetch_word(memory);
         self.registers.set_de(value);
         12
     }
 
-    fn ld_hl_nn(&mut self, memory: &Memory) -> u8 {
+    fn ld_hl_nn(&mut self, memory: &mut Memory) -> u8 {
         let value = self.fetch_word(memory);
         self.registers.set_hl(value);
         12
     }
 
-    fn ld_sp_nn(&mut self, memory: &Memory) -> u8 {
+    fn ld_sp_nn(&mut self, memory: &mut Memory) -> u8 {
         self.sp = self.fetch_word(memory);
         12
     }
@@ -473,138 +532,92 @@ impl Cpu {
     // INC 8-bit - Increment register
     // Flags: Z if result is 0, N=0, H if carry from bit 3, C not affected
     fn inc_a(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.a & 0x0F) == 0x0F;
-        self.registers.a = self.registers.a.wrapping_add(1);
-        self.registers.f.z = self.registers.a == 0;
-        self.registers.f.n = false;
+        (self.registers.a, self.registers.f) = alu::inc(self.registers.a, self.registers.f);
         4
     }
 
     fn inc_b(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.b & 0x0F) == 0x0F;
-        self.registers.b = self.registers.b.wrapping_add(1);
-        self.registers.f.z = self.registers.b == 0;
-        self.registers.f.n = false;
+        (self.registers.b, self.registers.f) = alu::inc(self.registers.b, self.registers.f);
         4
     }
 
     fn inc_c(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.c & 0x0F) == 0x0F;
-        self.registers.c = self.registers.c.wrapping_add(1);
-        self.registers.f.z = self.registers.c == 0;
-        self.registers.f.n = false;
+        (self.registers.c, self.registers.f) = alu::inc(self.registers.c, self.registers.f);
         4
     }
 
     fn inc_d(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.d & 0x0F) == 0x0F;
-        self.registers.d = self.registers.d.wrapping_add(1);
-        self.registers.f.z = self.registers.d == 0;
-        self.registers.f.n = false;
+        (self.registers.d, self.registers.f) = alu::inc(self.registers.d, self.registers.f);
         4
     }
 
     fn inc_e(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.e & 0x0F) == 0x0F;
-        self.registers.e = self.registers.e.wrapping_add(1);
-        self.registers.f.z = self.registers.e == 0;
-        self.registers.f.n = false;
+        (self.registers.e, self.registers.f) = alu::inc(self.registers.e, self.registers.f);
         4
     }
 
     fn inc_h(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.h & 0x0F) == 0x0F;
-        self.registers.h = self.registers.h.wrapping_add(1);
-        self.registers.f.z = self.registers.h == 0;
-        self.registers.f.n = false;
+        (self.registers.h, self.registers.f) = alu::inc(self.registers.h, self.registers.f);
         4
     }
 
     fn inc_l(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.l & 0x0F) == 0x0F;
-        self.registers.l = self.registers.l.wrapping_add(1);
-        self.registers.f.z = self.registers.l == 0;
-        self.registers.f.n = false;
+        (self.registers.l, self.registers.f) = alu::inc(self.registers.l, self.registers.f);
         4
     }
 
     fn inc_hl(&mut self, memory: &mut Memory) -> u8 {
         let addr = self.registers.hl();
         let value = memory.read_byte(addr);
-        self.registers.f.h = (value & 0x0F) == 0x0F;
-        let result = value.wrapping_add(1);
+        let (result, flags) = alu::inc(value, self.registers.f);
         memory.write_byte(addr, result);
-        self.registers.f.z = result == 0;
-        self.registers.f.n = false;
+        self.registers.f = flags;
         12
     }
 
     // DEC 8-bit - Decrement register
     // Flags: Z if result is 0, N=1, H if borrow from bit 4, C not affected
     fn dec_a(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.a & 0x0F) == 0x00;
-        self.registers.a = self.registers.a.wrapping_sub(1);
-        self.registers.f.z = self.registers.a == 0;
-        self.registers.f.n = true;
+        (self.registers.a, self.registers.f) = alu::dec(self.registers.a, self.registers.f);
         4
     }
 
     fn dec_b(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.b & 0x0F) == 0x00;
-        self.registers.b = self.registers.b.wrapping_sub(1);
-        self.registers.f.z = self.registers.b == 0;
-        self.registers.f.n = true;
+        (self.registers.b, self.registers.f) = alu::dec(self.registers.b, self.registers.f);
         4
     }
 
     fn dec_c(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.c & 0x0F) == 0x00;
-        self.registers.c = self.registers.c.wrapping_sub(1);
-        self.registers.f.z = self.registers.c == 0;
-        self.registers.f.n = true;
+        (self.registers.c, self.registers.f) = alu::dec(self.registers.c, self.registers.f);
         4
     }
 
     fn dec_d(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.d & 0x0F) == 0x00;
-        self.registers.d = self.registers.d.wrapping_sub(1);
-        self.registers.f.z = self.registers.d == 0;
-        self.registers.f.n = true;
+        (self.registers.d, self.registers.f) = alu::dec(self.registers.d, self.registers.f);
         4
     }
 
     fn dec_e(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.e & 0x0F) == 0x00;
-        self.registers.e = self.registers.e.wrapping_sub(1);
-        self.registers.f.z = self.registers.e == 0;
-        self.registers.f.n = true;
+        (self.registers.e, self.registers.f) = alu::dec(self.registers.e, self.registers.f);
         4
     }
 
     fn dec_h(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.h & 0x0F) == 0x00;
-        self.registers.h = self.registers.h.wrapping_sub(1);
-        self.registers.f.z = self.registers.h == 0;
-        self.registers.f.n = true;
+        (self.registers.h, self.registers.f) = alu::dec(self.registers.h, self.registers.f);
         4
     }
 
     fn dec_l(&mut self) -> u8 {
-        self.registers.f.h = (self.registers.l & 0x0F) == 0x00;
-        self.registers.l = self.registers.l.wrapping_sub(1);
-        self.registers.f.z = self.registers.l == 0;
-        self.registers.f.n = true;
+        (self.registers.l, self.registers.f) = alu::dec(self.registers.l, self.registers.f);
         4
     }
 
     fn dec_hl(&mut self, memory: &mut Memory) -> u8 {
         let addr = self.registers.hl();
         let value = memory.read_byte(addr);
-        self.registers.f.h = (value & 0x0F) == 0x00;
-        let result = value.wrapping_sub(1);
+        let (result, flags) = alu::dec(value, self.registers.f);
         memory.write_byte(addr, result);
-        self.registers.f.z = result == 0;
-        self.registers.f.n = true;
+        self.registers.f = flags;
         12
     }
 
@@ -656,12 +669,43 @@ impl Cpu {
         8
     }
 
+    // ADD HL,rr - Add 16-bit register to HL
+    // Flags: Z unchanged, N=0, H=carry from bit 11, C=carry from bit 15
+    fn add_hl(&mut self, value: u16) -> u8 {
+        let result;
+        (result, self.registers.f) = alu::add16(self.registers.hl(), value, self.registers.f);
+        self.registers.set_hl(result);
+        8
+    }
+
+    fn add_hl_bc(&mut self) -> u8 {
+        self.add_hl(self.registers.bc())
+    }
+
+    fn add_hl_de(&mut self) -> u8 {
+        self.add_hl(self.registers.de())
+    }
+
+    fn add_hl_hl(&mut self) -> u8 {
+        self.add_hl(self.registers.hl())
+    }
+
+    fn add_hl_sp(&mut self) -> u8 {
+        self.add_hl(self.sp)
+    }
+
     // JP nn - Absolute jump to 16-bit address
-    fn jp_nn(&mut self, memory: &Memory) -> u8 {
+    fn jp_nn(&mut self, memory: &mut Memory) -> u8 {
         self.pc = self.fetch_word(memory);
         16
     }
 
+    // JP HL - Jump to the address in HL
+    fn jp_hl(&mut self) -> u8 {
+        self.pc = self.registers.hl();
+        4
+    }
+
     // JR n - Relative jump by signed 8-bit offset
     fn jr_n(&mut self, memory: &Memory) -> u8 {
         let offset_16 = i16::from(
@@ -765,7 +809,7 @@ impl Cpu {
     }
 
     // JP Z, nn - Absolute jump if Zero flag is set
-    fn jp_z(&mut self, memory: &Memory) -> u8 {
+    fn jp_z(&mut self, memory: &mut Memory) -> u8 {
         let addr = self.fetch_word(memory);
         if self.registers.f.z {
             self.pc = addr;
@@ -776,7 +820,7 @@ impl Cpu {
     }
 
     // JP NZ, nn - Absolute jump if Zero flag is not set
-    fn jp_nz(&mut self, memory: &Memory) -> u8 {
+    fn jp_nz(&mut self, memory: &mut Memory) -> u8 {
         let addr = self.fetch_word(memory);
         if self.registers.f.z {
             12 // Not taken
@@ -787,7 +831,7 @@ impl Cpu {
     }
 
     // JP C, nn - Absolute jump if Carry flag is set
-    fn jp_c(&mut self, memory: &Memory) -> u8 {
+    fn jp_c(&mut self, memory: &mut Memory) -> u8 {
         let addr = self.fetch_word(memory);
         if self.registers.f.c {
             self.pc = addr;
@@ -798,7 +842,7 @@ impl Cpu {
     }
 
     // JP NC, nn - Absolute jump if Carry flag is not set
-    fn jp_nc(&mut self, memory: &Memory) -> u8 {
+    fn jp_nc(&mut self, memory: &mut Memory) -> u8 {
         let addr = self.fetch_word(memory);
         if self.registers.f.c {
             12 // Not taken
@@ -811,15 +855,7 @@ impl Cpu {
     // ADD A, r - Add register to A
     // Flags: Z if result is 0, N=0, H if carry from bit 3, C if carry from bit 7
     fn add_a(&mut self, value: u8) -> u8 {
-        let a = self.registers.a;
-        let result = a.wrapping_add(value);
-
-        self.registers.f.z = result == 0;
-        self.registers.f.n = false;
-        self.registers.f.h = (a & 0x0F) + (value & 0x0F) > 0x0F;
-        self.registers.f.c = u16::from(a) + u16::from(value) > 0xFF;
-
-        self.registers.a = result;
+        (self.registers.a, self.registers.f) = alu::add(self.registers.a, value);
         4
     }
 
@@ -868,15 +904,7 @@ impl Cpu {
     // SUB A, r - Subtract register from A
     // Flags: Z if result is 0, N=1, H if borrow from bit 4, C if borrow
     fn sub_a(&mut self, value: u8) -> u8 {
-        let a = self.registers.a;
-        let result = a.wrapping_sub(value);
-
-        self.registers.f.z = result == 0;
-        self.registers.f.n = true;
-        self.registers.f.h = (a & 0x0F) < (value & 0x0F);
-        self.registers.f.c = a < value;
-
-        self.registers.a = result;
+        (self.registers.a, self.registers.f) = alu::sub(self.registers.a, value);
         4
     }
 
@@ -1031,13 +1059,7 @@ impl Cpu {
     // CP A, r - Compare A with register (SUB without storing result)
     // Flags: Z if equal, N=1, H if borrow from bit 4, C if A < value
     fn cp_a(&mut self, value: u8) -> u8 {
-        let a = self.registers.a;
-        let result = a.wrapping_sub(value);
-
-        self.registers.f.z = result == 0;
-        self.registers.f.n = true;
-        self.registers.f.h = (a & 0x0F) < (value & 0x0F);
-        self.registers.f.c = a < value;
+        self.registers.f = alu::cp(self.registers.a, value);
         4
     }
 
@@ -1302,15 +1324,15 @@ impl Cpu {
         }
     }
 
-    // RETI - Return from interrupt (same as RET but enables interrupts)
-    // Note: Interrupt handling not yet implemented, so this is just like RET for now
+    // RETI - Return from interrupt: RET, enabling interrupts at once rather
+    // than after the next instruction as EI does
     fn reti(&mut self, memory: &Memory) -> u8 {
         let low = memory.read_byte(self.sp);
         self.sp = self.sp.wrapping_add(1);
         let high = memory.read_byte(self.sp);
         self.sp = self.sp.wrapping_add(1);
         self.pc = (u16::from(high) << 8) | u16::from(low);
-        // TODO: Enable interrupts when interrupt system is implemented
+        self.interrupts_enabled = true;
         16
     }
 
@@ -1343,7 +1365,7 @@ impl Cpu {
     }
 
     // LD A,(nn) - Load A from memory at 16-bit address
-    fn ld_a_nn(&mut self, memory: &Memory) -> u8 {
+    fn ld_a_nn(&mut self, memory: &mut Memory) -> u8 {
         let addr = self.fetch_word(memory);
         self.registers.a = memory.read_byte(addr);
         16
@@ -1434,44 +1456,26 @@ impl Cpu {
     // LD HL,SP+n or LDHL SP,n - Load HL with SP + signed 8-bit offset
     // Flags: Z=0, N=0, H=carry from bit 3, C=carry from bit 7
     fn ld_hl_sp_n(&mut self, memory: &Memory) -> u8 {
-        let offset = i16::from(
-            #[allow(clippy::cast_possible_wrap)]
-            {
-                self.fetch_byte(memory) as i8
-            },
-        );
-
-        let sp = self.sp;
-        #[allow(clippy::cast_sign_loss)]
-        let result = sp.wrapping_add(offset as u16);
-
-        // For LD HL,SP+n, half-carry and carry are calculated on the lower byte
-        let sp_low = (sp & 0xFF) as u8;
-        #[allow(clippy::cast_sign_loss)]
-        let offset_low = (offset & 0xFF) as u8;
-
-        self.registers.f.z = false;
-        self.registers.f.n = false;
-        self.registers.f.h = (sp_low & 0x0F) + (offset_low & 0x0F) > 0x0F;
-        self.registers.f.c = u16::from(sp_low) + u16::from(offset_low) > 0xFF;
-
+        let offset = self.fetch_byte(memory);
+        let result;
+        (result, self.registers.f) = alu::add_sp(self.sp, offset);
         self.registers.set_hl(result);
         12
     }
 
+    // ADD SP,n - Add signed 8-bit offset to SP
+    // Flags: Z=0, N=0, H=carry from bit 3, C=carry from bit 7
+    fn add_sp_n(&mut self, memory: &Memory) -> u8 {
+        let offset = self.fetch_byte(memory);
+        (self.sp, self.registers.f) = alu::add_sp(self.sp, offset);
+        16
+    }
+
     // ADC A,r - Add with carry
     // Flags: Z if result is 0, N=0, H if carry from bit 3, C if carry from bit 7
     fn adc_a(&mut self, value: u8) -> u8 {
-        let a = self.registers.a;
-        let carry = u8::from(self.registers.f.c);
-        let result = a.wrapping_add(value).wrapping_add(carry);
-
-        self.registers.f.z = result == 0;
-        self.registers.f.n = false;
-        self.registers.f.h = (a & 0x0F) + (value & 0x0F) + carry > 0x0F;
-        self.registers.f.c = u16::from(a) + u16::from(value) + u16::from(carry) > 0xFF;
-
-        self.registers.a = result;
+        (self.registers.a, self.registers.f) =
+            alu::adc(self.registers.a, value, self.registers.f.c);
         4
     }
 
@@ -1520,16 +1524,8 @@ impl Cpu {
     // SBC A,r - Subtract with carry (borrow)
     // Flags: Z if result is 0, N=1, H if borrow from bit 4, C if borrow
     fn sbc_a(&mut self, value: u8) -> u8 {
-        let a = self.registers.a;
-        let carry = u8::from(self.registers.f.c);
-        let result = a.wrapping_sub(value).wrapping_sub(carry);
-
-        self.registers.f.z = result == 0;
-        self.registers.f.n = true;
-        self.registers.f.h = (a & 0x0F) < (value & 0x0F) + carry;
-        self.registers.f.c = u16::from(a) < u16::from(value) + u16::from(carry);
-
-        self.registers.a = result;
+        (self.registers.a, self.registers.f) =
+            alu::sbc(self.registers.a, value, self.registers.f.c);
         4
     }
 
@@ -1627,27 +1623,7 @@ impl Cpu {
 
     // DAA - Decimal Adjust Accumulator (for BCD arithmetic)
     fn daa(&mut self) -> u8 {
-        let mut a = self.registers.a;
-        let mut adjust = 0u8;
-
-        if self.registers.f.h || (!self.registers.f.n && (a & 0x0F) > 0x09) {
-            adjust |= 0x06;
-        }
-
-        if self.registers.f.c || (!self.registers.f.n && a > 0x99) {
-            adjust |= 0x60;
-            self.registers.f.c = true;
-        }
-
-        if self.registers.f.n {
-            a = a.wrapping_sub(adjust);
-        } else {
-            a = a.wrapping_add(adjust);
-        }
-
-        self.registers.a = a;
-        self.registers.f.z = a == 0;
-        self.registers.f.h = false;
+        (self.registers.a, self.registers.f) = alu::daa(self.registers.a, self.registers.f);
         4
     }
 
@@ -1681,9 +1657,9 @@ impl Cpu {
         4
     }
 
-    // EI - Enable Interrupts
+    // EI - Enable Interrupts, once the following instruction has run
     fn ei(&mut self) -> u8 {
-        self.interrupts_enabled = true;
+        self.ime_pending = true;
         4
     }
 
@@ -2184,8 +2160,7 @@ impl Cpu {
                 let addr = self.registers.hl();
                 memory.read_byte(addr)
             }
-            7 => self.registers.a,
-            _ => unreachable!(),
+            _ => self.registers.a, // 7
         };
 
         let bit_set = (value & (1 << bit)) != 0;
@@ -2219,8 +2194,7 @@ impl Cpu {
                 let value = memory.read_byte(addr);
                 memory.write_byte(addr, value & mask);
             }
-            7 => self.registers.a &= mask,
-            _ => unreachable!(),
+            _ => self.registers.a &= mask, // 7
         }
 
         if reg == 6 {
@@ -2248,8 +2222,7 @@ impl Cpu {
                 let value = memory.read_byte(addr);
                 memory.write_byte(addr, value | mask);
             }
-            7 => self.registers.a |= mask,
-            _ => unreachable!(),
+            _ => self.registers.a |= mask, // 7
         }
 
         if reg == 6 {
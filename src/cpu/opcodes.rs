@@ -0,0 +1,692 @@
+/// Static description of one instruction encoding, used by the disassembler
+/// and tooling. Mnemonics use RGBDS syntax with operand placeholders:
+/// `n8` / `n16` immediate, `e8` signed offset, `a8` high-page address
+/// (0xFF00 + n8).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    /// Length in bytes, including the opcode (and CB prefix)
+    pub length: u8,
+    /// CPU cycles, or cycles when a conditional branch is not taken
+    pub cycles: u8,
+    /// CPU cycles when a conditional branch is taken (same as `cycles` otherwise)
+    pub branch_cycles: u8,
+}
+
+impl Opcode {
+    const fn new(mnemonic: &'static str, length: u8, cycles: u8) -> Self {
+        Self {
+            mnemonic,
+            length,
+            cycles,
+            branch_cycles: cycles,
+        }
+    }
+
+    const fn branch(mnemonic: &'static str, length: u8, cycles: u8, branch_cycles: u8) -> Self {
+        Self {
+            mnemonic,
+            length,
+            cycles,
+            branch_cycles,
+        }
+    }
+
+    /// Unused opcode (0xD3, 0xDB, ...); hangs real hardware
+    pub const ILLEGAL: Self = Self::new("illegal", 1, 0);
+
+    pub fn is_illegal(&self) -> bool {
+        self.cycles == 0
+    }
+
+    pub fn is_conditional(&self) -> bool {
+        self.cycles != self.branch_cycles
+    }
+
+    /// Effect on the Z, N, H and C flags in the usual notation: the flag's
+    /// letter if it depends on the result, `0` or `1` if it is reset or set,
+    /// `-` if it is left alone
+    pub fn flags(&self) -> &'static str {
+        let (operation, operands) = self.mnemonic.split_once(' ').unwrap_or((self.mnemonic, ""));
+        let register_pair = ["bc", "de", "hl", "sp"].contains(&operands);
+        match operation {
+            "inc" | "dec" if register_pair => "----",
+            "inc" => "Z0H-",
+            "dec" => "Z1H-",
+            "add" if operands.starts_with("hl") => "-0HC",
+            "add" if operands.starts_with("sp") => "00HC",
+            "ld" if operands == "hl, sp+e8" => "00HC",
+            "add" | "adc" => "Z0HC",
+            "sub" | "sbc" | "cp" => "Z1HC",
+            "and" => "Z010",
+            "or" | "xor" | "swap" => "Z000",
+            "rlca" | "rrca" | "rla" | "rra" => "000C",
+            "rlc" | "rrc" | "rl" | "rr" | "sla" | "sra" | "srl" => "Z00C",
+            "bit" => "Z01-",
+            "daa" => "Z-0C",
+            "cpl" => "-11-",
+            "scf" => "-001",
+            "ccf" => "-00C",
+            "pop" if operands == "af" => "ZNHC",
+            _ => "----",
+        }
+    }
+}
+
+/// Metadata for the instruction starting with `opcode`; `next` is the
+/// following byte, used for CB-prefixed instructions
+pub fn lookup(opcode: u8, next: u8) -> &'static Opcode {
+    if opcode == 0xCB {
+        &CB_OPCODES[usize::from(next)]
+    } else {
+        &OPCODES[usize::from(opcode)]
+    }
+}
+
+/// Base (unprefixed) opcodes, indexed by opcode byte
+pub static OPCODES: [Opcode; 256] = [
+    // 0x00
+    Opcode::new("nop", 1, 4),
+    Opcode::new("ld bc, n16", 3, 12),
+    Opcode::new("ld [bc], a", 1, 8),
+    Opcode::new("inc bc", 1, 8),
+    Opcode::new("inc b", 1, 4),
+    Opcode::new("dec b", 1, 4),
+    Opcode::new("ld b, n8", 2, 8),
+    Opcode::new("rlca", 1, 4),
+    Opcode::new("ld [n16], sp", 3, 20),
+    Opcode::new("add hl, bc", 1, 8),
+    Opcode::new("ld a, [bc]", 1, 8),
+    Opcode::new("dec bc", 1, 8),
+    Opcode::new("inc c", 1, 4),
+    Opcode::new("dec c", 1, 4),
+    Opcode::new("ld c, n8", 2, 8),
+    Opcode::new("rrca", 1, 4),
+    // 0x10
+    Opcode::new("stop", 2, 4),
+    Opcode::new("ld de, n16", 3, 12),
+    Opcode::new("ld [de], a", 1, 8),
+    Opcode::new("inc de", 1, 8),
+    Opcode::new("inc d", 1, 4),
+    Opcode::new("dec d", 1, 4),
+    Opcode::new("ld d, n8", 2, 8),
+    Opcode::new("rla", 1, 4),
+    Opcode::new("jr e8", 2, 12),
+    Opcode::new("add hl, de", 1, 8),
+    Opcode::new("ld a, [de]", 1, 8),
+    Opcode::new("dec de", 1, 8),
+    Opcode::new("inc e", 1, 4),
+    Opcode::new("dec e", 1, 4),
+    Opcode::new("ld e, n8", 2, 8),
+    Opcode::new("rra", 1, 4),
+    // 0x20
+    Opcode::branch("jr nz, e8", 2, 8, 12),
+    Opcode::new("ld hl, n16", 3, 12),
+    Opcode::new("ld [hl+], a", 1, 8),
+    Opcode::new("inc hl", 1, 8),
+    Opcode::new("inc h", 1, 4),
+    Opcode::new("dec h", 1, 4),
+    Opcode::new("ld h, n8", 2, 8),
+    Opcode::new("daa", 1, 4),
+    Opcode::branch("jr z, e8", 2, 8, 12),
+    Opcode::new("add hl, hl", 1, 8),
+    Opcode::new("ld a, [hl+]", 1, 8),
+    Opcode::new("dec hl", 1, 8),
+    Opcode::new("inc l", 1, 4),
+    Opcode::new("dec l", 1, 4),
+    Opcode::new("ld l, n8", 2, 8),
+    Opcode::new("cpl", 1, 4),
+    // 0x30
+    Opcode::branch("jr nc, e8", 2, 8, 12),
+    Opcode::new("ld sp, n16", 3, 12),
+    Opcode::new("ld [hl-], a", 1, 8),
+    Opcode::new("inc sp", 1, 8),
+    Opcode::new("inc [hl]", 1, 12),
+    Opcode::new("dec [hl]", 1, 12),
+    Opcode::new("ld [hl], n8", 2, 12),
+    Opcode::new("scf", 1, 4),
+    Opcode::branch("jr c, e8", 2, 8, 12),
+    Opcode::new("add hl, sp", 1, 8),
+    Opcode::new("ld a, [hl-]", 1, 8),
+    Opcode::new("dec sp", 1, 8),
+    Opcode::new("inc a", 1, 4),
+    Opcode::new("dec a", 1, 4),
+    Opcode::new("ld a, n8", 2, 8),
+    Opcode::new("ccf", 1, 4),
+    // 0x40
+    Opcode::new("ld b, b", 1, 4),
+    Opcode::new("ld b, c", 1, 4),
+    Opcode::new("ld b, d", 1, 4),
+    Opcode::new("ld b, e", 1, 4),
+    Opcode::new("ld b, h", 1, 4),
+    Opcode::new("ld b, l", 1, 4),
+    Opcode::new("ld b, [hl]", 1, 8),
+    Opcode::new("ld b, a", 1, 4),
+    Opcode::new("ld c, b", 1, 4),
+    Opcode::new("ld c, c", 1, 4),
+    Opcode::new("ld c, d", 1, 4),
+    Opcode::new("ld c, e", 1, 4),
+    Opcode::new("ld c, h", 1, 4),
+    Opcode::new("ld c, l", 1, 4),
+    Opcode::new("ld c, [hl]", 1, 8),
+    Opcode::new("ld c, a", 1, 4),
+    // 0x50
+    Opcode::new("ld d, b", 1, 4),
+    Opcode::new("ld d, c", 1, 4),
+    Opcode::new("ld d, d", 1, 4),
+    Opcode::new("ld d, e", 1, 4),
+    Opcode::new("ld d, h", 1, 4),
+    Opcode::new("ld d, l", 1, 4),
+    Opcode::new("ld d, [hl]", 1, 8),
+    Opcode::new("ld d, a", 1, 4),
+    Opcode::new("ld e, b", 1, 4),
+    Opcode::new("ld e, c", 1, 4),
+    Opcode::new("ld e, d", 1, 4),
+    Opcode::new("ld e, e", 1, 4),
+    Opcode::new("ld e, h", 1, 4),
+    Opcode::new("ld e, l", 1, 4),
+    Opcode::new("ld e, [hl]", 1, 8),
+    Opcode::new("ld e, a", 1, 4),
+    // 0x60
+    Opcode::new("ld h, b", 1, 4),
+    Opcode::new("ld h, c", 1, 4),
+    Opcode::new("ld h, d", 1, 4),
+    Opcode::new("ld h, e", 1, 4),
+    Opcode::new("ld h, h", 1, 4),
+    Opcode::new("ld h, l", 1, 4),
+    Opcode::new("ld h, [hl]", 1, 8),
+    Opcode::new("ld h, a", 1, 4),
+    Opcode::new("ld l, b", 1, 4),
+    Opcode::new("ld l, c", 1, 4),
+    Opcode::new("ld l, d", 1, 4),
+    Opcode::new("ld l, e", 1, 4),
+    Opcode::new("ld l, h", 1, 4),
+    Opcode::new("ld l, l", 1, 4),
+    Opcode::new("ld l, [hl]", 1, 8),
+    Opcode::new("ld l, a", 1, 4),
+    // 0x70
+    Opcode::new("ld [hl], b", 1, 8),
+    Opcode::new("ld [hl], c", 1, 8),
+    Opcode::new("ld [hl], d", 1, 8),
+    Opcode::new("ld [hl], e", 1, 8),
+    Opcode::new("ld [hl], h", 1, 8),
+    Opcode::new("ld [hl], l", 1, 8),
+    Opcode::new("halt", 1, 4),
+    Opcode::new("ld [hl], a", 1, 8),
+    Opcode::new("ld a, b", 1, 4),
+    Opcode::new("ld a, c", 1, 4),
+    Opcode::new("ld a, d", 1, 4),
+    Opcode::new("ld a, e", 1, 4),
+    Opcode::new("ld a, h", 1, 4),
+    Opcode::new("ld a, l", 1, 4),
+    Opcode::new("ld a, [hl]", 1, 8),
+    Opcode::new("ld a, a", 1, 4),
+    // 0x80
+    Opcode::new("add a, b", 1, 4),
+    Opcode::new("add a, c", 1, 4),
+    Opcode::new("add a, d", 1, 4),
+    Opcode::new("add a, e", 1, 4),
+    Opcode::new("add a, h", 1, 4),
+    Opcode::new("add a, l", 1, 4),
+    Opcode::new("add a, [hl]", 1, 8),
+    Opcode::new("add a, a", 1, 4),
+    Opcode::new("adc a, b", 1, 4),
+    Opcode::new("adc a, c", 1, 4),
+    Opcode::new("adc a, d", 1, 4),
+    Opcode::new("adc a, e", 1, 4),
+    Opcode::new("adc a, h", 1, 4),
+    Opcode::new("adc a, l", 1, 4),
+    Opcode::new("adc a, [hl]", 1, 8),
+    Opcode::new("adc a, a", 1, 4),
+    // 0x90
+    Opcode::new("sub a, b", 1, 4),
+    Opcode::new("sub a, c", 1, 4),
+    Opcode::new("sub a, d", 1, 4),
+    Opcode::new("sub a, e", 1, 4),
+    Opcode::new("sub a, h", 1, 4),
+    Opcode::new("sub a, l", 1, 4),
+    Opcode::new("sub a, [hl]", 1, 8),
+    Opcode::new("sub a, a", 1, 4),
+    Opcode::new("sbc a, b", 1, 4),
+    Opcode::new("sbc a, c", 1, 4),
+    Opcode::new("sbc a, d", 1, 4),
+    Opcode::new("sbc a, e", 1, 4),
+    Opcode::new("sbc a, h", 1, 4),
+    Opcode::new("sbc a, l", 1, 4),
+    Opcode::new("sbc a, [hl]", 1, 8),
+    Opcode::new("sbc a, a", 1, 4),
+    // 0xA0
+    Opcode::new("and a, b", 1, 4),
+    Opcode::new("and a, c", 1, 4),
+    Opcode::new("and a, d", 1, 4),
+    Opcode::new("and a, e", 1, 4),
+    Opcode::new("and a, h", 1, 4),
+    Opcode::new("and a, l", 1, 4),
+    Opcode::new("and a, [hl]", 1, 8),
+    Opcode::new("and a, a", 1, 4),
+    Opcode::new("xor a, b", 1, 4),
+    Opcode::new("xor a, c", 1, 4),
+    Opcode::new("xor a, d", 1, 4),
+    Opcode::new("xor a, e", 1, 4),
+    Opcode::new("xor a, h", 1, 4),
+    Opcode::new("xor a, l", 1, 4),
+    Opcode::new("xor a, [hl]", 1, 8),
+    Opcode::new("xor a, a", 1, 4),
+    // 0xB0
+    Opcode::new("or a, b", 1, 4),
+    Opcode::new("or a, c", 1, 4),
+    Opcode::new("or a, d", 1, 4),
+    Opcode::new("or a, e", 1, 4),
+    Opcode::new("or a, h", 1, 4),
+    Opcode::new("or a, l", 1, 4),
+    Opcode::new("or a, [hl]", 1, 8),
+    Opcode::new("or a, a", 1, 4),
+    Opcode::new("cp a, b", 1, 4),
+    Opcode::new("cp a, c", 1, 4),
+    Opcode::new("cp a, d", 1, 4),
+    Opcode::new("cp a, e", 1, 4),
+    Opcode::new("cp a, h", 1, 4),
+    Opcode::new("cp a, l", 1, 4),
+    Opcode::new("cp a, [hl]", 1, 8),
+    Opcode::new("cp a, a", 1, 4),
+    // 0xC0
+    Opcode::branch("ret nz", 1, 8, 20),
+    Opcode::new("pop bc", 1, 12),
+    Opcode::branch("jp nz, n16", 3, 12, 16),
+    Opcode::new("jp n16", 3, 16),
+    Opcode::branch("call nz, n16", 3, 12, 24),
+    Opcode::new("push bc", 1, 16),
+    Opcode::new("add a, n8", 2, 8),
+    Opcode::new("rst $00", 1, 16),
+    Opcode::branch("ret z", 1, 8, 20),
+    Opcode::new("ret", 1, 16),
+    Opcode::branch("jp z, n16", 3, 12, 16),
+    Opcode::new("prefix cb", 1, 4),
+    Opcode::branch("call z, n16", 3, 12, 24),
+    Opcode::new("call n16", 3, 24),
+    Opcode::new("adc a, n8", 2, 8),
+    Opcode::new("rst $08", 1, 16),
+    // 0xD0
+    Opcode::branch("ret nc", 1, 8, 20),
+    Opcode::new("pop de", 1, 12),
+    Opcode::branch("jp nc, n16", 3, 12, 16),
+    Opcode::ILLEGAL,
+    Opcode::branch("call nc, n16", 3, 12, 24),
+    Opcode::new("push de", 1, 16),
+    Opcode::new("sub a, n8", 2, 8),
+    Opcode::new("rst $10", 1, 16),
+    Opcode::branch("ret c", 1, 8, 20),
+    Opcode::new("reti", 1, 16),
+    Opcode::branch("jp c, n16", 3, 12, 16),
+    Opcode::ILLEGAL,
+    Opcode::branch("call c, n16", 3, 12, 24),
+    Opcode::ILLEGAL,
+    Opcode::new("sbc a, n8", 2, 8),
+    Opcode::new("rst $18", 1, 16),
+    // 0xE0
+    Opcode::new("ldh [a8], a", 2, 12),
+    Opcode::new("pop hl", 1, 12),
+    Opcode::new("ldh [c], a", 1, 8),
+    Opcode::ILLEGAL,
+    Opcode::ILLEGAL,
+    Opcode::new("push hl", 1, 16),
+    Opcode::new("and a, n8", 2, 8),
+    Opcode::new("rst $20", 1, 16),
+    Opcode::new("add sp, e8", 2, 16),
+    Opcode::new("jp hl", 1, 4),
+    Opcode::new("ld [n16], a", 3, 16),
+    Opcode::ILLEGAL,
+    Opcode::ILLEGAL,
+    Opcode::ILLEGAL,
+    Opcode::new("xor a, n8", 2, 8),
+    Opcode::new("rst $28", 1, 16),
+    // 0xF0
+    Opcode::new("ldh a, [a8]", 2, 12),
+    Opcode::new("pop af", 1, 12),
+    Opcode::new("ldh a, [c]", 1, 8),
+    Opcode::new("di", 1, 4),
+    Opcode::ILLEGAL,
+    Opcode::new("push af", 1, 16),
+    Opcode::new("or a, n8", 2, 8),
+    Opcode::new("rst $30", 1, 16),
+    Opcode::new("ld hl, sp+e8", 2, 12),
+    Opcode::new("ld sp, hl", 1, 8),
+    Opcode::new("ld a, [n16]", 3, 16),
+    Opcode::new("ei", 1, 4),
+    Opcode::ILLEGAL,
+    Opcode::ILLEGAL,
+    Opcode::new("cp a, n8", 2, 8),
+    Opcode::new("rst $38", 1, 16),
+];
+
+/// CB-prefixed opcodes, indexed by the byte after 0xCB. Lengths and
+/// cycles include the prefix.
+pub static CB_OPCODES: [Opcode; 256] = [
+    // 0xCB 0x00
+    Opcode::new("rlc b", 2, 8),
+    Opcode::new("rlc c", 2, 8),
+    Opcode::new("rlc d", 2, 8),
+    Opcode::new("rlc e", 2, 8),
+    Opcode::new("rlc h", 2, 8),
+    Opcode::new("rlc l", 2, 8),
+    Opcode::new("rlc [hl]", 2, 16),
+    Opcode::new("rlc a", 2, 8),
+    Opcode::new("rrc b", 2, 8),
+    Opcode::new("rrc c", 2, 8),
+    Opcode::new("rrc d", 2, 8),
+    Opcode::new("rrc e", 2, 8),
+    Opcode::new("rrc h", 2, 8),
+    Opcode::new("rrc l", 2, 8),
+    Opcode::new("rrc [hl]", 2, 16),
+    Opcode::new("rrc a", 2, 8),
+    // 0xCB 0x10
+    Opcode::new("rl b", 2, 8),
+    Opcode::new("rl c", 2, 8),
+    Opcode::new("rl d", 2, 8),
+    Opcode::new("rl e", 2, 8),
+    Opcode::new("rl h", 2, 8),
+    Opcode::new("rl l", 2, 8),
+    Opcode::new("rl [hl]", 2, 16),
+    Opcode::new("rl a", 2, 8),
+    Opcode::new("rr b", 2, 8),
+    Opcode::new("rr c", 2, 8),
+    Opcode::new("rr d", 2, 8),
+    Opcode::new("rr e", 2, 8),
+    Opcode::new("rr h", 2, 8),
+    Opcode::new("rr l", 2, 8),
+    Opcode::new("rr [hl]", 2, 16),
+    Opcode::new("rr a", 2, 8),
+    // 0xCB 0x20
+    Opcode::new("sla b", 2, 8),
+    Opcode::new("sla c", 2, 8),
+    Opcode::new("sla d", 2, 8),
+    Opcode::new("sla e", 2, 8),
+    Opcode::new("sla h", 2, 8),
+    Opcode::new("sla l", 2, 8),
+    Opcode::new("sla [hl]", 2, 16),
+    Opcode::new("sla a", 2, 8),
+    Opcode::new("sra b", 2, 8),
+    Opcode::new("sra c", 2, 8),
+    Opcode::new("sra d", 2, 8),
+    Opcode::new("sra e", 2, 8),
+    Opcode::new("sra h", 2, 8),
+    Opcode::new("sra l", 2, 8),
+    Opcode::new("sra [hl]", 2, 16),
+    Opcode::new("sra a", 2, 8),
+    // 0xCB 0x30
+    Opcode::new("swap b", 2, 8),
+    Opcode::new("swap c", 2, 8),
+    Opcode::new("swap d", 2, 8),
+    Opcode::new("swap e", 2, 8),
+    Opcode::new("swap h", 2, 8),
+    Opcode::new("swap l", 2, 8),
+    Opcode::new("swap [hl]", 2, 16),
+    Opcode::new("swap a", 2, 8),
+    Opcode::new("srl b", 2, 8),
+    Opcode::new("srl c", 2, 8),
+    Opcode::new("srl d", 2, 8),
+    Opcode::new("srl e", 2, 8),
+    Opcode::new("srl h", 2, 8),
+    Opcode::new("srl l", 2, 8),
+    Opcode::new("srl [hl]", 2, 16),
+    Opcode::new("srl a", 2, 8),
+    // 0xCB 0x40
+    Opcode::new("bit 0, b", 2, 8),
+    Opcode::new("bit 0, c", 2, 8),
+    Opcode::new("bit 0, d", 2, 8),
+    Opcode::new("bit 0, e", 2, 8),
+    Opcode::new("bit 0, h", 2, 8),
+    Opcode::new("bit 0, l", 2, 8),
+    Opcode::new("bit 0, [hl]", 2, 12),
+    Opcode::new("bit 0, a", 2, 8),
+    Opcode::new("bit 1, b", 2, 8),
+    Opcode::new("bit 1, c", 2, 8),
+    Opcode::new("bit 1, d", 2, 8),
+    Opcode::new("bit 1, e", 2, 8),
+    Opcode::new("bit 1, h", 2, 8),
+    Opcode::new("bit 1, l", 2, 8),
+    Opcode::new("bit 1, [hl]", 2, 12),
+    Opcode::new("bit 1, a", 2, 8),
+    // 0xCB 0x50
+    Opcode::new("bit 2, b", 2, 8),
+    Opcode::new("bit 2, c", 2, 8),
+    Opcode::new("bit 2, d", 2, 8),
+    Opcode::new("bit 2, e", 2, 8),
+    Opcode::new("bit 2, h", 2, 8),
+    Opcode::new("bit 2, l", 2, 8),
+    Opcode::new("bit 2, [hl]", 2, 12),
+    Opcode::new("bit 2, a", 2, 8),
+    Opcode::new("bit 3, b", 2, 8),
+    Opcode::new("bit 3, c", 2, 8),
+    Opcode::new("bit 3, d", 2, 8),
+    Opcode::new("bit 3, e", 2, 8),
+    Opcode::new("bit 3, h", 2, 8),
+    Opcode::new("bit 3, l", 2, 8),
+    Opcode::new("bit 3, [hl]", 2, 12),
+    Opcode::new("bit 3, a", 2, 8),
+    // 0xCB 0x60
+    Opcode::new("bit 4, b", 2, 8),
+    Opcode::new("bit 4, c", 2, 8),
+    Opcode::new("bit 4, d", 2, 8),
+    Opcode::new("bit 4, e", 2, 8),
+    Opcode::new("bit 4, h", 2, 8),
+    Opcode::new("bit 4, l", 2, 8),
+    Opcode::new("bit 4, [hl]", 2, 12),
+    Opcode::new("bit 4, a", 2, 8),
+    Opcode::new("bit 5, b", 2, 8),
+    Opcode::new("bit 5, c", 2, 8),
+    Opcode::new("bit 5, d", 2, 8),
+    Opcode::new("bit 5, e", 2, 8),
+    Opcode::new("bit 5, h", 2, 8),
+    Opcode::new("bit 5, l", 2, 8),
+    Opcode::new("bit 5, [hl]", 2, 12),
+    Opcode::new("bit 5, a", 2, 8),
+    // 0xCB 0x70
+    Opcode::new("bit 6, b", 2, 8),
+    Opcode::new("bit 6, c", 2, 8),
+    Opcode::new("bit 6, d", 2, 8),
+    Opcode::new("bit 6, e", 2, 8),
+    Opcode::new("bit 6, h", 2, 8),
+    Opcode::new("bit 6, l", 2, 8),
+    Opcode::new("bit 6, [hl]", 2, 12),
+    Opcode::new("bit 6, a", 2, 8),
+    Opcode::new("bit 7, b", 2, 8),
+    Opcode::new("bit 7, c", 2, 8),
+    Opcode::new("bit 7, d", 2, 8),
+    Opcode::new("bit 7, e", 2, 8),
+    Opcode::new("bit 7, h", 2, 8),
+    Opcode::new("bit 7, l", 2, 8),
+    Opcode::new("bit 7, [hl]", 2, 12),
+    Opcode::new("bit 7, a", 2, 8),
+    // 0xCB 0x80
+    Opcode::new("res 0, b", 2, 8),
+    Opcode::new("res 0, c", 2, 8),
+    Opcode::new("res 0, d", 2, 8),
+    Opcode::new("res 0, e", 2, 8),
+    Opcode::new("res 0, h", 2, 8),
+    Opcode::new("res 0, l", 2, 8),
+    Opcode::new("res 0, [hl]", 2, 16),
+    Opcode::new("res 0, a", 2, 8),
+    Opcode::new("res 1, b", 2, 8),
+    Opcode::new("res 1, c", 2, 8),
+    Opcode::new("res 1, d", 2, 8),
+    Opcode::new("res 1, e", 2, 8),
+    Opcode::new("res 1, h", 2, 8),
+    Opcode::new("res 1, l", 2, 8),
+    Opcode::new("res 1, [hl]", 2, 16),
+    Opcode::new("res 1, a", 2, 8),
+    // 0xCB 0x90
+    Opcode::new("res 2, b", 2, 8),
+    Opcode::new("res 2, c", 2, 8),
+    Opcode::new("res 2, d", 2, 8),
+    Opcode::new("res 2, e", 2, 8),
+    Opcode::new("res 2, h", 2, 8),
+    Opcode::new("res 2, l", 2, 8),
+    Opcode::new("res 2, [hl]", 2, 16),
+    Opcode::new("res 2, a", 2, 8),
+    Opcode::new("res 3, b", 2, 8),
+    Opcode::new("res 3, c", 2, 8),
+    Opcode::new("res 3, d", 2, 8),
+    Opcode::new("res 3, e", 2, 8),
+    Opcode::new("res 3, h", 2, 8),
+    Opcode::new("res 3, l", 2, 8),
+    Opcode::new("res 3, [hl]", 2, 16),
+    Opcode::new("res 3, a", 2, 8),
+    // 0xCB 0xA0
+    Opcode::new("res 4, b", 2, 8),
+    Opcode::new("res 4, c", 2, 8),
+    Opcode::new("res 4, d", 2, 8),
+    Opcode::new("res 4, e", 2, 8),
+    Opcode::new("res 4, h", 2, 8),
+    Opcode::new("res 4, l", 2, 8),
+    Opcode::new("res 4, [hl]", 2, 16),
+    Opcode::new("res 4, a", 2, 8),
+    Opcode::new("res 5, b", 2, 8),
+    Opcode::new("res 5, c", 2, 8),
+    Opcode::new("res 5, d", 2, 8),
+    Opcode::new("res 5, e", 2, 8),
+    Opcode::new("res 5, h", 2, 8),
+    Opcode::new("res 5, l", 2, 8),
+    Opcode::new("res 5, [hl]", 2, 16),
+    Opcode::new("res 5, a", 2, 8),
+    // 0xCB 0xB0
+    Opcode::new("res 6, b", 2, 8),
+    Opcode::new("res 6, c", 2, 8),
+    Opcode::new("res 6, d", 2, 8),
+    Opcode::new("res 6, e", 2, 8),
+    Opcode::new("res 6, h", 2, 8),
+    Opcode::new("res 6, l", 2, 8),
+    Opcode::new("res 6, [hl]", 2, 16),
+    Opcode::new("res 6, a", 2, 8),
+    Opcode::new("res 7, b", 2, 8),
+    Opcode::new("res 7, c", 2, 8),
+    Opcode::new("res 7, d", 2, 8),
+    Opcode::new("res 7, e", 2, 8),
+    Opcode::new("res 7, h", 2, 8),
+    Opcode::new("res 7, l", 2, 8),
+    Opcode::new("res 7, [hl]", 2, 16),
+    Opcode::new("res 7, a", 2, 8),
+    // 0xCB 0xC0
+    Opcode::new("set 0, b", 2, 8),
+    Opcode::new("set 0, c", 2, 8),
+    Opcode::new("set 0, d", 2, 8),
+    Opcode::new("set 0, e", 2, 8),
+    Opcode::new("set 0, h", 2, 8),
+    Opcode::new("set 0, l", 2, 8),
+    Opcode::new("set 0, [hl]", 2, 16),
+    Opcode::new("set 0, a", 2, 8),
+    Opcode::new("set 1, b", 2, 8),
+    Opcode::new("set 1, c", 2, 8),
+    Opcode::new("set 1, d", 2, 8),
+    Opcode::new("set 1, e", 2, 8),
+    Opcode::new("set 1, h", 2, 8),
+    Opcode::new("set 1, l", 2, 8),
+    Opcode::new("set 1, [hl]", 2, 16),
+    Opcode::new("set 1, a", 2, 8),
+    // 0xCB 0xD0
+    Opcode::new("set 2, b", 2, 8),
+    Opcode::new("set 2, c", 2, 8),
+    Opcode::new("set 2, d", 2, 8),
+    Opcode::new("set 2, e", 2, 8),
+    Opcode::new("set 2, h", 2, 8),
+    Opcode::new("set 2, l", 2, 8),
+    Opcode::new("set 2, [hl]", 2, 16),
+    Opcode::new("set 2, a", 2, 8),
+    Opcode::new("set 3, b", 2, 8),
+    Opcode::new("set 3, c", 2, 8),
+    Opcode::new("set 3, d", 2, 8),
+    Opcode::new("set 3, e", 2, 8),
+    Opcode::new("set 3, h", 2, 8),
+    Opcode::new("set 3, l", 2, 8),
+    Opcode::new("set 3, [hl]", 2, 16),
+    Opcode::new("set 3, a", 2, 8),
+    // 0xCB 0xE0
+    Opcode::new("set 4, b", 2, 8),
+    Opcode::new("set 4, c", 2, 8),
+    Opcode::new("set 4, d", 2, 8),
+    Opcode::new("set 4, e", 2, 8),
+    Opcode::new("set 4, h", 2, 8),
+    Opcode::new("set 4, l", 2, 8),
+    Opcode::new("set 4, [hl]", 2, 16),
+    Opcode::new("set 4, a", 2, 8),
+    Opcode::new("set 5, b", 2, 8),
+    Opcode::new("set 5, c", 2, 8),
+    Opcode::new("set 5, d", 2, 8),
+    Opcode::new("set 5, e", 2, 8),
+    Opcode::new("set 5, h", 2, 8),
+    Opcode::new("set 5, l", 2, 8),
+    Opcode::new("set 5, [hl]", 2, 16),
+    Opcode::new("set 5, a", 2, 8),
+    // 0xCB 0xF0
+    Opcode::new("set 6, b", 2, 8),
+    Opcode::new("set 6, c", 2, 8),
+    Opcode::new("set 6, d", 2, 8),
+    Opcode::new("set 6, e", 2, 8),
+    Opcode::new("set 6, h", 2, 8),
+    Opcode::new("set 6, l", 2, 8),
+    Opcode::new("set 6, [hl]", 2, 16),
+    Opcode::new("set 6, a", 2, 8),
+    Opcode::new("set 7, b", 2, 8),
+    Opcode::new("set 7, c", 2, 8),
+    Opcode::new("set 7, d", 2, 8),
+    Opcode::new("set 7, e", 2, 8),
+    Opcode::new("set 7, h", 2, 8),
+    Opcode::new("set 7, l", 2, 8),
+    Opcode::new("set 7, [hl]", 2, 16),
+    Opcode::new("set 7, a", 2, 8),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eleven_base_opcodes_are_illegal() {
+        let illegal: Vec<usize> = (0..256).filter(|&op| OPCODES[op].is_illegal()).collect();
+        assert_eq!(
+            illegal,
+            [
+                0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD
+            ]
+        );
+    }
+
+    #[test]
+    fn lengths_match_operand_placeholders() {
+        for opcode in &OPCODES {
+            let expected = if opcode.mnemonic.contains("n16") {
+                3
+            } else if ["n8", "e8", "a8", "stop"]
+                .iter()
+                .any(|operand| opcode.mnemonic.contains(operand))
+            {
+                2
+            } else {
+                1
+            };
+            assert_eq!(opcode.length, expected, "{}", opcode.mnemonic);
+        }
+    }
+
+    #[test]
+    fn flags_follow_the_operation() {
+        assert_eq!(OPCODES[0x04].flags(), "Z0H-"); // inc b
+        assert_eq!(OPCODES[0x03].flags(), "----"); // inc bc
+        assert_eq!(OPCODES[0x09].flags(), "-0HC"); // add hl, bc
+        assert_eq!(OPCODES[0xF8].flags(), "00HC"); // ld hl, sp+e8
+        assert_eq!(OPCODES[0xFE].flags(), "Z1HC"); // cp a, n8
+        assert_eq!(OPCODES[0xF1].flags(), "ZNHC"); // pop af
+        assert_eq!(CB_OPCODES[0x37].flags(), "Z000"); // swap a
+        assert_eq!(CB_OPCODES[0x7C].flags(), "Z01-"); // bit 7, h
+        assert_eq!(CB_OPCODES[0xFF].flags(), "----"); // set 7, a
+    }
+
+    #[test]
+    fn lookup_follows_cb_prefix() {
+        assert_eq!(lookup(0xCB, 0x7C).mnemonic, "bit 7, h");
+        assert_eq!(lookup(0xCB, 0x46).cycles, 12);
+        assert_eq!(lookup(0x20, 0x00).branch_cycles, 12);
+        assert!(lookup(0xC4, 0x00).is_conditional());
+        assert!(!lookup(0xCD, 0x00).is_conditional());
+    }
+}
@@ -1,5 +1,5 @@
 /// CPU Flags register
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct Flags {
     pub z: bool, // Zero flag
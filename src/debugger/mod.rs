@@ -0,0 +1,258 @@
+use crate::disasm::{self, Instruction};
+use crate::gameboy::GameBoy;
+use crate::memory::Memory;
+use crate::palette::{DmgPalette, SOURCE_BG, pixel};
+use crate::ppu::shade;
+use std::collections::BTreeSet;
+
+/// Tiles across the tile sheet, and down: all 384 tiles of VRAM
+pub const SHEET_TILES: (usize, usize) = (16, 24);
+/// Tile sheet size in pixels
+pub const SHEET_SIZE: (usize, usize) = (SHEET_TILES.0 * 8, SHEET_TILES.1 * 8);
+
+/// Longest instruction, for decoding backwards from PC
+const MAX_LENGTH: u16 = 3;
+
+/// Execution control for an interactive debugger: breakpoints on PC, and
+/// pausing, stepping and running frames that stop on them. Frontends run
+/// frames through `run_frame` instead of `GameBoy::finish_frame`, which it
+/// matches when there are no breakpoints.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    paused: bool,
+    /// The breakpoint that paused emulation, until it is resumed
+    hit: Option<u16>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.contains(&address)
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Add a breakpoint, or remove it if there is one, returning whether
+    /// there is one now
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if self.breakpoints.remove(&address) {
+            false
+        } else {
+            self.breakpoints.insert(address);
+            true
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The breakpoint emulation is stopped at, if a breakpoint stopped it
+    pub fn hit(&self) -> Option<u16> {
+        self.hit
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Continue running frames; a breakpoint at PC is passed over
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.hit = None;
+    }
+
+    /// Execute one instruction and stay paused
+    pub fn step(&mut self, game: &mut GameBoy) {
+        self.paused = true;
+        self.hit = None;
+        if !game.stopped() {
+            game.step();
+        }
+    }
+
+    /// Run to the end of the frame, as `GameBoy::finish_frame` does, unless
+    /// PC reaches a breakpoint first: then stop before that instruction and
+    /// pause. Returns whether a breakpoint was hit. The instruction at PC
+    /// when called always runs, so resuming from a breakpoint moves on.
+    pub fn run_frame(&mut self, game: &mut GameBoy) -> bool {
+        let frame = game.frame_count();
+        let mut first = true;
+        while game.frame_count() == frame && !game.stopped() {
+            if !first && self.breakpoints.contains(&game.cpu.pc) {
+                self.paused = true;
+                self.hit = Some(game.cpu.pc);
+                return true;
+            }
+            first = false;
+            game.step();
+        }
+        false
+    }
+}
+
+/// Decode the instruction at `address` from memory as the CPU would see it
+fn decode_at(memory: &Memory, address: u16) -> Instruction {
+    let bytes: Vec<u8> = (0..MAX_LENGTH)
+        .map(|offset| memory.peek(address.wrapping_add(offset)))
+        .collect();
+    disasm::decode(&bytes, address).expect("three bytes hold any instruction")
+}
+
+/// Up to `before` instructions leading to `pc`, then `after` from `pc` on.
+/// Code cannot be decoded backwards for certain, so the instructions
+/// before are those of the earliest start address within reach that
+/// decodes forward onto `pc` exactly; none if no start does.
+pub fn disassemble_around(
+    memory: &Memory,
+    pc: u16,
+    before: usize,
+    after: usize,
+) -> Vec<Instruction> {
+    let reach = u16::try_from(before)
+        .unwrap_or(u16::MAX)
+        .saturating_mul(MAX_LENGTH);
+    let mut lines = (pc.saturating_sub(reach)..pc)
+        .find_map(|start| {
+            let mut lines = Vec::new();
+            let mut address = start;
+            while address < pc {
+                let instruction = decode_at(memory, address);
+                address = address.saturating_add(u16::from(instruction.length));
+                lines.push(instruction);
+            }
+            (address == pc).then_some(lines)
+        })
+        .unwrap_or_default();
+    lines.drain(..lines.len().saturating_sub(before));
+
+    let mut address = pc;
+    for _ in 0..after {
+        let instruction = decode_at(memory, address);
+        address = address.wrapping_add(u16::from(instruction.length));
+        lines.push(instruction);
+    }
+    lines
+}
+
+/// One line of a hex view: the address, 16 bytes from it and those bytes
+/// as ASCII, e.g. `C000  48 45 4C 4C 4F 00 ...  HELLO...`
+pub fn hex_row(memory: &Memory, address: u16) -> String {
+    let bytes: Vec<u8> = (0..16)
+        .map(|offset| memory.peek(address.wrapping_add(offset)))
+        .collect();
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+    let text: String = bytes
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                char::from(byte)
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{address:04X}  {}  {text}", hex.join(" "))
+}
+
+/// Every tile in VRAM (0x8000-0x97FF) in a `SHEET_TILES` grid, in RGBA8,
+/// shaded through BGP with the background colors of `palette`
+pub fn tile_sheet(memory: &Memory, palette: &DmgPalette) -> Vec<u8> {
+    let bgp = memory.peek(0xFF47);
+    let mut rgba = vec![0; SHEET_SIZE.0 * SHEET_SIZE.1 * 4];
+    for (tile, address) in (0x8000..0x9800_u16).step_by(16).enumerate() {
+        let (column, row) = (tile % SHEET_TILES.0, tile / SHEET_TILES.0);
+        for y in 0..8_u16 {
+            let low = memory.peek(address + y * 2);
+            let high = memory.peek(address + y * 2 + 1);
+            for x in 0..8_u8 {
+                let color = (low >> (7 - x) & 1) | (high >> (7 - x) & 1) << 1;
+                let rgb = palette.color(pixel(shade(bgp, color), SOURCE_BG));
+                let offset =
+                    ((row * 8 + usize::from(y)) * SHEET_SIZE.0 + column * 8 + usize::from(x)) * 4;
+                rgba[offset..offset + 4].copy_from_slice(&rgb);
+            }
+        }
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game whose code at 0x0100 is `program`, with no cartridge
+    fn game(program: &[u8]) -> GameBoy {
+        let mut game = GameBoy::new();
+        for (address, &byte) in (0x0100..).zip(program) {
+            game.memory.write_byte(address, byte);
+        }
+        game
+    }
+
+    #[test]
+    fn stops_at_breakpoints_and_moves_on() {
+        // inc a; inc b; jr -4 (back to inc a)
+        let mut game = game(&[0x3C, 0x04, 0x18, 0xFC]);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x0101);
+        assert!(debugger.run_frame(&mut game));
+        assert_eq!((game.cpu.pc, debugger.hit()), (0x0101, Some(0x0101)));
+        assert!(debugger.is_paused());
+
+        debugger.resume();
+        assert!(debugger.run_frame(&mut game));
+        assert_eq!(game.cpu.registers.b, 0x01);
+
+        debugger.step(&mut game);
+        assert_eq!(game.cpu.pc, 0x0102);
+        assert!(debugger.is_paused() && debugger.hit().is_none());
+
+        assert!(!debugger.toggle_breakpoint(0x0101));
+        let frame = game.frame_count();
+        assert!(!debugger.run_frame(&mut game));
+        assert_eq!(game.frame_count(), frame + 1);
+    }
+
+    #[test]
+    fn disassembles_around_pc() {
+        // nop; ld a, $12; ld hl, $C000; inc a
+        let game = game(&[0x00, 0x3E, 0x12, 0x21, 0x00, 0xC0, 0x3C]);
+        let lines = disassemble_around(&game.memory, 0x0103, 2, 2);
+        let addresses: Vec<u16> = lines.iter().map(|line| line.address).collect();
+        assert_eq!(addresses, [0x0100, 0x0101, 0x0103, 0x0106]);
+        assert_eq!(lines[2].text, "ld hl, $C000");
+        assert_eq!(disassemble_around(&game.memory, 0x0000, 4, 1).len(), 1);
+    }
+
+    #[test]
+    fn formats_hex_rows_and_tiles() {
+        let mut game = game(b"HELLO");
+        assert!(hex_row(&game.memory, 0x0100).starts_with("0100  48 45 4C 4C 4F 00"));
+        assert!(hex_row(&game.memory, 0x0100).ends_with("  HELLO..........."));
+
+        // Tile 1, top row: color 3 then color 0 pixels
+        game.memory.write_byte(0xFF47, 0xE4);
+        game.memory.write_byte(0x8010, 0xF0);
+        game.memory.write_byte(0x8011, 0xF0);
+        let palette = DmgPalette::default();
+        let sheet = tile_sheet(&game.memory, &palette);
+        assert_eq!(sheet.len(), 128 * 192 * 4);
+        assert_eq!(sheet[8 * 4..8 * 4 + 4], palette.bg[3]);
+        assert_eq!(sheet[12 * 4..12 * 4 + 4], palette.bg[0]);
+    }
+}
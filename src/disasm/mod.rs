@@ -0,0 +1,352 @@
+use crate::cpu::opcodes;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const CODE: u8 = 0x01;
+const CODE_START: u8 = 0x02;
+
+/// Which ROM bytes were executed, recorded by `GameBoy::enable_coverage`.
+/// Indexed by offset into the ROM image, so banked code is told apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    pub fn new(rom_size: usize) -> Self {
+        Self {
+            flags: vec![0; rom_size],
+        }
+    }
+
+    /// Record an instruction of `length` bytes executed at ROM `offset`
+    pub fn mark(&mut self, offset: usize, length: u8) {
+        let end = (offset + usize::from(length)).min(self.flags.len());
+        if offset >= end {
+            return;
+        }
+        self.flags[offset] |= CODE_START;
+        for flag in &mut self.flags[offset..end] {
+            *flag |= CODE;
+        }
+    }
+
+    /// An executed instruction starts at `offset`
+    pub fn is_code_start(&self, offset: usize) -> bool {
+        self.flags
+            .get(offset)
+            .is_some_and(|flag| flag & CODE_START != 0)
+    }
+
+    /// `offset` belongs to an executed instruction
+    pub fn is_code(&self, offset: usize) -> bool {
+        self.flags.get(offset).is_some_and(|flag| flag & CODE != 0)
+    }
+
+    /// Number of ROM bytes that were executed
+    pub fn code_bytes(&self) -> usize {
+        self.flags.iter().filter(|&&flag| flag & CODE != 0).count()
+    }
+}
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    pub length: u8,
+    /// RGBDS syntax with operands filled in, e.g. `ld a, $12`
+    pub text: String,
+    /// Absolute destination of a jump, call or `rst`
+    pub target: Option<u16>,
+    pub is_call: bool,
+}
+
+/// Decode the instruction at `address` whose bytes start at `bytes[0]`.
+/// Returns `None` if `bytes` is too short to hold its operands.
+pub fn decode(bytes: &[u8], address: u16) -> Option<Instruction> {
+    let opcode = *bytes.first()?;
+    let info = opcodes::lookup(opcode, bytes.get(1).copied().unwrap_or(0));
+    let operands = bytes.get(1..usize::from(info.length))?;
+
+    let mut text = info.mnemonic.to_string();
+    let mut target = None;
+    if opcode == 0xCB {
+        // Operands are part of the mnemonic
+    } else if info.mnemonic.contains("n16") {
+        let value = u16::from_le_bytes([operands[0], operands[1]]);
+        text = text.replace("n16", &format!("${value:04X}"));
+        if info.mnemonic.starts_with("jp") || info.mnemonic.starts_with("call") {
+            target = Some(value);
+        }
+    } else if info.mnemonic.contains("a8") {
+        text = text.replace("a8", &format!("$FF{:02X}", operands[0]));
+    } else if info.mnemonic.contains("n8") {
+        text = text.replace("n8", &format!("${:02X}", operands[0]));
+    } else if info.mnemonic.starts_with("jr") {
+        let destination = address
+            .wrapping_add(2)
+            .wrapping_add_signed(i16::from(operands[0].cast_signed()));
+        text = text.replace("e8", &format!("${destination:04X}"));
+        target = Some(destination);
+    } else if info.mnemonic.contains("e8") {
+        text = text.replace("e8", &operands[0].cast_signed().to_string());
+    } else if info.mnemonic.starts_with("rst") {
+        target = Some(u16::from(opcode & 0x38));
+    } else if info.is_illegal() {
+        text = format!("db ${opcode:02X} ; illegal opcode");
+    }
+
+    Some(Instruction {
+        address,
+        length: info.length,
+        text,
+        target,
+        is_call: info.mnemonic.starts_with("call") || info.mnemonic.starts_with("rst"),
+    })
+}
+
+/// CPU address of ROM `offset` and the bank it lives in
+fn rom_address(offset: usize) -> (usize, u16) {
+    let bank = offset / 0x4000;
+    let base = if bank == 0 { 0x0000 } else { 0x4000 };
+    // offset % 0x4000 always fits in 14 bits
+    (bank, base + u16::try_from(offset % 0x4000).unwrap_or(0))
+}
+
+/// ROM offset a jump from `bank` to CPU address `target` lands on, if it
+/// can be known statically
+fn target_offset(bank: usize, target: u16, rom_size: usize) -> Option<usize> {
+    let offset = match target {
+        0x0000..=0x3FFF => usize::from(target),
+        // From bank 0 the switchable bank is unknown unless there is only one
+        0x4000..=0x7FFF if bank > 0 => bank * 0x4000 + usize::from(target - 0x4000),
+        0x4000..=0x7FFF if rom_size <= 0x8000 => usize::from(target),
+        _ => return None,
+    };
+    (offset < rom_size).then_some(offset)
+}
+
+struct Label {
+    name: String,
+    references: Vec<(usize, u16)>,
+}
+
+/// Export an annotated RGBDS listing: executed bytes are disassembled,
+/// everything else is emitted as `db` data, and jump/call targets get labels
+/// with cross-reference comments.
+pub fn listing(rom: &[u8], coverage: &Coverage, title: &str) -> String {
+    let instructions = decode_executed(rom, coverage);
+    let labels = collect_labels(rom, coverage, &instructions);
+
+    let mut out = String::new();
+    let code = coverage.code_bytes();
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    writeln!(out, "; Disassembly of {}", title.trim()).ok();
+    writeln!(
+        out,
+        "; {code} of {} bytes were executed and are shown as code; the rest is data",
+        rom.len()
+    )
+    .ok();
+
+    let mut offset = 0;
+    let mut data = Vec::new();
+    while offset < rom.len() {
+        let (bank, address) = rom_address(offset);
+        if offset % 0x4000 == 0 {
+            flush_data(&mut out, &mut data);
+            if bank == 0 {
+                writeln!(out, "\nSECTION \"ROM Bank $000\", ROM0[$0000]").ok();
+            } else {
+                writeln!(
+                    out,
+                    "\nSECTION \"ROM Bank ${bank:03X}\", ROMX[$4000], BANK[${bank:X}]"
+                )
+                .ok();
+            }
+        }
+        if let Some(label) = labels.get(&offset) {
+            flush_data(&mut out, &mut data);
+            let references: Vec<String> = label
+                .references
+                .iter()
+                .map(|&(bank, address)| format!("{bank:03X}:{address:04X}"))
+                .collect();
+            writeln!(out, "\n{}: ; xref {}", label.name, references.join(", ")).ok();
+        }
+
+        if let Some(instruction) = instructions.get(&offset) {
+            flush_data(&mut out, &mut data);
+            let mut text = instruction.text.clone();
+            // The target is always the last operand; `rst` needs a
+            // constant vector, so it keeps its number
+            if let Some(name) = instruction
+                .target
+                .filter(|_| !text.starts_with("rst"))
+                .and_then(|target| target_offset(bank, target, rom.len()))
+                .and_then(|target| labels.get(&target))
+                .map(|label| &label.name)
+                && let Some((head, _)) = text.rsplit_once(' ')
+            {
+                text = format!("{head} {name}");
+            }
+            writeln!(out, "    {text:<32}; ${address:04X}").ok();
+            offset += usize::from(instruction.length);
+        } else {
+            data.push(rom[offset]);
+            if data.len() == 8 {
+                flush_data(&mut out, &mut data);
+            }
+            offset += 1;
+        }
+    }
+    flush_data(&mut out, &mut data);
+    out
+}
+
+fn flush_data(out: &mut String, data: &mut Vec<u8>) {
+    if data.is_empty() {
+        return;
+    }
+    let bytes: Vec<String> = data.iter().map(|byte| format!("${byte:02X}")).collect();
+    writeln!(out, "    db {}", bytes.join(", ")).ok();
+    data.clear();
+}
+
+/// Every executed instruction that fits inside its bank, keyed by ROM offset
+fn decode_executed(rom: &[u8], coverage: &Coverage) -> BTreeMap<usize, Instruction> {
+    let mut instructions = BTreeMap::new();
+    for offset in (0..rom.len()).filter(|&offset| coverage.is_code_start(offset)) {
+        let (_, address) = rom_address(offset);
+        let bank_end = (offset / 0x4000 + 1) * 0x4000;
+        if let Some(instruction) = decode(&rom[offset..bank_end.min(rom.len())], address) {
+            instructions.insert(offset, instruction);
+        }
+    }
+    // Overlapping decodes (e.g. code that jumps into the middle of an
+    // instruction) are kept as data so the listing still assembles
+    let mut end = 0;
+    instructions.retain(|&offset, instruction| {
+        let keep = offset >= end;
+        if keep {
+            end = offset + usize::from(instruction.length);
+        }
+        keep
+    });
+    instructions
+}
+
+fn collect_labels(
+    rom: &[u8],
+    coverage: &Coverage,
+    instructions: &BTreeMap<usize, Instruction>,
+) -> BTreeMap<usize, Label> {
+    let mut labels: BTreeMap<usize, Label> = BTreeMap::new();
+    for (&offset, instruction) in instructions {
+        let (bank, address) = rom_address(offset);
+        let Some(target) = instruction
+            .target
+            .and_then(|target| target_offset(bank, target, rom.len()))
+        else {
+            continue;
+        };
+        // Labels can only go where the listing has a line boundary
+        if coverage.is_code(target) && !instructions.contains_key(&target) {
+            continue;
+        }
+        let label = labels.entry(target).or_insert_with(|| {
+            let (target_bank, target_address) = rom_address(target);
+            let kind = if instruction.is_call { "Call" } else { "Jump" };
+            Label {
+                name: format!("{kind}_{target_bank:03X}_{target_address:04X}"),
+                references: Vec::new(),
+            }
+        });
+        label.references.push((bank, address));
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_fills_in_operands() {
+        let cases: [(&[u8], &str); 8] = [
+            (&[0x3E, 0x12], "ld a, $12"),
+            (&[0xEA, 0x34, 0x12], "ld [$1234], a"),
+            (&[0xE0, 0x44], "ldh [$FF44], a"),
+            (&[0xE8, 0xFE], "add sp, -2"),
+            (&[0xCB, 0x7C], "bit 7, h"),
+            (&[0x18, 0xFE], "jr $0100"),
+            (&[0xFF], "rst $38"),
+            (&[0xD3], "db $D3 ; illegal opcode"),
+        ];
+        for (bytes, text) in cases {
+            assert_eq!(decode(bytes, 0x0100).unwrap().text, text);
+        }
+    }
+
+    #[test]
+    fn decode_reports_branch_targets() {
+        let call = decode(&[0xCD, 0x50, 0x01], 0x0100).unwrap();
+        assert_eq!(call.target, Some(0x0150));
+        assert!(call.is_call);
+
+        let jr = decode(&[0x20, 0x05], 0x0200).unwrap();
+        assert_eq!(jr.target, Some(0x0207));
+        assert!(!jr.is_call);
+
+        assert!(
+            decode(&[0xC3, 0x00], 0x0100).is_none(),
+            "Truncated operands"
+        );
+    }
+
+    #[test]
+    fn coverage_marks_whole_instructions() {
+        let mut coverage = Coverage::new(8);
+        coverage.mark(2, 3);
+        coverage.mark(7, 3); // Clipped at the end of the ROM
+
+        assert!(coverage.is_code_start(2));
+        assert!(coverage.is_code(4));
+        assert!(!coverage.is_code_start(3));
+        assert!(!coverage.is_code(5));
+        assert_eq!(coverage.code_bytes(), 4);
+    }
+
+    #[test]
+    fn listing_separates_code_data_and_labels() {
+        let mut rom = vec![0xAA; 0x8000];
+        // 0x0000: call $0010 / jr $0000    0x0010: ret
+        rom[0x0000..0x0005].copy_from_slice(&[0xCD, 0x10, 0x00, 0x18, 0xFB]);
+        rom[0x0010] = 0xC9;
+        let mut coverage = Coverage::new(rom.len());
+        coverage.mark(0x0000, 3);
+        coverage.mark(0x0003, 2);
+        coverage.mark(0x0010, 1);
+
+        let listing = listing(&rom, &coverage, "TEST");
+
+        assert!(listing.contains("SECTION \"ROM Bank $000\", ROM0[$0000]"));
+        assert!(listing.contains("SECTION \"ROM Bank $001\", ROMX[$4000], BANK[$1]"));
+        assert!(listing.contains("Jump_000_0000: ; xref 000:0003"));
+        assert!(listing.contains("Call_000_0010: ; xref 000:0000"));
+        assert!(listing.contains("    call Call_000_0010"));
+        assert!(listing.contains("    jr Jump_000_0000"));
+        assert!(listing.contains("    ret"));
+        assert!(listing.contains("    db $AA, $AA, $AA, $AA, $AA, $AA, $AA, $AA"));
+    }
+
+    #[test]
+    fn conditional_jump_keeps_condition_with_label() {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0000..0x0003].copy_from_slice(&[0xC2, 0x00, 0x00]); // jp nz, $0000
+        let mut coverage = Coverage::new(rom.len());
+        coverage.mark(0x0000, 3);
+
+        assert!(listing(&rom, &coverage, "TEST").contains("    jp nz, Jump_000_0000"));
+    }
+}
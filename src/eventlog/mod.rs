@@ -0,0 +1,295 @@
+use crate::interrupts::Interrupt;
+use serde_json::{Value, json};
+use std::str::FromStr;
+
+/// CPU cycles per microsecond, for trace timestamps
+const CYCLES_PER_MICROSECOND: f64 = 4.194_304;
+
+/// Perfetto track ids
+const PPU_TRACK: u8 = 1;
+const INTERRUPT_TRACK: u8 = 2;
+const MBC_TRACK: u8 = 3;
+
+/// Something worth seeing on a timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A bit was set in IF
+    InterruptRequested(Interrupt),
+    /// The PPU entered `mode` (0 = H-Blank, 1 = V-Blank, 2 = OAM scan, 3 = drawing)
+    ModeChange {
+        mode: u8,
+        line: u8,
+    },
+    RomBankSwitch(usize),
+    RamBankSwitch(usize),
+}
+
+impl Event {
+    fn name(self) -> String {
+        match self {
+            Event::InterruptRequested(interrupt) => format!("{} interrupt", interrupt.name()),
+            Event::ModeChange { mode, .. } => format!("Mode {mode}"),
+            Event::RomBankSwitch(bank) => format!("ROM bank {bank}"),
+            Event::RamBankSwitch(bank) => format!("RAM bank {bank}"),
+        }
+    }
+
+    fn to_json(self, cycle: u64) -> Value {
+        match self {
+            Event::InterruptRequested(interrupt) => json!({
+                "cycle": cycle,
+                "type": "interrupt_requested",
+                "interrupt": interrupt.name(),
+            }),
+            Event::ModeChange { mode, line } => json!({
+                "cycle": cycle,
+                "type": "mode_change",
+                "mode": mode,
+                "line": line,
+            }),
+            Event::RomBankSwitch(bank) => json!({
+                "cycle": cycle,
+                "type": "rom_bank_switch",
+                "bank": bank,
+            }),
+            Event::RamBankSwitch(bank) => json!({
+                "cycle": cycle,
+                "type": "ram_bank_switch",
+                "bank": bank,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedEvent {
+    /// CPU cycles since power on
+    pub cycle: u64,
+    pub event: Event,
+}
+
+/// Export format for `EventLog::export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventLogFormat {
+    /// `{"events": [...]}`, one object per event
+    #[default]
+    Json,
+    /// Chrome trace event JSON, which Perfetto (ui.perfetto.dev) opens directly
+    Perfetto,
+}
+
+impl FromStr for EventLogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(EventLogFormat::Json),
+            "perfetto" => Ok(EventLogFormat::Perfetto),
+            _ => Err(format!(
+                "Unknown event log format: {s} (expected json or perfetto)"
+            )),
+        }
+    }
+}
+
+/// Timeline of hardware events with cycle timestamps, for chasing races
+/// between the game and the hardware.
+///
+/// Mode changes and bank switches are reported as the current state and only
+/// recorded when it changes. Recording stops once `capacity` events are held.
+pub struct EventLog {
+    events: Vec<TimedEvent>,
+    capacity: usize,
+    dropped: u64,
+    mode: Option<u8>,
+    rom_bank: Option<usize>,
+    ram_bank: Option<usize>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: Vec::new(),
+            capacity,
+            dropped: 0,
+            mode: None,
+            rom_bank: None,
+            ram_bank: None,
+        }
+    }
+
+    pub fn record(&mut self, cycle: u64, event: Event) {
+        if self.events.len() < self.capacity {
+            self.events.push(TimedEvent { cycle, event });
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    /// Record a mode change if `mode` differs from the last one seen
+    pub fn record_mode(&mut self, cycle: u64, mode: u8, line: u8) {
+        if self.mode.replace(mode) != Some(mode) {
+            self.record(cycle, Event::ModeChange { mode, line });
+        }
+    }
+
+    /// Record bank switches if either bank differs from the last ones seen
+    pub fn record_banks(&mut self, cycle: u64, rom: usize, ram: usize) {
+        if self.rom_bank.replace(rom) != Some(rom) {
+            self.record(cycle, Event::RomBankSwitch(rom));
+        }
+        if self.ram_bank.replace(ram) != Some(ram) {
+            self.record(cycle, Event::RamBankSwitch(ram));
+        }
+    }
+
+    pub fn events(&self) -> &[TimedEvent] {
+        &self.events
+    }
+
+    /// Events not recorded because the log was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn export(&self, format: EventLogFormat) -> String {
+        match format {
+            EventLogFormat::Json => self.to_json(),
+            EventLogFormat::Perfetto => self.to_perfetto(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let events: Vec<Value> = self
+            .events
+            .iter()
+            .map(|timed| timed.event.to_json(timed.cycle))
+            .collect();
+        json!({ "events": events, "dropped": self.dropped }).to_string()
+    }
+
+    /// PPU modes become spans on one track; interrupts and bank switches are
+    /// instants on their own tracks
+    #[allow(clippy::cast_precision_loss)] // Exact up to 2^52 cycles, about 34 years
+    pub fn to_perfetto(&self) -> String {
+        let timestamp = |cycle: u64| cycle as f64 / CYCLES_PER_MICROSECOND;
+        let mut trace: Vec<Value> = [
+            (PPU_TRACK, "PPU"),
+            (INTERRUPT_TRACK, "Interrupts"),
+            (MBC_TRACK, "MBC"),
+        ]
+        .into_iter()
+        .map(|(tid, name)| {
+            json!({
+                "ph": "M",
+                "name": "thread_name",
+                "pid": 1,
+                "tid": tid,
+                "args": { "name": name },
+            })
+        })
+        .collect();
+
+        let modes: Vec<&TimedEvent> = self
+            .events
+            .iter()
+            .filter(|timed| matches!(timed.event, Event::ModeChange { .. }))
+            .collect();
+        let end = self.events.last().map_or(0, |timed| timed.cycle);
+        for (index, timed) in modes.iter().enumerate() {
+            let Event::ModeChange { line, .. } = timed.event else {
+                continue;
+            };
+            let until = modes.get(index + 1).map_or(end, |next| next.cycle);
+            trace.push(json!({
+                "ph": "X",
+                "name": timed.event.name(),
+                "pid": 1,
+                "tid": PPU_TRACK,
+                "ts": timestamp(timed.cycle),
+                "dur": timestamp(until - timed.cycle),
+                "args": { "line": line, "cycle": timed.cycle },
+            }));
+        }
+
+        for timed in &self.events {
+            let tid = match timed.event {
+                Event::ModeChange { .. } => continue,
+                Event::InterruptRequested(_) => INTERRUPT_TRACK,
+                Event::RomBankSwitch(_) | Event::RamBankSwitch(_) => MBC_TRACK,
+            };
+            trace.push(json!({
+                "ph": "i",
+                "s": "t",
+                "name": timed.event.name(),
+                "pid": 1,
+                "tid": tid,
+                "ts": timestamp(timed.cycle),
+                "args": { "cycle": timed.cycle },
+            }));
+        }
+
+        json!({ "traceEvents": trace, "displayTimeUnit": "ns" }).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_changes_are_deduplicated() {
+        let mut log = EventLog::new(100);
+        log.record_mode(0, 2, 0);
+        log.record_mode(4, 2, 0);
+        log.record_mode(80, 3, 0);
+        log.record_banks(90, 1, 0);
+        log.record_banks(94, 1, 0);
+        log.record_banks(98, 2, 0);
+
+        let events: Vec<Event> = log.events().iter().map(|timed| timed.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::ModeChange { mode: 2, line: 0 },
+                Event::ModeChange { mode: 3, line: 0 },
+                Event::RomBankSwitch(1),
+                Event::RamBankSwitch(0),
+                Event::RomBankSwitch(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn full_log_counts_dropped_events() {
+        let mut log = EventLog::new(1);
+        log.record(0, Event::InterruptRequested(Interrupt::Timer));
+        log.record(8, Event::InterruptRequested(Interrupt::Timer));
+
+        assert_eq!(log.events().len(), 1);
+        assert_eq!(log.dropped(), 1);
+        let json: Value = serde_json::from_str(&log.to_json()).unwrap();
+        assert_eq!(json["events"][0]["interrupt"], "timer");
+        assert_eq!(json["dropped"], 1);
+    }
+
+    #[test]
+    fn perfetto_trace_has_mode_spans_and_instants() {
+        let mut log = EventLog::new(100);
+        log.record_mode(0, 2, 0);
+        log.record_mode(80, 3, 0);
+        log.record(100, Event::InterruptRequested(Interrupt::Serial));
+
+        let trace: Value = serde_json::from_str(&log.to_perfetto()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let spans: Vec<&Value> = events.iter().filter(|e| e["ph"] == "X").collect();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0]["name"], "Mode 2");
+        let duration = spans[0]["dur"].as_f64().unwrap();
+        assert!((duration - 80.0 / CYCLES_PER_MICROSECOND).abs() < 1e-9);
+
+        let instant = events.iter().find(|e| e["ph"] == "i").unwrap();
+        assert_eq!(instant["name"], "serial interrupt");
+        assert_eq!(instant["tid"], INTERRUPT_TRACK);
+    }
+}
@@ -0,0 +1,83 @@
+/// Most of the previous picture a new one can keep, in percent. Any more
+/// and moving sprites leave trails across the screen.
+pub const MAX_PERSISTENCE: u8 = 90;
+
+/// The DMG LCD's slow response, simulated by mixing each finished frame
+/// into the picture shown so far. A pixel changing color fades towards it
+/// over a few frames, so games that flicker sprites on alternate frames
+/// for transparency (shadows, water, ghosts) look see-through as they did
+/// on the hardware instead of strobing.
+///
+/// `persistence` is the share of the old picture kept in each new one:
+/// 50 shows a changed pixel halfway on the first frame and 75% on the
+/// second.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ghosting {
+    persistence: u8,
+    /// The picture last shown, in RGBA8
+    shown: Option<Vec<u8>>,
+}
+
+/// `new` weighted against `old`, rounded
+fn mix(new: u8, old: u8, persistence: u8) -> u8 {
+    let new = u16::from(new) * u16::from(100 - persistence);
+    let old = u16::from(old) * u16::from(persistence);
+    // At most 255 * 100 + 50 before dividing by 100
+    u8::try_from((new + old + 50) / 100).unwrap_or(u8::MAX)
+}
+
+impl Ghosting {
+    /// `persistence` above `MAX_PERSISTENCE` is taken as the maximum
+    pub fn new(persistence: u8) -> Self {
+        Self {
+            persistence: persistence.min(MAX_PERSISTENCE),
+            shown: None,
+        }
+    }
+
+    pub fn persistence(&self) -> u8 {
+        self.persistence
+    }
+
+    /// Mix `rgba`, the frame just finished, into the picture. The first
+    /// frame is shown as is.
+    pub fn blend(&mut self, rgba: &[u8]) {
+        match &mut self.shown {
+            Some(shown) if shown.len() == rgba.len() => {
+                for (old, &new) in shown.iter_mut().zip(rgba) {
+                    *old = mix(new, *old, self.persistence);
+                }
+            }
+            _ => self.shown = Some(rgba.to_vec()),
+        }
+    }
+
+    /// The picture to show, or `None` before the first `blend`
+    pub fn picture(&self) -> Option<&[u8]> {
+        self.shown.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_pixels_fade_in_over_frames() {
+        let mut ghosting = Ghosting::new(50);
+        assert_eq!(ghosting.picture(), None);
+        ghosting.blend(&[255, 255, 255, 255]);
+        assert_eq!(ghosting.picture(), Some(&[255, 255, 255, 255][..]));
+
+        ghosting.blend(&[0, 0, 0, 255]);
+        assert_eq!(ghosting.picture(), Some(&[128, 128, 128, 255][..]));
+        ghosting.blend(&[0, 0, 0, 255]);
+        assert_eq!(ghosting.picture(), Some(&[64, 64, 64, 255][..]));
+
+        assert_eq!(Ghosting::new(100).persistence(), MAX_PERSISTENCE);
+        let mut none = Ghosting::new(0);
+        none.blend(&[255; 4]);
+        none.blend(&[7; 4]);
+        assert_eq!(none.picture(), Some(&[7; 4][..]));
+    }
+}
@@ -0,0 +1,161 @@
+mod ghosting;
+
+use crate::gameboy::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::palette::DmgPalette;
+use std::sync::OnceLock;
+
+pub use ghosting::{Ghosting, MAX_PERSISTENCE};
+
+/// One completed picture, as handed to frame sinks, tests and bindings.
+///
+/// Holds the raw frame buffer (a 2-bit shade plus source layer per pixel,
+/// see `palette::pixel`) together with the palette it is shown with. The
+/// RGBA8 version is only converted the first time something asks for it.
+// TODO: Carry 15-bit colors instead of shades once there is a CGB mode
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pixels: Vec<u8>,
+    palette: DmgPalette,
+    rgba: OnceLock<Vec<u8>>,
+    progress: Option<Progress>,
+}
+
+/// How far the PPU had drawn a frame taken mid-frame: line `ly` up to
+/// column `drawn`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub ly: usize,
+    pub drawn: usize,
+}
+
+/// Undrawn part of the current line in a partial frame
+const MARKER: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+impl Frame {
+    pub const WIDTH: usize = SCREEN_WIDTH;
+    pub const HEIGHT: usize = SCREEN_HEIGHT;
+
+    /// # Panics
+    ///
+    /// If `pixels` does not hold exactly `WIDTH` x `HEIGHT` pixels
+    pub fn new(pixels: Vec<u8>, palette: DmgPalette) -> Self {
+        assert_eq!(pixels.len(), Self::WIDTH * Self::HEIGHT, "Frame size");
+        Self {
+            pixels,
+            palette,
+            rgba: OnceLock::new(),
+            progress: None,
+        }
+    }
+
+    /// A frame the PPU is still drawing, for a debugger paused mid-frame.
+    /// `pixels` holds this frame's lines above `progress.ly` and the
+    /// previous frame's below; the RGBA version marks the rest of the
+    /// current line in red and dims the previous frame's lines.
+    ///
+    /// # Panics
+    ///
+    /// As `new`
+    pub fn partial(pixels: Vec<u8>, palette: DmgPalette, progress: Progress) -> Self {
+        Self {
+            progress: Some(progress),
+            ..Self::new(pixels, palette)
+        }
+    }
+
+    /// Where drawing had got to, for a partial frame
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress
+    }
+
+    /// Raw frame buffer pixels, row by row from the top left
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// 2-bit shade (0 = lightest) of the pixel at `x`, `y`
+    pub fn shade(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * Self::WIDTH + x] & 0x03
+    }
+
+    pub fn palette(&self) -> &DmgPalette {
+        &self.palette
+    }
+
+    /// The frame in packed RGBA8 with the palette applied
+    pub fn rgba(&self) -> &[u8] {
+        self.rgba.get_or_init(|| {
+            let mut rgba = self.palette.to_rgba(&self.pixels);
+            if let Some(progress) = self.progress {
+                mark_progress(&mut rgba, progress);
+            }
+            rgba
+        })
+    }
+}
+
+/// Paint the undrawn part of line `progress.ly` and dim every line below
+fn mark_progress(rgba: &mut [u8], progress: Progress) {
+    let undrawn = progress.ly * Frame::WIDTH + progress.drawn.min(Frame::WIDTH);
+    let below = (progress.ly + 1) * Frame::WIDTH;
+    for (index, pixel) in rgba.chunks_exact_mut(4).enumerate().skip(undrawn) {
+        if index < below {
+            pixel.copy_from_slice(&MARKER);
+        } else {
+            for channel in &mut pixel[..3] {
+                *channel = *channel / 2 + 0x40;
+            }
+        }
+    }
+}
+
+impl PartialEq for Frame {
+    fn eq(&self, other: &Self) -> bool {
+        self.pixels == other.pixels
+            && self.palette == other.palette
+            && self.progress == other.progress
+    }
+}
+
+impl Eq for Frame {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::{SOURCE_BG, pixel};
+
+    #[test]
+    fn exposes_shades_and_converts_rgba_on_demand() {
+        let mut pixels = vec![pixel(0, SOURCE_BG); Frame::WIDTH * Frame::HEIGHT];
+        pixels[Frame::WIDTH + 2] = pixel(3, SOURCE_BG);
+        let frame = Frame::new(pixels, DmgPalette::GRAYSCALE);
+
+        assert_eq!(frame.shade(2, 1), 3);
+        assert_eq!(frame.shade(0, 0), 0);
+        assert!(frame.rgba.get().is_none());
+        let rgba = frame.rgba();
+        assert_eq!(rgba.len(), Frame::WIDTH * Frame::HEIGHT * 4);
+        assert_eq!(rgba[(Frame::WIDTH + 2) * 4..][..4], [0, 0, 0, 0xFF]);
+        assert_eq!(rgba[..4], [0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn partial_frames_mark_the_current_line_and_dim_below() {
+        let pixels = vec![pixel(3, SOURCE_BG); Frame::WIDTH * Frame::HEIGHT];
+        let progress = Progress { ly: 2, drawn: 10 };
+        let frame = Frame::partial(pixels, DmgPalette::GRAYSCALE, progress);
+        assert_eq!(frame.progress(), Some(progress));
+
+        let rgba = frame.rgba();
+        let at = |x: usize, y: usize| &rgba[(y * Frame::WIDTH + x) * 4..][..4];
+        assert_eq!(at(159, 1), [0, 0, 0, 0xFF], "Drawn this frame");
+        assert_eq!(at(9, 2), [0, 0, 0, 0xFF]);
+        assert_eq!(at(10, 2), MARKER);
+        assert_eq!(at(159, 2), MARKER);
+        assert_eq!(at(0, 3), [0x40, 0x40, 0x40, 0xFF], "Previous frame, dimmed");
+        assert_ne!(
+            frame,
+            Frame::new(frame.pixels().to_vec(), DmgPalette::GRAYSCALE)
+        );
+    }
+}
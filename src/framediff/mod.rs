@@ -0,0 +1,110 @@
+use crate::image::Image;
+
+/// Color of pixels that differ in the diff image
+const HIGHLIGHT: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+/// Result of comparing two frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDiff {
+    /// Number of pixels whose RGBA values are not identical
+    pub differing: usize,
+    pub total: usize,
+    /// The first frame faded to a dim grayscale, with differing pixels in red
+    pub image: Image,
+}
+
+impl FrameDiff {
+    pub fn is_identical(&self) -> bool {
+        self.differing == 0
+    }
+
+    /// Share of differing pixels, from 0.0 to 100.0
+    #[allow(clippy::cast_precision_loss)] // Frame sizes are far below 2^52
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.differing as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+/// Compare two frames of the same size pixel by pixel
+pub fn compare(a: &Image, b: &Image) -> Result<FrameDiff, String> {
+    if (a.width, a.height) != (b.width, b.height) {
+        return Err(format!(
+            "Frame sizes differ: {}x{} vs {}x{}",
+            a.width, a.height, b.width, b.height
+        ));
+    }
+
+    let mut differing = 0;
+    let rgba = a
+        .rgba
+        .chunks_exact(4)
+        .zip(b.rgba.chunks_exact(4))
+        .flat_map(|(pa, pb)| {
+            if pa == pb {
+                // Dimmed so the highlights stand out while keeping the frame recognizable
+                let luma = (u16::from(pa[0]) * 3 + u16::from(pa[1]) * 6 + u16::from(pa[2])) / 10;
+                let shade = 0x20 + u8::try_from(luma / 4).unwrap_or(0x3F);
+                [shade, shade, shade, 0xFF]
+            } else {
+                differing += 1;
+                HIGHLIGHT
+            }
+        })
+        .collect();
+
+    Ok(FrameDiff {
+        differing,
+        total: a.width * a.height,
+        image: Image {
+            width: a.width,
+            height: a.height,
+            rgba,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pixels: &[[u8; 4]]) -> Image {
+        Image {
+            width: pixels.len(),
+            height: 1,
+            rgba: pixels.concat(),
+        }
+    }
+
+    #[test]
+    fn identical_frames_have_no_differences() {
+        let a = frame(&[[0xFF, 0xFF, 0xFF, 0xFF], [0, 0, 0, 0xFF]]);
+        let diff = compare(&a, &a.clone()).unwrap();
+
+        assert!(diff.is_identical());
+        assert_eq!(diff.image.rgba[..4], [0x5F, 0x5F, 0x5F, 0xFF]);
+        assert_eq!(diff.image.rgba[4..], [0x20, 0x20, 0x20, 0xFF]);
+    }
+
+    #[test]
+    fn differing_pixels_are_counted_and_highlighted() {
+        let a = frame(&[[0; 4], [0; 4], [0; 4], [0; 4]]);
+        let b = frame(&[[0; 4], [1, 0, 0, 0], [0; 4], [0, 0, 0, 0xFF]]);
+        let diff = compare(&a, &b).unwrap();
+
+        assert_eq!(diff.differing, 2);
+        assert!((diff.percent() - 50.0).abs() < f64::EPSILON);
+        assert_eq!(diff.image.rgba[4..8], HIGHLIGHT);
+        assert_eq!(diff.image.rgba[12..16], HIGHLIGHT);
+    }
+
+    #[test]
+    fn size_mismatch_is_an_error() {
+        let a = frame(&[[0; 4]]);
+        let b = frame(&[[0; 4], [0; 4]]);
+        assert!(compare(&a, &b).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::frame::Frame;
+use crate::frontend::FrameSink;
+use crate::gameboy::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::image::write_png;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Writes every `every`th completed frame to a directory as a numbered PNG,
+/// for regression galleries and documentation screenshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDumper {
+    dir: PathBuf,
+    every: u64,
+}
+
+impl FrameDumper {
+    /// Create `dir` if needed. An interval of 0 is treated as 1.
+    pub fn new(dir: &Path, every: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            every: every.max(1),
+        })
+    }
+
+    /// Whether frame number `frame` (counted from 1 at power on) is written
+    pub fn is_due(&self, frame: u64) -> bool {
+        frame.is_multiple_of(self.every)
+    }
+
+    /// File for frame number `frame`, zero-padded so names sort in order
+    pub fn path(&self, frame: u64) -> PathBuf {
+        self.dir.join(format!("frame_{frame:06}.png"))
+    }
+
+    /// Write a full-screen RGBA frame
+    pub fn write(&self, frame: u64, rgba: &[u8]) -> io::Result<()> {
+        write_png(&self.path(frame), SCREEN_WIDTH, SCREEN_HEIGHT, rgba)
+    }
+}
+
+impl FrameSink for FrameDumper {
+    fn wants_frame(&self, frame: u64) -> bool {
+        self.is_due(frame)
+    }
+
+    fn present(&mut self, frame: u64, image: &Frame) -> Result<(), String> {
+        self.write(frame, image.rgba())
+            .map_err(|e| format!("{}: {e}", self.path(frame).display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::read_png;
+
+    #[test]
+    fn writes_numbered_frames() {
+        let dir = std::env::temp_dir().join(format!("gameboy-framedump-{}", std::process::id()));
+        let dumper = FrameDumper::new(&dir, 30).unwrap();
+        let rgba = vec![0x7F; SCREEN_WIDTH * SCREEN_HEIGHT * 4];
+
+        assert!(!dumper.is_due(1));
+        assert!(dumper.is_due(60));
+        dumper.write(60, &rgba).unwrap();
+        let image = read_png(&dir.join("frame_000060.png"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let image = image.unwrap();
+        assert_eq!((image.width, image.height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(image.rgba, rgba);
+    }
+}
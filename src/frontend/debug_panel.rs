@@ -0,0 +1,368 @@
+use super::Viewport;
+use super::gpu::Renderer;
+use super::painter::Painter;
+use crate::debugger::{self, Debugger, SHEET_SIZE};
+use crate::gameboy::GameBoy;
+use crate::heatmap::HEAT_MAP_SIZE;
+use crate::objects::oam_objects;
+use egui::{
+    ClippedPrimitive, CollapsingHeader, ColorImage, Context, RichText, ScrollArea, TextureHandle,
+    TextureOptions, TexturesDelta, Ui,
+};
+use pixels::Pixels;
+use winit::event::WindowEvent;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::Window;
+
+/// Width of the panels beside the game, in points
+pub(super) const PANEL_WIDTH: f32 = 380.0;
+
+/// Instructions listed before and after PC
+const DISASSEMBLY_LINES: (usize, usize) = (8, 16);
+
+/// Height of the memory and OAM lists, in points
+const LIST_HEIGHT: f32 = 240.0;
+
+/// The area left for the game, in window pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct GameArea {
+    pub(super) x: u32,
+    pub(super) y: u32,
+    pub(super) width: u32,
+    pub(super) height: u32,
+}
+
+/// What the panels' input fields hold between frames
+#[derive(Default)]
+struct Fields {
+    /// Address typed into the memory view's go-to box
+    memory_address: String,
+    /// Row the memory view scrolls to on the next frame
+    scroll_to: Option<u16>,
+    /// Address typed into the new breakpoint box
+    breakpoint: String,
+}
+
+/// egui panels docked to the right of the game: execution controls,
+/// registers, disassembly around PC (click a line for a breakpoint), a
+/// memory hex view, the breakpoint list, the VRAM tile sheet, OAM and the
+/// memory access heat map. Shown with the `toggle_debugger` hotkey.
+pub(super) struct DebugPanel {
+    context: Context,
+    state: egui_winit::State,
+    painter: Painter,
+    fields: Fields,
+    tiles: Option<TextureHandle>,
+    heat_map: Option<TextureHandle>,
+    /// The last `update`'s output, for `render`
+    primitives: Vec<ClippedPrimitive>,
+    textures: TexturesDelta,
+}
+
+/// Parse a hex address, with or without a `$` or `0x` prefix
+fn parse_address(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+impl DebugPanel {
+    pub(super) fn new(
+        event_loop: &EventLoopWindowTarget<()>,
+        window: &Window,
+        pixels: &Pixels,
+    ) -> Self {
+        let mut state = egui_winit::State::new(event_loop);
+        state.set_pixels_per_point(egui_winit::native_pixels_per_point(window));
+        Self {
+            context: Context::default(),
+            state,
+            painter: Painter::new(pixels),
+            fields: Fields::default(),
+            tiles: None,
+            heat_map: None,
+            primitives: Vec::new(),
+            textures: TexturesDelta::default(),
+        }
+    }
+
+    /// Pass a window event to egui, returning whether egui took it, such as
+    /// typing into a box, so it should not reach the game
+    pub(super) fn on_event(&mut self, event: &WindowEvent) -> bool {
+        self.state.on_event(&self.context, event).consumed
+    }
+
+    /// Lay the panels out for this frame, acting on any clicks, and return
+    /// the area of the window left for the game
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(super) fn update(
+        &mut self,
+        window: &Window,
+        game: &mut GameBoy,
+        debugger: &mut Debugger,
+    ) -> GameArea {
+        let input = self.state.take_egui_input(window);
+        self.context.begin_frame(input);
+        let context = self.context.clone();
+        egui::SidePanel::right("debugger")
+            .exact_width(PANEL_WIDTH)
+            .show(&context, |ui| {
+                ScrollArea::vertical().show(ui, |ui| self.panels(ui, game, debugger));
+            });
+        let area = self.context.available_rect();
+        let output = self.context.end_frame();
+        self.state
+            .handle_platform_output(window, &self.context, output.platform_output);
+        self.primitives = self.context.tessellate(output.shapes);
+        self.textures.append(output.textures_delta);
+
+        let scale = self.context.pixels_per_point();
+        let pixel = |points: f32| (points * scale).round().max(0.0) as u32;
+        GameArea {
+            x: pixel(area.min.x),
+            y: pixel(area.min.y),
+            width: pixel(area.width()),
+            height: pixel(area.height()),
+        }
+    }
+
+    /// Draw the game into `viewport` with `renderer` and the panels over
+    /// the rest of the window
+    pub(super) fn render(
+        &mut self,
+        pixels: &Pixels,
+        renderer: &Renderer,
+        viewport: Viewport,
+        window_size: (u32, u32),
+    ) -> Result<(), String> {
+        let textures = std::mem::take(&mut self.textures);
+        self.painter.update_textures(pixels, &textures);
+        let scale = self.context.pixels_per_point();
+        let (painter, primitives) = (&self.painter, &self.primitives);
+        let result = renderer.render_with(pixels, viewport, |encoder, target, context| {
+            painter.paint(encoder, target, context, primitives, window_size, scale);
+        });
+        self.painter.free_textures(&textures);
+        result
+    }
+
+    fn panels(&mut self, ui: &mut Ui, game: &mut GameBoy, debugger: &mut Debugger) {
+        controls(ui, game, debugger);
+        CollapsingHeader::new("Registers")
+            .default_open(true)
+            .show(ui, |ui| registers(ui, game));
+        CollapsingHeader::new("Disassembly")
+            .default_open(true)
+            .show(ui, |ui| disassembly(ui, game, debugger));
+        CollapsingHeader::new("Memory").show(ui, |ui| self.memory(ui, game));
+        CollapsingHeader::new("Breakpoints").show(ui, |ui| self.breakpoints(ui, debugger));
+        CollapsingHeader::new("VRAM tiles").show(ui, |ui| self.tile_sheet(ui, game));
+        CollapsingHeader::new("OAM").show(ui, |ui| oam(ui, game));
+        CollapsingHeader::new("Heat map").show(ui, |ui| self.heat_map(ui, game));
+    }
+
+    fn memory(&mut self, ui: &mut Ui, game: &GameBoy) {
+        ui.horizontal(|ui| {
+            ui.label("Go to");
+            let response = ui.text_edit_singleline(&mut self.fields.memory_address);
+            if response.lost_focus()
+                && let Some(address) = parse_address(&self.fields.memory_address)
+            {
+                self.fields.scroll_to = Some(address / 16);
+            }
+        });
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let mut area = ScrollArea::vertical()
+            .id_source("memory")
+            .max_height(LIST_HEIGHT);
+        if let Some(row) = self.fields.scroll_to.take() {
+            let spacing = ui.spacing().item_spacing.y;
+            area = area.vertical_scroll_offset(f32::from(row) * (row_height + spacing));
+        }
+        area.show_rows(ui, row_height, 0x1000, |ui, rows| {
+            for row in rows {
+                let address = u16::try_from(row * 16).unwrap_or(u16::MAX);
+                ui.monospace(debugger::hex_row(&game.memory, address));
+            }
+        });
+    }
+
+    fn breakpoints(&mut self, ui: &mut Ui, debugger: &mut Debugger) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.fields.breakpoint);
+            if ui.button("Add").clicked()
+                && let Some(address) = parse_address(&self.fields.breakpoint)
+            {
+                debugger.add_breakpoint(address);
+                self.fields.breakpoint.clear();
+            }
+        });
+        let breakpoints: Vec<u16> = debugger.breakpoints().collect();
+        for address in breakpoints {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{address:04X}"));
+                if ui.small_button("Remove").clicked() {
+                    debugger.remove_breakpoint(address);
+                }
+            });
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn tile_sheet(&mut self, ui: &mut Ui, game: &GameBoy) {
+        let rgba = debugger::tile_sheet(&game.memory, &game.palette());
+        let image = ColorImage::from_rgba_unmultiplied([SHEET_SIZE.0, SHEET_SIZE.1], &rgba);
+        let tiles = match self.tiles {
+            Some(ref mut tiles) => {
+                tiles.set(image, TextureOptions::NEAREST);
+                tiles
+            }
+            None => self.tiles.insert(ui.ctx().load_texture(
+                "tiles",
+                image,
+                TextureOptions::NEAREST,
+            )),
+        };
+        let size = egui::vec2(SHEET_SIZE.0 as f32, SHEET_SIZE.1 as f32) * 2.0;
+        ui.image(tiles.id(), size);
+    }
+
+    /// Recent accesses to every address, one row per 256 bytes: writes red,
+    /// reads green, executes blue. Recording slows emulation, so it is off
+    /// until ticked.
+    #[allow(clippy::cast_precision_loss)]
+    fn heat_map(&mut self, ui: &mut Ui, game: &mut GameBoy) {
+        let mut recording = game.heat_map().is_some();
+        if ui.checkbox(&mut recording, "Record accesses").changed() {
+            game.set_heat_map(recording);
+        }
+        let Some(heat_map) = game.heat_map() else {
+            self.heat_map = None;
+            return;
+        };
+        let image =
+            ColorImage::from_rgba_unmultiplied([HEAT_MAP_SIZE, HEAT_MAP_SIZE], &heat_map.to_rgba());
+        let texture = match self.heat_map {
+            Some(ref mut texture) => {
+                texture.set(image, TextureOptions::NEAREST);
+                texture
+            }
+            None => self.heat_map.insert(ui.ctx().load_texture(
+                "heat map",
+                image,
+                TextureOptions::NEAREST,
+            )),
+        };
+        ui.image(
+            texture.id(),
+            egui::vec2(HEAT_MAP_SIZE as f32, HEAT_MAP_SIZE as f32),
+        );
+    }
+}
+
+/// Pause, continue and step buttons, and why emulation is stopped
+fn controls(ui: &mut Ui, game: &mut GameBoy, debugger: &mut Debugger) {
+    ui.horizontal(|ui| {
+        if debugger.is_paused() {
+            if ui.button("Continue").clicked() {
+                debugger.resume();
+            }
+        } else if ui.button("Pause").clicked() {
+            debugger.pause();
+        }
+        if ui.button("Step").clicked() {
+            debugger.step(game);
+        }
+        if ui.button("Step frame").clicked() {
+            debugger.resume();
+            debugger.run_frame(game);
+            debugger.pause();
+        }
+    });
+    let status = match debugger.hit() {
+        Some(address) => format!("Breakpoint at {address:04X}"),
+        None if game.stopped() => "Halted".to_string(),
+        None if debugger.is_paused() => "Paused".to_string(),
+        None => "Running".to_string(),
+    };
+    ui.label(status);
+}
+
+fn registers(ui: &mut Ui, game: &GameBoy) {
+    let cpu = &game.cpu;
+    let registers = &cpu.registers;
+    egui::Grid::new("registers").show(ui, |ui| {
+        for (name, value) in [
+            ("AF", registers.af()),
+            ("BC", registers.bc()),
+            ("DE", registers.de()),
+            ("HL", registers.hl()),
+            ("SP", cpu.sp),
+            ("PC", cpu.pc),
+        ] {
+            ui.monospace(name);
+            ui.monospace(format!("{value:04X}"));
+            ui.end_row();
+        }
+    });
+    let flags = &registers.f;
+    let flag = |set: bool, name: char| if set { name } else { '-' };
+    ui.monospace(format!(
+        "Flags {}{}{}{}  IME {}",
+        flag(flags.z, 'Z'),
+        flag(flags.n, 'N'),
+        flag(flags.h, 'H'),
+        flag(flags.c, 'C'),
+        u8::from(cpu.interrupts_enabled),
+    ));
+    ui.monospace(format!(
+        "LY {:3}  frame {}  cycle {}",
+        game.memory.ppu.ly(),
+        game.frame_count(),
+        game.cycle_count(),
+    ));
+}
+
+/// Instructions around PC; clicking one adds or removes a breakpoint
+fn disassembly(ui: &mut Ui, game: &GameBoy, debugger: &mut Debugger) {
+    let pc = game.cpu.pc;
+    let (before, after) = DISASSEMBLY_LINES;
+    for line in debugger::disassemble_around(&game.memory, pc, before, after) {
+        let marker = if debugger.has_breakpoint(line.address) {
+            '●'
+        } else {
+            ' '
+        };
+        let text = format!("{marker} {:04X}  {}", line.address, line.text);
+        let label = egui::SelectableLabel::new(line.address == pc, RichText::new(text).monospace());
+        if ui.add(label).clicked() {
+            debugger.toggle_breakpoint(line.address);
+        }
+    }
+}
+
+/// All 40 objects with their position, tile and attributes as stored
+fn oam(ui: &mut Ui, game: &GameBoy) {
+    ScrollArea::vertical()
+        .id_source("oam")
+        .max_height(LIST_HEIGHT)
+        .show(ui, |ui| {
+            egui::Grid::new("oam").striped(true).show(ui, |ui| {
+                for heading in ["#", "X", "Y", "Tile", "Attr"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for object in oam_objects(&game.memory.data[0xFE00..0xFEA0]) {
+                    ui.monospace(object.index.to_string());
+                    ui.monospace(object.x.to_string());
+                    ui.monospace(object.y.to_string());
+                    ui.monospace(format!("{:02X}", object.tile));
+                    ui.monospace(format!("{:02X}", object.attributes));
+                    ui.end_row();
+                }
+            });
+        });
+}
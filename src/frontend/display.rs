@@ -0,0 +1,199 @@
+use crate::frame::Frame;
+use std::str::FromStr;
+
+/// Largest fixed scale, in screen pixels per Game Boy pixel
+pub const MAX_SCALE: u32 = 6;
+/// Window size when the scale is left to fit the window
+pub const DEFAULT_SCALE: u32 = 4;
+
+/// How big the Game Boy screen is drawn in the window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scale {
+    /// The largest whole multiple that fits the window
+    #[default]
+    Fit,
+    /// This many screen pixels per Game Boy pixel, 1 to `MAX_SCALE`,
+    /// shrunk to a smaller multiple if the window is made too small
+    Times(u32),
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    /// `fit`, or a scale such as `3` or `3x`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("fit") {
+            return Ok(Scale::Fit);
+        }
+        let digits = s.strip_suffix(['x', 'X']).unwrap_or(s);
+        match digits.parse() {
+            Ok(times) if (1..=MAX_SCALE).contains(&times) => Ok(Scale::Times(times)),
+            _ => Err(format!(
+                "Unknown scale: {s} (expected 1 to {MAX_SCALE}, or fit)"
+            )),
+        }
+    }
+}
+
+/// How Game Boy pixels are stretched to screen pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// Sharp square pixels
+    #[default]
+    Nearest,
+    /// Blended pixel edges
+    Bilinear,
+}
+
+impl Filter {
+    pub fn name(self) -> &'static str {
+        match self {
+            Filter::Nearest => "nearest",
+            Filter::Bilinear => "bilinear",
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Filter::Nearest, Filter::Bilinear]
+            .into_iter()
+            .find(|filter| filter.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown filter: {s} (expected nearest or bilinear)"))
+    }
+}
+
+/// The rectangle of the window the screen is drawn into, in window pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Scale, filter and ghosting for a windowed frontend, set with `--scale`,
+/// `--filter` and `--ghosting`. Scale and filter change while playing with
+/// the `scale_up`, `scale_down` and `toggle_filter` hotkeys. The screen keeps the Game Boy's aspect
+/// ratio at every window size: whatever the scaled screen does not cover
+/// is a black border (letterboxing).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DisplayOptions {
+    pub scale: Scale,
+    pub filter: Filter,
+    /// Percent of the previous picture kept in each new one, to mimic the
+    /// DMG LCD's persistence; 0 for none (see `frame::Ghosting`)
+    pub ghosting: u8,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+const SIZE: (u32, u32) = (Frame::WIDTH as u32, Frame::HEIGHT as u32);
+
+impl DisplayOptions {
+    /// Window size to open with, or to resize to after the scale changes
+    pub fn window_size(&self) -> (u32, u32) {
+        let times = match self.scale {
+            Scale::Fit => DEFAULT_SCALE,
+            Scale::Times(times) => times,
+        };
+        (SIZE.0 * times, SIZE.1 * times)
+    }
+
+    /// Screen pixels per Game Boy pixel in a window this size, at least 1
+    pub fn multiple(&self, width: u32, height: u32) -> u32 {
+        let fits = (width / SIZE.0).min(height / SIZE.1).max(1);
+        match self.scale {
+            Scale::Fit => fits,
+            Scale::Times(times) => times.min(fits),
+        }
+    }
+
+    /// Where to draw the screen in a window this size: `multiple` times
+    /// the Game Boy screen, centered
+    pub fn viewport(&self, width: u32, height: u32) -> Viewport {
+        let multiple = self.multiple(width, height);
+        let (screen_width, screen_height) = (SIZE.0 * multiple, SIZE.1 * multiple);
+        Viewport {
+            x: width.saturating_sub(screen_width) / 2,
+            y: height.saturating_sub(screen_height) / 2,
+            width: screen_width,
+            height: screen_height,
+        }
+    }
+
+    /// Fix the scale one step up from what a window this size shows
+    pub fn scale_up(&mut self, width: u32, height: u32) {
+        let times = self.multiple(width, height) + 1;
+        self.scale = Scale::Times(times.min(MAX_SCALE));
+    }
+
+    /// Fix the scale one step down from what a window this size shows
+    pub fn scale_down(&mut self, width: u32, height: u32) {
+        let times = self.multiple(width, height) - 1;
+        self.scale = Scale::Times(times.max(1));
+    }
+
+    pub fn toggle_filter(&mut self) {
+        self.filter = match self.filter {
+            Filter::Nearest => Filter::Bilinear,
+            Filter::Bilinear => Filter::Nearest,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scales_and_filters() {
+        assert_eq!("fit".parse(), Ok(Scale::Fit));
+        assert_eq!("3".parse(), Ok(Scale::Times(3)));
+        assert_eq!("6x".parse(), Ok(Scale::Times(6)));
+        assert!("0".parse::<Scale>().is_err());
+        assert!("7".parse::<Scale>().is_err());
+        assert_eq!("Bilinear".parse(), Ok(Filter::Bilinear));
+        assert!("linear".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn letterboxes_whole_multiples() {
+        let fit = DisplayOptions::default();
+        assert_eq!(fit.window_size(), (640, 576));
+        // Wide window: height limits it to 3x, centered across
+        assert_eq!(
+            fit.viewport(1000, 450),
+            Viewport {
+                x: 260,
+                y: 9,
+                width: 480,
+                height: 432,
+            }
+        );
+        // Too small for 1x: drawn at 1x from the corner, cropped
+        assert_eq!(fit.viewport(100, 100).width, 160);
+
+        let fixed = DisplayOptions {
+            scale: Scale::Times(2),
+            ..DisplayOptions::default()
+        };
+        assert_eq!(fixed.viewport(1000, 1000).width, 320);
+        assert_eq!(fixed.viewport(200, 200).width, 160);
+    }
+
+    #[test]
+    fn hotkeys_step_from_the_shown_scale() {
+        let mut options = DisplayOptions::default();
+        options.scale_up(1000, 450);
+        assert_eq!(options.scale, Scale::Times(4));
+        options.scale = Scale::Times(MAX_SCALE);
+        options.scale_up(2000, 2000);
+        assert_eq!(options.scale, Scale::Times(MAX_SCALE));
+        options.scale_down(160, 144);
+        assert_eq!(options.scale, Scale::Times(1));
+        options.toggle_filter();
+        assert_eq!(options.filter, Filter::Bilinear);
+    }
+}
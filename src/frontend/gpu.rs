@@ -0,0 +1,253 @@
+use super::{Filter, Viewport};
+use crate::frame::Frame;
+use crate::shader::{PostShader, ShaderLanguage};
+use pixels::wgpu;
+use pixels::{Pixels, PixelsContext};
+use std::borrow::Cow;
+
+/// Copies the frame as is, for when no `PostShader` is given
+const PLAIN: &str = r"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(frame, frame_sampler, in.uv);
+}
+";
+
+/// Draws the pixel buffer, already holding the frame, into a viewport of
+/// the window through a `PostShader`, or a plain copy without one, so
+/// filters such as the LCD grid cost no CPU time. The shader sees the
+/// frame texture, a sampler filtering as `Filter` says and the output and
+/// source sizes (see `shader::WGSL_PRELUDE`). Pixels' own scaling
+/// renderer is not used, so the scale and filter are ours to choose.
+pub(super) struct Renderer {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// `Screen` uniform: output size, then source size
+    screen: wgpu::Buffer,
+}
+
+/// Bytes of the `Screen` uniform, four f32s
+const SCREEN_SIZE: u64 = 16;
+
+/// The frame texture, its sampler and the `Screen` uniform, at the
+/// bindings `shader::WGSL_PRELUDE` declares
+fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("post_shader_bind_group_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(SCREEN_SIZE),
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+impl Renderer {
+    /// Compile `shader` for the window behind `pixels`. A shader that does
+    /// not compile is an error here rather than a panic mid-game.
+    pub(super) fn new(
+        pixels: &Pixels,
+        shader: Option<&PostShader>,
+        filter: Filter,
+    ) -> Result<Self, String> {
+        let plain;
+        let shader = if let Some(shader) = shader {
+            shader
+        } else {
+            plain = PostShader::from_source(
+                "plain".to_string(),
+                ShaderLanguage::Wgsl,
+                PLAIN.to_string(),
+            )?;
+            &plain
+        };
+        if shader.language == ShaderLanguage::Glsl {
+            return Err(format!(
+                "Shader `{}`: GLSL shaders are not supported yet, only WGSL",
+                shader.name
+            ));
+        }
+        let context = pixels.context();
+        let device = &context.device;
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&shader.name),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(shader.module_source())),
+        });
+        let layout = bind_group_layout(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post_shader_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("post_shader_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: pixels.render_texture_format(),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(format!("Shader `{}`: {error}", shader.name));
+        }
+
+        let screen = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post_shader_screen"),
+            size: SCREEN_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = bind_group(pixels, &layout, &screen, filter);
+        Ok(Self {
+            pipeline,
+            layout,
+            bind_group,
+            screen,
+        })
+    }
+
+    /// Sample the frame with `filter` from the next frame on
+    pub(super) fn set_filter(&mut self, pixels: &Pixels, filter: Filter) {
+        self.bind_group = bind_group(pixels, &self.layout, &self.screen, filter);
+    }
+
+    /// Draw the frame into `viewport`, in window pixels, clearing the rest
+    /// of the window to black
+    pub(super) fn render(&self, pixels: &Pixels, viewport: Viewport) -> Result<(), String> {
+        self.render_with(pixels, viewport, |_, _, _| {})
+    }
+
+    /// `render`, then `overlay` into the same window texture, such as the
+    /// debug panels
+    #[allow(clippy::cast_precision_loss)]
+    pub(super) fn render_with(
+        &self,
+        pixels: &Pixels,
+        viewport: Viewport,
+        overlay: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::TextureView, &PixelsContext),
+    ) -> Result<(), String> {
+        pixels
+            .render_with(|encoder, target, context| {
+                let Viewport {
+                    x,
+                    y,
+                    width,
+                    height,
+                } = viewport;
+                let sizes = [
+                    width as f32,
+                    height as f32,
+                    Frame::WIDTH as f32,
+                    Frame::HEIGHT as f32,
+                ];
+                let bytes: Vec<u8> = sizes.iter().flat_map(|size| size.to_le_bytes()).collect();
+                context.queue.write_buffer(&self.screen, 0, &bytes);
+
+                {
+                    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("post_shader_pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: target,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+                    pass.set_pipeline(&self.pipeline);
+                    pass.set_bind_group(0, &self.bind_group, &[]);
+                    pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+                    pass.draw(0..3, 0..1);
+                }
+                overlay(encoder, target, context);
+                Ok(())
+            })
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Bind the frame texture, a sampler filtering as `filter` says and the
+/// `Screen` uniform
+fn bind_group(
+    pixels: &Pixels,
+    layout: &wgpu::BindGroupLayout,
+    screen: &wgpu::Buffer,
+    filter: Filter,
+) -> wgpu::BindGroup {
+    let context = pixels.context();
+    let mode = match filter {
+        Filter::Nearest => wgpu::FilterMode::Nearest,
+        Filter::Bilinear => wgpu::FilterMode::Linear,
+    };
+    let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("post_shader_sampler"),
+        mag_filter: mode,
+        min_filter: mode,
+        ..wgpu::SamplerDescriptor::default()
+    });
+    let view = context
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    context
+        .device
+        .create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post_shader_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: screen.as_entire_binding(),
+                },
+            ],
+        })
+}
@@ -0,0 +1,113 @@
+use super::keyboard::JOYPAD_KEYS;
+use super::osd::{self, LINE_HEIGHT};
+use super::{Action, Chord, Hotkeys};
+use crate::apu::Channel;
+use crate::frame::Frame;
+
+const TEXT: [u8; 4] = [255, 255, 255, 255];
+
+/// The controls overlay's text for `hotkeys`: the joypad keys, two to a
+/// line, then every action with its chords. The channel mutes share one
+/// line, a chord (or `NONE`) for each channel in turn. A joypad key taken by
+/// a hotkey shows as `NONE`, as does an unbound action.
+pub fn help_lines(hotkeys: &Hotkeys) -> Vec<String> {
+    let mut lines = vec!["CONTROLS".to_string()];
+    let joypad: Vec<String> = JOYPAD_KEYS
+        .iter()
+        .map(|&(key, button)| {
+            let taken = key
+                .parse()
+                .is_ok_and(|key| hotkeys.action(&Chord::key(key)).is_some());
+            let key = if taken { "none" } else { key };
+            format!("{:<7}{key:<7}", button.name())
+        })
+        .collect();
+    lines.extend(
+        joypad
+            .chunks(2)
+            .map(|pair| pair.concat().trim_end().to_string()),
+    );
+    lines.push(String::new());
+    let chords = |action| {
+        let chords = hotkeys.chords(action);
+        if chords.is_empty() {
+            "none".to_string()
+        } else {
+            chords
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+    };
+    for action in Action::ALL {
+        match action.channel() {
+            None => lines.push(format!(
+                "{:<18}{}",
+                action.name().replace('_', " "),
+                chords(action)
+            )),
+            Some(channel) if channel == Channel::ALL[0] => {
+                let mutes: Vec<String> = Action::ALL
+                    .into_iter()
+                    .filter(|action| action.channel().is_some())
+                    .map(chords)
+                    .collect();
+                lines.push(format!("{:<18}{}", "mute channel 1-4", mutes.join(" ")));
+            }
+            Some(_) => {}
+        }
+    }
+    lines
+        .into_iter()
+        .map(|line| line.to_ascii_uppercase())
+        .collect()
+}
+
+/// Dim an RGBA8 picture of the screen and write the controls for `hotkeys`
+/// over it, centered top to bottom. Lines too long for the screen are cut
+/// off.
+pub fn draw_help(rgba: &mut [u8], hotkeys: &Hotkeys) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        for channel in &mut pixel[..3] {
+            *channel /= 4;
+        }
+    }
+    let lines = help_lines(hotkeys);
+    let top = Frame::HEIGHT.saturating_sub(lines.len() * LINE_HEIGHT) / 2;
+    for (index, line) in lines.iter().enumerate() {
+        osd::draw_text(rgba, 2, top + index * LINE_HEIGHT, line, TEXT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_the_live_bindings() {
+        let mut hotkeys = Hotkeys::default();
+        hotkeys.bind(Action::Pause, "Enter".parse().unwrap());
+        hotkeys.unbind(Action::Reset);
+        let lines = help_lines(&hotkeys);
+
+        assert!(lines.contains(&"START  NONE   SELECT SPACE".to_string()));
+        assert!(lines.contains(&"A      X      B      Z".to_string()));
+        assert!(lines.contains(&"PAUSE             P ENTER".to_string()));
+        assert!(lines.contains(&"RESET             NONE".to_string()));
+        assert!(lines.contains(&"TOGGLE HELP       F1".to_string()));
+        assert!(lines.contains(&"MUTE CHANNEL 1-4  1 2 3 4".to_string()));
+        assert!(lines.contains(&"SOLO CHANNEL      S".to_string()));
+    }
+
+    #[test]
+    fn every_line_fits_on_the_screen() {
+        let lines = help_lines(&Hotkeys::default());
+        assert!(lines.len() * LINE_HEIGHT <= Frame::HEIGHT);
+
+        let mut rgba = vec![255; Frame::WIDTH * Frame::HEIGHT * 4];
+        draw_help(&mut rgba, &Hotkeys::default());
+        assert_eq!(&rgba[..4], [63, 63, 63, 255], "Dimmed");
+        assert!(rgba.chunks(4).any(|pixel| pixel == TEXT));
+    }
+}
@@ -0,0 +1,463 @@
+use crate::apu::Channel;
+use crate::ppu::Layer;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Emulator functions reachable from the keyboard in every frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    SaveState,
+    LoadState,
+    Rewind,
+    /// Held rather than pressed: frontends look up both the press and the
+    /// release
+    Turbo,
+    Screenshot,
+    Pause,
+    Reset,
+    /// Mute or unmute audio
+    Mute,
+    /// Mute or unmute one sound channel
+    MuteChannel1,
+    MuteChannel2,
+    MuteChannel3,
+    MuteChannel4,
+    /// Hear only channel 1, then only 2, and so on, then all of them again
+    SoloChannel,
+    /// Debug: hide or show one PPU layer
+    ToggleBackground,
+    ToggleWindow,
+    ToggleObjects,
+    /// Window: draw the screen a whole multiple bigger or smaller
+    ScaleUp,
+    ScaleDown,
+    /// Window: switch between sharp and smoothed pixels
+    ToggleFilter,
+    /// Window: show or hide the debug panels beside the game
+    ToggleDebugger,
+    /// Window: show or hide the list of controls over the game
+    ToggleHelp,
+}
+
+impl Action {
+    pub const ALL: [Action; 21] = [
+        Action::SaveState,
+        Action::LoadState,
+        Action::Rewind,
+        Action::Turbo,
+        Action::Screenshot,
+        Action::Pause,
+        Action::Reset,
+        Action::Mute,
+        Action::MuteChannel1,
+        Action::MuteChannel2,
+        Action::MuteChannel3,
+        Action::MuteChannel4,
+        Action::SoloChannel,
+        Action::ToggleBackground,
+        Action::ToggleWindow,
+        Action::ToggleObjects,
+        Action::ScaleUp,
+        Action::ScaleDown,
+        Action::ToggleFilter,
+        Action::ToggleDebugger,
+        Action::ToggleHelp,
+    ];
+
+    /// Name used in hotkey files
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::SaveState => "save_state",
+            Action::LoadState => "load_state",
+            Action::Rewind => "rewind",
+            Action::Turbo => "turbo",
+            Action::Screenshot => "screenshot",
+            Action::Pause => "pause",
+            Action::Reset => "reset",
+            Action::Mute => "mute",
+            Action::MuteChannel1 => "mute_channel_1",
+            Action::MuteChannel2 => "mute_channel_2",
+            Action::MuteChannel3 => "mute_channel_3",
+            Action::MuteChannel4 => "mute_channel_4",
+            Action::SoloChannel => "solo_channel",
+            Action::ToggleBackground => "toggle_background",
+            Action::ToggleWindow => "toggle_window",
+            Action::ToggleObjects => "toggle_objects",
+            Action::ScaleUp => "scale_up",
+            Action::ScaleDown => "scale_down",
+            Action::ToggleFilter => "toggle_filter",
+            Action::ToggleDebugger => "toggle_debugger",
+            Action::ToggleHelp => "toggle_help",
+        }
+    }
+
+    /// The layer a toggle action shows and hides
+    pub fn layer(self) -> Option<Layer> {
+        match self {
+            Action::ToggleBackground => Some(Layer::Background),
+            Action::ToggleWindow => Some(Layer::Window),
+            Action::ToggleObjects => Some(Layer::Objects),
+            _ => None,
+        }
+    }
+
+    /// The sound channel a mute action mutes
+    pub fn channel(self) -> Option<Channel> {
+        match self {
+            Action::MuteChannel1 => Some(Channel::Square1),
+            Action::MuteChannel2 => Some(Channel::Square2),
+            Action::MuteChannel3 => Some(Channel::Wave),
+            Action::MuteChannel4 => Some(Channel::Noise),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown hotkey action: {name}"))
+    }
+}
+
+/// Keys with names longer than one character, as written in hotkey files
+const NAMED_KEYS: [&str; 17] = [
+    "Space",
+    "Tab",
+    "Enter",
+    "Escape",
+    "Backspace",
+    "Delete",
+    "Insert",
+    "Home",
+    "End",
+    "PageUp",
+    "PageDown",
+    "Up",
+    "Down",
+    "Left",
+    "Right",
+    "Minus",
+    "Plus",
+];
+
+/// A key, by the name printed on it. Letters and digits are single
+/// characters (`R`, `5`), function keys are `F1` to `F24` and the rest are
+/// listed in `NAMED_KEYS`. Frontends translate their own key codes into
+/// these names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key(String);
+
+impl Key {
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    /// Parse a key name, ignoring case
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next())
+            && c.is_ascii_alphanumeric()
+        {
+            return Ok(Self(c.to_ascii_uppercase().to_string()));
+        }
+        if let Some(number) = name.strip_prefix(['F', 'f'])
+            && let Ok(number) = number.parse::<u8>()
+            && (1..=24).contains(&number)
+        {
+            return Ok(Self(format!("F{number}")));
+        }
+        NAMED_KEYS
+            .iter()
+            .find(|key| key.eq_ignore_ascii_case(name))
+            .map(|key| Self((*key).to_string()))
+            .ok_or_else(|| format!("Unknown key: {name}"))
+    }
+}
+
+/// Modifier keys held with a chord
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+/// A key pressed with an exact set of modifiers, e.g. `Shift+F1`. F1 and
+/// Shift+F1 are different chords and can do different things.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Chord {
+    /// `key` with no modifiers
+    pub fn key(key: Key) -> Self {
+        Self {
+            modifiers: Modifiers::default(),
+            key,
+        }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    /// Parse `Modifier+...+Key`, ignoring case and spaces around the `+`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let (key, held) = parts.split_last().unwrap_or((&"", &[]));
+        let mut modifiers = Modifiers::default();
+        for modifier in held {
+            let flag = match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut modifiers.ctrl,
+                "alt" => &mut modifiers.alt,
+                "shift" => &mut modifiers.shift,
+                _ => return Err(format!("Unknown modifier in {text}: {modifier}")),
+            };
+            *flag = true;
+        }
+        Ok(Self {
+            modifiers,
+            key: key.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (held, name) in [
+            (self.modifiers.ctrl, "Ctrl+"),
+            (self.modifiers.alt, "Alt+"),
+            (self.modifiers.shift, "Shift+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        f.write_str(self.key.name())
+    }
+}
+
+/// Which chord triggers which emulator action, shared by all frontends so
+/// the same keys work everywhere.
+///
+/// A hotkey file remaps actions, one per line: the action followed by its
+/// chords, or `none` to leave it unbound. Actions not mentioned keep their
+/// default keys. Blank lines and `#` comments are ignored.
+///
+/// ```text
+/// # Slot keys as in other emulators
+/// save_state  Shift+F1
+/// load_state  F1
+/// turbo       Tab  Space
+/// reset       none
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkeys {
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        let mut hotkeys = Self {
+            bindings: Vec::new(),
+        };
+        for (action, chord) in [
+            (Action::SaveState, "Shift+F2"),
+            (Action::LoadState, "F2"),
+            (Action::Rewind, "Backspace"),
+            (Action::Turbo, "Tab"),
+            (Action::Screenshot, "F12"),
+            (Action::Pause, "P"),
+            (Action::Reset, "Ctrl+R"),
+            (Action::Mute, "M"),
+            (Action::MuteChannel1, "1"),
+            (Action::MuteChannel2, "2"),
+            (Action::MuteChannel3, "3"),
+            (Action::MuteChannel4, "4"),
+            (Action::SoloChannel, "S"),
+            (Action::ToggleBackground, "Ctrl+1"),
+            (Action::ToggleWindow, "Ctrl+2"),
+            (Action::ToggleObjects, "Ctrl+3"),
+            (Action::ScaleUp, "Ctrl+Plus"),
+            (Action::ScaleDown, "Ctrl+Minus"),
+            (Action::ToggleFilter, "Ctrl+F"),
+            (Action::ToggleDebugger, "Ctrl+D"),
+            (Action::ToggleHelp, "F1"),
+        ] {
+            hotkeys.bind(action, chord.parse().expect("default chords parse"));
+        }
+        hotkeys
+    }
+}
+
+impl Hotkeys {
+    /// Defaults with the remappings in `text` applied
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut hotkeys = Self::default();
+        let mut remapped = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {message}", index + 1);
+
+            let mut parts = line.split_whitespace();
+            let action: Action = parts.next().unwrap_or_default().parse().map_err(error)?;
+            if remapped.contains(&action) {
+                return Err(error(format!("{} is mapped twice", action.name())));
+            }
+            remapped.push(action);
+            hotkeys.unbind(action);
+            for chord in parts {
+                if chord.eq_ignore_ascii_case("none") {
+                    continue;
+                }
+                let chord: Chord = chord.parse().map_err(error)?;
+                if let Some(other) = hotkeys.action(&chord)
+                    && remapped.contains(&other)
+                {
+                    return Err(error(format!(
+                        "{chord} is already mapped to {}",
+                        other.name()
+                    )));
+                }
+                hotkeys.bind(action, chord);
+            }
+        }
+        Ok(hotkeys)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Make `chord` trigger `action`, taking it from whatever it did before
+    pub fn bind(&mut self, action: Action, chord: Chord) {
+        self.bindings.retain(|(bound, _)| *bound != chord);
+        self.bindings.push((chord, action));
+    }
+
+    /// Remove every chord for `action`
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.retain(|&(_, bound)| bound != action);
+    }
+
+    /// Action for a key press, if it is a hotkey
+    pub fn action(&self, chord: &Chord) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == chord)
+            .map(|&(_, action)| action)
+    }
+
+    /// Chords bound to `action`, for showing in menus
+    pub fn chords(&self, action: Action) -> Vec<&Chord> {
+        self.bindings
+            .iter()
+            .filter(|&&(_, bound)| bound == action)
+            .map(|(chord, _)| chord)
+            .collect()
+    }
+
+    /// Every action with its chords, in the hotkey file format
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for action in Action::ALL {
+            let chords = self.chords(action);
+            let chords = if chords.is_empty() {
+                "none".to_string()
+            } else {
+                chords
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            writeln!(text, "{:<11} {chords}", action.name()).expect("writing to a String");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> Chord {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn chords_parse_in_any_case_and_print_canonically() {
+        assert_eq!(chord("shift + f1").to_string(), "Shift+F1");
+        assert_eq!(chord("Shift+Ctrl+r").to_string(), "Ctrl+Shift+R");
+        assert_eq!(chord("pageup").to_string(), "PageUp");
+        assert!("Hyper+F1".parse::<Chord>().is_err());
+        assert!("F25".parse::<Chord>().is_err());
+        assert!("Shift+".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let hotkeys = Hotkeys::default();
+        assert_eq!(hotkeys.action(&chord("F2")), Some(Action::LoadState));
+        assert_eq!(hotkeys.action(&chord("Shift+F2")), Some(Action::SaveState));
+        assert_eq!(hotkeys.action(&chord("Ctrl+F2")), None);
+        assert_eq!(hotkeys.action(&chord("F1")), Some(Action::ToggleHelp));
+        assert_eq!(hotkeys.action(&chord("R")), None);
+    }
+
+    #[test]
+    fn toggles_name_their_layer_or_channel() {
+        let action = Hotkeys::default().action(&chord("Ctrl+3"));
+        assert_eq!(action, Some(Action::ToggleObjects));
+        assert_eq!(action.and_then(Action::layer), Some(Layer::Objects));
+        assert_eq!(Action::Pause.layer(), None);
+
+        let action = Hotkeys::default().action(&chord("3"));
+        assert_eq!(action.and_then(Action::channel), Some(Channel::Wave));
+        assert_eq!(Action::SoloChannel.channel(), None);
+    }
+
+    #[test]
+    fn file_remaps_only_the_actions_it_names() {
+        let hotkeys = Hotkeys::parse(
+            "# Mine\n\
+             turbo  Space Shift+Tab\n\
+             reset  none\n\
+             \n\
+             pause  F2   # steals load_state's key\n",
+        )
+        .unwrap();
+        assert_eq!(hotkeys.chords(Action::Turbo).len(), 2);
+        assert_eq!(hotkeys.action(&chord("Tab")), None);
+        assert_eq!(hotkeys.action(&chord("Ctrl+R")), None);
+        assert_eq!(hotkeys.action(&chord("F2")), Some(Action::Pause));
+        assert!(hotkeys.chords(Action::LoadState).is_empty());
+        assert_eq!(hotkeys.action(&chord("F12")), Some(Action::Screenshot));
+        let text = hotkeys.to_text();
+        assert_eq!(Hotkeys::parse(&text).unwrap().to_text(), text);
+
+        assert_eq!(
+            Hotkeys::parse("pause P\nreset P").unwrap_err(),
+            "line 2: P is already mapped to pause"
+        );
+        assert!(Hotkeys::parse("rewind X\nrewind Y").is_err());
+        assert!(Hotkeys::parse("fast_forward Tab").is_err());
+    }
+}
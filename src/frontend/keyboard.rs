@@ -0,0 +1,148 @@
+use super::{Action, Chord, Hotkeys, Key};
+use crate::joypad::{Button, Buttons};
+
+/// Keys that hold the joypad buttons in windowed frontends
+pub(super) const JOYPAD_KEYS: [(&str, Button); 8] = [
+    ("Up", Button::Up),
+    ("Down", Button::Down),
+    ("Left", Button::Left),
+    ("Right", Button::Right),
+    ("X", Button::A),
+    ("Z", Button::B),
+    ("Enter", Button::Start),
+    ("Space", Button::Select),
+];
+
+/// Hotkey actions that last while their key is held
+const HELD: [Action; 2] = [Action::Turbo, Action::Rewind];
+
+/// The joypad button `key` holds, whatever modifiers are down with it
+pub fn joypad_button(key: &Key) -> Option<Button> {
+    JOYPAD_KEYS
+        .iter()
+        .find(|(name, _)| *name == key.name())
+        .map(|&(_, button)| button)
+}
+
+/// What a windowed frontend's keyboard is doing: the joypad buttons held,
+/// whether turbo or rewind is held, and hotkey presses for the frontend to
+/// act on.
+///
+/// A chord bound to a hotkey wins over the joypad, so rebinding a joypad
+/// key as a hotkey takes it off the joypad.
+#[derive(Debug, Clone)]
+pub struct Keyboard {
+    hotkeys: Hotkeys,
+    buttons: Buttons,
+    /// Which of `HELD` are down
+    held: [bool; HELD.len()],
+}
+
+impl Keyboard {
+    pub fn new(hotkeys: Hotkeys) -> Self {
+        Self {
+            hotkeys,
+            buttons: Buttons::new(),
+            held: [false; HELD.len()],
+        }
+    }
+
+    /// A key went down (not a repeat). Returns the hotkey action to carry
+    /// out, except turbo and rewind, which are tracked here while held.
+    pub fn key_down(&mut self, chord: &Chord) -> Option<Action> {
+        match self.hotkeys.action(chord) {
+            Some(action) => match HELD.iter().position(|&held| held == action) {
+                Some(index) => self.held[index] = true,
+                None => return Some(action),
+            },
+            None => {
+                if let Some(button) = joypad_button(&chord.key) {
+                    self.buttons.press(button);
+                }
+            }
+        }
+        None
+    }
+
+    /// A key went up. Modifiers may have changed since it went down, so
+    /// held actions and joypad buttons are released by the key alone.
+    pub fn key_up(&mut self, key: &Key) {
+        if let Some(button) = joypad_button(key) {
+            self.buttons.release(button);
+        }
+        for (held, action) in self.held.iter_mut().zip(HELD) {
+            if self
+                .hotkeys
+                .chords(action)
+                .iter()
+                .any(|chord| chord.key == *key)
+            {
+                *held = false;
+            }
+        }
+    }
+
+    /// The bindings key presses are looked up in
+    pub fn hotkeys(&self) -> &Hotkeys {
+        &self.hotkeys
+    }
+
+    /// Joypad buttons held, for `GameBoy::set_buttons`
+    pub fn buttons(&self) -> Buttons {
+        self.buttons
+    }
+
+    /// Whether turbo or rewind is held down
+    pub fn is_held(&self, action: Action) -> bool {
+        HELD.iter()
+            .zip(self.held)
+            .any(|(&held, down)| held == action && down)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> Chord {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn joypad_keys_hold_buttons_until_released() {
+        let mut keyboard = Keyboard::new(Hotkeys::default());
+        assert_eq!(keyboard.key_down(&chord("X")), None);
+        assert_eq!(keyboard.key_down(&chord("Shift+Left")), None);
+        assert_eq!(
+            keyboard.buttons(),
+            Buttons::new().with(Button::A).with(Button::Left)
+        );
+
+        keyboard.key_up(&"Left".parse().unwrap());
+        assert_eq!(keyboard.buttons(), Buttons::new().with(Button::A));
+    }
+
+    #[test]
+    fn hotkeys_win_over_the_joypad() {
+        let mut hotkeys = Hotkeys::default();
+        hotkeys.bind(Action::Pause, chord("Enter"));
+        let mut keyboard = Keyboard::new(hotkeys);
+
+        assert_eq!(keyboard.key_down(&chord("Enter")), Some(Action::Pause));
+        assert_eq!(keyboard.key_down(&chord("Ctrl+R")), Some(Action::Reset));
+        assert_eq!(keyboard.buttons(), Buttons::new());
+    }
+
+    #[test]
+    fn turbo_and_rewind_are_held_not_returned() {
+        let mut keyboard = Keyboard::new(Hotkeys::default());
+        assert_eq!(keyboard.key_down(&chord("Tab")), None);
+        assert_eq!(keyboard.key_down(&chord("Backspace")), None);
+        assert!(keyboard.is_held(Action::Turbo));
+
+        keyboard.key_up(&"Tab".parse().unwrap());
+        assert!(!keyboard.is_held(Action::Turbo));
+        assert!(keyboard.is_held(Action::Rewind));
+        assert!(!keyboard.is_held(Action::Pause), "Only held actions");
+    }
+}
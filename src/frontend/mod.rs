@@ -0,0 +1,208 @@
+#[cfg(feature = "debugger")]
+mod debug_panel;
+mod display;
+#[cfg(feature = "pixels")]
+mod gpu;
+mod help;
+mod hotkeys;
+mod keyboard;
+mod osd;
+#[cfg(feature = "debugger")]
+mod painter;
+#[cfg(feature = "pixels")]
+mod pixels;
+#[cfg(feature = "sdl")]
+mod sdl;
+mod splash;
+mod terminal;
+#[cfg(any(feature = "sdl", feature = "pixels"))]
+mod window;
+
+use crate::audio;
+use crate::frame::Frame;
+use crate::gameboy::GameBoy;
+use crate::paths::GameDirectory;
+use crate::shader::PostShader;
+pub use display::{DisplayOptions, Filter, MAX_SCALE, Scale, Viewport};
+pub use help::{draw_help, help_lines};
+pub use hotkeys::{Action, Chord, Hotkeys, Key, Modifiers};
+pub use keyboard::{Keyboard, joypad_button};
+pub use splash::splash_frame;
+use std::str::FromStr;
+pub use terminal::{ColorMode, TerminalSink, render};
+
+/// Destination for emulator output: a window, a terminal UI, the PNG
+/// dumper, a network stream. The core hands every completed frame to each
+/// attached sink (see `GameBoy::add_frame_sink`), so any combination can run
+/// at once.
+pub trait FrameSink: Send {
+    /// Whether frame number `frame` (counted from 1 at power on) should be
+    /// presented. Sinks that skip frames save the RGBA conversion.
+    fn wants_frame(&self, _frame: u64) -> bool {
+        true
+    }
+
+    /// A completed frame. Its RGBA8 conversion is shared by all sinks and
+    /// only done if one asks for it. An error detaches the sink.
+    fn present(&mut self, frame: u64, image: &Frame) -> Result<(), String>;
+
+    /// Stereo samples produced since the last call. An error detaches the sink.
+    fn audio(&mut self, _samples: &[audio::Frame]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Windowed frontends, each behind the cargo feature of the same name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowBackend {
+    /// SDL2, which needs the SDL2 libraries installed
+    Sdl,
+    /// winit and pixels, pure Rust
+    Pixels,
+}
+
+impl WindowBackend {
+    pub const ALL: [WindowBackend; 2] = [WindowBackend::Sdl, WindowBackend::Pixels];
+
+    /// Lowercase name, as accepted by `from_str` and used for the feature
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowBackend::Sdl => "sdl",
+            WindowBackend::Pixels => "pixels",
+        }
+    }
+}
+
+impl FromStr for WindowBackend {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        WindowBackend::ALL
+            .into_iter()
+            .find(|backend| backend.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown frontend: {name} (expected sdl or pixels)"))
+    }
+}
+
+/// Play `game` in a window until it is closed: a frame per V-Blank at the
+/// Game Boy's frame rate, keys looked up in `hotkeys` first and otherwise
+/// fed to the joypad (see `Keyboard`). The screen is scaled and filtered
+/// as `display` says, and a `shader` runs over each frame on the GPU, which
+/// only the pixels backend has. A backend left out of this build is an
+/// error.
+#[allow(unused_variables, clippy::needless_pass_by_value)]
+pub fn run_window(
+    backend: WindowBackend,
+    game: &mut GameBoy,
+    hotkeys: Hotkeys,
+    game_dir: Option<&GameDirectory>,
+    shader: Option<&PostShader>,
+    display: DisplayOptions,
+) -> Result<(), String> {
+    match backend {
+        WindowBackend::Sdl if shader.is_some() => {
+            Err("Shaders need the GPU renderer: use --frontend pixels".to_string())
+        }
+        #[cfg(feature = "sdl")]
+        WindowBackend::Sdl => sdl::run_window(game, hotkeys, game_dir, display),
+        #[cfg(feature = "pixels")]
+        WindowBackend::Pixels => pixels::run_window(game, hotkeys, game_dir, shader, display),
+        #[allow(unreachable_patterns)]
+        _ => Err(format!(
+            "This build has no {0} frontend. Rebuild with `cargo build --features {0}`.",
+            backend.name()
+        )),
+    }
+}
+
+/// What to do when the emulator window loses focus
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FocusPolicy {
+    /// Pause emulation and mute audio until focus returns
+    #[default]
+    Pause,
+    /// Keep running (and playing audio) in the background
+    KeepRunning,
+}
+
+/// Why emulation is paused. A user pause survives focus changes, so
+/// alt-tabbing back to a paused game does not resume it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PauseState {
+    pub focus_policy: FocusPolicy,
+    user_paused: bool,
+    unfocused: bool,
+}
+
+impl PauseState {
+    pub fn new(focus_policy: FocusPolicy) -> Self {
+        Self {
+            focus_policy,
+            ..Self::default()
+        }
+    }
+
+    /// Pause hotkey
+    pub fn toggle_user_pause(&mut self) {
+        self.user_paused = !self.user_paused;
+    }
+
+    /// Call from the window's focus event
+    pub fn set_focused(&mut self, focused: bool) {
+        self.unfocused = !focused;
+    }
+
+    /// Whether the core should be stepped this frame
+    pub fn is_paused(&self) -> bool {
+        self.user_paused || (self.unfocused && self.focus_policy == FocusPolicy::Pause)
+    }
+
+    /// Whether audio output should be silenced. Audio is muted whenever the
+    /// core is paused, otherwise the last buffered samples would loop.
+    pub fn is_muted(&self) -> bool {
+        self.is_paused()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_window_backends() {
+        assert_eq!("SDL".parse(), Ok(WindowBackend::Sdl));
+        assert_eq!("pixels".parse(), Ok(WindowBackend::Pixels));
+        assert!("wgpu".parse::<WindowBackend>().is_err());
+    }
+
+    #[test]
+    fn focus_loss_pauses_and_mutes_by_default() {
+        let mut state = PauseState::default();
+        state.set_focused(false);
+        assert!(state.is_paused());
+        assert!(state.is_muted());
+
+        state.set_focused(true);
+        assert!(!state.is_paused());
+    }
+
+    #[test]
+    fn keep_running_ignores_focus() {
+        let mut state = PauseState::new(FocusPolicy::KeepRunning);
+        state.set_focused(false);
+        assert!(!state.is_paused());
+        assert!(!state.is_muted());
+    }
+
+    #[test]
+    fn user_pause_survives_regaining_focus() {
+        let mut state = PauseState::default();
+        state.toggle_user_pause();
+        state.set_focused(false);
+        state.set_focused(true);
+        assert!(state.is_paused());
+
+        state.toggle_user_pause();
+        assert!(!state.is_paused());
+    }
+}
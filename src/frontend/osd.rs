@@ -0,0 +1,112 @@
+use crate::frame::Frame;
+
+/// Pixels from the left edge of one character to the next: three inked and
+/// one blank
+pub(super) const ADVANCE: usize = 4;
+/// Pixels from the top of one line of text to the next
+pub(super) const LINE_HEIGHT: usize = 6;
+
+/// 3x5 glyphs for text drawn over the screen, one row per byte with bit 2
+/// leftmost. Letters are upper case only.
+const FONT: [(char, [u8; 5]); 42] = [
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+];
+
+/// Rows of the glyph for `c`, ignoring case. Spaces and characters the
+/// font lacks are blank.
+fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    FONT.iter()
+        .find(|&&(glyph, _)| glyph == c)
+        .map_or([0; 5], |&(_, rows)| rows)
+}
+
+/// Pixels `text` inks at scale 1, as (x, y) from its top left corner
+pub(super) fn inked(text: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    text.chars().enumerate().flat_map(|(index, c)| {
+        glyph(c).into_iter().enumerate().flat_map(move |(y, row)| {
+            (0..3)
+                .filter(move |x| row & (0b100 >> x) != 0)
+                .map(move |x| (index * ADVANCE + x, y))
+        })
+    })
+}
+
+/// Write `text` into an RGBA8 picture of the screen with its top left
+/// corner at (`left`, `top`), cutting off whatever falls outside
+pub(super) fn draw_text(rgba: &mut [u8], left: usize, top: usize, text: &str, color: [u8; 4]) {
+    for (x, y) in inked(text) {
+        let (x, y) = (left + x, top + y);
+        if x < Frame::WIDTH && y < Frame::HEIGHT {
+            let start = (y * Frame::WIDTH + x) * 4;
+            rgba[start..start + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_clipped_to_the_screen() {
+        let mut rgba = vec![0; Frame::WIDTH * Frame::HEIGHT * 4];
+        draw_text(&mut rgba, Frame::WIDTH - 2, 0, "ab", [255; 4]);
+        let inked: Vec<usize> = rgba
+            .chunks(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[0] == 255)
+            .map(|(index, _)| index % Frame::WIDTH)
+            .collect();
+        assert!(!inked.is_empty());
+        assert!(inked.iter().all(|&x| x >= Frame::WIDTH - 2), "No wrap");
+
+        assert_eq!(inked_count("a"), inked_count("A"));
+        assert_eq!(inked_count("~ "), 0);
+    }
+
+    fn inked_count(text: &str) -> usize {
+        inked(text).count()
+    }
+}
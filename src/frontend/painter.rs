@@ -0,0 +1,430 @@
+use egui::epaint::{ImageData, Primitive};
+use egui::{ClippedPrimitive, Color32, TextureFilter, TextureId, TextureOptions, TexturesDelta};
+use pixels::wgpu::util::DeviceExt;
+use pixels::{Pixels, PixelsContext, wgpu};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Vertices in points, scaled to clip space by the screen size; colors
+/// and textures in premultiplied sRGB. `fs_linear` draws into an sRGB
+/// window texture, which takes linear color, and `fs_gamma` into any
+/// other.
+const SHADER: &str = r"
+struct Screen {
+    size: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(1) @binding(0) var image: texture_2d<f32>;
+@group(1) @binding(1) var image_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        2.0 * position.x / screen.size.x - 1.0,
+        1.0 - 2.0 * position.y / screen.size.y,
+        0.0,
+        1.0,
+    );
+    out.uv = uv;
+    out.color = color;
+    return out;
+}
+
+fn linear_from_gamma(rgb: vec3<f32>) -> vec3<f32> {
+    let lower = rgb / vec3<f32>(12.92);
+    let higher = pow((rgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, rgb < vec3<f32>(0.04045));
+}
+
+fn gamma_from_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let lower = rgb * vec3<f32>(12.92);
+    let higher = vec3<f32>(1.055) * pow(rgb, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(higher, lower, rgb < vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_linear(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = vec4<f32>(linear_from_gamma(in.color.rgb), in.color.a);
+    return color * textureSample(image, image_sampler, in.uv);
+}
+
+@fragment
+fn fs_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(image, image_sampler, in.uv);
+    return in.color * vec4<f32>(gamma_from_linear(texel.rgb), texel.a);
+}
+";
+
+/// Bytes per vertex: position and texture coordinates as f32 pairs, then
+/// RGBA8 color
+const VERTEX_SIZE: u64 = 20;
+
+/// Draws egui's meshes with the window's wgpu device, over what is already
+/// in the window texture. egui-wgpu would do the same but needs a newer
+/// wgpu than pixels uses.
+pub(super) struct Painter {
+    pipeline: wgpu::RenderPipeline,
+    /// `Screen` uniform: the window size in points
+    screen: wgpu::Buffer,
+    screen_group: wgpu::BindGroup,
+    texture_layout: wgpu::BindGroupLayout,
+    textures: HashMap<TextureId, (wgpu::Texture, wgpu::BindGroup)>,
+}
+
+/// The pipeline drawing egui meshes into a `format` window texture, with
+/// the screen uniform and a texture bound
+fn pipeline(
+    device: &wgpu::Device,
+    module: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    screen_layout: &wgpu::BindGroupLayout,
+    texture_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("egui_pipeline_layout"),
+        bind_group_layouts: &[screen_layout, texture_layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("egui_pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: VERTEX_SIZE,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x2,
+                    1 => Float32x2,
+                    2 => Unorm8x4,
+                ],
+            }],
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point: if format.is_srgb() {
+                "fs_linear"
+            } else {
+                "fs_gamma"
+            },
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                // Premultiplied alpha
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+impl Painter {
+    pub(super) fn new(pixels: &Pixels) -> Self {
+        let device = &pixels.context().device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("egui_shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+        let screen_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("egui_screen_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("egui_texture_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline = pipeline(
+            device,
+            &module,
+            pixels.render_texture_format(),
+            &screen_layout,
+            &texture_layout,
+        );
+
+        let screen = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("egui_screen"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("egui_screen_group"),
+            layout: &screen_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen.as_entire_binding(),
+            }],
+        });
+        Self {
+            pipeline,
+            screen,
+            screen_group,
+            texture_layout,
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Create and update the textures egui asks for. Call before `paint`.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(super) fn update_textures(&mut self, pixels: &Pixels, delta: &TexturesDelta) {
+        let context = pixels.context();
+        for (id, image) in &delta.set {
+            let [width, height] = image.image.size();
+            let rgba: Vec<u8> = match &image.image {
+                ImageData::Color(color) => {
+                    color.pixels.iter().flat_map(Color32::to_array).collect()
+                }
+                ImageData::Font(font) => font
+                    .srgba_pixels(None)
+                    .flat_map(|color| color.to_array())
+                    .collect(),
+            };
+            let size = wgpu::Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            };
+            let origin = if let Some([x, y]) = image.pos {
+                wgpu::Origin3d {
+                    x: x as u32,
+                    y: y as u32,
+                    z: 0,
+                }
+            } else {
+                let texture = self.create_texture(context, size, image.options);
+                self.textures.insert(*id, texture);
+                wgpu::Origin3d::ZERO
+            };
+            let Some((texture, _)) = self.textures.get(id) else {
+                continue;
+            };
+            context.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &rgba,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size.width * 4),
+                    rows_per_image: Some(size.height),
+                },
+                size,
+            );
+        }
+    }
+
+    fn create_texture(
+        &self,
+        context: &PixelsContext,
+        size: wgpu::Extent3d,
+        options: TextureOptions,
+    ) -> (wgpu::Texture, wgpu::BindGroup) {
+        let device = &context.device;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("egui_texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let filter = |filter| match filter {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("egui_sampler"),
+            mag_filter: filter(options.magnification),
+            min_filter: filter(options.minification),
+            ..wgpu::SamplerDescriptor::default()
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("egui_texture_group"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        (texture, group)
+    }
+
+    /// Drop the textures egui is done with. Call after `paint`.
+    pub(super) fn free_textures(&mut self, delta: &TexturesDelta) {
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    /// Draw `primitives` over `target`, a window `size` physical pixels big
+    /// at `pixels_per_point`
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub(super) fn paint(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        context: &PixelsContext,
+        primitives: &[ClippedPrimitive],
+        size: (u32, u32),
+        pixels_per_point: f32,
+    ) {
+        let mut vertices: Vec<u8> = Vec::new();
+        let mut indices: Vec<u8> = Vec::new();
+        // Clip rectangle in pixels, texture and index range of each mesh
+        let mut draws = Vec::new();
+        let mut index_count = 0;
+        for ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in primitives
+        {
+            let Primitive::Mesh(mesh) = primitive else {
+                continue;
+            };
+            let base = (vertices.len() as u64 / VERTEX_SIZE) as u32;
+            for vertex in &mesh.vertices {
+                for value in [vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y] {
+                    vertices.extend(value.to_le_bytes());
+                }
+                vertices.extend(vertex.color.to_array());
+            }
+            indices.extend(mesh.indices.iter().flat_map(|i| (base + i).to_le_bytes()));
+
+            let clamp = |value: f32, max: u32| ((value * pixels_per_point).round() as u32).min(max);
+            let (x, y) = (
+                clamp(clip_rect.min.x, size.0),
+                clamp(clip_rect.min.y, size.1),
+            );
+            let (right, bottom) = (
+                clamp(clip_rect.max.x, size.0),
+                clamp(clip_rect.max.y, size.1),
+            );
+            let count = mesh.indices.len() as u32;
+            if right > x && bottom > y {
+                let clip = (x, y, right - x, bottom - y);
+                draws.push((clip, mesh.texture_id, index_count..index_count + count));
+            }
+            index_count += count;
+        }
+        if draws.is_empty() {
+            return;
+        }
+
+        let device = &context.device;
+        let screen = [
+            size.0 as f32 / pixels_per_point,
+            size.1 as f32 / pixels_per_point,
+            0.0,
+            0.0,
+        ];
+        let bytes: Vec<u8> = screen
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        context.queue.write_buffer(&self.screen, 0, &bytes);
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("egui_vertices"),
+            contents: &vertices,
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("egui_indices"),
+            contents: &indices,
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("egui_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.screen_group, &[]);
+        pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for ((x, y, width, height), texture, range) in draws {
+            let Some((_, group)) = self.textures.get(&texture) else {
+                continue;
+            };
+            pass.set_scissor_rect(x, y, width, height);
+            pass.set_bind_group(1, group, &[]);
+            pass.draw_indexed(range, 0, 0..1);
+        }
+    }
+}
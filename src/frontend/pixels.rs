@@ -0,0 +1,226 @@
+#[cfg(feature = "debugger")]
+use super::debug_panel::{DebugPanel, PANEL_WIDTH};
+use super::gpu::Renderer;
+use super::window::Controls;
+use super::{Chord, DisplayOptions, Hotkeys, Key, Modifiers};
+use crate::frame::Frame;
+use crate::gameboy::GameBoy;
+use crate::paths::GameDirectory;
+use crate::shader::PostShader;
+use pixels::{Pixels, SurfaceTexture};
+use std::collections::HashSet;
+use std::time::Instant;
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::EventLoop;
+use winit::platform::run_return::EventLoopExtRunReturn;
+use winit::window::{Window, WindowBuilder};
+
+/// The hotkey name of a winit key, if it has one
+fn key(keycode: VirtualKeyCode) -> Option<Key> {
+    let name = format!("{keycode:?}");
+    let name = match name.as_str() {
+        "Back" => "Backspace",
+        "Return" => "Enter",
+        "Equals" => "Plus",
+        // Digits are Key0 to Key9
+        name => name.strip_prefix("Key").unwrap_or(name),
+    };
+    name.parse().ok()
+}
+
+/// Keys down, to tell presses from winit's key repeats, and the modifiers
+/// held
+#[derive(Default)]
+struct Held {
+    keys: HashSet<VirtualKeyCode>,
+    modifiers: Modifiers,
+}
+
+/// Handle one window event, returning false once the window is closed
+fn handle(
+    controls: &mut Controls,
+    held: &mut Held,
+    game: &mut GameBoy,
+    event: &WindowEvent,
+) -> bool {
+    match event {
+        WindowEvent::CloseRequested => return false,
+        WindowEvent::ModifiersChanged(state) => {
+            held.modifiers = Modifiers {
+                ctrl: state.ctrl(),
+                alt: state.alt(),
+                shift: state.shift(),
+            };
+        }
+        WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+            ..
+        } => {
+            let Some(key) = key(*keycode) else {
+                return true;
+            };
+            match state {
+                ElementState::Pressed if held.keys.insert(*keycode) => {
+                    let chord = Chord {
+                        modifiers: held.modifiers,
+                        key,
+                    };
+                    controls.key_down(game, &chord);
+                }
+                ElementState::Pressed => {}
+                ElementState::Released => {
+                    held.keys.remove(keycode);
+                    controls.key_up(&key);
+                }
+            }
+        }
+        WindowEvent::Focused(focused) => controls.set_focused(*focused),
+        _ => {}
+    }
+    true
+}
+
+/// Draw the last frame, letterboxed in the window or, with the debug panels
+/// shown, in the space they leave
+fn draw(
+    window: &Window,
+    pixels: &mut Pixels,
+    renderer: &Renderer,
+    controls: &mut Controls,
+    game: &mut GameBoy,
+    display: DisplayOptions,
+    #[cfg(feature = "debugger")] panel: &mut DebugPanel,
+) -> Result<(), String> {
+    let frame = controls.frame(game);
+    pixels
+        .frame_mut()
+        .copy_from_slice(&controls.picture(&frame));
+    let size = window.inner_size();
+    #[cfg(feature = "debugger")]
+    if controls.debugger_shown() {
+        let area = panel.update(window, game, controls.debugger_mut());
+        controls.resized(area.width, area.height);
+        let mut viewport = display.viewport(area.width, area.height);
+        viewport.x += area.x;
+        viewport.y += area.y;
+        return panel.render(pixels, renderer, viewport, (size.width, size.height));
+    }
+    renderer.render(pixels, display.viewport(size.width, size.height))
+}
+
+/// Widen the window for the debug panels when they are shown, or narrow it
+/// when they are hidden
+#[cfg(feature = "debugger")]
+fn fit_panel(window: &Window, shown: bool) {
+    let size = window.inner_size().to_logical::<f64>(window.scale_factor());
+    let panel = f64::from(PANEL_WIDTH);
+    let width = if shown {
+        size.width + panel
+    } else {
+        size.width - panel
+    };
+    window.set_inner_size(LogicalSize::new(width, size.height));
+}
+
+/// Run `game` in a winit window drawn through `pixels` until it is closed,
+/// a frame per V-Blank, scaled and letterboxed as `display` says and run
+/// through `shader` on the GPU if given. Needs no system libraries beyond
+/// the graphics driver.
+#[allow(clippy::cast_possible_truncation)]
+pub(super) fn run_window(
+    game: &mut GameBoy,
+    hotkeys: Hotkeys,
+    game_dir: Option<&GameDirectory>,
+    shader: Option<&PostShader>,
+    mut display: DisplayOptions,
+) -> Result<(), String> {
+    let (width, height) = (Frame::WIDTH as u32, Frame::HEIGHT as u32);
+    let title = game
+        .cartridge_header()
+        .map_or_else(|| "Game Boy".to_string(), |header| header.title.clone());
+
+    let mut event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title(title)
+        .with_inner_size(LogicalSize::<u32>::from(display.window_size()))
+        .with_min_inner_size(LogicalSize::new(width, height))
+        .build(&event_loop)
+        .map_err(|e| e.to_string())?;
+    let size = window.inner_size();
+    let surface = SurfaceTexture::new(size.width, size.height, &window);
+    let mut pixels = Pixels::new(width, height, surface).map_err(|e| e.to_string())?;
+    let mut renderer = Renderer::new(&pixels, shader, display.filter)?;
+    #[cfg(feature = "debugger")]
+    let mut panel = DebugPanel::new(&event_loop, &window, &pixels);
+    #[cfg(feature = "debugger")]
+    let mut panel_shown = false;
+
+    let mut controls = Controls::new(hotkeys, game_dir, display);
+    controls.resized(size.width, size.height);
+    let mut held = Held::default();
+    let mut due = Instant::now();
+    let mut result = Ok(());
+    event_loop.run_return(|event, _, control_flow| {
+        let step = match event {
+            Event::WindowEvent { event, .. } => {
+                #[cfg(feature = "debugger")]
+                let taken = controls.debugger_shown() && panel.on_event(&event);
+                #[cfg(not(feature = "debugger"))]
+                let taken = false;
+                if let WindowEvent::Resized(size) = event {
+                    controls.resized(size.width, size.height);
+                    pixels
+                        .resize_surface(size.width, size.height)
+                        .map_err(|e| e.to_string())
+                } else if taken || handle(&mut controls, &mut held, game, &event) {
+                    Ok(())
+                } else {
+                    control_flow.set_exit();
+                    return;
+                }
+            }
+            Event::MainEventsCleared if Instant::now() >= due => {
+                if let Some(changed) = controls.display_change() {
+                    if changed.scale != display.scale {
+                        window.set_inner_size(LogicalSize::<u32>::from(changed.window_size()));
+                    }
+                    renderer.set_filter(&pixels, changed.filter);
+                    display = changed;
+                }
+                #[cfg(feature = "debugger")]
+                if controls.debugger_shown() != panel_shown {
+                    panel_shown = !panel_shown;
+                    fit_panel(&window, panel_shown);
+                }
+                let advanced = controls.advance(game);
+                due = controls.schedule();
+                window.request_redraw();
+                advanced
+            }
+            Event::RedrawRequested(_) => draw(
+                &window,
+                &mut pixels,
+                &renderer,
+                &mut controls,
+                game,
+                display,
+                #[cfg(feature = "debugger")]
+                &mut panel,
+            ),
+            _ => Ok(()),
+        };
+        if let Err(e) = step {
+            result = Err(e);
+            control_flow.set_exit();
+        } else {
+            control_flow.set_wait_until(due);
+        }
+    });
+    result
+}
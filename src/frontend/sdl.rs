@@ -0,0 +1,152 @@
+use super::window::Controls;
+use super::{Chord, DisplayOptions, Filter, Hotkeys, Key, Modifiers, Viewport};
+use crate::frame::Frame;
+use crate::gameboy::GameBoy;
+use crate::paths::GameDirectory;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::ScaleMode;
+use std::time::Instant;
+
+/// The hotkey name of an SDL key, if it has one
+fn key(keycode: Keycode) -> Option<Key> {
+    let name = keycode.name();
+    let name = match name.as_str() {
+        "Return" => "Enter",
+        "-" => "Minus",
+        "=" | "+" => "Plus",
+        name => name,
+    };
+    name.parse().ok()
+}
+
+fn modifiers(keymod: Mod) -> Modifiers {
+    Modifiers {
+        ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+    }
+}
+
+fn scale_mode(filter: Filter) -> ScaleMode {
+    match filter {
+        Filter::Nearest => ScaleMode::Nearest,
+        Filter::Bilinear => ScaleMode::Linear,
+    }
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn rect(viewport: Viewport) -> Rect {
+    Rect::new(
+        viewport.x as i32,
+        viewport.y as i32,
+        viewport.width,
+        viewport.height,
+    )
+}
+
+/// Handle one window event, returning false once the window is closed
+fn handle(controls: &mut Controls, game: &mut GameBoy, event: &Event) -> bool {
+    match event {
+        Event::Quit { .. } => return false,
+        Event::KeyDown {
+            keycode: Some(keycode),
+            keymod,
+            repeat: false,
+            ..
+        } => {
+            if let Some(key) = key(*keycode) {
+                let chord = Chord {
+                    modifiers: modifiers(*keymod),
+                    key,
+                };
+                controls.key_down(game, &chord);
+            }
+        }
+        Event::KeyUp {
+            keycode: Some(keycode),
+            ..
+        } => {
+            if let Some(key) = key(*keycode) {
+                controls.key_up(&key);
+            }
+        }
+        Event::Window { win_event, .. } => match win_event {
+            WindowEvent::FocusGained => controls.set_focused(true),
+            WindowEvent::FocusLost => controls.set_focused(false),
+            _ => {}
+        },
+        _ => {}
+    }
+    true
+}
+
+/// Run `game` in an SDL2 window until it is closed, a frame per V-Blank,
+/// scaled and letterboxed as `display` says
+#[allow(clippy::cast_possible_truncation)]
+pub(super) fn run_window(
+    game: &mut GameBoy,
+    hotkeys: Hotkeys,
+    game_dir: Option<&GameDirectory>,
+    mut display: DisplayOptions,
+) -> Result<(), String> {
+    let (width, height) = (Frame::WIDTH as u32, Frame::HEIGHT as u32);
+    let title = game
+        .cartridge_header()
+        .map_or_else(|| "Game Boy".to_string(), |header| header.title.clone());
+
+    let sdl = sdl2::init()?;
+    let (window_width, window_height) = display.window_size();
+    let window = sdl
+        .video()?
+        .window(&title, window_width, window_height)
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let textures = canvas.texture_creator();
+    let mut texture = textures
+        .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+        .map_err(|e| e.to_string())?;
+    texture.set_scale_mode(scale_mode(display.filter));
+    let mut events = sdl.event_pump()?;
+
+    let mut controls = Controls::new(hotkeys, game_dir, display);
+    loop {
+        let (window_width, window_height) = canvas.output_size()?;
+        controls.resized(window_width, window_height);
+        for event in events.poll_iter() {
+            if !handle(&mut controls, game, &event) {
+                return Ok(());
+            }
+        }
+        if let Some(changed) = controls.display_change() {
+            if changed.scale != display.scale {
+                let (width, height) = changed.window_size();
+                canvas
+                    .window_mut()
+                    .set_size(width, height)
+                    .map_err(|e| e.to_string())?;
+            }
+            texture.set_scale_mode(scale_mode(changed.filter));
+            display = changed;
+        }
+        controls.advance(game)?;
+
+        let frame = controls.frame(game);
+        texture
+            .update(None, &controls.picture(&frame), Frame::WIDTH * 4)
+            .map_err(|e| e.to_string())?;
+        canvas.clear();
+        let viewport = display.viewport(window_width, window_height);
+        canvas.copy(&texture, None, rect(viewport))?;
+        canvas.present();
+
+        if let Some(wait) = controls.schedule().checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+}
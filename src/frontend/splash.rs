@@ -0,0 +1,50 @@
+use super::osd::{self, ADVANCE};
+use crate::gameboy::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::palette::{SOURCE_BG, pixel};
+
+/// Lines of the splash screen: text, top row, scale and shade
+const LINES: [(&str, usize, usize, u8); 3] = [
+    ("GAME BOY", 40, 4, 3),
+    ("NO ROM LOADED", 86, 2, 2),
+    ("DROP A ROM HERE", 104, 2, 2),
+];
+
+/// Built-in frame shown while no ROM is loaded, in the frame buffer's
+/// format (shade + layer per pixel) so it takes the selected palette
+pub fn splash_frame() -> Vec<u8> {
+    let mut frame = vec![pixel(0, SOURCE_BG); SCREEN_WIDTH * SCREEN_HEIGHT];
+    for (text, top, scale, shade) in LINES {
+        let width = (text.len() * ADVANCE - 1) * scale;
+        let left = (SCREEN_WIDTH - width) / 2;
+        for (x, y) in osd::inked(text) {
+            let column = left + x * scale;
+            for dy in 0..scale {
+                let start = (top + y * scale + dy) * SCREEN_WIDTH + column;
+                frame[start..start + scale].fill(pixel(shade, SOURCE_BG));
+            }
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splash_text_is_centered_on_a_blank_screen() {
+        let frame = splash_frame();
+        assert_eq!(frame.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(frame[0], pixel(0, SOURCE_BG));
+
+        let inked: Vec<usize> = frame
+            .iter()
+            .enumerate()
+            .filter(|&(_, &shade)| shade == 3)
+            .map(|(index, _)| index % SCREEN_WIDTH)
+            .collect();
+        assert!(!inked.is_empty());
+        let (first, last) = (inked.iter().min().unwrap(), inked.iter().max().unwrap());
+        assert_eq!(first + last, SCREEN_WIDTH - 1, "Title is centered");
+    }
+}
@@ -0,0 +1,191 @@
+use super::FrameSink;
+use crate::frame::Frame;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Upper half block: the foreground color paints the top pixel and the
+/// background color the bottom one
+const HALF_BLOCK: char = '▀';
+
+/// Levels of each channel in the xterm 256-color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Escape sequences a terminal understands for color
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 24-bit color, in most modern terminals
+    #[default]
+    TrueColor,
+    /// The xterm 256-color palette, for older terminals and multiplexers
+    Ansi256,
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truecolor" | "24bit" => Ok(ColorMode::TrueColor),
+            "256" => Ok(ColorMode::Ansi256),
+            _ => Err(format!(
+                "Unknown terminal colors: {s} (expected truecolor or 256)"
+            )),
+        }
+    }
+}
+
+/// Nearest entry of the xterm 256-color palette: the 6x6x6 cube or the
+/// grayscale ramp, whichever is closer
+fn ansi256(rgb: [u8; 3]) -> u8 {
+    let nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&index| CUBE_LEVELS[index].abs_diff(value))
+            .unwrap_or(0)
+    };
+    let cube = rgb.map(nearest_level);
+    let cube_rgb = cube.map(|index| CUBE_LEVELS[index]);
+
+    let average = rgb.iter().map(|&value| u32::from(value)).sum::<u32>() / 3;
+    // Grays 232-255 run from 8 to 238 in steps of 10
+    let gray = (average.saturating_sub(3) / 10).min(23);
+    let gray_level = u8::try_from(8 + gray * 10).unwrap_or(u8::MAX);
+
+    let distance = |other: [u8; 3]| {
+        rgb.iter()
+            .zip(other)
+            .map(|(&a, b)| u32::from(a.abs_diff(b)).pow(2))
+            .sum::<u32>()
+    };
+    if distance([gray_level; 3]) < distance(cube_rgb) {
+        232 + u8::try_from(gray).unwrap_or(23)
+    } else {
+        // At most 16 + 215, as each index is below 6
+        u8::try_from(16 + 36 * cube[0] + 6 * cube[1] + cube[2]).unwrap_or(u8::MAX)
+    }
+}
+
+/// `width` x `height` RGBA8 pixels as rows of half blocks, two pixel rows
+/// per line of text, starting from the top left of the screen. Colors are
+/// only sent when they change, and each line ends with a reset so a
+/// scrolled-back terminal is not left colored.
+pub fn render(rgba: &[u8], width: usize, height: usize, mode: ColorMode) -> String {
+    let pixel = |x: usize, y: usize| {
+        let offset = (y * width + x) * 4;
+        [rgba[offset], rgba[offset + 1], rgba[offset + 2]]
+    };
+    let mut text = String::from("\x1b[H");
+    for y in (0..height).step_by(2) {
+        let mut colors = None;
+        for x in 0..width {
+            let top = pixel(x, y);
+            let bottom = if y + 1 < height { pixel(x, y + 1) } else { top };
+            if colors != Some((top, bottom)) {
+                colors = Some((top, bottom));
+                // Writing to a String cannot fail
+                let _ = match mode {
+                    ColorMode::TrueColor => write!(
+                        text,
+                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                        top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                    ),
+                    ColorMode::Ansi256 => {
+                        write!(
+                            text,
+                            "\x1b[38;5;{}m\x1b[48;5;{}m",
+                            ansi256(top),
+                            ansi256(bottom)
+                        )
+                    }
+                };
+            }
+            text.push(HALF_BLOCK);
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    text
+}
+
+/// Draws every frame in a terminal with half blocks (see `render`), 80
+/// columns by 72 lines, so the emulator can be watched over SSH or in a CI
+/// log. A frame identical to the last one drawn is skipped.
+pub struct TerminalSink<W> {
+    out: W,
+    mode: ColorMode,
+    /// The last frame drawn, in RGBA8
+    shown: Option<Vec<u8>>,
+}
+
+impl<W: Write + Send> TerminalSink<W> {
+    pub fn new(out: W, mode: ColorMode) -> Self {
+        Self {
+            out,
+            mode,
+            shown: None,
+        }
+    }
+}
+
+impl<W: Write + Send> FrameSink for TerminalSink<W> {
+    fn present(&mut self, _frame: u64, image: &Frame) -> Result<(), String> {
+        let rgba = image.rgba();
+        if self.shown.as_deref() == Some(rgba) {
+            return Ok(());
+        }
+        let mut text = render(rgba, Frame::WIDTH, Frame::HEIGHT, self.mode);
+        if self.shown.is_none() {
+            // Clear whatever was printed before the first frame
+            text.insert_str(0, "\x1b[2J");
+        }
+        self.out
+            .write_all(text.as_bytes())
+            .and_then(|()| self.out.flush())
+            .map_err(|e| format!("Terminal: {e}"))?;
+        self.shown = Some(rgba.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::DmgPalette;
+
+    #[test]
+    fn half_blocks_pair_rows_and_skip_repeated_colors() {
+        // 2x2: white over black in both columns
+        let rgba = [
+            255, 255, 255, 255, 255, 255, 255, 255, //
+            0, 0, 0, 255, 0, 0, 0, 255,
+        ];
+        let text = render(&rgba, 2, 2, ColorMode::TrueColor);
+        assert_eq!(
+            text,
+            "\x1b[H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀▀\x1b[0m\n"
+        );
+        let text = render(&rgba, 2, 2, ColorMode::Ansi256);
+        assert!(text.contains("\x1b[38;5;231m\x1b[48;5;16m▀▀"), "{text:?}");
+    }
+
+    #[test]
+    fn ansi256_picks_cube_or_gray() {
+        assert_eq!(ansi256([255, 0, 0]), 196);
+        assert_eq!(ansi256([0, 0, 0]), 16);
+        assert_eq!(ansi256([128, 128, 128]), 244);
+        assert_eq!(ansi256([135, 175, 95]), 16 + 36 * 2 + 6 * 3 + 1);
+    }
+
+    #[test]
+    fn sink_clears_once_and_skips_unchanged_frames() {
+        let mut sink = TerminalSink::new(Vec::new(), ColorMode::TrueColor);
+        let blank = Frame::new(vec![0; Frame::WIDTH * Frame::HEIGHT], DmgPalette::default());
+        sink.present(1, &blank).unwrap();
+        let drawn = sink.out.len();
+        sink.present(2, &blank).unwrap();
+        assert_eq!(sink.out.len(), drawn, "Same picture");
+
+        let text = String::from_utf8(sink.out).unwrap();
+        assert!(text.starts_with("\x1b[2J\x1b[H"));
+        assert_eq!(text.lines().count(), Frame::HEIGHT / 2);
+    }
+}
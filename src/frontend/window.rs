@@ -0,0 +1,270 @@
+use super::{
+    Action, Chord, DisplayOptions, FocusPolicy, Hotkeys, Key, Keyboard, PauseState, draw_help,
+};
+use crate::apu::Channel;
+use crate::debugger::Debugger;
+use crate::frame::{Frame, Ghosting};
+use crate::gameboy::{GameBoy, ResetKind};
+use crate::image::write_png;
+use crate::paths::GameDirectory;
+use crate::playtime::emulated_time;
+use crate::rewind::{Rewind, RewindAudio};
+use crate::savestate::SaveState;
+use std::borrow::Cow;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Ten seconds of rewind, a snapshot per frame
+const REWIND_FRAMES: usize = 600;
+
+/// What a window keeps between frames besides its own handles, shared by
+/// the windowed frontends so they only translate events and draw.
+///
+/// States are saved to slot 0 and screenshots to the screenshots directory
+/// of the game directory; without one, states last until the window closes
+/// and screenshots go to the working directory.
+pub(super) struct Controls<'a> {
+    keyboard: Keyboard,
+    pause: PauseState,
+    rewind: Rewind,
+    game_dir: Option<&'a GameDirectory>,
+    /// The last state saved, for loading without a game directory
+    state: Option<SaveState>,
+    frame_time: Duration,
+    /// When the next frame is due
+    deadline: Instant,
+    display: DisplayOptions,
+    ghosting: Option<Ghosting>,
+    /// Size of the window's drawing area, for the scale hotkeys
+    window_size: (u32, u32),
+    /// Whether a hotkey changed `display` since `display_change`
+    display_changed: bool,
+    /// Breakpoints and stepping; frames run through it
+    debugger: Debugger,
+    /// Whether the debug panels are shown beside the game
+    #[cfg(feature = "debugger")]
+    debugger_shown: bool,
+    /// Whether the controls are listed over the game
+    help_shown: bool,
+}
+
+impl<'a> Controls<'a> {
+    pub(super) fn new(
+        hotkeys: Hotkeys,
+        game_dir: Option<&'a GameDirectory>,
+        display: DisplayOptions,
+    ) -> Self {
+        Self {
+            keyboard: Keyboard::new(hotkeys),
+            pause: PauseState::new(FocusPolicy::default()),
+            rewind: Rewind::new(REWIND_FRAMES, 1, RewindAudio::Mute),
+            game_dir,
+            state: None,
+            frame_time: emulated_time(1),
+            deadline: Instant::now(),
+            display,
+            ghosting: (display.ghosting > 0).then(|| Ghosting::new(display.ghosting)),
+            window_size: display.window_size(),
+            display_changed: false,
+            debugger: Debugger::new(),
+            #[cfg(feature = "debugger")]
+            debugger_shown: false,
+            help_shown: false,
+        }
+    }
+
+    /// A key went down, not a repeat. Hotkey errors are reported rather than
+    /// closing the window.
+    pub(super) fn key_down(&mut self, game: &mut GameBoy, chord: &Chord) {
+        if let Some(action) = self.keyboard.key_down(chord)
+            && let Err(e) = self.act(game, action)
+        {
+            eprintln!("{}: {e}", action.name());
+        }
+    }
+
+    pub(super) fn key_up(&mut self, key: &Key) {
+        self.keyboard.key_up(key);
+    }
+
+    pub(super) fn set_focused(&mut self, focused: bool) {
+        self.pause.set_focused(focused);
+    }
+
+    /// Call when the window's drawing area changes size
+    pub(super) fn resized(&mut self, width: u32, height: u32) {
+        self.window_size = (width, height);
+    }
+
+    /// The display options, if a hotkey has changed them since the last
+    /// call. The frontend resizes the window for a new scale and redraws
+    /// with the new filter.
+    pub(super) fn display_change(&mut self) -> Option<DisplayOptions> {
+        std::mem::take(&mut self.display_changed).then_some(self.display)
+    }
+
+    #[cfg(feature = "debugger")]
+    pub(super) fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    #[cfg(feature = "debugger")]
+    pub(super) fn debugger_shown(&self) -> bool {
+        self.debugger_shown
+    }
+
+    /// Carry out a pressed hotkey
+    fn act(&mut self, game: &mut GameBoy, action: Action) -> Result<(), String> {
+        match action {
+            Action::SaveState => {
+                let state = game.save_state();
+                if let Some(dir) = self.game_dir {
+                    fs::create_dir_all(dir.states_dir()).map_err(|e| e.to_string())?;
+                    fs::write(dir.state_file(0), state.as_bytes()).map_err(|e| e.to_string())?;
+                }
+                self.state = Some(state);
+            }
+            Action::LoadState => {
+                if let Some(dir) = self.game_dir
+                    && let Ok(data) = fs::read(dir.state_file(0))
+                {
+                    self.state = Some(SaveState::from_bytes(data)?);
+                }
+                let Some(state) = &self.state else {
+                    return Err("No state saved".to_string());
+                };
+                game.load_state(state)?;
+                self.rewind.clear();
+            }
+            Action::Screenshot => {
+                let dir = self
+                    .game_dir
+                    .map_or_else(|| PathBuf::from("."), GameDirectory::screenshots_dir);
+                fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                let path = dir.join(format!("frame{}.png", game.frame_count()));
+                write_png(&path, Frame::WIDTH, Frame::HEIGHT, game.frame().rgba())
+                    .map_err(|e| e.to_string())?;
+                println!("Screenshot saved to {}", path.display());
+            }
+            Action::Pause => self.pause.toggle_user_pause(),
+            Action::Reset => {
+                game.reset(ResetKind::Soft);
+                self.rewind.clear();
+            }
+            Action::Mute => {
+                let muted = game.volume_mut().toggle_mute();
+                println!("{}", if muted { "Muted" } else { "Unmuted" });
+            }
+            Action::MuteChannel1
+            | Action::MuteChannel2
+            | Action::MuteChannel3
+            | Action::MuteChannel4 => {
+                if let Some(channel) = action.channel() {
+                    let heard = game.toggle_channel(channel);
+                    let state = if heard { "unmuted" } else { "muted" };
+                    println!("Channel {} {state}", channel.number());
+                }
+            }
+            Action::SoloChannel => {
+                let next = match game.channels().soloed() {
+                    None => Some(Channel::Square1),
+                    Some(channel) => channel.next(),
+                };
+                game.solo_channel(next);
+                match next {
+                    Some(channel) => println!("Channel {} solo", channel.number()),
+                    None => println!("All channels"),
+                }
+            }
+            Action::ToggleBackground | Action::ToggleWindow | Action::ToggleObjects => {
+                if let Some(layer) = action.layer() {
+                    game.toggle_layer(layer);
+                }
+            }
+            Action::ScaleUp | Action::ScaleDown | Action::ToggleFilter => {
+                let (width, height) = self.window_size;
+                match action {
+                    Action::ScaleUp => self.display.scale_up(width, height),
+                    Action::ScaleDown => self.display.scale_down(width, height),
+                    _ => self.display.toggle_filter(),
+                }
+                self.display_changed = true;
+            }
+            #[cfg(feature = "debugger")]
+            Action::ToggleDebugger => self.debugger_shown = !self.debugger_shown,
+            #[cfg(not(feature = "debugger"))]
+            Action::ToggleDebugger => {
+                return Err(
+                    "This build has no debugger. Rebuild with `cargo build --features debugger`."
+                        .to_string(),
+                );
+            }
+            Action::ToggleHelp => self.help_shown = !self.help_shown,
+            // Held, so tracked by the keyboard instead
+            Action::Turbo | Action::Rewind => {}
+        }
+        Ok(())
+    }
+
+    /// Run or rewind one frame, unless paused
+    pub(super) fn advance(&mut self, game: &mut GameBoy) -> Result<(), String> {
+        if self.pause.is_paused() || self.debugger.is_paused() || game.stopped() {
+            return Ok(());
+        }
+        game.volume_mut().fast_forward = self.keyboard.is_held(Action::Turbo);
+        if self.keyboard.is_held(Action::Rewind) {
+            self.rewind.step_back(game)?;
+        } else {
+            game.set_buttons(self.keyboard.buttons());
+            self.debugger.run_frame(game);
+            self.rewind.record_frame(game, &[]);
+        }
+        if let Some(ref mut ghosting) = self.ghosting {
+            ghosting.blend(game.frame().rgba());
+        }
+        Ok(())
+    }
+
+    /// The frame to draw: the last one finished, or while the debugger has
+    /// emulation stopped, the frame so far (`GameBoy::partial_frame`)
+    pub(super) fn frame(&self, game: &GameBoy) -> Frame {
+        if self.debugger.is_paused() {
+            game.partial_frame()
+        } else {
+            game.frame()
+        }
+    }
+
+    /// The picture to draw for `frame` (see `frame`): the frame itself, or
+    /// with ghosting on, the frames so far blended together. With help
+    /// shown, the controls are written over it.
+    pub(super) fn picture<'f>(&'f self, frame: &'f Frame) -> Cow<'f, [u8]> {
+        let picture = if self.debugger.is_paused() {
+            frame.rgba()
+        } else {
+            self.ghosting
+                .as_ref()
+                .and_then(Ghosting::picture)
+                .unwrap_or_else(|| frame.rgba())
+        };
+        if !self.help_shown {
+            return Cow::Borrowed(picture);
+        }
+        let mut picture = picture.to_vec();
+        draw_help(&mut picture, self.keyboard.hotkeys());
+        Cow::Owned(picture)
+    }
+
+    /// When the frame after the one just shown is due: a frame time later
+    /// at the Game Boy's frame rate, or straight away while turbo is held or
+    /// when running behind
+    pub(super) fn schedule(&mut self) -> Instant {
+        let now = Instant::now();
+        self.deadline += self.frame_time;
+        if self.keyboard.is_held(Action::Turbo) || self.deadline < now {
+            self.deadline = now;
+        }
+        self.deadline
+    }
+}
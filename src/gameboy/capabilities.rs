@@ -0,0 +1,181 @@
+use crate::cartridge::CartridgeType;
+use crate::savestate;
+use serde_json::{Value, json};
+use std::fmt;
+
+/// A piece of hardware behavior and whether this core emulates it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Feature {
+    /// Stable kebab-case name for `Capabilities::supports`
+    pub name: &'static str,
+    pub supported: bool,
+    pub description: &'static str,
+}
+
+impl Feature {
+    const fn new(name: &'static str, supported: bool, description: &'static str) -> Self {
+        Self {
+            name,
+            supported,
+            description,
+        }
+    }
+}
+
+/// Accuracy features, including the ones still missing, so a frontend or
+/// test harness can tell an unsupported ROM from a regression
+const FEATURES: [Feature; 11] = [
+    Feature::new(
+        "pixel-fifo",
+        true,
+        "mode 3 drawn dot by dot, its length following SCX, the window and objects",
+    ),
+    Feature::new(
+        "stat-interrupt",
+        true,
+        "STAT line from modes 0-2 and LY=LYC, raised on its rising edge",
+    ),
+    Feature::new("lcdc", true, "every LCDC bit, including LCD-off blanking"),
+    Feature::new(
+        "timer",
+        true,
+        "DIV, TIMA, TMA and TAC with the timer interrupt",
+    ),
+    Feature::new(
+        "serial",
+        true,
+        "serial transfers on the internal and external clock",
+    ),
+    Feature::new(
+        "mid-frame-save-states",
+        true,
+        "states taken mid-scanline resume the same picture",
+    ),
+    Feature::new(
+        "cgb-speed-switch",
+        true,
+        "KEY1 double speed through STOP, in CGB mode only",
+    ),
+    Feature::new(
+        "complete-instruction-set",
+        true,
+        "every legal base and CB-prefixed opcode",
+    ),
+    Feature::new(
+        "interrupt-dispatch",
+        false,
+        "interrupts are requested in IF but never serviced",
+    ),
+    Feature::new("oam-dma", false, "writes to DMA (0xFF46) copy nothing"),
+    Feature::new(
+        "apu",
+        false,
+        "square channels 1 and 2 only; no wave or noise channel yet",
+    ),
+];
+
+/// What this build of the core emulates, for frontends and test harnesses
+/// to adapt to and for bug reports to quote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Package version of the core
+    pub version: &'static str,
+    /// Save state format (`savestate::VERSION`)
+    pub save_state_version: u8,
+    /// Hardware models the core can run as
+    pub models: &'static [&'static str],
+    pub mappers: &'static [CartridgeType],
+    pub features: &'static [Feature],
+}
+
+/// This build's capabilities
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        save_state_version: savestate::VERSION,
+        models: &["DMG"],
+        mappers: &CartridgeType::SUPPORTED,
+        features: &FEATURES,
+    }
+}
+
+impl Capabilities {
+    /// Whether the feature called `name` is emulated; unknown names are not
+    pub fn supports(&self, name: &str) -> bool {
+        self.features
+            .iter()
+            .any(|feature| feature.name == name && feature.supported)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": self.version,
+            "save_state_version": self.save_state_version,
+            "models": self.models,
+            "mappers": self.mappers.iter().map(|mapper| mapper.name()).collect::<Vec<_>>(),
+            "features": self.features.iter().map(|feature| json!({
+                "name": feature.name,
+                "supported": feature.supported,
+                "description": feature.description,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A block to paste into bug reports, one feature per line marked `+` or `-`
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mappers: Vec<&str> = self.mappers.iter().map(|mapper| mapper.name()).collect();
+        writeln!(
+            f,
+            "gameboy core {} (save state format {})",
+            self.version, self.save_state_version
+        )?;
+        writeln!(f, "Models: {}", self.models.join(", "))?;
+        writeln!(f, "Mappers: {}", mappers.join(", "))?;
+        write!(f, "Features:")?;
+        for feature in self.features {
+            let mark = if feature.supported { '+' } else { '-' };
+            write!(f, "\n  {mark} {:<24} {}", feature.name, feature.description)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_features_up_by_name() {
+        let capabilities = capabilities();
+        assert!(capabilities.supports("pixel-fifo"));
+        assert!(!capabilities.supports("apu"));
+        assert!(!capabilities.supports("time-travel"));
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert!(
+            capabilities
+                .mappers
+                .iter()
+                .all(|&mapper| mapper.name() != "unknown")
+        );
+    }
+
+    #[test]
+    fn report_and_json_list_everything() {
+        let capabilities = capabilities();
+        let report = capabilities.to_string();
+        assert!(report.starts_with(&format!(
+            "gameboy core {} (save state format {})\nModels: DMG\nMappers: ROM ONLY, MBC1,",
+            capabilities.version,
+            savestate::VERSION
+        )));
+        assert!(report.contains("\n  - apu "));
+        assert_eq!(report.lines().count(), 4 + FEATURES.len());
+
+        let json = capabilities.to_json();
+        assert_eq!(json["mappers"][3], "MBC1+RAM+BATTERY");
+        assert_eq!(json["features"][0]["name"], "pixel-fifo");
+        assert_eq!(json["features"][0]["supported"], true);
+    }
+}
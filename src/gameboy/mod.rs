@@ -1,110 +1,2522 @@
-use crate::{cartridge, cpu, memory};
-use std::fs::File;
+mod capabilities;
+
+use crate::audio::Volume;
+use crate::autosave::Autosave;
+use crate::autosplit::{Autosplitter, SplitEvent};
+use crate::bankaudit::BankAudit;
+use crate::bankstats::BankStats;
+use crate::battery::BatterySave;
+use crate::bootlogo::{LOGO_LEN, LOGO_START, LogoScroll};
+use crate::cheats::CheatList;
+use crate::clocktrace::{ClockTrace, Component};
+use crate::disasm::Coverage;
+use crate::eventlog::{Event, EventLog};
+use crate::frame::{self, Frame};
+use crate::frontend::{FrameSink, splash_frame};
+use crate::heatmap::HeatMap;
+use crate::interrupts::Interrupt;
+use crate::ioregs::Io;
+use crate::joypad::{Buttons, InputLayer};
+use crate::lastwrite::{LastWrite, WriteTracker};
+use crate::memory::RamInit;
+use crate::palette::DmgPalette;
+use crate::paths::GameDirectory;
+use crate::quirks::{QuirkEvent, QuirkPolicy};
+use crate::rtc::{ClockSource, SystemClock};
+use crate::savestate::{
+    SaveState, StateCheck, StateMetadata, StateProblem, StateWriter, THUMBNAIL_HEIGHT,
+    THUMBNAIL_WIDTH,
+};
+use crate::serial::SerialDevice;
+use crate::throttle::Throttle;
+use crate::tick::{self, Tickable};
+use crate::{apu, cartridge, cpu, memory, ppu};
+use std::fs::{self, File};
 use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use capabilities::{Capabilities, Feature, capabilities};
+
+/// CPU cycles in one full frame (154 scanlines of 456 cycles)
+pub const CYCLES_PER_FRAME: u32 = 70224;
+/// CPU cycles in one scanline
+pub const CYCLES_PER_LINE: u32 = 456;
+/// Visible LCD size in pixels
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+/// Most cycles one `step` idles through while halted, the last whole
+/// M-cycle that fits its `u8`
+const MAX_HALT_SKIP: u32 = 252;
+
+/// Called at the end of each visible scanline with the line index (LY) and
+/// that line's pixels (one byte per pixel, see `palette::pixel`)
+pub type ScanlineCallback = Box<dyn FnMut(u8, &[u8]) + Send>;
+
+/// Called with the battery save whenever `Autosave` decides to flush it
+pub type AutosaveCallback = Box<dyn FnMut(BatterySave) + Send>;
+
+/// Called with each start, split or reset the `Autosplitter` reports
+pub type SplitCallback = Box<dyn FnMut(&SplitEvent) + Send>;
+
+/// Hardware model being emulated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    Dmg,
+}
+
+impl Model {
+    /// Short lowercase name used in file names and reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Model::Dmg => "dmg",
+        }
+    }
+}
+
+/// How much state `GameBoy::reset` throws away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetKind {
+    /// Reinitialise the CPU, IO registers and mapper but keep all RAM,
+    /// including battery-backed cartridge RAM
+    Soft,
+    /// Reinitialise everything, filling RAM according to the RAM init policy
+    Hard,
+}
+
+/// What `GameBoy::run_frame` ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameRun {
+    /// The picture at the end of the run, with the selected palette
+    pub frame: Frame,
+    /// Cycles run, which can overshoot the start of V-Blank by up to one
+    /// instruction
+    pub cycles: u64,
+    /// False if the run stopped short of V-Blank: the LCD was off for a
+    /// frame's worth of cycles, or the CPU fell asleep or a quirk trapped
+    pub vblank: bool,
+}
+
+/// When the core samples host input
+pub enum InputPolling {
+    /// The frontend pushes input with `set_buttons`, typically once per host frame
+    HostFrame,
+    /// The core pulls input from the source as the PPU enters mode 2 on LY 0,
+    /// right before each frame's first OAM scan, so the game sees the
+    /// freshest possible state. With the LCD off there is no OAM scan, and no
+    /// polling.
+    BeforeOamScan(Box<dyn FnMut() -> Buttons + Send>),
+}
+
+pub struct GameBoy {
+    pub cpu: cpu::Cpu,
+    pub memory: memory::Memory,
+    log_file: Option<File>,
+    input_polling: InputPolling,
+    input: InputLayer,
+    frame_cycles: u32, // Position within the current frame
+    frames: u64,       // Frames started since power on
+    ram_init: RamInit,
+    frame_buffer: Vec<u8>, // SCREEN_WIDTH x SCREEN_HEIGHT pixels (shade + layer)
+    scanline_callback: Option<ScanlineCallback>,
+    palette: DmgPalette,
+    layers: ppu::Layers,
+    coverage: Option<Coverage>,
+    autosave: Option<(Autosave, AutosaveCallback)>,
+    autosplit: Option<(Autosplitter, SplitCallback)>,
+    event_log: Option<EventLog>,
+    clock_trace: Option<ClockTrace>,
+    cheats: CheatList,
+    state_check: StateCheck,
+    /// Playing in place of the boot ROM, with the CPU held
+    logo_scroll: Option<LogoScroll>,
+    // TODO: Drive the MBC3 RTC from this once MBC3 is emulated
+    clock: Box<dyn ClockSource>,
+    frame_sinks: Vec<Box<dyn FrameSink>>,
+    throttle: Option<Throttle>,
+}
+
+impl GameBoy {
+    pub fn new() -> Self {
+        Self {
+            cpu: cpu::Cpu::default(),
+            memory: memory::Memory::default(),
+            log_file: None,
+            input_polling: InputPolling::HostFrame,
+            input: InputLayer::new(),
+            frame_cycles: 0,
+            frames: 0,
+            ram_init: RamInit::default(),
+            frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            scanline_callback: None,
+            palette: DmgPalette::default(),
+            layers: ppu::Layers::default(),
+            coverage: None,
+            autosave: None,
+            autosplit: None,
+            event_log: None,
+            clock_trace: None,
+            cheats: CheatList::default(),
+            state_check: StateCheck::Off,
+            logo_scroll: None,
+            clock: Box::new(SystemClock),
+            frame_sinks: Vec::new(),
+            throttle: None,
+        }
+    }
+
+    /// Load a ROM file
+    pub fn load_rom(&mut self, path: &str) -> std::io::Result<()> {
+        let cartridge = cartridge::Cartridge::load(path)?;
+        self.memory.load_cartridge(cartridge);
+        Ok(())
+    }
+
+    /// Like `load_rom`, but maps the file instead of reading it into memory
+    pub fn load_rom_mapped(&mut self, path: &str) -> std::io::Result<()> {
+        let cartridge = cartridge::Cartridge::load_mapped(path)?;
+        self.memory.load_cartridge(cartridge);
+        Ok(())
+    }
+
+    /// Hardware model being emulated
+    pub fn model(&self) -> Model {
+        Model::Dmg
+    }
+
+    /// CRC-32 of the loaded ROM, if any
+    pub fn rom_hash(&self) -> Option<u32> {
+        self.memory
+            .cartridge
+            .as_ref()
+            .map(cartridge::Cartridge::rom_hash)
+    }
+
+    /// Header of the loaded cartridge, if any
+    pub fn cartridge_header(&self) -> Option<&cartridge::CartridgeHeader> {
+        self.memory
+            .cartridge
+            .as_ref()
+            .map(cartridge::Cartridge::header)
+    }
+
+    /// Battery-backed RAM of the loaded cartridge, if it has a battery
+    pub fn battery_save(&self) -> Option<BatterySave> {
+        let cartridge = self
+            .memory
+            .cartridge
+            .as_ref()
+            .filter(|cart| cart.has_battery())?;
+        Some(BatterySave {
+            ram: cartridge.ram().to_vec(),
+            rtc: None,
+        })
+    }
+
+    /// Restore battery-backed RAM from a `.sav` file's contents
+    pub fn load_battery_save(&mut self, save: &BatterySave) -> Result<(), String> {
+        self.memory
+            .cartridge
+            .as_mut()
+            .ok_or("No cartridge loaded")?
+            .load_ram(&save.ram)
+    }
+
+    /// Flush battery-backed RAM through `save` as the game writes it, on the
+    /// schedule of `autosave`. Does nothing for cartridges without a battery.
+    pub fn set_autosave(&mut self, autosave: Option<(Autosave, AutosaveCallback)>) {
+        self.autosave = autosave.map(|(mut autosave, save)| {
+            autosave.mark_saved(self.ram_writes());
+            (autosave, save)
+        });
+    }
+
+    /// Sleep between frames to hold a headless run to the throttle's share
+    /// of a CPU, or with `None` run flat out
+    pub fn set_throttle(&mut self, throttle: Option<Throttle>) {
+        self.throttle = throttle;
+    }
+
+    /// Check `autosplitter`'s conditions as each frame starts, calling
+    /// `split` with the events they trigger
+    pub fn set_autosplitter(&mut self, autosplit: Option<(Autosplitter, SplitCallback)>) {
+        self.autosplit = autosplit;
+    }
+
+    fn ram_writes(&self) -> u64 {
+        self.memory
+            .cartridge
+            .as_ref()
+            .map_or(0, cartridge::Cartridge::ram_writes)
+    }
+
+    /// Per-game data directory for the loaded cartridge, if any
+    pub fn game_directory(&self, data_dir: Option<&Path>) -> Option<GameDirectory> {
+        self.cartridge_header()
+            .map(|header| GameDirectory::for_rom(header, data_dir))
+    }
+
+    /// Enable CPU state logging to a file (gameboy-doctor format)
+    pub fn enable_logging(&mut self, path: &str) -> std::io::Result<()> {
+        self.log_file = Some(File::create(path)?);
+        Ok(())
+    }
+
+    /// Initialize to post-boot state (skipping boot ROM for now)
+    pub fn power_on(&mut self) {
+        // CPU initialized with correct values in Cpu::new()
+        // Set initial flag values (from docs: AF = 01B0h for DMG)
+        self.cpu.registers.f.z = true;
+        self.cpu.registers.f.n = false;
+        self.cpu.registers.f.h = true;
+        self.cpu.registers.f.c = true;
+    }
+
+    /// Play the logo scroll from the cartridge header before the game starts
+    /// (see `LogoScroll`). Call after `power_on`; without a cartridge there
+    /// is no logo and nothing happens.
+    pub fn start_logo_scroll(&mut self) {
+        let logo = self.memory.cartridge.as_ref().and_then(|cart| {
+            let logo = cart.rom().get(LOGO_START..LOGO_START + LOGO_LEN)?;
+            <[u8; LOGO_LEN]>::try_from(logo).ok()
+        });
+        if let Some(logo) = logo {
+            self.logo_scroll = Some(LogoScroll::start(&logo, &mut self.memory));
+        }
+    }
+
+    /// Whether the logo scroll is still playing and the game has not started
+    pub fn logo_scrolling(&self) -> bool {
+        self.logo_scroll.is_some()
+    }
+
+    /// The IO registers by name: `io().lcdc()`, `io().set_scx(8)`, or
+    /// `io().get("STAT")` (see `ioregs::Io`)
+    pub fn io(&mut self) -> Io<'_> {
+        Io::new(&mut self.memory)
+    }
+
+    /// Cheats loaded for this game, enabled or not
+    pub fn cheats(&self) -> &CheatList {
+        &self.cheats
+    }
+
+    /// Replace the cheat list. `GameShark` codes are written at the start of
+    /// each frame and Game Genie codes patch ROM reads straight away.
+    pub fn set_cheats(&mut self, cheats: CheatList) {
+        self.memory.rom_patches = cheats.rom_patches();
+        self.cheats = cheats;
+    }
+
+    /// Toggle one cheat while the game runs
+    pub fn set_cheat_enabled(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        self.cheats.set_enabled(name, enabled)?;
+        self.memory.rom_patches = self.cheats.rom_patches();
+        Ok(())
+    }
+
+    /// Choose what RAM contains after a hard reset
+    pub fn set_ram_init(&mut self, init: RamInit) {
+        self.ram_init = init;
+    }
+
+    /// Reset the machine and power it back on. The loaded cartridge, link
+    /// cable, cheats, quirk policy, logging and input configuration are kept.
+    pub fn reset(&mut self, kind: ResetKind) {
+        let mut cartridge = self.memory.cartridge.take();
+        let bank_stats = self.memory.bank_stats.take();
+        let heat_map = self.memory.heat_map.take();
+        let bank_audit = self.memory.bank_audit.take();
+        let write_tracker = self.memory.write_tracker.take();
+        let link = self.memory.serial.take_device();
+        let (sample_rate, volume) = (self.memory.apu.sample_rate(), self.memory.apu.volume());
+        let channels = self.memory.apu.channels();
+        let quirk_policy = self.memory.quirks.policy;
+        let ram = match kind {
+            ResetKind::Soft => self.memory.data.clone(),
+            ResetKind::Hard => {
+                let mut fresh = memory::Memory::new();
+                fresh.init_ram(self.ram_init);
+                fresh.data
+            }
+        };
+        if let Some(ref mut cart) = cartridge {
+            cart.reset((kind == ResetKind::Hard).then_some(self.ram_init));
+        }
+
+        self.cpu = cpu::Cpu::default();
+        self.memory = memory::Memory::new();
+        for range in [0x8000..=0x9FFF, 0xC000..=0xDFFF, 0xFF80..=0xFFFE] {
+            self.memory.data[range.clone()].copy_from_slice(&ram[range]);
+        }
+        self.memory.cartridge = cartridge;
+        self.memory.bank_stats = bank_stats;
+        self.memory.heat_map = heat_map;
+        self.memory.bank_audit = bank_audit;
+        self.memory.write_tracker = write_tracker;
+        self.memory.serial.connect(link);
+        self.memory.apu.set_sample_rate(sample_rate);
+        self.memory.apu.set_volume(volume);
+        self.memory.apu.set_channels(channels);
+        self.memory.quirks.policy = quirk_policy;
+        self.memory.rom_patches = self.cheats.rom_patches();
+        self.frame_cycles = 0;
+        self.frames = 0;
+        self.frame_buffer.fill(0);
+        self.logo_scroll = None;
+        self.power_on();
+    }
+
+    /// Execute one instruction and return the cycles it took. While stopped,
+    /// during a CGB speed switch stall or the logo scroll, one M-cycle passes
+    /// without executing anything. While halted, the step skips ahead to the
+    /// M-cycle before the next component event (see `idle_cycles`).
+    ///
+    /// With the clock trace enabled, a component falling behind the CPU by
+    /// more than the trace's window is kept as the trace's failure, and
+    /// `stopped` is true from then on.
+    pub fn step(&mut self) -> u8 {
+        // Any enabled interrupt ends HALT, even with IME clear, when the CPU
+        // carries on after HALT without servicing it
+        // TODO: Service it here once interrupts are dispatched
+        if self.cpu.halted && self.memory.interrupts.pending().is_some() {
+            self.cpu.halted = false;
+        }
+        // A press on a selected joypad line ends STOP, whatever IE holds
+        if self.cpu.stopped && self.memory.joypad.selected_line_low() {
+            self.cpu.stopped = false;
+        }
+        let stalled = self.memory.speed.stall(4);
+        // An unused opcode locks the CPU up until reset
+        let idle = stalled
+            || self.logo_scroll.is_some()
+            || self.cpu.halted
+            || self.cpu.stopped
+            || self.cpu.locked;
+        if !idle {
+            // Log CPU state before execution (gameboy-doctor format)
+            self.log();
+
+            if let Some(ref mut coverage) = self.coverage {
+                record_coverage(coverage, &self.cpu, &self.memory);
+            }
+            if let Some(ref mut heat_map) = self.memory.heat_map {
+                heat_map.record_execute(self.cpu.pc);
+            }
+            if let (Some(audit), Some(cart)) = (&self.memory.bank_audit, &self.memory.cartridge) {
+                audit.record_execute(self.cpu.pc, cart.banks().0);
+            }
+            let (cycle, frame) = (self.cycle_count(), self.frames);
+            if let Some(ref mut tracker) = self.memory.write_tracker {
+                tracker.begin_instruction(self.cpu.pc, cycle, frame);
+            }
+        }
+
+        // Execute instruction
+        let cycles = if idle {
+            self.idle_cycles(stalled)
+        } else {
+            self.cpu.execute(&mut self.memory)
+        };
+        let cycle = self.cycle_count();
+        if let (Some(log), Some(cart)) = (&mut self.event_log, &self.memory.cartridge) {
+            let (rom, ram) = cart.banks();
+            log.record_banks(cycle, rom, ram);
+        }
+        let now = cycle + u64::from(cycles);
+        if let Some(ref mut tracker) = self.memory.write_tracker {
+            // Cheats and debuggers write between instructions
+            tracker.end_instruction(now, self.frames);
+        }
+        // DIV was reset by STOP and stays stopped until it ends or through a
+        // speed switch
+        let frozen = stalled || self.cpu.stopped;
+        // TIMA goes up at most once per `tick`, and at most every 16 cycles.
+        // A word access has already run it between its two bytes.
+        let ahead = self.memory.timer_ahead.min(cycles);
+        self.memory.timer_ahead -= ahead;
+        let mut timer_interrupt = std::mem::take(&mut self.memory.timer_overflowed);
+        if !frozen {
+            for step in tick::in_steps(u32::from(cycles - ahead), 16) {
+                timer_interrupt |= self.memory.timer.tick(step);
+            }
+        }
+        self.trace_clock(Component::Timer, now);
+        if timer_interrupt {
+            self.request_interrupt(Interrupt::Timer);
+            // TODO: Dispatch pending interrupts
+        }
+        if self.memory.serial.tick(cycles) {
+            self.request_interrupt(Interrupt::Serial);
+        }
+        self.trace_clock(Component::Serial, now);
+        // One M-cycle at a time, so a halted skip sounds the same
+        for step in tick::in_steps(u32::from(cycles), 4) {
+            self.memory.apu.tick(step);
+        }
+        self.trace_clock(Component::Apu, now);
+
+        // The PPU lives in memory but draws from the rest of it
+        let mut ppu = std::mem::take(&mut self.memory.ppu);
+        let events = ppu.tick(u32::from(cycles), &self.memory, self.layers);
+        self.memory.ppu = ppu;
+        if events.lcd_off {
+            self.frame_buffer.fill(0);
+        }
+        if let Some(ly) = events.line_finished {
+            self.end_scanline(ly);
+        }
+        if events.frame_started {
+            self.poll_input();
+        }
+        if events.vblank {
+            self.request_interrupt(Interrupt::VBlank);
+        }
+        if events.stat {
+            self.request_interrupt(Interrupt::LcdStat);
+        }
+        // TODO: Start frames when the PPU wraps to line 0, so frames stay
+        // aligned with the picture after the LCD is switched on mid-frame
+        self.frame_cycles += u32::from(cycles);
+        if self.frame_cycles >= CYCLES_PER_FRAME {
+            self.frame_cycles -= CYCLES_PER_FRAME;
+            self.start_frame();
+        }
+        self.update_lcd_mode();
+        self.trace_clock(Component::Ppu, self.cycle_count());
+
+        if let Some(ref mut trace) = self.clock_trace {
+            trace.advance_cpu(now);
+            // A failure is kept in the trace and stops emulation
+            let _ = trace.check();
+        }
+        cycles
+    }
+
+    /// Cycles an idle `step` lets pass: one M-cycle, or while halted every
+    /// whole M-cycle before the timer, serial port, PPU or APU next does
+    /// something or the frame ends, up to `MAX_HALT_SKIP`. The step that
+    /// reaches the event is then the same M-cycle it would be stepping 4
+    /// cycles at a time, so cycle counts and interrupt timing are unchanged.
+    fn idle_cycles(&self, stalled: bool) -> u8 {
+        if !self.cpu.halted || stalled || self.logo_scroll.is_some() {
+            return 4;
+        }
+        // The PPU only notices LCDC turning it on at its next tick
+        let lcd_on = ppu::Lcdc(self.memory.peek(0xFF40)).lcd_enabled();
+        let next = [
+            self.memory.timer.next_event(),
+            self.memory.serial.next_event(),
+            self.memory.ppu.next_event().or(lcd_on.then_some(1)),
+            self.memory.apu.next_event(),
+            Some(CYCLES_PER_FRAME - self.frame_cycles),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(4);
+        let skip = (next.saturating_sub(1) / 4 * 4).clamp(4, MAX_HALT_SKIP);
+        u8::try_from(skip).unwrap_or(4)
+    }
+
+    fn trace_clock(&mut self, component: Component, cycle: u64) {
+        if let Some(ref mut trace) = self.clock_trace {
+            trace.advance(component, cycle);
+        }
+    }
+
+    /// Start recording which ROM bytes are executed (see `disasm::listing`).
+    /// Does nothing if no cartridge is loaded.
+    pub fn enable_coverage(&mut self) {
+        let rom_size = self.memory.cartridge.as_ref().map(|cart| cart.rom().len());
+        self.coverage = rom_size.map(Coverage::new);
+    }
+
+    /// Execution coverage recorded since `enable_coverage`
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Turn the memory access heat map on or off
+    pub fn set_heat_map(&mut self, enabled: bool) {
+        self.memory.heat_map = enabled.then(HeatMap::new);
+    }
+
+    /// Recent reads, writes and executes per address, faded each frame
+    pub fn heat_map(&self) -> Option<&HeatMap> {
+        self.memory.heat_map.as_ref()
+    }
+
+    /// Start (or with `false`, stop) counting how each cartridge bank is
+    /// used. Does nothing if no cartridge is loaded.
+    pub fn set_bank_stats(&mut self, enabled: bool) {
+        let banks = self
+            .memory
+            .cartridge
+            .as_ref()
+            .map(cartridge::Cartridge::bank_counts);
+        self.memory.bank_stats = banks
+            .filter(|_| enabled)
+            .map(|(rom, ram)| BankStats::new(rom, ram));
+    }
+
+    /// Bank usage counted since `set_bank_stats`
+    pub fn bank_stats(&self) -> Option<&BankStats> {
+        self.memory.bank_stats.as_ref()
+    }
+
+    /// Start (or with `false`, stop) auditing ROM bank accesses against the
+    /// header's ROM size and the file. Does nothing if no cartridge is
+    /// loaded.
+    pub fn set_bank_audit(&mut self, enabled: bool) {
+        self.memory.bank_audit = self
+            .memory
+            .cartridge
+            .as_ref()
+            .filter(|_| enabled)
+            .map(|cart| BankAudit::new(cart.bank_counts().0, cart.rom().len()));
+    }
+
+    /// ROM bank accesses audited since `set_bank_audit`
+    pub fn bank_audit(&self) -> Option<&BankAudit> {
+        self.memory.bank_audit.as_ref()
+    }
+
+    /// Start (or with `false`, stop) recording the last writer of each
+    /// address, for `last_write`
+    pub fn set_write_tracking(&mut self, enabled: bool) {
+        self.memory.write_tracker = enabled.then(WriteTracker::new);
+    }
+
+    /// The last write to `address` since `set_write_tracking`: the value,
+    /// the PC of the instruction that wrote it, and when. `Err` if tracking
+    /// is off, `Ok(None)` if nothing has written the address since.
+    pub fn last_write(&self, address: u16) -> Result<Option<LastWrite>, String> {
+        self.memory
+            .write_tracker
+            .as_ref()
+            .map(|tracker| tracker.last_write(address))
+            .ok_or_else(|| "Write tracking is off".to_string())
+    }
+
+    /// Plug in (or with `None`, unplug) the link cable
+    pub fn connect_serial(&mut self, device: Option<Box<dyn SerialDevice>>) {
+        self.memory.serial.connect(device);
+    }
+
+    /// Start recording a timeline of hardware events, keeping at most
+    /// `capacity` of them
+    pub fn enable_event_log(&mut self, capacity: usize) {
+        self.event_log = Some(EventLog::new(capacity));
+    }
+
+    pub fn event_log(&self) -> Option<&EventLog> {
+        self.event_log.as_ref()
+    }
+
+    /// Debug mode: track when each component was last advanced and stop
+    /// emulation (see `stopped`) if one lags the CPU by more than `window`
+    /// cycles
+    pub fn enable_clock_trace(&mut self, window: u64) {
+        self.clock_trace = Some(ClockTrace::new(window));
+    }
+
+    pub fn clock_trace(&self) -> Option<&ClockTrace> {
+        self.clock_trace.as_ref()
+    }
+
+    /// Hand every completed frame to `sink` as well as any already attached
+    pub fn add_frame_sink(&mut self, sink: Box<dyn FrameSink>) {
+        self.frame_sinks.push(sink);
+    }
+
+    /// Detach all frame sinks
+    pub fn clear_frame_sinks(&mut self) {
+        self.frame_sinks.clear();
+    }
+
+    /// Choose how edge-case behavior (echo RAM, illegal opcodes, ...) is reported
+    pub fn set_quirk_policy(&mut self, policy: QuirkPolicy) {
+        self.memory.quirks.policy = policy;
+    }
+
+    /// The quirk that stopped emulation, if any. Taking it lets emulation
+    /// continue.
+    pub fn take_quirk_trap(&mut self) -> Option<QuirkEvent> {
+        self.memory.quirks.take_trap()
+    }
+
+    /// Whether the CPU can never run again on its own: halted with no
+    /// interrupt enabled to wake it, in STOP with no joypad line selected
+    /// for a press to pull low, or locked up by an unused opcode
+    pub fn asleep(&self) -> bool {
+        (self.cpu.halted && !self.memory.interrupts.any_enabled())
+            || (self.cpu.stopped && !self.memory.joypad.any_line_selected())
+            || self.cpu.locked
+    }
+
+    /// Whether the CPU sleeping for good, a quirk trap or a failed clock
+    /// trace has stopped emulation
+    pub fn stopped(&self) -> bool {
+        self.asleep()
+            || self.memory.quirks.trapped()
+            || self
+                .clock_trace
+                .as_ref()
+                .is_some_and(|trace| trace.failure().is_some())
+    }
+
+    /// Install (or with `None`, remove) the end-of-scanline callback. Lets tools
+    /// build scanline visualizers and frontends apply per-line effects without
+    /// copying whole frames.
+    pub fn set_scanline_callback(&mut self, callback: Option<ScanlineCallback>) {
+        self.scanline_callback = callback;
+    }
+
+    /// Produce audio at the host device's rate, in samples per second
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.memory.apu.set_sample_rate(rate);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.memory.apu.sample_rate()
+    }
+
+    /// Master volume and mute, which the APU mixes at
+    pub fn set_volume(&mut self, volume: Volume) {
+        self.memory.apu.set_volume(volume);
+    }
+
+    pub fn volume(&self) -> Volume {
+        self.memory.apu.volume()
+    }
+
+    /// For frontends to mute and report fast-forwarding through
+    pub fn volume_mut(&mut self) -> &mut Volume {
+        self.memory.apu.volume_mut()
+    }
+
+    /// Which sound channels are heard (see `apu::Channels`)
+    pub fn channels(&self) -> apu::Channels {
+        self.memory.apu.channels()
+    }
+
+    /// Mute or unmute one sound channel, as the `--mute` option and the
+    /// channel hotkeys do
+    pub fn set_channel_enabled(&mut self, channel: apu::Channel, enabled: bool) {
+        self.memory.apu.set_channel_enabled(channel, enabled);
+    }
+
+    /// Flip one sound channel, returning whether it is now heard
+    pub fn toggle_channel(&mut self, channel: apu::Channel) -> bool {
+        let mut channels = self.memory.apu.channels();
+        let heard = channels.toggle(channel);
+        self.memory.apu.set_channels(channels);
+        heard
+    }
+
+    /// Hear only `channel`, or every channel again for `None`
+    pub fn solo_channel(&mut self, channel: Option<apu::Channel>) {
+        let mut channels = self.memory.apu.channels();
+        channels.solo(channel);
+        self.memory.apu.set_channels(channels);
+    }
+
+    /// Where the cartridge clock gets the time: the host's clock by default,
+    /// or an `rtc::EmulatedClock` for runs that must replay identically
+    pub fn set_clock_source(&mut self, clock: Box<dyn ClockSource>) {
+        self.clock = clock;
+    }
+
+    /// The time the cartridge clock reads now, in seconds since the UNIX
+    /// epoch
+    pub fn clock_time(&self) -> u64 {
+        self.clock.now(self.cycle_count())
+    }
+
+    /// Choose the colors used when converting DMG shades to RGBA
+    pub fn set_palette(&mut self, palette: DmgPalette) {
+        self.palette = palette;
+    }
+
+    pub fn palette(&self) -> DmgPalette {
+        self.palette
+    }
+
+    /// Which layers the PPU draws (see `ppu::Layers`)
+    pub fn layers(&self) -> ppu::Layers {
+        self.layers
+    }
+
+    /// Show or hide one layer, from the next line drawn
+    pub fn set_layer_visible(&mut self, layer: ppu::Layer, visible: bool) {
+        self.layers.set_visible(layer, visible);
+    }
+
+    /// Flip one layer, as the layer hotkeys do, returning whether it is now
+    /// visible
+    pub fn toggle_layer(&mut self, layer: ppu::Layer) -> bool {
+        self.layers.toggle(layer)
+    }
+
+    /// The frame buffer, borrowed: `Frame::WIDTH` x `Frame::HEIGHT` pixels
+    /// row by row from the top left, each a 2-bit shade plus the layer it
+    /// came from (see `palette::pixel`). For harnesses that check pixels
+    /// every frame without a frontend or a copy; `frame` wraps a copy with
+    /// the palette. Mid-frame, lines from LY down still hold the last frame.
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.frame_buffer
+    }
+
+    /// Current frame with the selected palette
+    pub fn frame(&self) -> Frame {
+        Frame::new(self.frame_buffer.clone(), self.palette)
+    }
+
+    /// Current frame converted to RGBA8 with the selected palette
+    pub fn frame_rgba(&self) -> Vec<u8> {
+        self.palette.to_rgba(&self.frame_buffer)
+    }
+
+    /// The frame as far as the PPU has drawn it, for a debugger paused
+    /// mid-frame: the lines finished this frame and the current line up to
+    /// the pixel being drawn, over the previous frame (see
+    /// `Frame::partial`). Before the first pixel of a frame, during V-Blank
+    /// and with the LCD off, the last finished frame.
+    pub fn partial_frame(&self) -> Frame {
+        let ppu = &self.memory.ppu;
+        let ly = usize::from(ppu.ly());
+        let drawn = match ppu.mode() {
+            0 | 3 => ppu.line().len(),
+            _ => 0,
+        };
+        let lcd_on = ppu::Lcdc(self.memory.peek(0xFF40)).lcd_enabled();
+        if ly >= SCREEN_HEIGHT || (ly == 0 && drawn == 0) || !lcd_on {
+            return self.frame();
+        }
+        let mut pixels = self.frame_buffer.clone();
+        let start = ly * SCREEN_WIDTH;
+        pixels[start..start + drawn].copy_from_slice(&ppu.line()[..drawn]);
+        let progress = frame::Progress { ly, drawn };
+        Frame::partial(pixels, self.palette, progress)
+    }
+
+    /// Hand `partial_frame` to the frame sinks, as the frame in progress, so
+    /// a paused frontend shows where the PPU has got to
+    pub fn present_partial_frame(&mut self) {
+        let frame = self.frames + 1;
+        if self.frame_sinks.iter().any(|sink| sink.wants_frame(frame)) {
+            let image = self.partial_frame();
+            self.present_to_sinks(frame, &image);
+        }
+    }
+
+    /// Put the built-in splash screen in the frame buffer and hand it to the
+    /// frame sinks, for frontends started without a ROM. Loading a ROM and
+    /// resetting clears it.
+    pub fn show_splash(&mut self) {
+        self.frame_buffer = splash_frame();
+        self.present_frame();
+    }
+
+    /// Track whether the PPU is drawing, which locks VRAM
+    fn update_lcd_mode(&mut self) {
+        let mode = self.memory.ppu.mode();
+        self.memory.lcd_mode3 = mode == 3;
+        let cycle = self.cycle_count();
+        if let Some(ref mut log) = self.event_log {
+            log.record_mode(cycle, mode, self.memory.ppu.ly());
+        }
+    }
+
+    /// CPU cycles since power on
+    pub fn cycle_count(&self) -> u64 {
+        self.frames * u64::from(CYCLES_PER_FRAME) + u64::from(self.frame_cycles)
+    }
+
+    /// Copy a finished visible line into the frame buffer and hand it to the
+    /// scanline callback
+    fn end_scanline(&mut self, ly: u8) {
+        let start = usize::from(ly) * SCREEN_WIDTH;
+        let pixels = &mut self.frame_buffer[start..start + SCREEN_WIDTH];
+        for (out, &pixel) in pixels.iter_mut().zip(self.memory.ppu.line()) {
+            *out = pixel;
+        }
+        if let Some(ref mut callback) = self.scanline_callback {
+            callback(ly, pixels);
+        }
+    }
+
+    /// Update the buttons the host holds, requesting the joypad interrupt on
+    /// a new press. Turbo and macro playback are applied on top.
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.input.set_host(buttons);
+        self.apply_input();
+    }
+
+    /// Turbo buttons and input macros. Changes take effect at the next
+    /// `set_buttons` or frame start.
+    pub fn input_mut(&mut self) -> &mut InputLayer {
+        &mut self.input
+    }
+
+    pub fn input(&self) -> &InputLayer {
+        &self.input
+    }
+
+    fn apply_input(&mut self) {
+        if self.memory.joypad.set_buttons(self.input.effective()) {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+    }
+
+    /// Choose when input is sampled (see `InputPolling`)
+    pub fn set_input_polling(&mut self, polling: InputPolling) {
+        self.input_polling = polling;
+    }
+
+    /// Snapshot the full machine state (everything except the ROM), along
+    /// with metadata describing it
+    pub fn save_state(&self) -> SaveState {
+        let mut state = StateWriter::new();
+        self.state_metadata().write(&mut state);
+        self.write_machine_state(&mut state);
+        // Lines already drawn this frame, so a state taken mid-frame resumes
+        // with the same picture. The line in progress needs nothing more: it
+        // is drawn whole when the restored PPU reaches its end.
+        state.write_bytes(&self.frame_buffer);
+        state.finish()
+    }
+
+    /// Restore a state produced by `save_state` for the same ROM, checking
+    /// it first as `set_state_check` chose. A state that fails to load, such
+    /// as a truncated one, or is refused leaves the machine as it was.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), String> {
+        let previous = self.save_state();
+        let mut loaded = self.restore_state(state);
+        if self.state_check != StateCheck::Off {
+            loaded = loaded.and_then(|()| self.check_loaded_state());
+        }
+        if loaded.is_err() {
+            self.restore_state(&previous)?;
+        }
+        loaded
+    }
+
+    /// Choose whether `load_state` checks states for broken invariants
+    /// (see `state_problems`), and what it does about them
+    pub fn set_state_check(&mut self, check: StateCheck) {
+        self.state_check = check;
+    }
+
+    /// Invariants the machine breaks: PC in memory code can't run from, SP
+    /// outside RAM, a PPU position `tick` could never reach, or banks past
+    /// the end of the cartridge. A game can get into some of these on its
+    /// own, but a state caught there is far more likely corrupt.
+    #[allow(clippy::similar_names)]
+    pub fn state_problems(&self) -> Vec<StateProblem> {
+        let mut problems = Vec::new();
+        let cartridge = self.memory.cartridge.as_ref();
+        let has_ram = cartridge.is_some_and(|cart| cart.header().ram_size > 0);
+
+        let pc = self.cpu.pc;
+        if matches!(pc, 0xFEA0..=0xFF7F) || (matches!(pc, 0xA000..=0xBFFF) && !has_ram) {
+            problems.push(StateProblem::UnmappedPc(pc));
+        }
+        // SP sits one past the top of an empty stack, so the top of each
+        // RAM counts
+        let sp = self.cpu.sp;
+        if !(matches!(sp, 0xC000..=0xE000 | 0xFF81..=0xFFFF)
+            || (matches!(sp, 0xA001..=0xC000) && has_ram))
+        {
+            problems.push(StateProblem::StrayStack(sp));
+        }
+
+        let ppu = &self.memory.ppu;
+        if !ppu.position_is_valid() {
+            problems.push(StateProblem::PpuPosition {
+                ly: ppu.ly(),
+                dot: ppu.dot(),
+                mode: ppu.mode(),
+            });
+        }
+
+        if let Some(cart) = cartridge {
+            let (rom_bank, ram_bank) = cart.banks();
+            let (rom_banks, ram_banks) = cart.bank_counts();
+            if rom_bank >= rom_banks {
+                problems.push(StateProblem::RomBank {
+                    bank: rom_bank,
+                    banks: rom_banks,
+                });
+            }
+            if ram_banks > 0 && ram_bank >= ram_banks {
+                problems.push(StateProblem::RamBank {
+                    bank: ram_bank,
+                    banks: ram_banks,
+                });
+            }
+        }
+        problems
+    }
+
+    /// Refuse or repair the state just restored, as `state_check` says
+    fn check_loaded_state(&mut self) -> Result<(), String> {
+        let problems = self.state_problems();
+        let refused: Vec<String> = problems
+            .iter()
+            .filter(|problem| self.state_check == StateCheck::Refuse || !problem.repairable())
+            .map(ToString::to_string)
+            .collect();
+        if !refused.is_empty() {
+            return Err(format!(
+                "Save state failed its sanity check: {}",
+                refused.join("; ")
+            ));
+        }
+
+        for problem in problems {
+            match problem {
+                StateProblem::PpuPosition { .. } => {
+                    self.memory.ppu.restart_line();
+                    self.memory.lcd_mode3 = false;
+                }
+                StateProblem::RomBank { .. } | StateProblem::RamBank { .. } => {
+                    if let Some(cart) = self.memory.cartridge.as_mut() {
+                        cart.wrap_banks();
+                    }
+                }
+                StateProblem::UnmappedPc(_) | StateProblem::StrayStack(_) => {}
+            }
+            eprintln!("Warning: repaired save state: {problem}");
+        }
+        Ok(())
+    }
+
+    fn restore_state(&mut self, state: &SaveState) -> Result<(), String> {
+        let mut reader = state.reader();
+        let metadata = StateMetadata::read(&mut reader)?;
+        if let (Some(saved), Some(loaded)) = (metadata.rom_hash, self.rom_hash())
+            && saved != loaded
+        {
+            return Err(format!(
+                "Save state is for a different ROM ({saved:08X}, loaded {loaded:08X})"
+            ));
+        }
+        self.cpu.load_state(&mut reader)?;
+        self.memory.load_state(&mut reader)?;
+        self.frame_cycles = reader.read_u32()?;
+        self.frames = reader.read_u64()?;
+        self.logo_scroll = LogoScroll::load_state(&mut reader)?;
+        reader.read_into(&mut self.frame_buffer)?;
+        self.memory.lcd_mode3 = self.memory.ppu.mode() == 3;
+        Ok(())
+    }
+
+    /// CRC-32 of the emulated machine, leaving out the metadata (wall-clock
+    /// timestamp, thumbnail) a save state also carries, so two runs that
+    /// agree hash the same
+    pub fn state_hash(&self) -> u32 {
+        let mut state = StateWriter::new();
+        self.write_machine_state(&mut state);
+        cartridge::crc32(state.finish().as_bytes())
+    }
+
+    fn write_machine_state(&self, state: &mut StateWriter) {
+        self.cpu.save_state(state);
+        self.memory.save_state(state);
+        state.write_u32(self.frame_cycles);
+        state.write_u64(self.frames);
+        LogoScroll::save_state(self.logo_scroll, state);
+    }
+
+    fn state_metadata(&self) -> StateMetadata {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        StateMetadata {
+            title: self
+                .cartridge_header()
+                .map_or_else(String::new, |header| header.title.clone()),
+            rom_hash: self.rom_hash(),
+            timestamp,
+            frames: self.frames,
+            thumbnail: self.thumbnail(),
+        }
+    }
+
+    /// Current frame at half size (every other pixel of every other line), as RGBA8
+    pub fn thumbnail(&self) -> Vec<u8> {
+        let pixels: Vec<u8> = self
+            .frame_buffer
+            .chunks_exact(SCREEN_WIDTH)
+            .step_by(SCREEN_HEIGHT / THUMBNAIL_HEIGHT)
+            .flat_map(|line| line.iter().step_by(SCREEN_WIDTH / THUMBNAIL_WIDTH).copied())
+            .collect();
+        self.palette.to_rgba(&pixels)
+    }
+
+    /// Skip past the boot sequence and the first `frames` frames of the game's
+    /// intro using a cached save state at `cache`.
+    ///
+    /// Call after `power_on`. If the cache is missing or unreadable the frames
+    /// are emulated (with no input held, so the result is deterministic) and
+    /// the resulting state is written to `cache`. Returns true on a cache hit.
+    pub fn fast_boot(&mut self, cache: &Path, frames: u32) -> std::io::Result<bool> {
+        if let Ok(bytes) = fs::read(cache) {
+            let restored = SaveState::from_bytes(bytes).and_then(|state| self.load_state(&state));
+            if restored.is_ok() {
+                return Ok(true);
+            }
+        }
+
+        for _ in 0..frames {
+            self.finish_frame();
+        }
+        if let Some(dir) = cache.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(cache, self.save_state().as_bytes())?;
+        Ok(false)
+    }
+
+    /// Number of frames started since power on
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Run until the current frame ends (or the CPU halts or a quirk traps)
+    pub fn finish_frame(&mut self) {
+        let frame = self.frames;
+        while self.frames == frame && !self.stopped() {
+            self.step();
+        }
+    }
+
+    /// Run until the PPU next enters V-Blank, when the picture is complete,
+    /// for frontends that pace themselves by frames. Unlike `finish_frame`,
+    /// which ends at the frame counter's boundary, this follows the PPU.
+    /// With the LCD off, stops after a frame's worth of cycles.
+    pub fn run_frame(&mut self) -> FrameRun {
+        let mut cycles = 0;
+        let mut vblank = false;
+        while !vblank && cycles < u64::from(CYCLES_PER_FRAME) && !self.stopped() {
+            let ly = self.memory.ppu.ly();
+            cycles += u64::from(self.step());
+            vblank = ly != ppu::VBLANK_LINE && self.memory.ppu.ly() == ppu::VBLANK_LINE;
+        }
+        FrameRun {
+            frame: self.frame(),
+            cycles,
+            vblank,
+        }
+    }
+
+    /// Run at full speed until `frame` frames have completed, without
+    /// presenting any of them, then present the last one to the frame sinks.
+    /// For reproducing bug reports at an exact frame.
+    ///
+    /// Returns the frame count reached, which falls short of `frame` if the
+    /// CPU halts or a quirk traps first. Seeking backwards is an error.
+    pub fn seek(&mut self, frame: u64) -> Result<u64, String> {
+        self.seek_with(frame, |_| {})
+    }
+
+    /// `seek`, calling `each_frame` after every frame run on the way, e.g. to
+    /// report progress on a long seek
+    pub fn seek_with(
+        &mut self,
+        frame: u64,
+        mut each_frame: impl FnMut(&GameBoy),
+    ) -> Result<u64, String> {
+        if frame < self.frames {
+            return Err(format!(
+                "Cannot seek back to frame {frame} from frame {}",
+                self.frames
+            ));
+        }
+        let sinks = std::mem::take(&mut self.frame_sinks);
+        while self.frames < frame && !self.stopped() {
+            self.finish_frame();
+            each_frame(self);
+        }
+        self.frame_sinks = sinks;
+        if self.frames == frame && frame > 0 {
+            self.present_frame();
+        }
+        Ok(self.frames)
+    }
+
+    /// Advance one host frame with run-ahead.
+    ///
+    /// The real frame is emulated with `buttons` applied, then `frames` more
+    /// frames are run speculatively with the same input and `present` is called
+    /// on that future state, before rolling back with a save state. Games that
+    /// react to input a frame or two late therefore appear to react instantly.
+    /// With `frames == 0` this is a plain frame advance. The frame sinks,
+    /// autosave and autosplitter only see the real frames. Fails only if the
+    /// rollback state, produced by this same instance, does not load.
+    pub fn run_ahead_frame(
+        &mut self,
+        buttons: Buttons,
+        frames: u8,
+        present: impl FnOnce(&GameBoy),
+    ) -> Result<(), String> {
+        self.set_buttons(buttons);
+        self.finish_frame();
+
+        if frames == 0 {
+            present(self);
+            return Ok(());
+        }
+
+        let state = self.save_state();
+        let input = self.input.clone();
+        // The speculative frames are thrown away, so nothing outside sees them
+        let sinks = std::mem::take(&mut self.frame_sinks);
+        let autosave = self.autosave.take();
+        let autosplit = self.autosplit.take();
+        for _ in 0..frames {
+            self.finish_frame();
+        }
+        present(self);
+        let restored = self.load_state(&state);
+        self.memory.apu.take_samples();
+        self.frame_sinks = sinks;
+        self.autosave = autosave;
+        self.autosplit = autosplit;
+        self.input = input;
+        restored
+    }
+
+    /// Called when a new frame begins
+    fn start_frame(&mut self) {
+        self.frames += 1;
+        if let Some(ref mut scroll) = self.logo_scroll
+            && !scroll.advance(&mut self.memory)
+        {
+            self.logo_scroll = None;
+        }
+        self.cheats.apply_ram(&mut self.memory);
+        self.present_frame();
+        self.present_audio();
+        if let Some(ref mut heat_map) = self.memory.heat_map {
+            heat_map.decay();
+        }
+        let ram_writes = self.ram_writes();
+        let frame = self.frames;
+        let save_due = self
+            .autosave
+            .as_mut()
+            .is_some_and(|(autosave, _)| autosave.poll(frame, ram_writes));
+        if save_due
+            && let (Some(save), Some((_, callback))) = (self.battery_save(), &mut self.autosave)
+        {
+            callback(save);
+        }
+        if let Some((ref mut splitter, ref mut callback)) = self.autosplit {
+            for event in splitter.poll(&self.memory, frame) {
+                callback(&event);
+            }
+        }
+        if let Some(ref mut throttle) = self.throttle {
+            throttle.pause();
+        }
+        self.input.advance_frame();
+        if let InputPolling::HostFrame = self.input_polling {
+            self.apply_input();
+        }
+    }
+
+    /// Pull input from the source of `InputPolling::BeforeOamScan`
+    fn poll_input(&mut self) {
+        if let InputPolling::BeforeOamScan(ref mut source) = self.input_polling {
+            let buttons = source();
+            self.set_buttons(buttons);
+        }
+    }
+
+    /// Hand the frame that just finished to the sinks that want it
+    fn present_frame(&mut self) {
+        let frame = self.frames;
+        if self.frame_sinks.iter().any(|sink| sink.wants_frame(frame)) {
+            let image = self.frame();
+            self.present_to_sinks(frame, &image);
+        }
+    }
+
+    /// Hand the samples the APU produced over the last frame to the sinks.
+    /// A sink that fails is reported once and detached.
+    fn present_audio(&mut self) {
+        let samples = self.memory.apu.take_samples();
+        if samples.is_empty() {
+            return;
+        }
+        self.frame_sinks.retain_mut(|sink| {
+            let result = sink.audio(&samples);
+            if let Err(ref e) = result {
+                eprintln!("Detaching frame sink: {e}");
+            }
+            result.is_ok()
+        });
+    }
+
+    /// A sink that fails is reported once and detached
+    fn present_to_sinks(&mut self, frame: u64, image: &Frame) {
+        self.frame_sinks.retain_mut(|sink| {
+            if !sink.wants_frame(frame) {
+                return true;
+            }
+            let result = sink.present(frame, image);
+            if let Err(ref e) = result {
+                eprintln!("Detaching frame sink: {e}");
+            }
+            result.is_ok()
+        });
+    }
+
+    /// Set the interrupt's bit in IF
+    fn request_interrupt(&mut self, interrupt: Interrupt) {
+        let cycle = self.cycle_count();
+        if let Some(ref mut log) = self.event_log {
+            log.record(cycle, Event::InterruptRequested(interrupt));
+        }
+        self.memory.interrupts.request(interrupt);
+    }
+
+    /// Run whole instructions until at least `cycles` cycles have elapsed or
+    /// the emulator stops.
+    ///
+    /// This is a loop over `step`, so interrupts and component events are
+    /// still checked after every instruction; only a halted CPU skips ahead
+    /// to the next event. There is no scheduler batching the rest.
+    ///
+    /// Returns the number of cycles actually executed, which may overshoot the
+    /// budget by up to one instruction, or one step of HALT. Embedders driving
+    /// the core at frame granularity should carry the overshoot into their
+    /// next call.
+    pub fn run_cycles(&mut self, cycles: u64) -> u64 {
+        let mut elapsed = 0;
+        while elapsed < cycles && !self.stopped() {
+            elapsed += u64::from(self.step());
+        }
+        elapsed
+    }
+
+    /// Run the emulator for a number of instructions
+    pub fn run(&mut self, num_instructions: usize) {
+        for _ in 0..num_instructions {
+            if self.stopped() {
+                break;
+            }
+            self.step();
+        }
+    }
+
+    fn log(&mut self) {
+        if self.log_file.is_some() {
+            let line = format!("{}\n", self.trace_line());
+            if let Some(ref mut log) = self.log_file {
+                let _ = log.write_all(line.as_bytes());
+            }
+        }
+    }
+
+    /// CPU state before the next instruction, as one line of a gameboy-doctor
+    /// log (without the newline)
+    #[allow(clippy::many_single_char_names)]
+    pub fn trace_line(&self) -> String {
+        let a = self.cpu.registers.a;
+        let f = self.cpu.registers.f.to_u8();
+        let b = self.cpu.registers.b;
+        let c = self.cpu.registers.c;
+        let d = self.cpu.registers.d;
+        let e = self.cpu.registers.e;
+        let h = self.cpu.registers.h;
+        let l = self.cpu.registers.l;
+        let sp = self.cpu.sp;
+        let pc = self.cpu.pc;
+
+        // Read next 4 bytes at PC for PCMEM
+        let pcmem0 = self.memory.peek(pc);
+        let pcmem1 = self.memory.peek(pc.wrapping_add(1));
+        let pcmem2 = self.memory.peek(pc.wrapping_add(2));
+        let pcmem3 = self.memory.peek(pc.wrapping_add(3));
+
+        format!(
+            "A:{a:02X} F:{f:02X} B:{b:02X} C:{c:02X} D:{d:02X} E:{e:02X} H:{h:02X} L:{l:02X} SP:{sp:04X} PC:{pc:04X} PCMEM:{pcmem0:02X},{pcmem1:02X},{pcmem2:02X},{pcmem3:02X}",
+        )
+    }
+}
+
+impl Default for GameBoy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Mark the instruction at PC as executed if it is in cartridge ROM
+fn record_coverage(coverage: &mut Coverage, cpu: &cpu::Cpu, memory: &memory::Memory) {
+    let Some(offset) = memory
+        .cartridge
+        .as_ref()
+        .and_then(|cart| cart.rom_offset(cpu.pc))
+    else {
+        return;
+    };
+    let opcode = memory.peek(cpu.pc);
+    let next = memory.peek(cpu.pc.wrapping_add(1));
+    coverage.mark(offset, cpu::opcodes::lookup(opcode, next).length);
+}
+
+#[cfg(test)]
+// The instruction tests predate the pedantic lints and are kept as written
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::unnecessary_cast,
+    clippy::unreadable_literal
+)]
+mod tests {
+    use super::*;
+    use crate::joypad::Button;
+    use crate::quirks::{Quirk, QuirkAction};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn run_cycles_executes_whole_instructions() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+
+        // NOPs are 4 cycles each, so a 10 cycle budget runs 3 of them
+        let elapsed = gb.run_cycles(10);
+        assert_eq!(elapsed, 12);
+        assert_eq!(gb.cpu.pc, 0x0003);
+    }
+
+    #[test]
+    fn word_fetches_run_the_timer_once() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0xC000;
+        gb.memory.data[0xC000..0xE000].fill(0x01); // LD BC,0x0101
+        gb.memory.write_byte(0xFF07, 0x05); // TIMA every 16 cycles
+
+        // 64 instructions of 12 cycles, one of them run between the bytes
+        // of each operand
+        gb.run(64);
+        assert_eq!(gb.cycle_count(), 768);
+        assert_eq!(gb.memory.read_byte(0xFF04), 3);
+        assert_eq!(gb.memory.read_byte(0xFF05), 48);
+        assert_eq!(gb.memory.timer_ahead, 0);
+    }
+
+    #[test]
+    fn run_cycles_stops_on_halt_with_nothing_enabled() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.memory.data[0x0001] = 0x76; // HALT
+
+        let elapsed = gb.run_cycles(1000);
+        assert_eq!(elapsed, 8, "NOP + HALT should run before stopping");
+        assert!(gb.cpu.halted);
+        assert!(gb.asleep());
+    }
+
+    #[test]
+    fn enabled_interrupt_ends_halt_without_ime() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0xC000;
+        gb.memory.data[0xC000] = 0x76; // HALT
+        gb.memory.data[0xC001] = 0x3C; // INC A
+        gb.memory.write_byte(0xFFFF, Interrupt::Timer.mask());
+        gb.memory.write_byte(0xFF0F, 0x00);
+        let a = gb.cpu.registers.a;
+
+        gb.run_cycles(1000);
+        assert!(gb.cpu.halted && !gb.stopped(), "Waits for the timer");
+        assert_eq!(gb.cpu.pc, 0xC001);
+        gb.request_interrupt(Interrupt::Serial);
+        gb.step();
+        assert!(gb.cpu.halted, "Serial is not enabled");
+
+        gb.request_interrupt(Interrupt::Timer);
+        gb.step();
+        assert!(!gb.cpu.halted);
+        assert_eq!(gb.cpu.registers.a, a.wrapping_add(1));
+        assert!(!gb.cpu.interrupts_enabled);
+        assert!(
+            gb.memory.interrupts.is_requested(Interrupt::Timer),
+            "Not serviced"
+        );
+    }
+
+    #[test]
+    fn halt_skips_ahead_without_moving_any_event() {
+        // Halted, and running NOPs, which step one M-cycle at a time
+        let run = |opcode: u8| {
+            let mut gb = GameBoy::new();
+            gb.cpu.pc = 0xC000;
+            gb.memory.data[0xC000..0xE000].fill(opcode);
+            gb.memory.write_byte(0xFF40, 0x91); // LCD on
+            gb.memory.write_byte(0xFF05, 0xF0);
+            gb.memory.write_byte(0xFF07, 0x05); // Every 16 cycles
+            gb.memory.write_byte(0xFF26, 0x80); // APU on
+            gb.memory.write_byte(0xFFFF, Interrupt::VBlank.mask());
+            gb.memory.write_byte(0xFF0F, 0x00);
+
+            let (mut steps, mut timer) = (0, None);
+            while !gb.memory.interrupts.is_requested(Interrupt::VBlank) {
+                gb.step();
+                steps += 1;
+                if timer.is_none() && gb.memory.interrupts.is_requested(Interrupt::Timer) {
+                    timer = Some(gb.cycle_count());
+                }
+            }
+            let state = (
+                timer,
+                gb.cycle_count(),
+                gb.memory.ppu.ly(),
+                gb.memory.ppu.dot(),
+                gb.memory.read_byte(0xFF04),
+                gb.memory.read_byte(0xFF05),
+            );
+            (state, steps, gb.cpu.halted)
+        };
+
+        let (halted, halted_steps, still_halted) = run(0x76);
+        let (running, running_steps, _) = run(0x00);
+        assert_eq!(halted, running);
+        assert_eq!(halted.2, ppu::VBLANK_LINE);
+        assert!(halted.0.is_some(), "The timer overflowed on the way");
+        assert!(still_halted, "Woken at the start of the next step");
+        assert!(
+            halted_steps * 10 < running_steps,
+            "{halted_steps} steps halted, {running_steps} running"
+        );
+    }
+
+    #[test]
+    fn clock_trace_sees_every_component_kept_in_step() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.enable_clock_trace(crate::clocktrace::DEFAULT_WINDOW);
+        gb.run_cycles(u64::from(CYCLES_PER_FRAME) * 2);
+
+        let trace = gb.clock_trace().unwrap();
+        for component in Component::ALL {
+            assert_eq!(trace.last_advanced(component), gb.cycle_count());
+            assert_eq!(trace.max_lag(component), 0);
+        }
+    }
+
+    #[test]
+    fn stop_resets_div_and_stops() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.memory.data[0x0000] = 0x10; // STOP
+        gb.memory.timer.tick(255);
+        gb.memory.timer.tick(255);
+        assert_eq!(gb.memory.read_byte(0xFF04), 0x01);
+
+        assert_eq!(gb.run_cycles(1000), 4);
+        assert!(gb.cpu.stopped);
+        assert_eq!(gb.cpu.pc, 0x0002, "STOP skips the byte after it");
+        assert_eq!(gb.memory.read_byte(0xFF04), 0x00);
+    }
+
+    #[test]
+    fn joypad_press_ends_stop() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0xC000;
+        gb.memory.data[0xC000] = 0x10; // STOP
+        gb.memory.data[0xC002] = 0x3C; // INC A
+        gb.memory.write_byte(0xFF00, 0x20); // D-pad selected
+        let a = gb.cpu.registers.a;
+
+        assert_eq!(gb.run_cycles(1000), 1000, "A press could still wake it");
+        assert!(gb.cpu.stopped);
+        assert_eq!(gb.memory.read_byte(0xFF04), 0x00, "DIV stays stopped");
+        gb.set_buttons(Buttons::new().with(Button::A));
+        gb.step();
+        assert!(gb.cpu.stopped, "A is not on the d-pad line");
+
+        gb.set_buttons(Buttons::new().with(Button::Left));
+        gb.step();
+        assert!(!gb.cpu.stopped);
+        assert_eq!(gb.cpu.registers.a, a.wrapping_add(1));
+    }
+
+    #[test]
+    fn speed_switch_stalls_instead_of_stopping() {
+        let mut gb = GameBoy::new();
+        gb.memory.cgb_mode = true;
+        gb.cpu.pc = 0x0000;
+        gb.memory.data[0x0000] = 0x10; // STOP
+        gb.memory.data[0x0002] = 0x3C; // INC A
+        gb.memory.write_byte(0xFF4D, 0x01);
+        let a = gb.cpu.registers.a;
+
+        assert_eq!(gb.run_cycles(4), 4);
+        assert!(!gb.cpu.stopped);
+        gb.run_cycles(u64::from(crate::cgb::SPEED_SWITCH_STALL));
+        assert_eq!(gb.cpu.pc, 0x0002, "Stalled for the whole switch");
+        assert_eq!(gb.memory.read_byte(0xFF04), 0x00, "DIV stopped");
+
+        gb.step();
+        assert_eq!(gb.cpu.registers.a, a.wrapping_add(1));
+        assert!(gb.memory.speed.is_double_speed());
+    }
+
+    #[test]
+    fn finished_lines_are_rendered_into_the_frame() {
+        let mut gb = GameBoy::new();
+        gb.memory.write_byte(0xFF40, 0x91); // LCD and background on, tiles at 0x8000
+        gb.memory.write_byte(0xFF47, 0xE4);
+        gb.memory.write_byte(0x8012, 0xFF); // Tile 1, row 1: color 1
+        gb.memory.write_byte(0x9800, 0x01);
+
+        gb.run_cycles(u64::from(CYCLES_PER_LINE) * 2);
+        let frame = gb.frame();
+        assert_eq!(frame.shade(7, 1), 1);
+        assert_eq!(frame.shade(8, 1), 0);
+        assert_eq!(frame.shade(0, 0), 0);
+    }
+
+    #[test]
+    fn switching_the_lcd_off_blanks_the_frame() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000; // NOPs
+        gb.memory.write_byte(0xFF40, 0x91);
+        gb.memory.write_byte(0xFF47, 0xFF); // Every color black
+        gb.run_cycles(u64::from(CYCLES_PER_LINE) * 2);
+        assert_eq!(gb.frame().shade(0, 1), 3);
+
+        gb.memory.write_byte(0xFF40, 0x11);
+        gb.step();
+        assert!((0..SCREEN_HEIGHT).all(|y| gb.frame().shade(0, y) == 0));
+    }
+
+    #[test]
+    fn partial_frame_shows_the_lines_drawn_so_far() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000; // NOPs
+        gb.memory.write_byte(0xFF40, 0x91);
+        gb.memory.write_byte(0xFF47, 0xFF); // Every color black
+        gb.finish_frame();
+        assert_eq!(gb.partial_frame(), gb.frame(), "Nothing drawn yet");
+
+        gb.memory.write_byte(0xFF47, 0x00); // White from this frame on
+        gb.run_cycles(u64::from(CYCLES_PER_LINE) * 10 + 80 + 100);
+        let frame = gb.partial_frame();
+        let progress = frame.progress().unwrap();
+        assert_eq!(progress.ly, 10);
+        assert!((50..110).contains(&progress.drawn), "{progress:?}");
+        assert_eq!(frame.shade(159, 9), 0);
+        assert_eq!(frame.shade(progress.drawn - 1, 10), 0);
+        assert_eq!(frame.shade(progress.drawn, 10), 3, "Previous frame");
+        assert_eq!(frame.shade(0, 11), 3);
+
+        gb.finish_frame();
+        assert_eq!(gb.partial_frame(), gb.frame());
+    }
+
+    #[test]
+    fn ly_and_stat_follow_the_ppu() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000; // NOPs
+        gb.memory.write_byte(0xFF40, 0x80); // LCD on
+        gb.memory.write_byte(0xFF44, 0x42); // Read-only
+
+        gb.run_cycles(u64::from(CYCLES_PER_LINE) * 3 + 100);
+        assert_eq!(gb.memory.read_byte(0xFF44), 3);
+        assert_eq!(gb.memory.read_byte(0xFF41) & 0x03, 3, "Drawing");
+
+        gb.memory.write_byte(0xFF40, 0x00);
+        gb.step();
+        assert_eq!(gb.memory.read_byte(0xFF44), 0);
+        assert_eq!(gb.memory.read_byte(0xFF41) & 0x03, 0);
+    }
+
+    #[test]
+    fn lyc_match_requests_the_stat_interrupt() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000; // NOPs
+        gb.memory.write_byte(0xFF40, 0x80); // LCD on
+        gb.memory.write_byte(0xFF45, 2);
+        gb.memory.write_byte(0xFF41, 0x40); // LY=LYC source
+        gb.memory.write_byte(0xFFFF, 0x02);
+
+        gb.run_cycles(u64::from(CYCLES_PER_LINE) * 2 - 8);
+        assert_eq!(gb.memory.interrupts.pending(), None);
+        assert_eq!(gb.memory.read_byte(0xFF41) & 0x04, 0);
+        gb.run_cycles(8);
+        assert_eq!(gb.memory.interrupts.pending(), Some(Interrupt::LcdStat));
+        assert_eq!(gb.memory.read_byte(0xFF41) & 0x04, 0x04, "Coincidence flag");
+    }
+
+    #[test]
+    fn entering_vblank_requests_its_interrupt() {
+        let mut gb = GameBoy::new();
+        gb.memory.write_byte(0xFF40, 0x80); // LCD on
+        gb.memory.write_byte(0xFFFF, 0x01);
+        while gb.frame_cycles < CYCLES_PER_LINE * 143 {
+            gb.step();
+        }
+        assert_eq!(gb.memory.interrupts.pending(), None);
+
+        while gb.frame_cycles < CYCLES_PER_LINE * 144 {
+            gb.step();
+        }
+        assert_eq!(gb.memory.interrupts.pending(), Some(Interrupt::VBlank));
+    }
+
+    #[test]
+    fn set_buttons_requests_joypad_interrupt() {
+        let mut gb = GameBoy::new();
+        gb.memory.write_byte(0xFF00, 0x10); // Select action buttons
+        gb.memory.write_byte(0xFF0F, 0x00);
+
+        gb.set_buttons(Buttons::new().with(Button::A));
+
+        assert_eq!(gb.memory.read_byte(0xFF0F) & 0x10, 0x10);
+        assert_eq!(gb.memory.read_byte(0xFF00) & 0x0F, 0x0E);
+    }
+
+    #[test]
+    fn before_oam_scan_polls_as_line_zero_starts() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&polls);
+        gb.set_input_polling(InputPolling::BeforeOamScan(Box::new(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Buttons::new().with(Button::Start)
+        })));
+
+        gb.run_cycles(u64::from(CYCLES_PER_FRAME));
+        assert_eq!(
+            polls.load(Ordering::Relaxed),
+            0,
+            "No OAM scan with the LCD off"
+        );
+
+        gb.memory.write_byte(0xFF40, 0x91); // LCD on
+        gb.step();
+        assert_eq!(
+            polls.load(Ordering::Relaxed),
+            1,
+            "Switching on starts line 0"
+        );
+        assert!(gb.memory.joypad.buttons().is_pressed(Button::Start));
+
+        while polls.load(Ordering::Relaxed) == 1 {
+            gb.step();
+        }
+        assert_eq!(polls.load(Ordering::Relaxed), 2);
+        assert_eq!((gb.memory.ppu.ly(), gb.memory.ppu.mode()), (0, 2));
+        assert!(gb.memory.ppu.dot() <= 4, "In the step that wrapped");
+    }
+
+    #[test]
+    fn host_frame_polling_never_calls_into_frontend() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+
+        gb.run_cycles(u64::from(CYCLES_PER_FRAME));
+
+        assert_eq!(gb.memory.joypad.buttons(), Buttons::new());
+    }
+
+    #[test]
+    fn save_state_round_trip() {
+        let mut gb = GameBoy::new();
+        gb.cpu.registers.a = 0x42;
+        gb.cpu.pc = 0x1234;
+        gb.memory.write_byte(0xC000, 0x99);
+        gb.memory.write_byte(0xFF05, 0x77); // TIMA
+        let state = gb.save_state();
+
+        gb.cpu.registers.a = 0x00;
+        gb.cpu.pc = 0x0000;
+        gb.memory.write_byte(0xC000, 0x00);
+        gb.memory.write_byte(0xFF05, 0x00);
+        gb.load_state(&state).unwrap();
+
+        assert_eq!(gb.cpu.registers.a, 0x42);
+        assert_eq!(gb.cpu.pc, 0x1234);
+        assert_eq!(gb.memory.read_byte(0xC000), 0x99);
+        assert_eq!(gb.memory.read_byte(0xFF05), 0x77);
+    }
+
+    #[test]
+    fn mid_frame_state_resumes_exactly() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.run_cycles(u64::from(CYCLES_PER_FRAME) + 1000); // Mid-scanline
+        gb.memory.write_byte(0xFF01, 0xA5);
+        gb.memory.write_byte(0xFF02, 0x81); // Serial transfer in flight
+        gb.run_cycles(100);
+        gb.frame_buffer[5] = 3;
+        let state = gb.save_state();
+
+        let mut resumed = GameBoy::new();
+        resumed.load_state(&state).unwrap();
+        assert_eq!(resumed.memory.lcd_mode3, gb.memory.lcd_mode3);
+        for game in [&mut gb, &mut resumed] {
+            game.run_cycles(5000);
+        }
+
+        assert_eq!(resumed.cpu.pc, gb.cpu.pc);
+        assert_eq!(resumed.frame_cycles, gb.frame_cycles);
+        assert_eq!(resumed.frame_count(), gb.frame_count());
+        assert_eq!(resumed.frame_buffer, gb.frame_buffer);
+        for address in [0xFF01, 0xFF02, 0xFF04, 0xFF0F] {
+            assert_eq!(resumed.memory.peek(address), gb.memory.peek(address));
+        }
+        assert_eq!(
+            gb.memory.peek(0xFF01),
+            0xFF,
+            "Transfer finished after resuming"
+        );
+    }
+
+    #[test]
+    fn state_loaded_mid_scanline_resumes_the_same_picture() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        // INC A; LDH (SCX),A; JR -5: scrolls a little more every few dots
+        gb.memory.data[..5].copy_from_slice(&[0x3C, 0xE0, 0x43, 0x18, 0xFB]);
+        for row in 0..8 {
+            gb.memory.data[0x8000 + row * 2] = 0xF0;
+            gb.memory.data[0x8001 + row * 2] = 0x3C;
+        }
+        gb.memory.write_byte(0xFF40, 0x91);
+        gb.memory.write_byte(0xFF47, 0xE4);
+        gb.finish_frame();
+        gb.run_cycles(u64::from(CYCLES_PER_LINE) * 70 + 200);
+        assert_eq!(gb.memory.ppu.mode(), 3, "Mid-scanline");
+
+        let state = gb.save_state();
+        let mut resumed = GameBoy::new();
+        resumed.load_state(&state).unwrap();
+        assert_eq!(resumed.frame(), gb.frame(), "Lines drawn before the save");
+        for _ in 0..2 {
+            gb.finish_frame();
+            resumed.finish_frame();
+            assert_eq!(resumed.frame(), gb.frame());
+        }
+        assert_eq!(resumed.state_hash(), gb.state_hash());
+    }
+
+    #[test]
+    fn load_state_rejects_truncated_data() {
+        let mut gb = GameBoy::new();
+        let bytes = gb.save_state().as_bytes()[..100].to_vec();
+        let state = SaveState::from_bytes(bytes).unwrap();
+        assert!(gb.load_state(&state).is_err());
+    }
+
+    #[test]
+    fn state_check_refuses_corrupt_states_and_keeps_the_old_one() {
+        let mut gb = GameBoy::new();
+        gb.cpu.sp = 0x8000; // VRAM
+        gb.cpu.pc = 0xFF10;
+        let corrupt = gb.save_state();
+        gb.cpu.sp = 0xFFFE;
+        gb.cpu.pc = 0x0150;
+
+        gb.load_state(&corrupt).unwrap();
+        assert_eq!(gb.cpu.pc, 0xFF10, "Not checked by default");
+        assert_eq!(
+            gb.state_problems(),
+            [
+                StateProblem::UnmappedPc(0xFF10),
+                StateProblem::StrayStack(0x8000)
+            ]
+        );
+
+        gb.reset(ResetKind::Hard);
+        gb.cpu.pc = 0x0150;
+        for check in [StateCheck::Refuse, StateCheck::Repair] {
+            gb.set_state_check(check);
+            let error = gb.load_state(&corrupt).unwrap_err();
+            assert!(
+                error.contains("PC FF10 is not in runnable memory"),
+                "{error}"
+            );
+            assert!(error.contains("SP 8000 is not in RAM"), "{error}");
+            assert_eq!((gb.cpu.pc, gb.cpu.sp), (0x0150, 0xFFFE));
+        }
+    }
+
+    #[test]
+    fn truncated_state_leaves_the_machine_as_it_was() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.finish_frame();
+        let bytes = gb.save_state().as_bytes().to_vec();
+        let truncated = SaveState::from_bytes(bytes[..bytes.len() - 30000].to_vec()).unwrap();
+
+        gb.finish_frame();
+        let (hash, pc) = (gb.state_hash(), gb.cpu.pc);
+        assert!(gb.load_state(&truncated).is_err());
+        assert_eq!((gb.state_hash(), gb.cpu.pc), (hash, pc));
+    }
+
+    #[test]
+    fn random_code_never_panics() {
+        // The fuzz target in fuzz/ explores this further
+        for (seed, kind) in (0..8).zip([0x00, 0x01, 0x02, 0x03].into_iter().cycle()) {
+            let mut rom = vec![0; 0x8000];
+            RamInit::Random(seed).fill(&mut rom[0x0100..]);
+            rom[0x0147] = kind;
+            rom[0x0148] = 0x00; // 32KB
+            rom[0x0149] = 0x02; // 8KB RAM
+            let mut gb = GameBoy::new();
+            gb.memory
+                .load_cartridge(cartridge::Cartridge::from_rom(rom).unwrap());
+            for _ in 0..10 {
+                gb.finish_frame();
+            }
+        }
+    }
+
+    #[test]
+    fn short_rom_reads_open_bus_past_its_end() {
+        let mut rom = vec![0; 0x0150];
+        rom[0x0100] = 0x18; // JR -2
+        rom[0x0101] = 0xFE;
+        let mut gb = GameBoy::new();
+        gb.memory
+            .load_cartridge(cartridge::Cartridge::from_rom(rom).unwrap());
+        assert_eq!(gb.memory.read_byte(0x3FFF), 0xFF);
+        gb.finish_frame();
+        assert!(!gb.stopped());
+    }
+
+    #[test]
+    fn state_check_repairs_banks_past_the_end_of_the_cartridge() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x0147] = 0x01; // MBC1
+        rom[0x0148] = 0x01; // 64KB, 4 banks
+        rom[0x4000 + 0x4000] = 0x22; // Start of bank 2
+        let mut gb = GameBoy::new();
+        gb.memory
+            .load_cartridge(cartridge::Cartridge::from_rom(rom).unwrap());
+        gb.memory.write_byte(0x2000, 0x06); // Only 4 banks
+        let state = gb.save_state();
+
+        gb.set_state_check(StateCheck::Refuse);
+        assert_eq!(
+            gb.load_state(&state),
+            Err("Save state failed its sanity check: ROM bank 6 mapped, ROM has 4".to_string())
+        );
+        gb.set_state_check(StateCheck::Repair);
+        gb.load_state(&state).unwrap();
+        assert_eq!(gb.memory.read_byte(0x4000), 0x22, "Bank 6 wraps to bank 2");
+        assert!(gb.state_problems().is_empty());
+    }
+
+    #[test]
+    fn run_frame_runs_from_vblank_to_vblank() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.memory.write_byte(0xFF40, 0x91);
+        let first = gb.run_frame();
+        assert!(first.vblank);
+        assert_eq!(gb.memory.ppu.ly(), ppu::VBLANK_LINE);
+        assert!(first.cycles < u64::from(CYCLES_PER_FRAME));
+
+        let second = gb.run_frame();
+        assert!(second.vblank);
+        assert_eq!(
+            second.cycles,
+            u64::from(CYCLES_PER_FRAME),
+            "NOPs land exactly"
+        );
+        assert_eq!(second.frame, gb.frame());
+
+        gb.memory.write_byte(0xFF40, 0x11); // LCD off
+        let dark = gb.run_frame();
+        assert!(!dark.vblank);
+        assert_eq!(dark.cycles, u64::from(CYCLES_PER_FRAME));
+    }
+
+    #[test]
+    fn logo_scroll_holds_the_cpu_until_the_logo_lands() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0104..0x0134].fill(0xFF); // Solid 96x16 logo
+        let mut gb = GameBoy::new();
+        gb.memory
+            .load_cartridge(cartridge::Cartridge::from_rom(rom).unwrap());
+        gb.power_on();
+        gb.start_logo_scroll();
+        for _ in 0..50 {
+            gb.finish_frame();
+        }
+        assert_eq!(gb.cpu.pc, 0x0100, "CPU held");
+        let state = gb.save_state();
+
+        while gb.logo_scrolling() {
+            gb.finish_frame();
+        }
+        assert_eq!(gb.frame_count(), u64::from(crate::bootlogo::LOGO_FRAMES));
+        gb.finish_frame();
+        let shade = |x: usize, y: usize| gb.frame_buffer()[y * SCREEN_WIDTH + x] & 0x03;
+        assert_eq!(
+            [shade(32, 63), shade(32, 64), shade(127, 79), shade(32, 80)],
+            [0, 3, 3, 0]
+        );
+        assert_ne!(gb.cpu.pc, 0x0100, "Game started");
+
+        gb.load_state(&state).unwrap();
+        assert!(gb.logo_scrolling());
+    }
+
+    #[test]
+    fn finish_frame_stops_at_frame_boundary() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+
+        gb.finish_frame();
+        assert_eq!(gb.frame_count(), 1);
+        assert_eq!(gb.frame_cycles, 0);
+    }
+
+    #[test]
+    fn run_ahead_presents_future_and_rolls_back() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+
+        let mut presented = 0;
+        gb.run_ahead_frame(Buttons::new(), 2, |future| {
+            presented = future.frame_count();
+        })
+        .unwrap();
+
+        assert_eq!(presented, 3, "Presented state should be two frames ahead");
+        assert_eq!(
+            gb.frame_count(),
+            1,
+            "Real state should only advance one frame"
+        );
+    }
 
-pub struct GameBoy {
-    pub cpu: cpu::Cpu,
-    pub memory: memory::Memory,
-    log_file: Option<File>,
-}
+    #[test]
+    fn run_ahead_presents_each_frame_to_sinks_once() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        let frames = Arc::new(std::sync::Mutex::new(Vec::new()));
+        gb.add_frame_sink(Box::new(RecordingSink {
+            every: 1,
+            fail_at: u64::MAX,
+            frames: Arc::clone(&frames),
+        }));
 
-impl GameBoy {
-    pub fn new() -> Self {
-        Self {
-            cpu: cpu::Cpu::default(),
-            memory: memory::Memory::default(),
-            log_file: None,
+        for _ in 0..3 {
+            gb.run_ahead_frame(Buttons::new(), 2, |_| {}).unwrap();
         }
+
+        assert_eq!(*frames.lock().unwrap(), vec![1, 2, 3]);
     }
 
-    /// Load a ROM file
-    pub fn load_rom(&mut self, path: &str) -> std::io::Result<()> {
-        let cartridge = cartridge::Cartridge::load(path)?;
-        self.memory.load_cartridge(cartridge);
-        Ok(())
+    #[test]
+    fn save_state_carries_metadata_and_thumbnail() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.finish_frame();
+
+        let metadata = gb.save_state().metadata().unwrap();
+        assert_eq!(metadata.frames, 1);
+        assert_eq!(metadata.rom_hash, None);
+        assert!(metadata.timestamp > 0);
+        assert_eq!(
+            metadata.thumbnail.len(),
+            THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 4
+        );
     }
 
-    /// Enable CPU state logging to a file (gameboy-doctor format)
-    pub fn enable_logging(&mut self, path: &str) -> std::io::Result<()> {
-        self.log_file = Some(File::create(path)?);
-        Ok(())
+    #[test]
+    fn run_ahead_zero_is_plain_frame_advance() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+
+        let mut presented = 0;
+        gb.run_ahead_frame(Buttons::new().with(Button::A), 0, |gb| {
+            presented = gb.frame_count();
+        })
+        .unwrap();
+
+        assert_eq!(presented, 1);
+        assert!(gb.memory.joypad.buttons().is_pressed(Button::A));
     }
 
-    /// Initialize to post-boot state (skipping boot ROM for now)
-    pub fn power_on(&mut self) {
-        // CPU initialized with correct values in Cpu::new()
-        // Set initial flag values (from docs: AF = 01B0h for DMG)
-        self.cpu.registers.f.z = true;
-        self.cpu.registers.f.n = false;
-        self.cpu.registers.f.h = true;
-        self.cpu.registers.f.c = true;
+    #[test]
+    fn fast_boot_creates_then_reuses_cache() {
+        let cache = std::env::temp_dir().join(format!(
+            "gameboy-fastboot-{}/fastboot.state",
+            std::process::id()
+        ));
+
+        let mut first = GameBoy::new();
+        first.cpu.pc = 0x0000;
+        assert!(
+            !first.fast_boot(&cache, 2).unwrap(),
+            "First boot is a cache miss"
+        );
+        assert_eq!(first.frame_count(), 2);
+
+        let mut second = GameBoy::new();
+        assert!(
+            second.fast_boot(&cache, 2).unwrap(),
+            "Second boot hits the cache"
+        );
+        assert_eq!(second.frame_count(), 2);
+        assert_eq!(second.cpu.pc, first.cpu.pc);
+
+        fs::remove_dir_all(cache.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn soft_reset_keeps_ram_and_resets_cpu() {
+        let mut gb = GameBoy::new();
+        gb.power_on();
+        gb.cpu.pc = 0x4567;
+        gb.memory.write_byte(0xC123, 0x42);
+        gb.memory.write_byte(0xFF05, 0x99); // TIMA
+        gb.memory.write_byte(0xFF0F, 0x1F);
+
+        gb.reset(ResetKind::Soft);
+
+        assert_eq!(gb.cpu.pc, 0x0100);
+        assert!(gb.cpu.registers.f.z, "Reset should power back on");
+        assert_eq!(gb.memory.read_byte(0xC123), 0x42);
+        assert_eq!(gb.memory.read_byte(0xFF05), 0x00);
+        assert_eq!(gb.memory.read_byte(0xFF0F), 0x00);
     }
 
-    pub fn step(&mut self) {
-        // Log CPU state before execution (gameboy-doctor format)
-        self.log();
+    #[test]
+    fn hard_reset_applies_ram_init_policy() {
+        let mut gb = GameBoy::new();
+        gb.memory.write_byte(0xC123, 0x42);
+        gb.set_ram_init(RamInit::Ones);
 
-        // Execute instruction
-        let cycles = self.cpu.execute(&mut self.memory);
-        let timer_interrupt = self.memory.timer.tick(cycles);
-        if timer_interrupt {
-            let if_register = self.memory.read_byte(0xFF0F);
-            self.memory.write_byte(0xFF0F, if_register | 0x04);
-            // TODO: Implement interrupt system
-        }
+        gb.reset(ResetKind::Hard);
+
+        assert_eq!(gb.memory.read_byte(0xC123), 0xFF);
+        assert_eq!(gb.memory.read_byte(0xFF80), 0xFF);
+        assert_eq!(gb.frame_count(), 0);
     }
 
-    /// Run the emulator for a number of instructions
-    pub fn run(&mut self, num_instructions: usize) {
-        for _ in 0..num_instructions {
-            if self.cpu.halted {
-                break;
+    #[test]
+    fn scanline_callback_fires_for_each_visible_line() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb.memory.data[0xFF40] = 0x80; // LCD on, or no lines are drawn
+        let lines = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&lines);
+        gb.set_scanline_callback(Some(Box::new(move |ly, pixels| {
+            assert_eq!(pixels.len(), SCREEN_WIDTH);
+            seen.lock().unwrap().push(ly);
+        })));
+
+        gb.finish_frame();
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), SCREEN_HEIGHT, "VBlank lines are not reported");
+        assert!(lines.iter().copied().eq(0..144));
+    }
+
+    /// Records presented frame numbers, failing on frame `fail_at`
+    struct RecordingSink {
+        every: u64,
+        fail_at: u64,
+        frames: Arc<std::sync::Mutex<Vec<u64>>>,
+    }
+
+    impl FrameSink for RecordingSink {
+        fn wants_frame(&self, frame: u64) -> bool {
+            frame.is_multiple_of(self.every)
+        }
+
+        fn present(&mut self, frame: u64, image: &Frame) -> Result<(), String> {
+            assert_eq!(image.rgba().len(), Frame::WIDTH * Frame::HEIGHT * 4);
+            self.frames.lock().unwrap().push(frame);
+            if frame == self.fail_at {
+                Err("disconnected".to_string())
+            } else {
+                Ok(())
             }
-            self.step();
         }
     }
 
-    #[allow(clippy::many_single_char_names)]
-    fn log(&mut self) {
-        if self.log_file.is_some() {
-            let a = self.cpu.registers.a;
-            let f = self.cpu.registers.f.to_u8();
-            let b = self.cpu.registers.b;
-            let c = self.cpu.registers.c;
-            let d = self.cpu.registers.d;
-            let e = self.cpu.registers.e;
-            let h = self.cpu.registers.h;
-            let l = self.cpu.registers.l;
-            let sp = self.cpu.sp;
-            let pc = self.cpu.pc;
-
-            // Read next 4 bytes at PC for PCMEM
-            let pcmem0 = self.memory.read_byte(pc);
-            let pcmem1 = self.memory.read_byte(pc.wrapping_add(1));
-            let pcmem2 = self.memory.read_byte(pc.wrapping_add(2));
-            let pcmem3 = self.memory.read_byte(pc.wrapping_add(3));
-
-            let line = format!(
-                "A:{a:02X} F:{f:02X} B:{b:02X} C:{c:02X} D:{d:02X} E:{e:02X} H:{h:02X} L:{l:02X} SP:{sp:04X} PC:{pc:04X} PCMEM:{pcmem0:02X},{pcmem1:02X},{pcmem2:02X},{pcmem3:02X}\n",
-            );
+    #[test]
+    fn frame_sinks_receive_wanted_frames_until_they_fail() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        let every_frame = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let every_other = Arc::new(std::sync::Mutex::new(Vec::new()));
+        gb.add_frame_sink(Box::new(RecordingSink {
+            every: 1,
+            fail_at: 2,
+            frames: Arc::clone(&every_frame),
+        }));
+        gb.add_frame_sink(Box::new(RecordingSink {
+            every: 2,
+            fail_at: 0,
+            frames: Arc::clone(&every_other),
+        }));
 
-            if let Some(ref mut log) = self.log_file {
-                let _ = log.write_all(line.as_bytes());
+        for _ in 0..4 {
+            gb.finish_frame();
+        }
+
+        assert_eq!(*every_frame.lock().unwrap(), vec![1, 2]);
+        assert_eq!(*every_other.lock().unwrap(), vec![2, 4]);
+    }
+
+    #[test]
+    fn splash_is_presented_until_reset() {
+        let mut gb = GameBoy::new();
+        let frames = Arc::new(std::sync::Mutex::new(Vec::new()));
+        gb.add_frame_sink(Box::new(RecordingSink {
+            every: 1,
+            fail_at: u64::MAX,
+            frames: Arc::clone(&frames),
+        }));
+        let blank = gb.frame_rgba();
+
+        gb.show_splash();
+        assert_eq!(*frames.lock().unwrap(), vec![0]);
+        assert_ne!(gb.frame_rgba(), blank);
+
+        gb.reset(ResetKind::Hard);
+        assert_eq!(gb.frame_rgba(), blank);
+    }
+
+    #[test]
+    fn seek_presents_only_the_target_frame() {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        let frames = Arc::new(std::sync::Mutex::new(Vec::new()));
+        gb.add_frame_sink(Box::new(RecordingSink {
+            every: 1,
+            fail_at: 0,
+            frames: Arc::clone(&frames),
+        }));
+
+        assert_eq!(gb.seek(5), Ok(5));
+        assert_eq!(gb.frame_count(), 5);
+        assert_eq!(gb.frame_cycles, 0);
+        gb.finish_frame();
+        assert_eq!(*frames.lock().unwrap(), vec![5, 6]);
+        assert!(gb.seek(2).is_err());
+    }
+
+    #[test]
+    fn frame_buffer_is_readable_without_a_frontend() {
+        let mut gb = GameBoy::new();
+        gb.memory.write_byte(0xFF40, 0x91); // LCD and background on
+        gb.memory.write_byte(0xFF47, 0xE4);
+        gb.memory.write_byte(0x8000, 0xFF); // Tile 0 row 0: color 1
+        gb.cpu.pc = 0xC000; // NOPs
+        gb.finish_frame();
+        gb.finish_frame();
+
+        let pixels = gb.frame_buffer();
+        assert_eq!(pixels.len(), Frame::WIDTH * Frame::HEIGHT);
+        assert_eq!(pixels[0] & 0x03, 1);
+        assert_eq!(pixels[Frame::WIDTH] & 0x03, 0, "Row 1 of the tile is blank");
+        assert_eq!(pixels, gb.frame().pixels());
+    }
+
+    #[test]
+    fn frame_rgba_applies_selected_palette() {
+        let mut gb = GameBoy::new();
+        assert_eq!(gb.frame_rgba()[..4], [0xFF, 0xFF, 0xFF, 0xFF]);
+
+        gb.set_palette(DmgPalette::DMG_GREEN);
+        let rgba = gb.frame_rgba();
+        assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        assert_eq!(rgba[..4], [0x9B, 0xBC, 0x0F, 0xFF]);
+    }
+
+    #[test]
+    fn battery_save_round_trips_cartridge_ram() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x03; // MBC1+RAM+BATTERY
+        rom[0x0149] = 0x02; // 8KB RAM
+        let path = std::env::temp_dir().join(format!("gameboy-battery-{}.gb", std::process::id()));
+        fs::write(&path, &rom).unwrap();
+
+        let mut gb = GameBoy::new();
+        assert!(gb.battery_save().is_none(), "No cartridge, no battery");
+        gb.load_rom(path.to_str().unwrap()).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let mut save = gb.battery_save().unwrap();
+        assert_eq!(save.ram.len(), 0x2000);
+        save.ram[0x10] = 0x42;
+        gb.load_battery_save(&save).unwrap();
+        gb.memory.write_byte(0x0000, 0x0A); // Enable RAM
+        assert_eq!(gb.memory.read_byte(0xA010), 0x42);
+
+        save.ram.truncate(0x100);
+        assert!(gb.load_battery_save(&save).is_err());
+    }
+
+    #[test]
+    fn autosave_flushes_after_ram_writes_settle() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x03; // MBC1+RAM+BATTERY
+        rom[0x0149] = 0x02; // 8KB RAM
+        let path = std::env::temp_dir().join(format!("gameboy-autosave-{}.gb", std::process::id()));
+        fs::write(&path, &rom).unwrap();
+        let mut gb = GameBoy::new();
+        gb.load_rom(path.to_str().unwrap()).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let saved = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&saved);
+        gb.set_autosave(Some((
+            Autosave::new(2, 100),
+            Box::new(move |save: BatterySave| sink.lock().unwrap().push(save.ram[0])),
+        )));
+
+        gb.memory.write_byte(0x0000, 0x0A); // Enable RAM
+        gb.memory.write_byte(0xA000, 0x42);
+        for _ in 0..5 {
+            gb.finish_frame();
+        }
+
+        assert_eq!(*saved.lock().unwrap(), vec![0x42]);
+    }
+
+    #[test]
+    fn coverage_records_executed_rom_offsets() {
+        let path = std::env::temp_dir().join(format!("gameboy-coverage-{}.gb", std::process::id()));
+        fs::write(&path, vec![0; 0x8000]).unwrap();
+        let mut gb = GameBoy::new();
+        gb.load_rom(path.to_str().unwrap()).unwrap();
+        fs::remove_file(path).unwrap();
+
+        gb.enable_coverage();
+        for _ in 0..3 {
+            gb.step();
+        }
+        gb.cpu.pc = 0xC000; // Work RAM is not ROM
+        gb.step();
+
+        let coverage = gb.coverage().unwrap();
+        assert!((0x0100..0x0103).all(|offset| coverage.is_code_start(offset)));
+        assert_eq!(coverage.code_bytes(), 3);
+    }
+
+    #[test]
+    fn heat_map_tracks_cpu_accesses_but_not_peeks() {
+        let mut gb = GameBoy::new();
+        gb.set_heat_map(true);
+        gb.cpu.pc = 0xC000;
+        gb.memory.data[0xC000] = 0xEA; // LD (a16),A
+        gb.memory.data[0xC001] = 0x00;
+        gb.memory.data[0xC002] = 0xD0;
+
+        gb.step();
+        gb.memory.peek(0xC100);
+
+        let heat_map = gb.heat_map().unwrap();
+        assert_eq!(heat_map.heat(0xC000), (0xFF, 0, 0xFF));
+        assert_eq!(heat_map.heat(0xD000), (0, 0xFF, 0));
+        assert_eq!(heat_map.heat(0xC100), (0, 0, 0));
+
+        gb.reset(ResetKind::Hard);
+        assert_eq!(
+            gb.heat_map().unwrap().heat(0xD000),
+            (0, 0xFF, 0),
+            "Kept over reset"
+        );
+        gb.set_heat_map(false);
+        assert!(gb.heat_map().is_none());
+    }
+
+    #[test]
+    fn link_cable_stays_connected_over_reset() {
+        /// Records the bytes sent to it
+        struct Recorder(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl SerialDevice for Recorder {
+            fn start(&mut self, byte: u8, _internal_clock: bool) {
+                self.0.lock().unwrap().push(byte);
+            }
+
+            fn receive(&mut self) -> Option<u8> {
+                Some(0x00)
             }
         }
+
+        let mut gb = GameBoy::new();
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        gb.connect_serial(Some(Box::new(Recorder(Arc::clone(&sent)))));
+
+        gb.reset(ResetKind::Hard);
+        gb.memory.write_byte(0xFF01, 0x42);
+        gb.memory.write_byte(0xFF02, 0x81);
+
+        assert_eq!(*sent.lock().unwrap(), vec![0x42]);
     }
-}
 
-impl Default for GameBoy {
-    fn default() -> Self {
-        Self::new()
+    #[test]
+    fn bank_stats_follow_the_mapper_and_survive_reset() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x0147] = 0x01; // MBC1
+        rom[0x0148] = 0x01; // 64KB, 4 banks
+        let mut gb = GameBoy::new();
+        gb.set_bank_stats(true);
+        assert!(gb.bank_stats().is_none(), "No cartridge, no stats");
+        gb.memory
+            .load_cartridge(cartridge::Cartridge::from_rom(rom).unwrap());
+        gb.set_bank_stats(true);
+
+        gb.memory.write_byte(0x2000, 0x03);
+        gb.memory.read_byte(0x4000);
+        gb.memory.peek(0x4001);
+        gb.reset(ResetKind::Soft);
+        gb.memory.write_byte(0x2000, 0x01); // Already mapped after the reset
+
+        let stats = gb.bank_stats().unwrap();
+        assert_eq!(stats.rom_usage().len(), 4);
+        assert_eq!(stats.rom_usage()[3].selects, 1);
+        assert_eq!(stats.rom_usage()[3].reads, 1, "Peeks are not counted");
+        assert_eq!(stats.mapper_writes(), [0, 2, 0, 0]);
+        assert_eq!(stats.reselects(), 1);
     }
-}
 
+    #[test]
+    fn bank_audit_flags_missing_banks_across_state_loads() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x0147] = 0x01; // MBC1
+        rom[0x0148] = 0x01; // 64KB, 4 banks
+        let mut gb = GameBoy::new();
+        gb.memory
+            .load_cartridge(cartridge::Cartridge::from_rom(rom).unwrap());
+        gb.set_bank_audit(true);
+        let state = gb.save_state();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        gb.memory.write_byte(0x2000, 0x06);
+        assert_eq!(gb.memory.read_byte(0x4010), 0xFF);
+        gb.load_state(&state).unwrap();
+        gb.memory.read_byte(0x4010);
+
+        let audit = gb.bank_audit().unwrap();
+        let flagged = audit.out_of_range();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!((flagged[0].bank, flagged[0].address), (6, 0x4010));
+        assert_eq!(audit.coverage().len(), 2, "Bank 1 after the state load");
+    }
+
+    #[test]
+    fn illegal_opcode_traps_and_locks_cpu() {
+        let mut gb = GameBoy::new();
+        gb.set_quirk_policy("illegal-opcode=trap".parse().unwrap());
+        gb.cpu.pc = 0xC000;
+        gb.memory.data[0xC000] = 0x00; // NOP
+        gb.memory.data[0xC001] = 0xD3;
+        gb.memory.data[0xC002] = 0x00;
+
+        gb.run(10);
+
+        assert_eq!(gb.cpu.pc, 0xC002);
+        assert!(gb.cpu.locked);
+        let trap = gb.take_quirk_trap().unwrap();
+        assert_eq!(trap.quirk, Quirk::IllegalOpcode);
+        assert_eq!(trap.address, 0xC001);
+
+        gb.step();
+        assert_eq!(gb.cpu.pc, 0xC002, "Locked until reset");
+    }
+
+    #[test]
+    fn quirk_policy_is_kept_over_reset() {
+        let mut gb = GameBoy::new();
+        gb.set_quirk_policy("illegal-opcode=trap".parse().unwrap());
+
+        gb.reset(ResetKind::Soft);
+        gb.cpu.pc = 0xC000;
+        gb.memory.data[0xC000] = 0xD3;
+        gb.step();
+
+        assert_eq!(gb.take_quirk_trap().unwrap().quirk, Quirk::IllegalOpcode);
+    }
+
+    #[test]
+    fn echo_ram_trap_stops_the_frame() {
+        let mut gb = GameBoy::new();
+        gb.set_quirk_policy("echo-ram=trap".parse().unwrap());
+        gb.cpu.pc = 0xC000;
+        gb.memory.data[0xC000] = 0xEA; // LD (a16),A
+        gb.memory.data[0xC001] = 0x00;
+        gb.memory.data[0xC002] = 0xE1;
+
+        gb.finish_frame();
+
+        assert_eq!(gb.cpu.pc, 0xC003);
+        assert_eq!(gb.frame_count(), 0);
+        assert_eq!(gb.take_quirk_trap().unwrap().address, 0xE100);
+    }
+
+    #[test]
+    fn vram_access_is_only_a_quirk_while_drawing() {
+        let mut gb = GameBoy::new();
+        gb.set_quirk_policy(QuirkPolicy::all(QuirkAction::Trap));
+        gb.memory.data[0xFF40] = 0x91; // LCD on
+
+        // What the PPU draws from doesn't matter here, only LCDC bit 7
+        let mut lcd = memory::Memory::new();
+        lcd.data[0xFF40] = 0x91;
+        gb.memory.ppu.tick(4, &lcd, ppu::Layers::default()); // OAM scan
+        gb.update_lcd_mode();
+        gb.memory.read_byte(0x8000);
+        assert!(gb.take_quirk_trap().is_none());
+
+        gb.memory.ppu.tick(96, &lcd, ppu::Layers::default());
+        gb.update_lcd_mode();
+        gb.memory.read_byte(0x8000);
+        assert_eq!(
+            gb.take_quirk_trap().unwrap().quirk,
+            Quirk::VramAccessInMode3
+        );
+
+        gb.memory.data[0xFF40] = 0x11; // LCD off
+        lcd.data[0xFF40] = 0x11;
+        gb.memory.ppu.tick(4, &lcd, ppu::Layers::default());
+        gb.update_lcd_mode();
+        gb.memory.write_byte(0x8000, 0);
+        assert!(gb.take_quirk_trap().is_none());
+    }
+
+    #[test]
+    fn event_log_records_interrupts_and_mode_changes() {
+        let mut gb = GameBoy::new();
+        gb.enable_event_log(1000);
+        gb.memory.data[0xFF40] = 0x91; // LCD on
+        gb.memory.write_byte(0xFF07, 0x05); // Enable timer, frequency 16
+        gb.memory.write_byte(0xFF05, 0xFF);
+
+        gb.run_cycles(u64::from(CYCLES_PER_LINE));
+
+        let events: Vec<Event> = gb
+            .event_log()
+            .unwrap()
+            .events()
+            .iter()
+            .map(|timed| timed.event)
+            .collect();
+        assert_eq!(events[0], Event::ModeChange { mode: 2, line: 0 });
+        assert_eq!(events[1], Event::InterruptRequested(Interrupt::Timer));
+        assert!(events.contains(&Event::ModeChange { mode: 3, line: 0 }));
+        assert!(events.contains(&Event::ModeChange { mode: 0, line: 0 }));
+        assert_eq!(events.last(), Some(&Event::ModeChange { mode: 2, line: 1 }));
+    }
 
     #[test]
     fn timer_interrupt_sets_if_flag() {
@@ -1021,6 +3433,7 @@ mod tests {
         assert_eq!(cycles, 16);
         assert_eq!(gb.cpu.pc, 0x0800);
         assert_eq!(gb.cpu.sp, stack_sp + 2); // SP restored
+        assert!(gb.cpu.interrupts_enabled, "At once, unlike EI");
     }
 
     #[test]
@@ -1297,6 +3710,107 @@ mod tests {
         assert_eq!(gb.cpu.registers.f.c, false);
     }
 
+    // ADD HL,rr, ADD SP,n and JP HL tests
+    #[test]
+    fn test_add_hl_bc_half_carry() {
+        let mut gb = GameBoy::new();
+        gb.cpu.registers.set_hl(0x8A23);
+        gb.cpu.registers.set_bc(0x0605);
+        gb.cpu.registers.f.z = true;
+        gb.cpu.registers.f.n = true;
+        gb.memory.write_byte(0x0100, 0x09); // ADD HL,BC
+        let cycles = gb.cpu.execute(&mut gb.memory);
+        assert_eq!(cycles, 8);
+        assert_eq!(gb.cpu.registers.hl(), 0x9028);
+        assert!(gb.cpu.registers.f.z); // Preserved
+        assert!(!gb.cpu.registers.f.n);
+        assert!(gb.cpu.registers.f.h); // Carry from bit 11
+        assert!(!gb.cpu.registers.f.c);
+    }
+
+    #[test]
+    fn test_add_hl_de_carry_keeps_z_clear() {
+        let mut gb = GameBoy::new();
+        gb.cpu.registers.set_hl(0xFFFF);
+        gb.cpu.registers.set_de(0x0001);
+        gb.cpu.registers.f.z = false;
+        gb.memory.write_byte(0x0100, 0x19); // ADD HL,DE
+        let cycles = gb.cpu.execute(&mut gb.memory);
+        assert_eq!(cycles, 8);
+        assert_eq!(gb.cpu.registers.hl(), 0x0000);
+        assert!(!gb.cpu.registers.f.z); // Preserved, though the result is zero
+        assert!(gb.cpu.registers.f.h);
+        assert!(gb.cpu.registers.f.c); // Carry from bit 15
+    }
+
+    #[test]
+    fn test_add_hl_hl() {
+        let mut gb = GameBoy::new();
+        gb.cpu.registers.set_hl(0x8A23);
+        gb.memory.write_byte(0x0100, 0x29); // ADD HL,HL
+        let cycles = gb.cpu.execute(&mut gb.memory);
+        assert_eq!(cycles, 8);
+        assert_eq!(gb.cpu.registers.hl(), 0x1446);
+        assert!(gb.cpu.registers.f.h);
+        assert!(gb.cpu.registers.f.c);
+    }
+
+    #[test]
+    fn test_add_hl_sp_no_carry() {
+        let mut gb = GameBoy::new();
+        gb.cpu.registers.set_hl(0x1000);
+        gb.cpu.sp = 0x0234;
+        gb.cpu.registers.f.h = true;
+        gb.cpu.registers.f.c = true;
+        gb.memory.write_byte(0x0100, 0x39); // ADD HL,SP
+        let cycles = gb.cpu.execute(&mut gb.memory);
+        assert_eq!(cycles, 8);
+        assert_eq!(gb.cpu.registers.hl(), 0x1234);
+        assert!(!gb.cpu.registers.f.h);
+        assert!(!gb.cpu.registers.f.c);
+    }
+
+    #[test]
+    fn test_add_sp_n_negative() {
+        let mut gb = GameBoy::new();
+        gb.cpu.sp = 0xFFF8;
+        gb.cpu.registers.f.z = true;
+        gb.cpu.registers.f.n = true;
+        gb.memory.write_byte(0x0100, 0xE8); // ADD SP,n
+        gb.memory.write_byte(0x0101, 0xFE_u8); // -2
+        let cycles = gb.cpu.execute(&mut gb.memory);
+        assert_eq!(cycles, 16);
+        assert_eq!(gb.cpu.sp, 0xFFF6);
+        assert!(!gb.cpu.registers.f.z); // Always cleared
+        assert!(!gb.cpu.registers.f.n);
+        assert!(gb.cpu.registers.f.h); // 0x8 + 0xE in the low nibble
+        assert!(gb.cpu.registers.f.c); // 0xF8 + 0xFE in the low byte
+    }
+
+    #[test]
+    fn test_add_sp_n_negative_without_low_byte_carry() {
+        let mut gb = GameBoy::new();
+        gb.cpu.sp = 0x0100;
+        gb.memory.write_byte(0x0100, 0xE8); // ADD SP,n
+        gb.memory.write_byte(0x0101, 0xFF_u8); // -1
+        gb.cpu.execute(&mut gb.memory);
+        assert_eq!(gb.cpu.sp, 0x00FF);
+        // The high byte borrows, but flags only look at 0x00 + 0xFF
+        assert!(!gb.cpu.registers.f.h);
+        assert!(!gb.cpu.registers.f.c);
+    }
+
+    #[test]
+    fn test_jp_hl() {
+        let mut gb = GameBoy::new();
+        gb.cpu.registers.set_hl(0xC123);
+        gb.memory.write_byte(0x0100, 0xE9); // JP HL
+        let cycles = gb.cpu.execute(&mut gb.memory);
+        assert_eq!(cycles, 4);
+        assert_eq!(gb.cpu.pc, 0xC123);
+        assert_eq!(gb.cpu.registers.hl(), 0xC123);
+    }
+
     // ADC tests
     #[test]
     fn test_adc_a_b_no_carry() {
@@ -1490,9 +4004,25 @@ mod tests {
         let mut gb = GameBoy::new();
         gb.cpu.interrupts_enabled = false;
         gb.memory.write_byte(0x0100, 0xFB); // EI
+        gb.memory.write_byte(0x0101, 0x00); // NOP
         let cycles = gb.cpu.execute(&mut gb.memory);
         assert_eq!(cycles, 4);
-        assert_eq!(gb.cpu.interrupts_enabled, true);
+        assert!(!gb.cpu.interrupts_enabled, "Not until the next instruction");
+        gb.cpu.execute(&mut gb.memory);
+        assert!(gb.cpu.interrupts_enabled);
+    }
+
+    #[test]
+    fn di_straight_after_ei_keeps_interrupts_disabled() {
+        let mut gb = GameBoy::new();
+        gb.memory.write_byte(0x0100, 0xFB); // EI
+        gb.memory.write_byte(0x0101, 0xF3); // DI
+        gb.memory.write_byte(0x0102, 0x00); // NOP
+        for _ in 0..3 {
+            gb.cpu.execute(&mut gb.memory);
+        }
+        assert!(!gb.cpu.interrupts_enabled);
+        assert!(!gb.cpu.ime_pending);
     }
 
     // RST tests
@@ -1891,9 +4421,9 @@ mod tests {
     #[test]
     fn test_ldh_a_n_io_port() {
         let mut gb = GameBoy::new();
-        gb.memory.write_byte(0xFF44, 0x90); // LY register (scanline)
+        gb.memory.write_byte(0xFF43, 0x90); // SCX register
         gb.memory.write_byte(0x0100, 0xF0); // LDH A,(n)
-        gb.memory.write_byte(0x0101, 0x44); // Offset 0x44
+        gb.memory.write_byte(0x0101, 0x43); // Offset 0x43
         gb.cpu.execute(&mut gb.memory);
         assert_eq!(gb.cpu.registers.a, 0x90); // Loaded from I/O port
         assert_eq!(gb.cpu.pc, 0x0102);
@@ -1903,11 +4433,11 @@ mod tests {
     fn test_ldh_c_a() {
         let mut gb = GameBoy::new();
         gb.cpu.registers.a = 0x77;
-        gb.cpu.registers.c = 0x10; // Offset in C register
+        gb.cpu.registers.c = 0x90; // Offset in C register
         gb.memory.write_byte(0x0100, 0xE2); // LDH (C),A
         let cycles = gb.cpu.execute(&mut gb.memory);
         assert_eq!(cycles, 8);
-        assert_eq!(gb.memory.read_byte(0xFF10), 0x77); // Stored at 0xFF00 + C
+        assert_eq!(gb.memory.read_byte(0xFF90), 0x77); // Stored at 0xFF00 + C
         assert_eq!(gb.cpu.pc, 0x0101);
     }
 
@@ -1917,11 +4447,11 @@ mod tests {
         gb.cpu.registers.a = 0xAB;
 
         // Test with different C values
-        gb.cpu.registers.c = 0x00; // Write to 0xFF00
+        gb.cpu.registers.c = 0x80; // Write to 0xFF80 (start of HRAM)
         gb.memory.write_byte(0x0100, 0xE2); // LDH (C),A
         gb.cpu.pc = 0x0100;
         gb.cpu.execute(&mut gb.memory);
-        assert_eq!(gb.memory.read_byte(0xFF00), 0xAB);
+        assert_eq!(gb.memory.read_byte(0xFF80), 0xAB);
 
         gb.cpu.registers.c = 0xFF; // Write to 0xFFFF
         gb.cpu.pc = 0x0100;
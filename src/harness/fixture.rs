@@ -0,0 +1,217 @@
+use crate::gameboy::GameBoy;
+use crate::joypad::Button;
+use std::fs;
+use std::path::Path;
+
+/// What a fixture line does once its frame is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Press(Button),
+    Release(Button),
+    Poke {
+        address: u16,
+        value: u8,
+    },
+    /// Fail unless memory at `address` holds (or with `equal` false, does not
+    /// hold) `value`
+    Expect {
+        address: u16,
+        value: u8,
+        equal: bool,
+    },
+}
+
+/// A scripted test run: input and memory checks pinned to frame numbers, so
+/// the same fixture always sees the same emulation.
+///
+/// One step per line, `FRAME ACTION ARGS`. Steps run once FRAME frames have
+/// completed, in file order within a frame. Numbers are decimal or `0x` hex;
+/// blank lines and `#` comments are ignored.
+///
+/// ```text
+/// # Start the game and check the lives counter
+/// 120 press start
+/// 126 release start
+/// 180 expect 0xC0A0 == 3
+/// 180 poke 0xC0A1 0
+/// 240 expect 0xC0A1 != 0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fixture {
+    steps: Vec<(u64, Step)>,
+}
+
+impl Fixture {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let step = parse_line(line).map_err(|e| format!("line {}: {e}", index + 1))?;
+            steps.push(step);
+        }
+        // Stable, so steps sharing a frame keep their file order
+        steps.sort_by_key(|&(frame, _)| frame);
+        Ok(Self { steps })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Steps in the order they run, with their frames
+    pub fn steps(&self) -> &[(u64, Step)] {
+        &self.steps
+    }
+
+    /// Run a powered-on `game` through every step, stopping at the first
+    /// failed expectation. Also fails if the CPU halts before a step's frame.
+    pub fn run(&self, game: &mut GameBoy) -> Result<(), String> {
+        for &(frame, step) in &self.steps {
+            while game.frame_count() < frame {
+                if game.stopped() {
+                    return Err(format!(
+                        "CPU stopped at {:04X} before frame {frame}",
+                        game.cpu.pc
+                    ));
+                }
+                game.finish_frame();
+            }
+            let mut buttons = game.memory.joypad.buttons();
+            match step {
+                Step::Press(button) => {
+                    buttons.press(button);
+                    game.set_buttons(buttons);
+                }
+                Step::Release(button) => {
+                    buttons.release(button);
+                    game.set_buttons(buttons);
+                }
+                Step::Poke { address, value } => game.memory.write_byte(address, value),
+                Step::Expect {
+                    address,
+                    value,
+                    equal,
+                } => {
+                    let actual = game.memory.peek(address);
+                    if (actual == value) != equal {
+                        let relation = if equal { "==" } else { "!=" };
+                        return Err(format!(
+                            "frame {frame}: expected [{address:04X}] {relation} {value:02X}, found {actual:02X}"
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str) -> Result<(u64, Step), String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let frame = number(words[0])?;
+    let step = match words[1..] {
+        ["press", button] => Step::Press(button.parse()?),
+        ["release", button] => Step::Release(button.parse()?),
+        ["poke", address, value] => Step::Poke {
+            address: address_number(address)?,
+            value: byte(value)?,
+        },
+        ["expect", address, relation @ ("==" | "!="), value] => Step::Expect {
+            address: address_number(address)?,
+            value: byte(value)?,
+            equal: relation == "==",
+        },
+        _ => {
+            return Err(format!(
+                "expected FRAME press|release BUTTON, FRAME poke ADDRESS VALUE or FRAME expect ADDRESS ==|!= VALUE, not {line:?}"
+            ));
+        }
+    };
+    Ok((frame, step))
+}
+
+fn number(text: &str) -> Result<u64, String> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("Invalid number: {text}"))
+}
+
+fn address_number(text: &str) -> Result<u16, String> {
+    u16::try_from(number(text)?).map_err(|_| format!("Address out of range: {text}"))
+}
+
+fn byte(text: &str) -> Result<u8, String> {
+    u8::try_from(number(text)?).map_err(|_| format!("Value out of range: {text}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_steps_in_frame_order() {
+        let fixture = Fixture::parse(
+            "# Mine\n\
+             180 expect 0xC0A0 == 3\n\
+             \n\
+             120 press Start  # Title screen\n\
+             120 poke 0xC000 0xFF\n",
+        )
+        .unwrap();
+        assert_eq!(
+            fixture.steps(),
+            [
+                (120, Step::Press(Button::Start)),
+                (
+                    120,
+                    Step::Poke {
+                        address: 0xC000,
+                        value: 0xFF
+                    }
+                ),
+                (
+                    180,
+                    Step::Expect {
+                        address: 0xC0A0,
+                        value: 3,
+                        equal: true
+                    }
+                ),
+            ]
+        );
+
+        assert!(Fixture::parse("10 press turbo").is_err());
+        assert!(Fixture::parse("10 expect 0xC000 = 3").is_err());
+        assert!(Fixture::parse("10 poke 0xC000 256").is_err());
+    }
+
+    #[test]
+    fn runs_steps_at_their_frames() {
+        let mut game = GameBoy::new();
+        game.cpu.pc = 0x0000;
+        game.memory.data[0x0000..0x0002].copy_from_slice(&[0x18, 0xFE]); // JR -2
+        let fixture = Fixture::parse(
+            "2 press a\n\
+             2 poke 0xC000 7\n\
+             3 expect 0xC000 == 7\n\
+             3 expect 0xC001 != 7\n",
+        )
+        .unwrap();
+
+        assert_eq!(fixture.run(&mut game), Ok(()));
+        assert_eq!(game.frame_count(), 3);
+        assert!(game.memory.joypad.buttons().is_pressed(Button::A));
+
+        let failing = Fixture::parse("4 expect 0xC000 == 3").unwrap();
+        assert_eq!(
+            failing.run(&mut game),
+            Err("frame 4: expected [C000] == 03, found 07".to_string())
+        );
+    }
+}
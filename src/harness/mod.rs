@@ -0,0 +1,677 @@
+mod fixture;
+
+use crate::GameBoy;
+use crate::cartridge::{self, Cartridge};
+use crate::serial::SerialDevice;
+use serde_json::{Map, Value, json};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub use fixture::{Fixture, Step};
+
+/// Registers B, C, D, E, H and L when a mooneye test passes
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// Value of every one of those registers when a mooneye test fails
+const MOONEYE_FAIL: u8 = 0x42;
+
+/// Written to 0xA001-0xA003 by blargg tests that report through cartridge RAM
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+/// Status byte at 0xA000 while such a test is still running
+const BLARGG_RUNNING: u8 = 0x80;
+
+/// How a test ROM ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// The ROM reported failure, with whatever it printed
+    Failed(String),
+    /// No verdict within the frame budget or wall-clock limit
+    Timeout,
+    /// The emulator panicked, with the panic message
+    Crashed(String),
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Passed => "PASS",
+            Outcome::Failed(_) => "FAIL",
+            Outcome::Timeout => "TIME",
+            Outcome::Crashed(_) => "CRASH",
+        }
+    }
+
+    pub fn detail(&self) -> &str {
+        match self {
+            Outcome::Failed(detail) | Outcome::Crashed(detail) => detail,
+            Outcome::Passed | Outcome::Timeout => "",
+        }
+    }
+
+    /// Process exit code for CI: 0 passed, 3 failed, 4 no verdict, 5 crashed.
+    /// 1 and 2 stay free for load errors and bad arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Passed => 0,
+            Outcome::Failed(_) => 3,
+            Outcome::Timeout => 4,
+            Outcome::Crashed(_) => 5,
+        }
+    }
+
+    fn from_name(name: &str, detail: String) -> Option<Self> {
+        match name {
+            "PASS" => Some(Outcome::Passed),
+            "FAIL" => Some(Outcome::Failed(detail)),
+            "TIME" => Some(Outcome::Timeout),
+            "CRASH" => Some(Outcome::Crashed(detail)),
+            _ => None,
+        }
+    }
+}
+
+/// Limits on a single test ROM run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Emulated frames before giving up (about 60 per second)
+    pub frames: u64,
+    /// Wall-clock time before giving up, whatever the frame count
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub rom: PathBuf,
+    pub rom_hash: u32,
+    pub outcome: Outcome,
+    /// Frames run before the verdict
+    pub frames: u64,
+    /// Taken from the cache rather than run
+    pub cached: bool,
+}
+
+/// Why a test mode run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Halted,
+    /// A quirk configured to trap was hit
+    Trapped,
+    InstructionLimit,
+    /// The emulator panicked
+    Panicked,
+}
+
+impl StopReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            StopReason::Halted => "halted",
+            StopReason::Trapped => "trapped",
+            StopReason::InstructionLimit => "instruction_limit",
+            StopReason::Panicked => "panicked",
+        }
+    }
+}
+
+/// Machine-readable result of test mode, printed as JSON for CI scripts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    pub instructions: usize,
+    pub cycles: u64,
+    pub stop: StopReason,
+    pub serial: String,
+    pub outcome: Outcome,
+}
+
+impl TestReport {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "instructions": self.instructions,
+            "cycles": self.cycles,
+            "stop_reason": self.stop.name(),
+            "serial": self.serial,
+            "result": self.outcome.name(),
+            "detail": self.outcome.detail(),
+            "exit_code": self.outcome.exit_code(),
+        })
+    }
+}
+
+/// Run up to `max_instructions` on an already powered-on `game`, capturing
+/// serial output, then check for a verdict the same way the harness does.
+/// Without one, halting or trapping counts as a failure and running out of
+/// instructions as a timeout.
+///
+/// # Panics
+///
+/// Never in practice: emulator panics are caught and reported as crashes.
+pub fn run_test(game: &mut GameBoy, max_instructions: usize) -> TestReport {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    game.connect_serial(Some(Box::new(SerialCapture(Arc::clone(&output)))));
+
+    let mut instructions = 0;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        while instructions < max_instructions && !game.stopped() {
+            game.step();
+            instructions += 1;
+        }
+    }));
+    game.connect_serial(None);
+    let serial = String::from_utf8_lossy(&output.lock().unwrap()).into_owned();
+
+    let (stop, fallback) = if let Err(payload) = result {
+        (
+            StopReason::Panicked,
+            Outcome::Crashed(panic_message(&*payload)),
+        )
+    } else if game.memory.quirks.trapped() {
+        let reason = format!("quirk trap at {:04X}", game.cpu.pc);
+        (StopReason::Trapped, Outcome::Failed(reason))
+    } else if game.asleep() {
+        let reason = format!("CPU halted at {:04X} without a result", game.cpu.pc);
+        (StopReason::Halted, Outcome::Failed(reason))
+    } else {
+        (StopReason::InstructionLimit, Outcome::Timeout)
+    };
+    let outcome = match fallback {
+        Outcome::Crashed(_) => fallback,
+        _ => verdict(game, &serial).unwrap_or(fallback),
+    };
+    TestReport {
+        instructions,
+        cycles: game.cycle_count(),
+        stop,
+        serial,
+        outcome,
+    }
+}
+
+/// Collects the bytes a test ROM prints over the link port
+struct SerialCapture(Arc<Mutex<Vec<u8>>>);
+
+impl SerialDevice for SerialCapture {
+    fn start(&mut self, byte: u8, _internal_clock: bool) {
+        self.0.lock().unwrap().push(byte);
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        None
+    }
+}
+
+/// Run one test ROM until it reports a verdict or a limit is reached.
+/// Returns the outcome and the number of frames run.
+///
+/// With a `fixture`, the fixture decides instead: the ROM passes once every
+/// step has run without a failed expectation. Fixtures run to their last
+/// frame whatever the limits.
+pub fn run_rom(rom: Vec<u8>, limits: Limits, fixture: Option<&Fixture>) -> (Outcome, u64) {
+    let cartridge = match Cartridge::from_rom(rom) {
+        Ok(cartridge) => cartridge,
+        Err(e) => return (Outcome::Crashed(e), 0),
+    };
+    panic::catch_unwind(AssertUnwindSafe(|| match fixture {
+        Some(fixture) => run_fixture(cartridge, fixture),
+        None => run_to_verdict(cartridge, limits),
+    }))
+    .unwrap_or_else(|payload| (Outcome::Crashed(panic_message(&*payload)), 0))
+}
+
+/// Where the harness looks for a ROM's fixture: next to it, with the
+/// extension `.fixture`
+pub fn fixture_path(rom: &Path) -> PathBuf {
+    rom.with_extension("fixture")
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+fn run_to_verdict(cartridge: Cartridge, limits: Limits) -> (Outcome, u64) {
+    let mut game = GameBoy::new();
+    game.memory.load_cartridge(cartridge);
+    let output = Arc::new(Mutex::new(Vec::new()));
+    game.connect_serial(Some(Box::new(SerialCapture(Arc::clone(&output)))));
+    game.power_on();
+
+    let started = Instant::now();
+    while game.frame_count() < limits.frames {
+        game.finish_frame();
+        let serial = String::from_utf8_lossy(&output.lock().unwrap()).into_owned();
+        if let Some(outcome) = verdict(&game, &serial) {
+            return (outcome, game.frame_count());
+        }
+        if game.asleep() {
+            let reason = format!("CPU halted at {:04X} without a result", game.cpu.pc);
+            return (Outcome::Failed(reason), game.frame_count());
+        }
+        if started.elapsed() > limits.timeout {
+            break;
+        }
+    }
+    (Outcome::Timeout, game.frame_count())
+}
+
+fn run_fixture(cartridge: Cartridge, fixture: &Fixture) -> (Outcome, u64) {
+    let mut game = GameBoy::new();
+    game.memory.load_cartridge(cartridge);
+    game.power_on();
+    let outcome = match fixture.run(&mut game) {
+        Ok(()) => Outcome::Passed,
+        Err(e) => Outcome::Failed(e),
+    };
+    (outcome, game.frame_count())
+}
+
+/// Check the three ways test ROMs report results: mooneye's magic registers,
+/// blargg's status block in cartridge RAM and blargg's serial output
+fn verdict(game: &GameBoy, serial: &str) -> Option<Outcome> {
+    let registers = &game.cpu.registers;
+    let values = [
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+    ];
+    if values == MOONEYE_PASS {
+        return Some(Outcome::Passed);
+    }
+    if values == [MOONEYE_FAIL; 6] {
+        return Some(Outcome::Failed("mooneye failure registers".to_string()));
+    }
+
+    let memory = &game.memory;
+    let signature = [0xA001, 0xA002, 0xA003].map(|address| memory.peek(address));
+    let status = memory.peek(0xA000);
+    if signature == BLARGG_SIGNATURE && status != BLARGG_RUNNING {
+        if status == 0 {
+            return Some(Outcome::Passed);
+        }
+        let text: Vec<u8> = (0xA004..0xC000)
+            .map(|address| memory.peek(address))
+            .take_while(|&byte| byte != 0)
+            .collect();
+        let text = String::from_utf8_lossy(&text);
+        return Some(Outcome::Failed(format!(
+            "result code {status}: {}",
+            text.trim()
+        )));
+    }
+
+    if serial.contains("Passed") {
+        Some(Outcome::Passed)
+    } else if serial.contains("Failed") {
+        Some(Outcome::Failed(
+            serial.split_whitespace().collect::<Vec<_>>().join(" "),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Every .gb and .gbc file under `dir`, sorted by path
+pub fn find_roms(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "gb" || extension == "gbc")
+            {
+                roms.push(path);
+            }
+        }
+    }
+    roms.sort();
+    Ok(roms)
+}
+
+/// Identifies the emulator build results were produced with: the package
+/// version plus the CRC-32 of the running executable, so any rebuild that
+/// changes the core invalidates cached results
+pub fn core_version() -> String {
+    let exe = std::env::current_exe()
+        .and_then(fs::read)
+        .map_or(0, |bytes| cartridge::crc32(&bytes));
+    format!("{}+{exe:08X}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Results from earlier runs, keyed by core version and ROM hash.
+///
+/// Timeouts are never cached since they depend on the limits and on how busy
+/// the machine was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultCache {
+    core_version: String,
+    results: HashMap<u32, (Outcome, u64)>,
+}
+
+impl ResultCache {
+    pub fn new(core_version: String) -> Self {
+        Self {
+            core_version,
+            results: HashMap::new(),
+        }
+    }
+
+    /// Read the cache, keeping only results from `core_version`
+    pub fn load(path: &Path, core_version: String) -> Result<Self, String> {
+        let mut cache = Self::new(core_version);
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        let value: Value =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        if value["core_version"] != cache.core_version.as_str() {
+            return Ok(cache);
+        }
+
+        let results = value["results"].as_object().into_iter().flatten();
+        for (hash, result) in results {
+            let outcome = Outcome::from_name(
+                result["outcome"].as_str().unwrap_or_default(),
+                result["detail"].as_str().unwrap_or_default().to_string(),
+            );
+            let (Ok(hash), Some(outcome), Some(frames)) = (
+                u32::from_str_radix(hash, 16),
+                outcome,
+                result["frames"].as_u64(),
+            ) else {
+                return Err(format!("{}: invalid result for {hash}", path.display()));
+            };
+            cache.results.insert(hash, (outcome, frames));
+        }
+        Ok(cache)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let results: Map<String, Value> = self
+            .results
+            .iter()
+            .map(|(hash, (outcome, frames))| {
+                let result = json!({
+                    "outcome": outcome.name(),
+                    "detail": outcome.detail(),
+                    "frames": frames,
+                });
+                (format!("{hash:08X}"), result)
+            })
+            .collect();
+        let value = json!({ "core_version": self.core_version, "results": results });
+        fs::write(path, format!("{value:#}\n")).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// A cached verdict reached within `limits`
+    pub fn get(&self, rom_hash: u32, limits: Limits) -> Option<(Outcome, u64)> {
+        self.results
+            .get(&rom_hash)
+            .filter(|(_, frames)| *frames <= limits.frames)
+            .cloned()
+    }
+
+    pub fn insert(&mut self, rom_hash: u32, outcome: Outcome, frames: u64) {
+        if outcome != Outcome::Timeout {
+            self.results.insert(rom_hash, (outcome, frames));
+        }
+    }
+}
+
+/// Run every ROM on `jobs` worker threads, one emulator instance per ROM,
+/// skipping those with a cached verdict. Results come back in `roms` order
+/// and fresh verdicts are added to `cache`. ROMs with a fixture (see
+/// `fixture_path`) are run through it and never cached, since the fixture
+/// can change without the ROM.
+///
+/// # Panics
+///
+/// Never in practice: emulator panics are caught and reported as crashes.
+pub fn run_all(
+    roms: &[PathBuf],
+    limits: Limits,
+    jobs: usize,
+    mut cache: Option<&mut ResultCache>,
+) -> Vec<TestResult> {
+    let mut results: Vec<TestResult> = Vec::with_capacity(roms.len());
+    let mut pending = Vec::new();
+    for path in roms {
+        let (rom, rom_hash) = match fs::read(path) {
+            Ok(rom) => {
+                let hash = cartridge::crc32(&rom);
+                (rom, hash)
+            }
+            Err(e) => {
+                results.push(TestResult {
+                    rom: path.clone(),
+                    rom_hash: 0,
+                    outcome: Outcome::Crashed(e.to_string()),
+                    frames: 0,
+                    cached: false,
+                });
+                continue;
+            }
+        };
+        let fixture_path = fixture_path(path);
+        let fixture = match fixture_path.exists().then(|| Fixture::load(&fixture_path)) {
+            Some(Ok(fixture)) => Some(fixture),
+            Some(Err(e)) => {
+                results.push(TestResult {
+                    rom: path.clone(),
+                    rom_hash,
+                    outcome: Outcome::Crashed(e),
+                    frames: 0,
+                    cached: false,
+                });
+                continue;
+            }
+            None => None,
+        };
+        if let Some((outcome, frames)) = cache
+            .as_ref()
+            .filter(|_| fixture.is_none())
+            .and_then(|c| c.get(rom_hash, limits))
+        {
+            results.push(TestResult {
+                rom: path.clone(),
+                rom_hash,
+                outcome,
+                frames,
+                cached: true,
+            });
+        } else {
+            pending.push((path.clone(), rom_hash, Mutex::new(Some(rom)), fixture));
+        }
+    }
+
+    let next = AtomicUsize::new(0);
+    let finished = Mutex::new(Vec::with_capacity(pending.len()));
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| {
+                while let Some((path, rom_hash, rom, fixture)) =
+                    pending.get(next.fetch_add(1, Ordering::Relaxed))
+                {
+                    let rom = rom.lock().unwrap().take().unwrap_or_default();
+                    let (outcome, frames) = run_rom(rom, limits, fixture.as_ref());
+                    finished.lock().unwrap().push(TestResult {
+                        rom: path.clone(),
+                        rom_hash: *rom_hash,
+                        outcome,
+                        frames,
+                        cached: false,
+                    });
+                }
+            });
+        }
+    });
+
+    for result in finished.into_inner().unwrap() {
+        let has_fixture = pending
+            .iter()
+            .any(|(path, _, _, fixture)| *path == result.rom && fixture.is_some());
+        if let Some(ref mut cache) = cache
+            && !has_fixture
+        {
+            cache.insert(result.rom_hash, result.outcome.clone(), result.frames);
+        }
+        results.push(result);
+    }
+    results.sort_by_key(|result| roms.iter().position(|rom| *rom == result.rom));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_rom;
+
+    const LIMITS: Limits = Limits {
+        frames: 10,
+        timeout: Duration::from_secs(10),
+    };
+
+    #[test]
+    fn mooneye_registers_pass() {
+        let code = [
+            0x06, 3, 0x0E, 5, 0x16, 8, 0x1E, 13, 0x26, 21, 0x2E, 34, // LD B..L, n
+            0x18, 0xFE, // JR -2
+        ];
+        assert_eq!(run_rom(test_rom(&code), LIMITS, None), (Outcome::Passed, 1));
+    }
+
+    #[test]
+    fn endless_loop_times_out_and_bad_rom_crashes() {
+        assert_eq!(
+            run_rom(test_rom(&[0x18, 0xFE]), LIMITS, None),
+            (Outcome::Timeout, 10)
+        );
+        assert!(matches!(
+            run_rom(vec![0; 16], LIMITS, None),
+            (Outcome::Crashed(_), 0)
+        ));
+    }
+
+    #[test]
+    fn test_mode_reports_verdict_and_stop_reason() {
+        let code = [
+            0x06, 3, 0x0E, 5, 0x16, 8, 0x1E, 13, 0x26, 21, 0x2E, 34, // LD B..L, n
+            0x18, 0xFE, // JR -2
+        ];
+        let mut game = GameBoy::new();
+        game.memory
+            .load_cartridge(Cartridge::from_rom(test_rom(&code)).unwrap());
+        game.power_on();
+        let report = run_test(&mut game, 100);
+        assert_eq!(report.outcome, Outcome::Passed);
+        assert_eq!(report.stop, StopReason::InstructionLimit);
+        assert_eq!(report.instructions, 100);
+        assert_eq!(report.to_json()["exit_code"], 0);
+
+        let mut game = GameBoy::new();
+        game.memory
+            .load_cartridge(Cartridge::from_rom(test_rom(&[0x00, 0x76])).unwrap());
+        game.power_on();
+        let report = run_test(&mut game, 100);
+        assert_eq!(report.stop, StopReason::Halted);
+        assert_eq!(report.instructions, 2);
+        assert_eq!(report.cycles, 8);
+        assert_eq!(report.outcome.exit_code(), 3);
+    }
+
+    #[test]
+    fn cache_round_trips_and_skips_timeouts() {
+        let path = env_path("cache");
+        let mut cache = ResultCache::new("1.0+ABCD".to_string());
+        cache.insert(0x1234, Outcome::Passed, 5);
+        cache.insert(0x5678, Outcome::Failed("Failed #2".to_string()), 20);
+        cache.insert(0x9ABC, Outcome::Timeout, 10);
+        cache.save(&path).unwrap();
+
+        let loaded = ResultCache::load(&path, "1.0+ABCD".to_string());
+        let other_core = ResultCache::load(&path, "1.0+0000".to_string());
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, cache);
+        assert_eq!(loaded.get(0x1234, LIMITS), Some((Outcome::Passed, 5)));
+        assert_eq!(loaded.get(0x5678, LIMITS), None); // Verdict came after the budget
+        assert_eq!(loaded.get(0x9ABC, LIMITS), None);
+        assert!(other_core.unwrap().results.is_empty());
+    }
+
+    #[test]
+    fn run_all_uses_the_cache() {
+        let dir = env_path("roms");
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let looping = test_rom(&[0x18, 0xFE]);
+        let halting = test_rom(&[0x76]);
+        fs::write(dir.join("loop.gb"), &looping).unwrap();
+        fs::write(dir.join("nested/halt.gb"), &halting).unwrap();
+        let roms = find_roms(&dir).unwrap();
+
+        let mut cache = ResultCache::new("test".to_string());
+        cache.insert(cartridge::crc32(&looping), Outcome::Passed, 1);
+        let results = run_all(&roms, LIMITS, 2, Some(&mut cache));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(roms, vec![dir.join("loop.gb"), dir.join("nested/halt.gb")]);
+        assert_eq!(results[0].outcome, Outcome::Passed);
+        assert!(results[0].cached);
+        assert!(matches!(results[1].outcome, Outcome::Failed(_)));
+        assert!(!results[1].cached);
+        assert!(cache.get(cartridge::crc32(&halting), LIMITS).is_some());
+    }
+
+    #[test]
+    fn fixtures_decide_and_are_never_cached() {
+        let dir = env_path("fixtures");
+        fs::create_dir_all(&dir).unwrap();
+        // LD A,n; LD (C000),A; JR -2
+        let failing = test_rom(&[0x3E, 5, 0xEA, 0x00, 0xC0, 0x18, 0xFE]);
+        let passing = test_rom(&[0x3E, 7, 0xEA, 0x00, 0xC0, 0x18, 0xFE]);
+        fs::write(dir.join("fail.gb"), &failing).unwrap();
+        fs::write(dir.join("fail.fixture"), "5 expect 0xC000 == 3\n").unwrap();
+        fs::write(dir.join("pass.gb"), &passing).unwrap();
+        fs::write(
+            dir.join("pass.fixture"),
+            "30 press start\n60 expect 0xC000 == 7\n",
+        )
+        .unwrap();
+        let roms = find_roms(&dir).unwrap();
+
+        let mut cache = ResultCache::new("test".to_string());
+        cache.insert(cartridge::crc32(&failing), Outcome::Passed, 1);
+        let results = run_all(&roms, LIMITS, 2, Some(&mut cache));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(results[0].outcome, Outcome::Failed(ref e) if e.contains("frame 5")));
+        assert!(!results[0].cached, "Cached verdict ignored");
+        assert_eq!(
+            (results[1].outcome.clone(), results[1].frames),
+            (Outcome::Passed, 60)
+        );
+        assert!(cache.get(cartridge::crc32(&passing), LIMITS).is_none());
+    }
+
+    fn env_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gameboy-harness-{name}-{}", std::process::id()))
+    }
+}
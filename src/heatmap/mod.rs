@@ -0,0 +1,122 @@
+use std::cell::Cell;
+
+/// Side length of the heat map image: one pixel per address, 256 per row
+pub const HEAT_MAP_SIZE: usize = 256;
+
+const ADDRESS_SPACE: usize = 0x10000;
+
+/// Recent reads, writes and executes across the whole 64K address space.
+/// Each access sets that address to full heat, which then fades every frame,
+/// so variables a game touches constantly stay lit.
+///
+/// Reads come in through `&Memory`, hence the cells.
+pub struct HeatMap {
+    reads: Vec<Cell<u8>>,
+    writes: Vec<u8>,
+    executes: Vec<u8>,
+}
+
+impl Default for HeatMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeatMap {
+    pub fn new() -> Self {
+        Self {
+            reads: vec![Cell::new(0); ADDRESS_SPACE],
+            writes: vec![0; ADDRESS_SPACE],
+            executes: vec![0; ADDRESS_SPACE],
+        }
+    }
+
+    pub fn record_read(&self, address: u16) {
+        self.reads[usize::from(address)].set(u8::MAX);
+    }
+
+    pub fn record_write(&mut self, address: u16) {
+        self.writes[usize::from(address)] = u8::MAX;
+    }
+
+    pub fn record_execute(&mut self, address: u16) {
+        self.executes[usize::from(address)] = u8::MAX;
+    }
+
+    /// Fade everything by 1/8, called once per frame
+    pub fn decay(&mut self) {
+        let fade = |heat: u8| heat - heat.div_ceil(8);
+        for read in &self.reads {
+            read.set(fade(read.get()));
+        }
+        for heat in self.writes.iter_mut().chain(self.executes.iter_mut()) {
+            *heat = fade(*heat);
+        }
+    }
+
+    /// Current (read, write, execute) heat of one address
+    pub fn heat(&self, address: u16) -> (u8, u8, u8) {
+        let index = usize::from(address);
+        (
+            self.reads[index].get(),
+            self.writes[index],
+            self.executes[index],
+        )
+    }
+
+    /// `HEAT_MAP_SIZE` x `HEAT_MAP_SIZE` RGBA8 image, address 0x0000 at the
+    /// top left and one row per 256 bytes. Writes are red, reads green and
+    /// executes blue.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        (0..ADDRESS_SPACE)
+            .flat_map(|index| {
+                [
+                    self.writes[index],
+                    self.reads[index].get(),
+                    self.executes[index],
+                    0xFF,
+                ]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accesses_light_up_and_fade() {
+        let mut map = HeatMap::new();
+        map.record_read(0xC000);
+        map.record_write(0xC001);
+        map.record_execute(0x0150);
+
+        assert_eq!(map.heat(0xC000), (0xFF, 0, 0));
+        assert_eq!(map.heat(0xC001), (0, 0xFF, 0));
+        assert_eq!(map.heat(0x0150), (0, 0, 0xFF));
+
+        map.decay();
+        assert_eq!(map.heat(0xC000).0, 0xDF);
+    }
+
+    #[test]
+    fn heat_fades_to_zero() {
+        let mut map = HeatMap::new();
+        map.record_write(0x8000);
+        for _ in 0..60 {
+            map.decay();
+        }
+        assert_eq!(map.heat(0x8000), (0, 0, 0));
+    }
+
+    #[test]
+    fn image_has_one_pixel_per_address() {
+        let mut map = HeatMap::new();
+        map.record_write(0x0101);
+
+        let rgba = map.to_rgba();
+        assert_eq!(rgba.len(), HEAT_MAP_SIZE * HEAT_MAP_SIZE * 4);
+        assert_eq!(rgba[0x0101 * 4..0x0101 * 4 + 4], [0xFF, 0, 0, 0xFF]);
+    }
+}
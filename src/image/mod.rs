@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// An RGBA8 image, row by row from the top left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+/// Read a PNG file of any color type as RGBA8
+pub fn read_png(path: &Path) -> io::Result<Image> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "image too large"))?;
+    let mut pixels = vec![0; size];
+    let info = reader.next_frame(&mut pixels).map_err(io::Error::other)?;
+    pixels.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels,
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g, 0xFF]).collect(),
+        png::ColorType::Indexed => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "indexed PNG was not expanded",
+            ));
+        }
+    };
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        rgba,
+    })
+}
+
+/// Write `rgba` (`width` x `height` RGBA8 pixels) as a PNG file
+pub fn write_png(path: &Path, width: usize, height: usize, rgba: &[u8]) -> io::Result<()> {
+    let dimension = |value: usize| {
+        u32::try_from(value)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "image too large"))
+    };
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, dimension(width)?, dimension(height)?);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgba).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn png_round_trips() {
+        let path = env::temp_dir().join(format!("gameboy-image-rt-{}.png", std::process::id()));
+        let rgba = vec![1, 2, 3, 255, 4, 5, 6, 128];
+
+        write_png(&path, 1, 2, &rgba).unwrap();
+        let image = read_png(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            image,
+            Image {
+                width: 1,
+                height: 2,
+                rgba
+            }
+        );
+    }
+
+    #[test]
+    fn writes_png_signature_and_rejects_wrong_size() {
+        let path = env::temp_dir().join(format!("gameboy-image-{}.png", std::process::id()));
+
+        write_png(&path, 2, 1, &[0xFF; 8]).unwrap();
+        assert_eq!(&fs::read(&path).unwrap()[..8], b"\x89PNG\r\n\x1a\n");
+        assert!(write_png(&path, 2, 2, &[0xFF; 8]).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
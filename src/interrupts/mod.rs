@@ -0,0 +1,152 @@
+use crate::savestate::{StateReader, StateWriter};
+
+/// Interrupt sources, in priority order (V-Blank is serviced first)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// Bit in IF and IE
+    pub fn mask(self) -> u8 {
+        match self {
+            Interrupt::VBlank => 0x01,
+            Interrupt::LcdStat => 0x02,
+            Interrupt::Timer => 0x04,
+            Interrupt::Serial => 0x08,
+            Interrupt::Joypad => 0x10,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Interrupt::VBlank => "vblank",
+            Interrupt::LcdStat => "stat",
+            Interrupt::Timer => "timer",
+            Interrupt::Serial => "serial",
+            Interrupt::Joypad => "joypad",
+        }
+    }
+
+    /// Address the CPU jumps to when servicing the interrupt
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x0040,
+            Interrupt::LcdStat => 0x0048,
+            Interrupt::Timer => 0x0050,
+            Interrupt::Serial => 0x0058,
+            Interrupt::Joypad => 0x0060,
+        }
+    }
+}
+
+/// The IF (0xFF0F) and IE (0xFFFF) registers. Every component requests its
+/// interrupt through here rather than poking IF directly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interrupts {
+    flags: u8,   // IF, requested interrupts (bits 0-4)
+    enabled: u8, // IE, all 8 bits are read/write
+}
+
+impl Interrupts {
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flags |= interrupt.mask();
+    }
+
+    /// Acknowledge `interrupt`, as servicing it does
+    pub fn clear(&mut self, interrupt: Interrupt) {
+        self.flags &= !interrupt.mask();
+    }
+
+    pub fn is_requested(&self, interrupt: Interrupt) -> bool {
+        self.flags & interrupt.mask() != 0
+    }
+
+    pub fn is_enabled(&self, interrupt: Interrupt) -> bool {
+        self.enabled & interrupt.mask() != 0
+    }
+
+    /// Whether IE enables any interrupt, so one could end HALT
+    pub fn any_enabled(&self) -> bool {
+        self.enabled & 0x1F != 0
+    }
+
+    /// Highest priority interrupt that is both requested and enabled
+    pub fn pending(&self) -> Option<Interrupt> {
+        Interrupt::ALL
+            .into_iter()
+            .find(|&interrupt| self.is_requested(interrupt) && self.is_enabled(interrupt))
+    }
+
+    // TODO: Real hardware reads the unused top three bits of IF as 1
+    pub(crate) fn read_register(&self, address: u16) -> u8 {
+        match address {
+            0xFF0F => self.flags,
+            0xFFFF => self.enabled,
+            _ => 0xFF,
+        }
+    }
+
+    pub(crate) fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF0F => self.flags = value & 0x1F,
+            0xFFFF => self.enabled = value,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.flags);
+        state.write_u8(self.enabled);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.flags = state.read_u8()? & 0x1F;
+        self.enabled = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_and_clear_only_touch_their_bit() {
+        let mut interrupts = Interrupts::default();
+        interrupts.write_register(0xFF0F, 0xFF);
+        assert_eq!(interrupts.read_register(0xFF0F), 0x1F);
+
+        interrupts.clear(Interrupt::Timer);
+        assert_eq!(interrupts.read_register(0xFF0F), 0x1B);
+        interrupts.request(Interrupt::Timer);
+        assert!(interrupts.is_requested(Interrupt::Timer));
+        assert_eq!(interrupts.read_register(0xFF0F), 0x1F);
+    }
+
+    #[test]
+    fn pending_is_the_highest_priority_enabled_request() {
+        let mut interrupts = Interrupts::default();
+        interrupts.request(Interrupt::Joypad);
+        interrupts.request(Interrupt::Timer);
+        assert_eq!(interrupts.pending(), None, "Nothing enabled");
+
+        interrupts.write_register(0xFFFF, 0xF4 | Interrupt::Joypad.mask());
+        assert_eq!(interrupts.read_register(0xFFFF), 0xF4 | 0x10);
+        assert_eq!(interrupts.pending(), Some(Interrupt::Timer));
+        interrupts.clear(Interrupt::Timer);
+        assert_eq!(interrupts.pending(), Some(Interrupt::Joypad));
+    }
+}
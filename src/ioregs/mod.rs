@@ -0,0 +1,180 @@
+use crate::memory::Memory;
+
+/// One memory-mapped hardware register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register {
+    /// Pan Docs name, upper case
+    pub name: &'static str,
+    pub address: u16,
+    /// Bits with nothing behind them, which read as 1
+    pub unused: u8,
+    /// Bits a write can change; the rest keep their value
+    pub writable: u8,
+}
+
+/// Builds a constant per register, `REGISTERS`, and a getter and setter
+/// on `Io` for each
+macro_rules! registers {
+    ($($get:ident, $set:ident, $name:ident, $address:literal, $unused:literal, $writable:literal;)*) => {
+        $(
+            pub const $name: Register = Register {
+                name: stringify!($name),
+                address: $address,
+                unused: $unused,
+                writable: $writable,
+            };
+        )*
+
+        /// The DMG's IO registers, in address order
+        pub const REGISTERS: &[Register] = &[$($name),*];
+
+        impl Io<'_> {
+            $(
+                #[doc = concat!(stringify!($name), " as the CPU reads it")]
+                pub fn $get(&self) -> u8 {
+                    self.read(&$name)
+                }
+
+                #[doc = concat!("Write ", stringify!($name), " as the CPU does")]
+                pub fn $set(&mut self, value: u8) {
+                    self.write(&$name, value);
+                }
+            )*
+        }
+    };
+}
+
+registers! {
+    p1, set_p1, P1, 0xFF00, 0xC0, 0x30;
+    sb, set_sb, SB, 0xFF01, 0x00, 0xFF;
+    sc, set_sc, SC, 0xFF02, 0x7E, 0x81;
+    div, set_div, DIV, 0xFF04, 0x00, 0xFF;
+    tima, set_tima, TIMA, 0xFF05, 0x00, 0xFF;
+    tma, set_tma, TMA, 0xFF06, 0x00, 0xFF;
+    tac, set_tac, TAC, 0xFF07, 0xF8, 0x07;
+    if_, set_if, IF, 0xFF0F, 0xE0, 0x1F;
+    nr10, set_nr10, NR10, 0xFF10, 0x80, 0x7F;
+    nr11, set_nr11, NR11, 0xFF11, 0x3F, 0xFF;
+    nr12, set_nr12, NR12, 0xFF12, 0x00, 0xFF;
+    nr13, set_nr13, NR13, 0xFF13, 0xFF, 0xFF;
+    nr14, set_nr14, NR14, 0xFF14, 0xBF, 0xC7;
+    nr21, set_nr21, NR21, 0xFF16, 0x3F, 0xFF;
+    nr22, set_nr22, NR22, 0xFF17, 0x00, 0xFF;
+    nr23, set_nr23, NR23, 0xFF18, 0xFF, 0xFF;
+    nr24, set_nr24, NR24, 0xFF19, 0xBF, 0xC7;
+    nr30, set_nr30, NR30, 0xFF1A, 0x7F, 0x80;
+    nr31, set_nr31, NR31, 0xFF1B, 0xFF, 0xFF;
+    nr32, set_nr32, NR32, 0xFF1C, 0x9F, 0x60;
+    nr33, set_nr33, NR33, 0xFF1D, 0xFF, 0xFF;
+    nr34, set_nr34, NR34, 0xFF1E, 0xBF, 0xC7;
+    nr41, set_nr41, NR41, 0xFF20, 0xFF, 0x3F;
+    nr42, set_nr42, NR42, 0xFF21, 0x00, 0xFF;
+    nr43, set_nr43, NR43, 0xFF22, 0x00, 0xFF;
+    nr44, set_nr44, NR44, 0xFF23, 0xBF, 0xC0;
+    nr50, set_nr50, NR50, 0xFF24, 0x00, 0xFF;
+    nr51, set_nr51, NR51, 0xFF25, 0x00, 0xFF;
+    nr52, set_nr52, NR52, 0xFF26, 0x70, 0x80;
+    lcdc, set_lcdc, LCDC, 0xFF40, 0x00, 0xFF;
+    stat, set_stat, STAT, 0xFF41, 0x80, 0x78;
+    scy, set_scy, SCY, 0xFF42, 0x00, 0xFF;
+    scx, set_scx, SCX, 0xFF43, 0x00, 0xFF;
+    ly, set_ly, LY, 0xFF44, 0x00, 0x00;
+    lyc, set_lyc, LYC, 0xFF45, 0x00, 0xFF;
+    dma, set_dma, DMA, 0xFF46, 0x00, 0xFF;
+    bgp, set_bgp, BGP, 0xFF47, 0x00, 0xFF;
+    obp0, set_obp0, OBP0, 0xFF48, 0x00, 0xFF;
+    obp1, set_obp1, OBP1, 0xFF49, 0x00, 0xFF;
+    wy, set_wy, WY, 0xFF4A, 0x00, 0xFF;
+    wx, set_wx, WX, 0xFF4B, 0x00, 0xFF;
+    ie, set_ie, IE, 0xFFFF, 0x00, 0xFF;
+}
+
+/// The register called `name`, in any case
+pub fn register(name: &str) -> Option<&'static Register> {
+    REGISTERS
+        .iter()
+        .find(|register| register.name.eq_ignore_ascii_case(name))
+}
+
+/// Named access to the IO registers, from `GameBoy::io`, so tests and tools
+/// need no magic addresses. Reads are side-effect free and come back as
+/// the hardware returns them, unused bits set. Writes go through the same
+/// path as CPU writes, so their side effects happen too: writing DIV
+/// resets it, and LY cannot be written at all.
+pub struct Io<'a> {
+    memory: &'a mut Memory,
+}
+
+impl<'a> Io<'a> {
+    pub fn new(memory: &'a mut Memory) -> Self {
+        Self { memory }
+    }
+
+    pub fn read(&self, register: &Register) -> u8 {
+        self.memory.peek(register.address) | register.unused
+    }
+
+    /// Write `value` to the writable bits of `register`
+    pub fn write(&mut self, register: &Register, value: u8) {
+        let kept = self.memory.peek(register.address) & !register.writable;
+        self.memory
+            .write_byte(register.address, kept | (value & register.writable));
+    }
+
+    /// Read the register called `name`
+    pub fn get(&self, name: &str) -> Result<u8, String> {
+        let register = register(name).ok_or_else(|| format!("Unknown IO register: {name}"))?;
+        Ok(self.read(register))
+    }
+
+    /// Write the register called `name`
+    pub fn set(&mut self, name: &str, value: u8) -> Result<(), String> {
+        let register = register(name).ok_or_else(|| format!("Unknown IO register: {name}"))?;
+        self.write(register, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_is_sorted_and_unique() {
+        assert!(
+            REGISTERS
+                .windows(2)
+                .all(|pair| pair[0].address < pair[1].address)
+        );
+        assert_eq!(
+            register("lcdc").map(|register| register.address),
+            Some(0xFF40)
+        );
+        assert_eq!(register("NR53"), None);
+    }
+
+    #[test]
+    fn masks_apply_to_reads_and_writes() {
+        let mut memory = Memory::new();
+        let mut io = Io::new(&mut memory);
+        io.set_scx(0x42);
+        assert_eq!(io.scx(), 0x42);
+        assert_eq!(io.memory.peek(0xFF43), 0x42);
+
+        io.set_tac(0xFF);
+        assert_eq!(io.memory.peek(0xFF07) & 0x07, 0x07);
+        assert_eq!(io.tac(), 0xFF);
+        io.set_if(0x01);
+        assert_eq!(io.if_(), 0xE1, "Top three bits read as 1");
+
+        io.set_nr30(0x00);
+        io.set_nr30(0x7F); // Only bit 7 is writable
+        assert_eq!(io.memory.peek(0xFF1A), 0x00);
+        assert_eq!(io.nr30(), 0x7F);
+
+        assert_eq!(io.get("Ly"), Ok(0));
+        io.set("ly", 0x99).unwrap();
+        assert_eq!(io.ly(), 0, "Read-only");
+        assert!(io.set("LX", 0).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::cpu::Cpu;
+use crate::cpu::opcodes::{CB_OPCODES, OPCODES, Opcode};
+use crate::memory::Memory;
+use serde_json::{Value, json};
+
+/// Where probed instructions are placed (work RAM, so operands read as zero)
+const PROBE_ADDRESS: u16 = 0xC000;
+
+/// One row of the instruction set reference: the table's metadata plus what
+/// the CPU actually does with the opcode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeEntry {
+    /// CB-prefixed instruction
+    pub prefixed: bool,
+    pub code: u8,
+    pub info: &'static Opcode,
+    /// Cycles the CPU reported with all flags clear and with all flags set
+    /// (so conditional branches run both ways)
+    pub measured: [u8; 2],
+}
+
+impl OpcodeEntry {
+    /// Whether the measured cycles agree with the table. Illegal opcodes and
+    /// the CB prefix itself have no meaningful count and always agree.
+    pub fn cycles_match(&self) -> bool {
+        if self.info.is_illegal() || (!self.prefixed && self.code == 0xCB) {
+            return true;
+        }
+        let mut expected = [self.info.cycles, self.info.branch_cycles];
+        let mut measured = self.measured;
+        measured.sort_unstable();
+        expected.sort_unstable();
+        measured == expected
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "code": self.code,
+            "prefixed": self.prefixed,
+            "mnemonic": self.info.mnemonic,
+            "length": self.info.length,
+            "cycles": self.info.cycles,
+            "branch_cycles": self.info.branch_cycles,
+            "flags": self.info.flags(),
+            "illegal": self.info.is_illegal(),
+            "measured_cycles": self.measured,
+        })
+    }
+}
+
+/// Every base and CB-prefixed opcode, each executed on a scratch CPU
+pub fn entries() -> Vec<OpcodeEntry> {
+    let base = (0..=0xFF).map(|code| (false, code, &OPCODES[usize::from(code)]));
+    let prefixed = (0..=0xFF).map(|code| (true, code, &CB_OPCODES[usize::from(code)]));
+    base.chain(prefixed)
+        .map(|(prefixed, code, info)| {
+            let bytes = if prefixed { [0xCB, code] } else { [code, 0x00] };
+            OpcodeEntry {
+                prefixed,
+                code,
+                info,
+                measured: [measure(bytes, 0x00), measure(bytes, 0xF0)],
+            }
+        })
+        .collect()
+}
+
+/// Cycles taken by the instruction starting with `bytes` when run with the
+/// flags register set to `flags`
+fn measure(bytes: [u8; 2], flags: u8) -> u8 {
+    let mut memory = Memory::default();
+    memory.write_byte(PROBE_ADDRESS, bytes[0]);
+    memory.write_byte(PROBE_ADDRESS + 1, bytes[1]);
+    let mut cpu = Cpu::new();
+    cpu.pc = PROBE_ADDRESS;
+    cpu.registers.f.set_from_u8(flags);
+    cpu.execute(&mut memory)
+}
+
+/// Machine-readable instruction set reference for debugger UIs and tests:
+/// one object per opcode plus a summary
+pub fn to_json(entries: &[OpcodeEntry]) -> Value {
+    let legal = entries.iter().filter(|entry| !entry.info.is_illegal());
+    let mismatches: Vec<&str> = entries
+        .iter()
+        .filter(|entry| !entry.cycles_match())
+        .map(|entry| entry.info.mnemonic)
+        .collect();
+    json!({
+        "opcodes": entries.iter().map(OpcodeEntry::to_json).collect::<Vec<_>>(),
+        "summary": {
+            "legal": legal.count(),
+            "cycle_mismatches": mismatches,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcodes_take_the_cycles_in_the_table() {
+        let entries = entries();
+        assert_eq!(entries.len(), 512);
+        let mismatches: Vec<String> = entries
+            .iter()
+            .filter(|entry| !entry.cycles_match())
+            .map(|entry| format!("{} measured {:?}", entry.info.mnemonic, entry.measured))
+            .collect();
+        assert!(mismatches.is_empty(), "{mismatches:#?}");
+    }
+
+    #[test]
+    fn json_describes_each_opcode() {
+        let json = to_json(&entries());
+        let jr_nz = &json["opcodes"][0x20];
+        assert_eq!(jr_nz["mnemonic"], "jr nz, e8");
+        assert_eq!(jr_nz["flags"], "----");
+        assert_eq!(jr_nz["measured_cycles"], json!([12, 8]));
+        assert_eq!(json["opcodes"][0x100 + 0x37]["mnemonic"], "swap a");
+        assert_eq!(json["summary"]["legal"], 501);
+    }
+}
@@ -0,0 +1,323 @@
+use super::{Button, Buttons};
+use crate::gameboy::CYCLES_PER_FRAME;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Frames per second of the original Game Boy (about 59.73)
+const FRAMES_PER_SECOND: f64 = 4_194_304.0 / CYCLES_PER_FRAME as f64;
+
+const ALL_BUTTONS: [Button; 8] = [
+    Button::Right,
+    Button::Left,
+    Button::Up,
+    Button::Down,
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+];
+
+/// Recorded input, one set of held buttons per frame.
+///
+/// As text, each line holds the buttons for one frame joined with `+` (`-`
+/// for none), optionally followed by `xN` to repeat it for N frames:
+///
+/// ```text
+/// right x30
+/// right+a x4
+/// - x10
+/// start
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputMacro {
+    frames: Vec<Buttons>,
+}
+
+impl InputMacro {
+    pub fn new(frames: Vec<Buttons>) -> Self {
+        Self { frames }
+    }
+
+    pub fn frames(&self) -> &[Buttons] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl FromStr for InputMacro {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut frames = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {message}", index + 1);
+
+            let (names, count) = match line.split_once(char::is_whitespace) {
+                Some((names, repeat)) => {
+                    let count = repeat
+                        .trim()
+                        .strip_prefix('x')
+                        .and_then(|count| count.parse::<usize>().ok())
+                        .ok_or_else(|| error(format!("invalid repeat: {repeat}")))?;
+                    (names, count)
+                }
+                None => (line, 1),
+            };
+            let mut buttons = Buttons::new();
+            if names != "-" {
+                for name in names.split('+') {
+                    buttons.press(name.parse().map_err(error)?);
+                }
+            }
+            frames.extend(std::iter::repeat_n(buttons, count));
+        }
+        Ok(Self { frames })
+    }
+}
+
+impl fmt::Display for InputMacro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut frames = self.frames.iter().peekable();
+        while let Some(&buttons) = frames.next() {
+            let mut count = 1;
+            while frames.next_if_eq(&&buttons).is_some() {
+                count += 1;
+            }
+            let names: Vec<&str> = ALL_BUTTONS
+                .into_iter()
+                .filter(|&button| buttons.is_pressed(button))
+                .map(Button::name)
+                .collect();
+            let names = if names.is_empty() {
+                "-".to_string()
+            } else {
+                names.join("+")
+            };
+            if count == 1 {
+                writeln!(f, "{names}")?;
+            } else {
+                writeln!(f, "{names} x{count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Auto-fire for one held button
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Turbo {
+    period: u64,             // Frames per press-release cycle
+    held_since: Option<u64>, // Frame the host started holding the button
+}
+
+/// Sits between the host's buttons and the joypad: turbo buttons toggle
+/// while held, and input macros are recorded and played back frame by frame.
+///
+/// Lives in the core so turbo and macros behave the same in a window, in
+/// scripts and headless. `GameBoy` advances it once per frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputLayer {
+    host: Buttons,
+    frame: u64,
+    turbo: HashMap<Button, Turbo>,
+    recording: Option<Vec<Buttons>>,
+    macros: HashMap<String, InputMacro>,
+    playing: Option<(String, usize)>, // Macro name and frame within it
+}
+
+impl InputLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Auto-fire `button` at `hz` presses per second while it is held, or
+    /// turn turbo off with `None`. Rates are rounded to whole frames, so the
+    /// fastest is one press every two frames (about 30 Hz).
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn set_turbo(&mut self, button: Button, hz: Option<f64>) {
+        let Some(hz) = hz.filter(|&hz| hz > 0.0) else {
+            self.turbo.remove(&button);
+            return;
+        };
+        let period = (FRAMES_PER_SECOND / hz).round().max(2.0) as u64;
+        let held_since = self.host.is_pressed(button).then_some(self.frame);
+        self.turbo.insert(button, Turbo { period, held_since });
+    }
+
+    /// Buttons the host holds this frame
+    pub fn set_host(&mut self, buttons: Buttons) {
+        for (&button, turbo) in &mut self.turbo {
+            if !buttons.is_pressed(button) {
+                turbo.held_since = None;
+            } else if turbo.held_since.is_none() {
+                turbo.held_since = Some(self.frame);
+            }
+        }
+        self.host = buttons;
+    }
+
+    /// Buttons the game sees this frame
+    pub fn effective(&self) -> Buttons {
+        let mut buttons = self.host;
+        for (&button, turbo) in &self.turbo {
+            if let Some(since) = turbo.held_since
+                && (self.frame - since) % turbo.period >= turbo.period.div_ceil(2)
+            {
+                buttons.release(button);
+            }
+        }
+        if let Some((ref name, index)) = self.playing
+            && let Some(&played) = self.macros.get(name).and_then(|m| m.frames.get(index))
+        {
+            buttons = Buttons::from_bits(buttons.bits() | played.bits());
+        }
+        buttons
+    }
+
+    /// Move on to the next frame, recording the host's buttons for the one
+    /// that just ended
+    pub fn advance_frame(&mut self) {
+        if let Some(ref mut recording) = self.recording {
+            recording.push(self.host);
+        }
+        if let Some((ref name, ref mut index)) = self.playing {
+            *index += 1;
+            if *index >= self.macros.get(name).map_or(0, InputMacro::len) {
+                self.playing = None;
+            }
+        }
+        self.frame += 1;
+    }
+
+    /// Start recording the host's buttons, discarding any recording in progress
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Stop recording and keep the macro under `name` (typically a hotkey),
+    /// replacing any macro already there. Returns the recording.
+    pub fn stop_recording(&mut self, name: &str) -> Option<&InputMacro> {
+        let frames = self.recording.take()?;
+        self.macros
+            .insert(name.to_string(), InputMacro::new(frames));
+        self.macros.get(name)
+    }
+
+    /// Bind a macro loaded from elsewhere
+    pub fn add_macro(&mut self, name: &str, input: InputMacro) {
+        self.macros.insert(name.to_string(), input);
+    }
+
+    pub fn get_macro(&self, name: &str) -> Option<&InputMacro> {
+        self.macros.get(name)
+    }
+
+    /// Play the macro under `name` from its first frame, on top of whatever
+    /// the host holds
+    pub fn play(&mut self, name: &str) -> Result<(), String> {
+        match self.macros.get(name) {
+            Some(input) if !input.is_empty() => {
+                self.playing = Some((name.to_string(), 0));
+                Ok(())
+            }
+            Some(_) => Ok(()),
+            None => Err(format!("No macro named {name}")),
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Effective buttons over `frames` frames with `host` held throughout
+    fn run(input: &mut InputLayer, host: Buttons, frames: usize) -> Vec<u8> {
+        (0..frames)
+            .map(|_| {
+                input.set_host(host);
+                let bits = input.effective().bits();
+                input.advance_frame();
+                bits
+            })
+            .collect()
+    }
+
+    #[test]
+    fn turbo_toggles_while_held() {
+        let mut input = InputLayer::new();
+        input.set_turbo(Button::A, Some(15.0)); // Period of 4 frames
+        let a = Button::A.mask();
+        let b = Button::B.mask();
+
+        assert_eq!(run(&mut input, Buttons::new(), 2), vec![0, 0]);
+        assert_eq!(
+            run(
+                &mut input,
+                Buttons::new().with(Button::A).with(Button::B),
+                6
+            ),
+            vec![a | b, a | b, b, b, a | b, a | b]
+        );
+
+        input.set_turbo(Button::A, None);
+        assert_eq!(
+            run(&mut input, Buttons::new().with(Button::A), 3),
+            vec![a; 3]
+        );
+    }
+
+    #[test]
+    fn macros_record_and_play_back() {
+        let mut input = InputLayer::new();
+        input.start_recording();
+        run(&mut input, Buttons::new().with(Button::Right), 2);
+        run(&mut input, Buttons::new().with(Button::A), 1);
+        let recorded = input.stop_recording("f1").unwrap().clone();
+        assert_eq!(recorded.to_string(), "right x2\na\n");
+
+        input.play("f1").unwrap();
+        let start = Buttons::new().with(Button::Start).bits();
+        let right = Button::Right.mask();
+        assert_eq!(
+            run(&mut input, Buttons::from_bits(start), 4),
+            vec![
+                start | right,
+                start | right,
+                start | Button::A.mask(),
+                start
+            ]
+        );
+        assert!(!input.is_playing());
+        assert!(input.play("f2").is_err());
+    }
+
+    #[test]
+    fn macro_text_round_trips() {
+        let text = "right x30\nright+a x4\n- x10\nstart\n";
+        let input: InputMacro = text.parse().unwrap();
+        assert_eq!(input.len(), 45);
+        assert_eq!(input.to_string(), text);
+        assert!("jump".parse::<InputMacro>().is_err());
+        assert!("a y3".parse::<InputMacro>().is_err());
+    }
+}
@@ -0,0 +1,252 @@
+mod input;
+
+pub use input::{InputLayer, InputMacro};
+
+use crate::savestate::{StateReader, StateWriter};
+use std::str::FromStr;
+
+/// Game Boy buttons, as seen by the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl FromStr for Button {
+    type Err = String;
+
+    /// Parse a button name (case-insensitive), e.g. "a", "start", "up"
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "right" => Ok(Button::Right),
+            "left" => Ok(Button::Left),
+            "up" => Ok(Button::Up),
+            "down" => Ok(Button::Down),
+            "a" => Ok(Button::A),
+            "b" => Ok(Button::B),
+            "select" => Ok(Button::Select),
+            "start" => Ok(Button::Start),
+            _ => Err(format!("Unknown button: {name}")),
+        }
+    }
+}
+
+impl Button {
+    /// Lowercase name, as accepted by `from_str`
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::Right => "right",
+            Button::Left => "left",
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::A => "a",
+            Button::B => "b",
+            Button::Select => "select",
+            Button::Start => "start",
+        }
+    }
+
+    /// Bit used for this button in a `Buttons` mask
+    fn mask(self) -> u8 {
+        match self {
+            Button::Right => 0x01,
+            Button::Left => 0x02,
+            Button::Up => 0x04,
+            Button::Down => 0x08,
+            Button::A => 0x10,
+            Button::B => 0x20,
+            Button::Select => 0x40,
+            Button::Start => 0x80,
+        }
+    }
+}
+
+/// Set of currently held buttons (1 = pressed).
+/// Low nibble is the d-pad, high nibble the action buttons.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Buttons(u8);
+
+impl Buttons {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    #[must_use]
+    pub fn with(mut self, button: Button) -> Self {
+        self.press(button);
+        self
+    }
+
+    pub fn press(&mut self, button: Button) {
+        self.0 |= button.mask();
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.0 &= !button.mask();
+    }
+
+    pub fn is_pressed(self, button: Button) -> bool {
+        self.0 & button.mask() != 0
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+/// P1/JOYP register (0xFF00)
+pub struct Joypad {
+    select: u8, // bits 4-5 as written by the game (0 = line selected)
+    buttons: Buttons,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            select: 0x30,
+            buttons: Buttons::new(),
+        }
+    }
+
+    /// Read P1. Bits 6-7 are unused and read as 1, the lower nibble is
+    /// active-low for whichever button lines the game has selected.
+    pub fn read_register(&self) -> u8 {
+        0xC0 | self.select | self.lines()
+    }
+
+    /// Only the select bits (4-5) are writable
+    pub fn write_register(&mut self, value: u8) {
+        self.select = value & 0x30;
+    }
+
+    pub fn buttons(&self) -> Buttons {
+        self.buttons
+    }
+
+    /// Update the held buttons, returning true if the joypad interrupt should
+    /// be requested (a selected line went from high to low)
+    pub fn set_buttons(&mut self, buttons: Buttons) -> bool {
+        let before = self.lines();
+        self.buttons = buttons;
+        let after = self.lines();
+        before & !after != 0
+    }
+
+    /// Whether the game has selected the d-pad or button lines, so a press
+    /// can reach P1
+    pub fn any_line_selected(&self) -> bool {
+        self.select != 0x30
+    }
+
+    /// Whether a held button pulls a selected line low, which ends STOP
+    pub fn selected_line_low(&self) -> bool {
+        self.lines() != 0x0F
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.select);
+        state.write_u8(self.buttons.bits());
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.select = state.read_u8()? & 0x30;
+        self.buttons = Buttons::from_bits(state.read_u8()?);
+        Ok(())
+    }
+
+    /// Lower nibble of P1 (0 = pressed)
+    fn lines(&self) -> u8 {
+        let mut pressed = 0;
+        if self.select & 0x10 == 0 {
+            pressed |= self.buttons.bits() & 0x0F;
+        }
+        if self.select & 0x20 == 0 {
+            pressed |= self.buttons.bits() >> 4;
+        }
+        !pressed & 0x0F
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_names_parse_case_insensitively() {
+        assert_eq!("Start".parse(), Ok(Button::Start));
+        assert_eq!("a".parse(), Ok(Button::A));
+        assert!("turbo".parse::<Button>().is_err());
+    }
+
+    #[test]
+    fn nothing_selected_reads_all_high() {
+        let mut joypad = Joypad::new();
+        joypad.set_buttons(Buttons::new().with(Button::A).with(Button::Up));
+        assert_eq!(joypad.read_register(), 0xFF);
+    }
+
+    #[test]
+    fn dpad_selected_reports_directions() {
+        let mut joypad = Joypad::new();
+        joypad.write_register(0x20); // Select d-pad
+        joypad.set_buttons(Buttons::new().with(Button::Left).with(Button::A));
+        assert_eq!(joypad.read_register(), 0xED);
+    }
+
+    #[test]
+    fn action_selected_reports_buttons() {
+        let mut joypad = Joypad::new();
+        joypad.write_register(0x10); // Select action buttons
+        joypad.set_buttons(Buttons::new().with(Button::Start).with(Button::Up));
+        assert_eq!(joypad.read_register(), 0xD7);
+    }
+
+    #[test]
+    fn only_select_bits_are_writable() {
+        let mut joypad = Joypad::new();
+        joypad.write_register(0x0F);
+        assert_eq!(joypad.read_register(), 0xCF);
+    }
+
+    #[test]
+    fn press_on_selected_line_requests_interrupt() {
+        let mut joypad = Joypad::new();
+        joypad.write_register(0x10);
+        assert!(joypad.set_buttons(Buttons::new().with(Button::B)));
+        assert!(!joypad.set_buttons(Buttons::new().with(Button::B)));
+    }
+
+    #[test]
+    fn only_selected_presses_pull_a_line_low() {
+        let mut joypad = Joypad::new();
+        joypad.set_buttons(Buttons::new().with(Button::Start));
+        assert!(!joypad.any_line_selected() && !joypad.selected_line_low());
+        joypad.write_register(0x20); // D-pad
+        assert!(joypad.any_line_selected() && !joypad.selected_line_low());
+        joypad.write_register(0x10); // Buttons
+        assert!(joypad.selected_line_low());
+    }
+
+    #[test]
+    fn press_on_unselected_line_does_not_request_interrupt() {
+        let mut joypad = Joypad::new();
+        joypad.write_register(0x20); // d-pad only
+        assert!(!joypad.set_buttons(Buttons::new().with(Button::B)));
+    }
+}
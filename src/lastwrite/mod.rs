@@ -0,0 +1,101 @@
+/// The most recent write to one address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastWrite {
+    pub value: u8,
+    /// Address of the instruction that wrote it, or `None` for writes from
+    /// outside the CPU: cheats, scripts, the debugger
+    pub pc: Option<u16>,
+    /// Cycle the writing instruction started on (see `GameBoy::cycle_count`)
+    pub cycle: u64,
+    pub frame: u64,
+}
+
+/// Who last wrote each address, for answering "what clobbered 0xC123?"
+/// without single-stepping to the write. `GameBoy::step` marks the
+/// instruction about to run and `Memory::write_byte` records each write
+/// against it, so peeks and direct `data` writes are not seen.
+///
+/// One entry per address, overwritten by the next write: this keeps the
+/// latest writer, not a history.
+pub struct WriteTracker {
+    writes: Vec<Option<LastWrite>>,
+    /// The instruction executing, if any
+    pc: Option<u16>,
+    cycle: u64,
+    frame: u64,
+}
+
+impl WriteTracker {
+    pub fn new() -> Self {
+        Self {
+            writes: vec![None; 0x10000],
+            pc: None,
+            cycle: 0,
+            frame: 0,
+        }
+    }
+
+    /// Attribute the writes that follow to the instruction at `pc`
+    pub fn begin_instruction(&mut self, pc: u16, cycle: u64, frame: u64) {
+        self.pc = Some(pc);
+        self.cycle = cycle;
+        self.frame = frame;
+    }
+
+    /// The instruction has finished: writes until the next one come from
+    /// outside the CPU
+    pub fn end_instruction(&mut self, cycle: u64, frame: u64) {
+        self.pc = None;
+        self.cycle = cycle;
+        self.frame = frame;
+    }
+
+    pub fn record(&mut self, address: u16, value: u8) {
+        self.writes[usize::from(address)] = Some(LastWrite {
+            value,
+            pc: self.pc,
+            cycle: self.cycle,
+            frame: self.frame,
+        });
+    }
+
+    /// The last write to `address` since tracking started
+    pub fn last_write(&self, address: u16) -> Option<LastWrite> {
+        self.writes[usize::from(address)]
+    }
+}
+
+impl Default for WriteTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_writes_replace_earlier_ones() {
+        let mut tracker = WriteTracker::new();
+        assert_eq!(tracker.last_write(0xC123), None);
+
+        tracker.begin_instruction(0x0150, 100, 1);
+        tracker.record(0xC123, 1);
+        tracker.end_instruction(108, 1);
+        tracker.record(0xC124, 2);
+        tracker.begin_instruction(0x0200, 70_300, 2);
+        tracker.record(0xC123, 3);
+
+        assert_eq!(
+            tracker.last_write(0xC123),
+            Some(LastWrite {
+                value: 3,
+                pc: Some(0x0200),
+                cycle: 70_300,
+                frame: 2,
+            })
+        );
+        assert_eq!(tracker.last_write(0xC124).map(|write| write.pc), Some(None));
+    }
+}
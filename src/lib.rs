@@ -0,0 +1,72 @@
+pub mod audio;
+pub mod audiodump;
+pub mod autosave;
+pub mod autosplit;
+pub mod bankaudit;
+pub mod bankstats;
+pub mod battery;
+pub mod bisect;
+pub mod bootlogo;
+pub mod cgb;
+pub mod cheats;
+pub mod clocktrace;
+pub mod debugger;
+pub mod disasm;
+pub mod eventlog;
+pub mod frame;
+pub mod framediff;
+pub mod framedump;
+pub mod frontend;
+pub mod harness;
+pub mod heatmap;
+pub mod image;
+pub mod ioregs;
+pub mod isadoc;
+pub mod lastwrite;
+pub mod objects;
+pub mod palette;
+pub mod paths;
+pub mod playtime;
+pub mod quirks;
+pub mod rammap;
+pub mod rewind;
+pub mod rpc;
+pub mod rtc;
+pub mod savestate;
+pub mod script;
+pub mod shader;
+pub mod snapshot;
+pub mod soak;
+pub mod throttle;
+pub mod tick;
+pub mod verify;
+pub mod watch;
+
+/// Declare the emulation core's modules. Nothing a ROM, an opcode stream or
+/// an IO write does may panic in them, so panicking macros, `unwrap` and
+/// `expect` are errors there outside tests; `fuzz/` runs arbitrary ROMs
+/// through them.
+macro_rules! core_modules {
+    ($($module:ident),*) => {
+        $(
+            #[cfg_attr(
+                not(test),
+                deny(
+                    clippy::panic,
+                    clippy::unwrap_used,
+                    clippy::expect_used,
+                    clippy::todo,
+                    clippy::unimplemented,
+                    clippy::unreachable
+                )
+            )]
+            pub mod $module;
+        )*
+    };
+}
+
+core_modules!(
+    apu, cartridge, cpu, gameboy, interrupts, joypad, memory, ppu, serial, timer
+);
+
+pub use crate::gameboy::GameBoy;
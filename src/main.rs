@@ -1,47 +1,1188 @@
-pub mod cartridge;
-pub mod cpu;
-mod gameboy;
-pub mod memory;
-mod timer;
 mod args;
 
-use crate::gameboy::GameBoy;
 use clap::Parser;
-use crate::args::{GameboyArgs, RunCommand, RunType, TestCommand};
+use gameboy::GameBoy;
+use gameboy::apu::Channel;
+use gameboy::audiodump::WavDumper;
+use gameboy::autosave::Autosave;
+use gameboy::autosplit::{Autosplitter, LiveSplit, SplitEvent};
+use gameboy::battery::BatterySave;
+use gameboy::bisect::Trace;
+use gameboy::cheats::CheatList;
+use gameboy::clocktrace::ClockTrace;
+use gameboy::eventlog::EventLogFormat;
+use gameboy::framedump::FrameDumper;
+use gameboy::frontend::{self, DisplayOptions, Hotkeys, TerminalSink, WindowBackend};
+use gameboy::harness::{Limits, ResultCache};
+use gameboy::palette::DmgPalette;
+use gameboy::paths::GameDirectory;
+use gameboy::playtime::{PlayTime, emulated_frames, format_duration};
+use gameboy::rammap::RamMap;
+use gameboy::rpc::RpcServer;
+use gameboy::verify::verify_rom;
+use gameboy::watch::{self, Watch};
+use gameboy::serial::LinkCable;
+use gameboy::shader::PostShader;
+use gameboy::soak::{SoakLimits, SoakOutcome};
+use gameboy::throttle::Throttle;
+use gameboy::savestate::{SaveState, THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::args::{
+    BisectCommand, CapabilitiesCommand, DisasmCommand, FramediffCommand, GameboyArgs, HarnessCommand, InfoCommand, OpcodesCommand, RunCommand, RunType,
+    SaveTransferCommand, ScriptCommand, SoakCommand, StateAction, StateInfoCommand, TestCommand, VerifyCommand,
+};
+
+/// Events kept by `--event-log`, roughly 40 seconds of mode changes
+const EVENT_LOG_CAPACITY: usize = 1_000_000;
+
+/// Settings shared by run and test mode once the ROM is loaded
+#[derive(Default)]
+struct Session {
+    data_dir: Option<String>,
+    fast_boot_frames: Option<u32>,
+    /// Play the logo scroll before the game
+    boot_logo: bool,
+    palette: Option<String>,
+    autosave: Option<Autosave>,
+    event_log: Option<(String, EventLogFormat)>,
+    /// Where to write the memory access heat map on exit
+    heat_map: Option<String>,
+    ram_map: Option<String>,
+    pause_at_frame: Option<u64>,
+    rpc: Option<String>,
+    /// Play in a window
+    frontend: Option<WindowBackend>,
+    /// Hotkey file for the window
+    hotkeys: Option<String>,
+    /// Post-process shader for the window
+    shader: Option<PostShader>,
+    /// Scale and filter for the window
+    display: DisplayOptions,
+    /// Play in real time for the terminal frame sink
+    terminal: bool,
+    /// Status line interval for long headless runs
+    watch: Option<Duration>,
+    /// Finish with a JSON result on stdout and a per-outcome exit code
+    test_report: bool,
+}
 
 fn main() {
     let args = GameboyArgs::parse();
     let mut game = GameBoy::new();
 
-    match args.run_type {
-        RunType::Run(RunCommand { rom }) => {
-            if let Err(e) = game.load_rom(&rom) {
-                eprintln!("Error loading ROM: {e}");
+    let session = match args.run_type {
+        RunType::Run(command) if command.rom.is_none() => {
+            show_splash(game, command);
+            return;
+        }
+        RunType::Run(command) => prepare_run(&mut game, command),
+        RunType::Test(TestCommand { rom, log, data_dir }) => {
+            load_rom(&mut game, &rom);
+            if let Err(e) = game.enable_logging(&log) {
+                eprintln!("Error creating log file: {e}");
                 std::process::exit(1);
             }
-        },
-        RunType::Test(TestCommand { rom, log }) => {
-            if let Err(e) = game.load_rom(&rom) {
-                eprintln!("Error loading ROM: {e}");
-                std::process::exit(1);
+            println!("Logging enabled to: {log}");
+            Session {
+                data_dir,
+                test_report: true,
+                ..Session::default()
+            }
+        }
+        RunType::Script(ScriptCommand {
+            rom,
+            script,
+            ram_map,
+            max_host_cpu,
+        }) => {
+            load_rom(&mut game, &rom);
+            game.set_throttle(max_host_cpu.map(Throttle::new));
+            run_script(game, &script, ram_map);
+            return;
+        }
+        RunType::ImportSave(command) => {
+            transfer_save(game, &command, SaveTransfer::Import);
+            return;
+        }
+        RunType::ExportSave(command) => {
+            transfer_save(game, &command, SaveTransfer::Export);
+            return;
+        }
+        RunType::Verify(command) => verify(&command),
+        RunType::Bisect(command) => {
+            load_rom(&mut game, &command.rom);
+            bisect(game, &command);
+        }
+        RunType::Framediff(command) => framediff(&command),
+        RunType::Harness(command) => harness(&command),
+        RunType::Soak(command) => soak(&command),
+        RunType::Opcodes(command) => {
+            opcodes(&command);
+            return;
+        }
+        RunType::Capabilities(command) => {
+            capabilities(&command);
+            return;
+        }
+        RunType::Disasm(command) => {
+            load_rom(&mut game, &command.rom);
+            disassemble(game, &command);
+            return;
+        }
+        RunType::Info(command) => {
+            load_rom(&mut game, &command.rom);
+            info(&game, &command);
+            return;
+        }
+        RunType::State(command) => {
+            match command.action {
+                StateAction::Info(info) => state_info(&info),
             }
+            return;
+        }
+    };
 
-            if let Err(e) = game.enable_logging(&log) {
-                eprintln!("Error creating log file: {e}");
+    run_session(game, session);
+}
+
+/// Load the ROM and apply run mode's options
+#[allow(clippy::too_many_lines)] // A line or two per option
+fn prepare_run(game: &mut GameBoy, command: RunCommand) -> Session {
+    let RunCommand {
+        rom,
+        data_dir,
+        mmap,
+        fast_boot,
+        fast_boot_frames,
+        boot_logo,
+        palette,
+        quirks,
+        state_check,
+        volume,
+        mute,
+        solo,
+        clock,
+        link_cable,
+        autosave_debounce,
+        autosave_max_interval,
+        event_log,
+        event_log_format,
+        clock_trace,
+        dump_frames,
+        every,
+        dump_audio,
+        pause_at_frame,
+        ram_map,
+        bank_stats,
+        bank_audit,
+        heat_map,
+        track_writes,
+        rpc,
+        frontend,
+        hotkeys,
+        shader,
+        scale,
+        filter,
+        ghosting,
+        terminal,
+        watch,
+        max_host_cpu,
+        autosplit,
+        livesplit,
+    } = command;
+
+    match rom {
+        Some(rom) if mmap => {
+            if let Err(e) = game.load_rom_mapped(&rom) {
+                eprintln!("Error mapping ROM: {e}");
                 std::process::exit(1);
             }
-            println!("Logging enabled to: {log}");
         }
+        Some(rom) => load_rom(game, &rom),
+        None => {}
+    }
+    game.set_quirk_policy(quirks);
+    game.set_state_check(state_check);
+    game.set_volume(volume);
+    select_channels(game, &mute, solo);
+    game.set_clock_source(clock.source());
+    if let Some(port) = link_cable {
+        connect_link_cable(game, &port);
+    }
+    if let Some(path) = autosplit {
+        enable_autosplit(game, &path, livesplit.as_deref());
     }
+    if event_log.is_some() {
+        game.enable_event_log(EVENT_LOG_CAPACITY);
+    }
+    if clock_trace {
+        game.enable_clock_trace(gameboy::clocktrace::DEFAULT_WINDOW);
+    }
+    game.set_bank_stats(bank_stats);
+    game.set_bank_audit(bank_audit);
+    game.set_heat_map(heat_map.is_some());
+    game.set_write_tracking(track_writes);
+    game.set_throttle(max_host_cpu.map(Throttle::new));
+    attach_dumpers(game, dump_frames.as_deref(), every, dump_audio.as_deref());
+    if let Some(colors) = terminal {
+        game.add_frame_sink(Box::new(TerminalSink::new(std::io::stdout(), colors)));
+    }
+    Session {
+        data_dir,
+        fast_boot_frames: fast_boot.then_some(fast_boot_frames),
+        boot_logo,
+        palette,
+        autosave: Some(Autosave::new(autosave_debounce, autosave_max_interval)),
+        event_log: event_log.map(|path| (path, event_log_format)),
+        heat_map,
+        ram_map,
+        pause_at_frame,
+        rpc,
+        frontend,
+        hotkeys,
+        shader,
+        display: DisplayOptions {
+            scale,
+            filter,
+            ghosting,
+        },
+        terminal: terminal.is_some(),
+        watch: watch.map(Duration::from_secs),
+        test_report: false,
+    }
+}
+
+/// Run mode without a ROM: present the splash screen to the attached sinks
+/// and wait for a ROM. Headless there is nothing to drop a ROM onto, so this
+/// only shows what a frontend would.
+fn show_splash(mut game: GameBoy, command: RunCommand) {
+    apply_palette(&mut game, command.palette, None);
+    if let Some(dir) = command.dump_frames {
+        dump_frames_to(&mut game, &dir, command.every);
+    }
+    if let Some(colors) = command.terminal {
+        game.add_frame_sink(Box::new(TerminalSink::new(std::io::stdout(), colors)));
+    }
+    game.show_splash();
+    println!("No ROM loaded. Pass the path of a ROM (.gb) file to run it.");
+}
+
+fn run_session(mut game: GameBoy, session: Session) {
+    let game_dir = game.game_directory(session.data_dir.as_deref().map(Path::new));
+    if let Some(ref dir) = game_dir {
+        if let Err(e) = dir.create() {
+            eprintln!("Error creating game data directory: {e}");
+            std::process::exit(1);
+        }
+        println!("Game data directory: {}", dir.root().display());
+    }
+
+    apply_palette(&mut game, session.palette, game_dir.as_ref());
+    let cheats = game_dir.as_ref().map(|dir| load_cheats(&mut game, dir));
+
+    let battery = game_dir
+        .as_ref()
+        .map(GameDirectory::save_file)
+        .filter(|path| path.exists())
+        .and_then(|path| load_battery(&mut game, &path));
 
     game.power_on();
 
+    if let (Some(frames), Some(dir)) = (session.fast_boot_frames, &game_dir) {
+        fast_boot(&mut game, dir, frames, battery.as_ref());
+    } else if session.boot_logo {
+        game.start_logo_scroll();
+    }
+
+    if let (Some(autosave), Some(dir)) = (session.autosave, &game_dir) {
+        enable_autosave(&mut game, dir, autosave, battery.as_ref());
+    }
+
     // Run for a large number of instructions (or until HALT)
     // For testing with gameboy-doctor, you typically want to run until
     // a specific point or until HALT
     println!("Running emulator...");
-    game.run(1_000_000); // Run for 1 million instructions or until HALT
+    let session_start = (game.frame_count(), Instant::now());
+    let mut report = None;
+    let mut watching = session
+        .watch
+        .map(|interval| start_watch(&game, interval, game_dir.as_ref()));
+    if let Some(frame) = session.pause_at_frame {
+        pause_at_frame(&mut game, frame, watching.as_mut());
+    } else if let Some(ref address) = session.rpc {
+        serve_rpc(&mut game, address, watching.as_mut());
+    } else if let Some(backend) = session.frontend {
+        open_window(
+            &mut game,
+            backend,
+            session.hotkeys.as_deref(),
+            session.shader.as_ref(),
+            session.display,
+            game_dir.as_ref(),
+        );
+    } else if session.terminal {
+        play_in_terminal(&mut game);
+    } else if session.test_report {
+        report = Some(gameboy::harness::run_test(&mut game, 1_000_000));
+    } else {
+        game.run(1_000_000); // Run for 1 million instructions or until HALT
+    }
 
     println!("Emulator stopped. CPU halted: {}", game.cpu.halted);
+    if let Some(trap) = game.take_quirk_trap() {
+        println!("Trapped: {trap} (PC: 0x{:04X})", game.cpu.pc);
+    }
+    if let Some(failure) = game.clock_trace().and_then(ClockTrace::failure) {
+        println!("Clock trace: {failure}");
+    }
+    if let Some(ram_map) = load_ram_map(session.ram_map, game_dir.as_ref()) {
+        for (field, value) in ram_map.read_all(&game.memory) {
+            println!("{:<16} {}", field.name, field.format(value));
+        }
+    }
+    if let Some(stats) = game.bank_stats() {
+        println!("{stats}");
+    }
+    let audit_failed = game.bank_audit().is_some_and(|audit| {
+        println!("{audit}");
+        !audit.is_clean()
+    });
+
+    if let Some((path, format)) = session.event_log {
+        write_event_log(&game, &path, format);
+    }
+    if let Some(path) = session.heat_map {
+        write_heat_map(&game, &path);
+    }
+    if let Some(ref dir) = game_dir {
+        let frames = game.frame_count() - session_start.0;
+        record_play_time(dir, frames, session_start.1.elapsed());
+        write_battery(&game, dir, battery.as_ref());
+        save_cheats(&game, dir, cheats.as_ref());
+    }
+    if let Some(report) = report {
+        println!("{}", report.to_json());
+        std::process::exit(report.outcome.exit_code());
+    }
+    if audit_failed {
+        std::process::exit(1);
+    }
+}
+
+/// A status reporter and where SIGUSR1 dumps go
+type Watching = (Watch, PathBuf);
+
+fn start_watch(game: &GameBoy, interval: Duration, game_dir: Option<&GameDirectory>) -> Watching {
+    if let Err(e) = watch::install_dump_signal() {
+        eprintln!("State dumps on SIGUSR1 unavailable: {e}");
+    }
+    let dump_dir = game_dir.map_or_else(|| PathBuf::from("."), GameDirectory::states_dir);
+    (Watch::new(interval, game, Instant::now()), dump_dir)
 }
 
+/// Print the status line when due, and dump the state if SIGUSR1 arrived
+fn check_watch(game: &GameBoy, (watch, dump_dir): &mut Watching) {
+    if let Some(line) = watch.poll(game, Instant::now()) {
+        println!("{line}");
+    }
+    if watch::take_dump_request() {
+        println!("{}", watch.report(game, Instant::now()));
+        match watch::dump_state(game, dump_dir) {
+            Ok(path) => println!("State dumped to {}", path.display()),
+            Err(e) => eprintln!("Error dumping state: {e}"),
+        }
+    }
+}
+
+fn pause_at_frame(game: &mut GameBoy, frame: u64, mut watching: Option<&mut Watching>) {
+    let result = game.seek_with(frame, |game| {
+        if let Some(watching) = watching.as_deref_mut() {
+            check_watch(game, watching);
+        }
+    });
+    match result {
+        Ok(reached) if reached == frame => println!("Paused at frame {frame}"),
+        Ok(reached) => println!("Stopped at frame {reached} before reaching frame {frame}"),
+        Err(e) => eprintln!("Error: {e}"),
+    }
+}
+
+/// Run in real time, answering RPC requests between frames, until a client
+/// calls quit. Emulation waits while paused or halted so clients can still
+/// inspect it.
+fn serve_rpc(game: &mut GameBoy, address: &str, mut watching: Option<&mut Watching>) {
+    let mut server = match RpcServer::bind(address) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Error starting RPC server on {address}: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Ok(address) = server.local_addr() {
+        println!("RPC server listening on http://{address}");
+    }
+
+    let frame_time = gameboy::playtime::emulated_time(1);
+    let mut deadline = Instant::now();
+    while !server.quit_requested() {
+        server.poll(game);
+        if let Some(watching) = watching.as_deref_mut() {
+            check_watch(game, watching);
+        }
+        if server.is_paused() || game.stopped() {
+            std::thread::sleep(Duration::from_millis(5));
+            deadline = Instant::now();
+            continue;
+        }
+        game.finish_frame();
+        deadline += frame_time;
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            None => deadline = Instant::now(),
+        }
+    }
+}
+
+/// Run in real time until the CPU halts, for the terminal sink to draw
+fn play_in_terminal(game: &mut GameBoy) {
+    let frame_time = gameboy::playtime::emulated_time(1);
+    let mut deadline = Instant::now();
+    while !game.stopped() {
+        game.finish_frame();
+        deadline += frame_time;
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            None => deadline = Instant::now(),
+        }
+    }
+}
+
+/// Play in a window until it is closed
+fn open_window(
+    game: &mut GameBoy,
+    backend: WindowBackend,
+    hotkeys: Option<&str>,
+    shader: Option<&PostShader>,
+    display: DisplayOptions,
+    game_dir: Option<&GameDirectory>,
+) {
+    let hotkeys = match hotkeys.map(|path| Hotkeys::load(Path::new(path))) {
+        Some(Ok(hotkeys)) => hotkeys,
+        Some(Err(e)) => {
+            eprintln!("Error loading hotkeys: {e}");
+            std::process::exit(1);
+        }
+        None => Hotkeys::default(),
+    };
+    if let Err(e) = frontend::run_window(backend, game, hotkeys, game_dir, shader, display) {
+        eprintln!("Window error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn write_event_log(game: &GameBoy, path: &str, format: EventLogFormat) {
+    let Some(log) = game.event_log() else {
+        return;
+    };
+    if let Err(e) = std::fs::write(path, log.export(format)) {
+        eprintln!("Error writing event log: {e}");
+        return;
+    }
+    println!("Event log: {path} ({} events)", log.events().len());
+    let dropped = log.dropped();
+    if dropped > 0 {
+        eprintln!("Event log full; {dropped} later events were dropped");
+    }
+}
+
+fn write_heat_map(game: &GameBoy, path: &str) {
+    let Some(heat_map) = game.heat_map() else {
+        return;
+    };
+    let size = gameboy::heatmap::HEAT_MAP_SIZE;
+    match gameboy::image::write_png(Path::new(path), size, size, &heat_map.to_rgba()) {
+        Ok(()) => println!("Heat map: {path}"),
+        Err(e) => eprintln!("Error writing heat map: {e}"),
+    }
+}
+
+/// Write the battery save back, keeping the RTC footer it was loaded with
+fn write_battery(game: &GameBoy, dir: &GameDirectory, battery: Option<&BatterySave>) {
+    let Some(save) = game.battery_save() else {
+        return;
+    };
+    let save = BatterySave {
+        rtc: battery.and_then(|battery| battery.rtc),
+        ..save
+    };
+    if let Err(e) = save.save(&dir.save_file()) {
+        eprintln!("Error writing battery save: {e}");
+    }
+}
+
+/// Restore (or create) the post-intro state cache
+fn fast_boot(game: &mut GameBoy, dir: &GameDirectory, frames: u32, battery: Option<&BatterySave>) {
+    let Some(hash) = game.rom_hash() else {
+        return;
+    };
+    let cache = dir.fast_boot_file(game.model().name(), hash, frames);
+    match game.fast_boot(&cache, frames) {
+        Ok(true) => {
+            println!("Fast boot: restored {}", cache.display());
+            // The cached state holds whatever cartridge RAM existed when
+            // it was made; the battery save on disk is newer
+            if let Some(save) = battery {
+                game.load_battery_save(save).ok();
+            }
+        }
+        Ok(false) => println!("Fast boot: cached {}", cache.display()),
+        Err(e) => eprintln!("Fast boot cache unavailable: {e}"),
+    }
+}
+
+/// Write the battery save to the game's data directory while the game runs,
+/// keeping the RTC footer it was loaded with
+fn enable_autosave(
+    game: &mut GameBoy,
+    dir: &GameDirectory,
+    autosave: Autosave,
+    battery: Option<&BatterySave>,
+) {
+    let path = dir.save_file();
+    let rtc = battery.and_then(|battery| battery.rtc);
+    game.set_autosave(Some((
+        autosave,
+        Box::new(move |save: BatterySave| {
+            if let Err(e) = (BatterySave { rtc, ..save }).save(&path) {
+                eprintln!("Error autosaving: {e}");
+            }
+        }),
+    )));
+}
+
+/// Apply the game's cheats.txt, returning the list as loaded
+fn load_cheats(game: &mut GameBoy, dir: &GameDirectory) -> CheatList {
+    let path = dir.cheats_file();
+    if !path.exists() {
+        return CheatList::default();
+    }
+    let cheats = CheatList::load(&path)
+        .inspect_err(|e| eprintln!("Ignoring cheats: {e}"))
+        .unwrap_or_default();
+    let enabled = cheats.cheats().iter().filter(|cheat| cheat.enabled).count();
+    if enabled > 0 {
+        println!("Cheats: {enabled} enabled");
+    }
+    game.set_cheats(cheats.clone());
+    cheats
+}
+
+/// Write cheats toggled during the session back to cheats.txt
+fn save_cheats(game: &GameBoy, dir: &GameDirectory, loaded: Option<&CheatList>) {
+    if loaded == Some(game.cheats()) {
+        return;
+    }
+    if let Err(e) = game.cheats().save(&dir.cheats_file()) {
+        eprintln!("Error saving cheats: {e}");
+    }
+}
+
+/// Add this session to the game's cumulative play time
+fn record_play_time(dir: &GameDirectory, frames: u64, wall_clock: Duration) {
+    let path = dir.play_time_file();
+    let result = PlayTime::load(&path).and_then(|mut play_time| {
+        play_time.add(frames, wall_clock);
+        play_time.save(&path)
+    });
+    if let Err(e) = result {
+        eprintln!("Error recording play time: {e}");
+    }
+}
+
+/// Read a battery save into the cartridge, returning it so the RTC footer
+/// can be written back unchanged
+fn load_battery(game: &mut GameBoy, path: &Path) -> Option<BatterySave> {
+    let ram_size = game.battery_save()?.ram.len();
+    let save = BatterySave::load(path, ram_size).and_then(|save| {
+        game.load_battery_save(&save)?;
+        Ok(save)
+    });
+    match save {
+        Ok(save) => {
+            println!("Loaded battery save: {}", path.display());
+            Some(save)
+        }
+        Err(e) => {
+            eprintln!("Error loading battery save: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SaveTransfer {
+    Import,
+    Export,
+}
+
+/// Copy a battery save between the game's data directory and an external
+/// file, checking it fits the cartridge on the way
+fn transfer_save(mut game: GameBoy, command: &SaveTransferCommand, direction: SaveTransfer) {
+    load_rom(&mut game, &command.rom);
+    let Some(ram_size) = game.battery_save().map(|save| save.ram.len()) else {
+        eprintln!("This cartridge has no battery-backed RAM");
+        std::process::exit(1);
+    };
+    let Some(dir) = game.game_directory(command.data_dir.as_deref().map(Path::new)) else {
+        return;
+    };
+
+    let external = Path::new(&command.file);
+    let game_save = dir.save_file();
+    let (from, to) = match direction {
+        SaveTransfer::Import => (external, game_save.as_path()),
+        SaveTransfer::Export => (game_save.as_path(), external),
+    };
+    let result = dir
+        .create()
+        .map_err(|e| e.to_string())
+        .and_then(|()| BatterySave::load(from, ram_size))
+        .and_then(|save| save.save(to));
+    match result {
+        Ok(()) => println!("Copied {} to {}", from.display(), to.display()),
+        Err(e) => {
+            eprintln!("Error copying save: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn load_rom(game: &mut GameBoy, rom: &str) {
+    if let Err(e) = game.load_rom(rom) {
+        eprintln!("Error loading ROM: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn dump_frames_to(game: &mut GameBoy, dir: &str, every: u64) {
+    match FrameDumper::new(Path::new(dir), every) {
+        Ok(dumper) => game.add_frame_sink(Box::new(dumper)),
+        Err(e) => {
+            eprintln!("Error creating frame dump directory {dir}: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Apply `--mute` and `--solo`
+fn select_channels(game: &mut GameBoy, mute: &[Channel], solo: Option<Channel>) {
+    for &channel in mute {
+        game.set_channel_enabled(channel, false);
+    }
+    if solo.is_some() {
+        game.solo_channel(solo);
+    }
+}
+
+/// Attach the `--dump-frames` and `--dump-audio` sinks
+fn attach_dumpers(game: &mut GameBoy, frames: Option<&str>, every: u64, audio: Option<&str>) {
+    if let Some(dir) = frames {
+        dump_frames_to(game, dir, every);
+    }
+    if let Some(path) = audio {
+        dump_audio_to(game, path);
+    }
+}
+
+fn dump_audio_to(game: &mut GameBoy, path: &str) {
+    match WavDumper::create(Path::new(path), game.sample_rate()) {
+        Ok(dumper) => game.add_frame_sink(Box::new(dumper)),
+        Err(e) => {
+            eprintln!("Error creating audio dump {path}: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn connect_link_cable(game: &mut GameBoy, port: &str) {
+    match LinkCable::open(port) {
+        Ok(cable) => {
+            game.connect_serial(Some(Box::new(cable)));
+            println!("Link cable: {port}");
+        }
+        Err(e) => {
+            eprintln!("Error opening link cable: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Report the autosplitter's events on stdout and, with `--livesplit`, to
+/// `LiveSplit`. Losing the connection only stops the sending.
+fn enable_autosplit(game: &mut GameBoy, path: &str, livesplit: Option<&str>) {
+    let splitter = Autosplitter::load(Path::new(path)).unwrap_or_else(|e| {
+        eprintln!("Error loading autosplitter: {e}");
+        std::process::exit(1);
+    });
+    let mut client = livesplit.map(|address| match LiveSplit::connect(address) {
+        Ok(client) => {
+            println!("LiveSplit: {address}");
+            client
+        }
+        Err(e) => {
+            eprintln!("Error connecting to LiveSplit at {address}: {e}");
+            std::process::exit(1);
+        }
+    });
+    game.set_autosplitter(Some((
+        splitter,
+        Box::new(move |event: &SplitEvent| {
+            println!("Autosplit: {event}");
+            if let Some(ref mut livesplit) = client
+                && let Err(e) = livesplit.send(event)
+            {
+                eprintln!("Disconnected from LiveSplit: {e}");
+                client = None;
+            }
+        }),
+    )));
+}
+
+/// Use the `--palette` choice, falling back to the game's palette.txt
+fn apply_palette(game: &mut GameBoy, palette: Option<String>, game_dir: Option<&GameDirectory>) {
+    let palette = palette.or_else(|| {
+        game_dir
+            .map(GameDirectory::palette_file)
+            .filter(|path| path.exists())
+            .map(|path| path.display().to_string())
+    });
+    if let Some(palette) = palette {
+        let rom = game
+            .memory
+            .cartridge
+            .as_ref()
+            .map(gameboy::cartridge::Cartridge::rom);
+        match load_palette(&palette, rom) {
+            Ok(palette) => game.set_palette(palette),
+            Err(e) => {
+                eprintln!("Error loading palette: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn run_script(mut game: GameBoy, path: &str, ram_map: Option<String>) {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading script: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let game_dir = game.game_directory(None);
+    let ram_map = load_ram_map(ram_map, game_dir.as_ref()).unwrap_or_default();
+    let cheats = game_dir.as_ref().map(|dir| load_cheats(&mut game, dir));
+    game.power_on();
+    match gameboy::script::run_script_with_ram_map(game, &source, ram_map) {
+        Ok(game) => {
+            if let Some(ref dir) = game_dir {
+                save_cheats(&game, dir, cheats.as_ref());
+            }
+        }
+        Err(e) => {
+            eprintln!("Script failed: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Read the `--ram-map` file, falling back to the game's `ram_map.txt`
+fn load_ram_map(path: Option<String>, game_dir: Option<&GameDirectory>) -> Option<RamMap> {
+    if let Some(path) = path {
+        return match RamMap::load(Path::new(&path)) {
+            Ok(ram_map) => Some(ram_map),
+            Err(e) => {
+                eprintln!("Error loading RAM map: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    let path = game_dir?.ram_map_file();
+    if !path.exists() {
+        return None;
+    }
+    RamMap::load(&path)
+        .inspect_err(|e| eprintln!("Ignoring RAM map: {e}"))
+        .ok()
+}
+
+/// Resolve a built-in palette name or read a palette file
+fn load_palette(name_or_path: &str, rom: Option<&[u8]>) -> Result<DmgPalette, String> {
+    if name_or_path.eq_ignore_ascii_case("cgb") {
+        return rom
+            .map(DmgPalette::cgb_compat)
+            .ok_or_else(|| "The cgb palette needs a ROM".to_string());
+    }
+    if let Some(palette) = DmgPalette::from_name(name_or_path) {
+        return Ok(palette);
+    }
+    let text = std::fs::read_to_string(name_or_path).map_err(|e| format!("{name_or_path}: {e}"))?;
+    DmgPalette::parse(&text)
+}
+
+/// Print the metadata block of a save state
+/// Print the header (already shown by loading the ROM) plus stored game data
+fn info(game: &GameBoy, command: &InfoCommand) {
+    if let Some(hash) = game.rom_hash() {
+        println!("ROM CRC32: {hash:08X}");
+    }
+    let Some(dir) = game.game_directory(command.data_dir.as_deref().map(Path::new)) else {
+        return;
+    };
+    println!("Game data directory: {}", dir.root().display());
+    let has_save = dir.save_file().exists();
+    println!("Battery save: {}", if has_save { "yes" } else { "no" });
+    match PlayTime::load(&dir.play_time_file()) {
+        Ok(play_time) => {
+            let emulated = format_duration(play_time.emulated());
+            let wall_clock = format_duration(play_time.wall_clock);
+            println!("Play time (emulated):   {emulated}");
+            println!("Play time (wall-clock): {wall_clock}");
+        }
+        Err(e) => eprintln!("Error reading play time: {e}"),
+    }
+}
+
+fn state_info(command: &StateInfoCommand) {
+    let metadata = std::fs::read(&command.file)
+        .map_err(|e| e.to_string())
+        .and_then(SaveState::from_bytes)
+        .and_then(|state| state.metadata());
+    let metadata = match metadata {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            eprintln!("Error reading state {}: {e}", command.file);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Title:     {}", metadata.title);
+    match metadata.rom_hash {
+        Some(hash) => println!("ROM CRC32: {hash:08X}"),
+        None => println!("ROM CRC32: (no cartridge)"),
+    }
+    println!("Saved:     {}", format_timestamp(metadata.timestamp));
+    println!(
+        "Play time: {} ({} frames)",
+        format_duration(metadata.play_time()),
+        metadata.frames
+    );
+
+    if let Some(ref path) = command.thumbnail {
+        let path = Path::new(path);
+        match gameboy::image::write_png(
+            path,
+            THUMBNAIL_WIDTH,
+            THUMBNAIL_HEIGHT,
+            &metadata.thumbnail,
+        ) {
+            Ok(()) => println!("Thumbnail: {}", path.display()),
+            Err(e) => {
+                eprintln!("Error writing thumbnail: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` for seconds since the UNIX epoch
+fn format_timestamp(timestamp: u64) -> String {
+    let days = i64::try_from(timestamp / 86_400).unwrap_or(i64::MAX);
+    let seconds = timestamp % 86_400;
+
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Report on every ROM, exiting with status 1 if any check failed
+fn verify(command: &VerifyCommand) -> ! {
+    let mut all_passed = true;
+    for path in &command.roms {
+        let report = match std::fs::read(path) {
+            Ok(rom) => verify_rom(&rom),
+            Err(e) => {
+                eprintln!("Error reading {path}: {e}");
+                all_passed = false;
+                continue;
+            }
+        };
+        all_passed &= report.passed();
+
+        if command.json {
+            let mut json = report.to_json();
+            json["path"] = path.as_str().into();
+            println!("{json}");
+            continue;
+        }
+        println!("{path}: {} [{:08X}]", report.title, report.crc32);
+        for check in &report.checks {
+            println!(
+                "  {:<4} {:<16} {}",
+                check.status.name(),
+                check.name,
+                check.message
+            );
+        }
+    }
+    std::process::exit(i32::from(!all_passed));
+}
+
+/// Run the ROM with coverage enabled, then write the annotated listing
+fn disassemble(mut game: GameBoy, command: &DisasmCommand) {
+    game.power_on();
+    game.enable_coverage();
+
+    for _ in 0..command.frames {
+        game.finish_frame();
+    }
+
+    let (Some(cartridge), Some(coverage)) = (game.memory.cartridge.as_ref(), game.coverage())
+    else {
+        return;
+    };
+    let listing = gameboy::disasm::listing(cartridge.rom(), coverage, &cartridge.header().title);
+    if let Err(e) = std::fs::write(&command.output, listing) {
+        eprintln!("Error writing listing: {e}");
+        std::process::exit(1);
+    }
+    println!(
+        "Wrote {} ({} bytes of code)",
+        command.output,
+        coverage.code_bytes()
+    );
+}
+
+/// Compare two frames; exits with status 1 if they differ
+fn bisect(mut game: GameBoy, command: &BisectCommand) -> ! {
+    let mut trace = Trace::open(Path::new(&command.reference)).unwrap_or_else(|e| {
+        eprintln!("Error reading {}: {e}", command.reference);
+        std::process::exit(1);
+    });
+    game.power_on();
+    let report = match gameboy::bisect::bisect(&mut game, &mut trace, command.interval) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error reading {}: {e}", command.reference);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{} checkpoints, {} of {} lines compared",
+        report.checkpoints,
+        report.compared,
+        trace.len()
+    );
+    if let Some(divergence) = report.divergence {
+        println!("Diverged at instruction {}", divergence.instruction);
+        println!("  expected: {}", divergence.expected);
+        println!("  actual:   {}", divergence.actual);
+        std::process::exit(1);
+    }
+    println!("No divergence in {} instructions", trace.len());
+    std::process::exit(0);
+}
+
+fn framediff(command: &FramediffCommand) -> ! {
+    let read = |path: &str| {
+        gameboy::image::read_png(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("Error reading {path}: {e}");
+            std::process::exit(1);
+        })
+    };
+    let diff = match gameboy::framediff::compare(&read(&command.a), &read(&command.b)) {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{} of {} pixels differ ({:.2}%)",
+        diff.differing,
+        diff.total,
+        diff.percent()
+    );
+    if let Some(ref output) = command.output {
+        let image = &diff.image;
+        if let Err(e) =
+            gameboy::image::write_png(Path::new(output), image.width, image.height, &image.rgba)
+        {
+            eprintln!("Error writing diff image: {e}");
+            std::process::exit(1);
+        }
+        println!("Diff image: {output}");
+    }
+    std::process::exit(i32::from(!diff.is_identical()));
+}
+
+fn harness(command: &HarnessCommand) -> ! {
+    let roms = gameboy::harness::find_roms(Path::new(&command.dir)).unwrap_or_else(|e| {
+        eprintln!("Error reading {}: {e}", command.dir);
+        std::process::exit(1);
+    });
+    let cache_path = Path::new(&command.cache);
+    let mut cache = (!command.no_cache).then(|| {
+        let core_version = gameboy::harness::core_version();
+        ResultCache::load(cache_path, core_version.clone()).unwrap_or_else(|e| {
+            eprintln!("Ignoring results cache: {e}");
+            ResultCache::new(core_version)
+        })
+    });
+    let limits = Limits {
+        frames: command.frames,
+        timeout: Duration::from_secs(command.timeout),
+    };
+    let jobs = command
+        .jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, std::num::NonZero::get));
+
+    // Crashes are reported in the scoreboard, not as a backtrace per ROM
+    std::panic::set_hook(Box::new(|_| {}));
+    let results = gameboy::harness::run_all(&roms, limits, jobs, cache.as_mut());
+    let _ = std::panic::take_hook();
+
+    let width = results
+        .iter()
+        .map(|result| result.rom.display().to_string().len())
+        .max()
+        .unwrap_or(0);
+    for result in &results {
+        let cached = if result.cached { " (cached)" } else { "" };
+        println!(
+            "{:<5} {:<width$} {:>6} frames{cached}  {}",
+            result.outcome.name(),
+            result.rom.display(),
+            result.frames,
+            result.outcome.detail()
+        );
+    }
+    let passed = results
+        .iter()
+        .filter(|result| result.outcome == gameboy::harness::Outcome::Passed)
+        .count();
+    let cached = results.iter().filter(|result| result.cached).count();
+    println!("\n{passed}/{} passed ({cached} cached)", results.len());
+
+    if let Some(cache) = cache {
+        if let Some(dir) = cache_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(e) = cache.save(cache_path) {
+            eprintln!("Error writing results cache: {e}");
+        }
+    }
+    std::process::exit(i32::from(passed != results.len()));
+}
+
+fn soak(command: &SoakCommand) -> ! {
+    let roms = gameboy::harness::find_roms(Path::new(&command.dir)).unwrap_or_else(|e| {
+        eprintln!("Error reading {}: {e}", command.dir);
+        std::process::exit(1);
+    });
+    let limits = SoakLimits {
+        frames: emulated_frames(command.seconds_per_rom),
+        hang_frames: emulated_frames(command.hang_seconds).max(1),
+        max_host_cpu: command.max_host_cpu,
+    };
+    let jobs = command
+        .jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, std::num::NonZero::get));
+
+    // Panics are reported per ROM, not as a backtrace each
+    std::panic::set_hook(Box::new(|_| {}));
+    let results = gameboy::soak::soak_all(&roms, command.seed, limits, jobs);
+    let _ = std::panic::take_hook();
+
+    let survived = results
+        .iter()
+        .filter(|result| result.outcome == SoakOutcome::Survived)
+        .count();
+    if command.json {
+        for result in &results {
+            println!("{}", result.to_json());
+        }
+    } else {
+        let width = results
+            .iter()
+            .map(|result| result.rom.display().to_string().len())
+            .max()
+            .unwrap_or(0);
+        for result in &results {
+            println!(
+                "{:<7} {:<width$} {:>7} frames  {}",
+                result.outcome.name(),
+                result.rom.display(),
+                result.frames,
+                result.outcome.detail()
+            );
+        }
+        println!(
+            "\n{survived}/{} survived {}s each (seed {})",
+            results.len(),
+            command.seconds_per_rom,
+            command.seed
+        );
+    }
+    std::process::exit(i32::from(survived != results.len()));
+}
+
+fn capabilities(command: &CapabilitiesCommand) {
+    let capabilities = gameboy::gameboy::capabilities();
+    if command.json {
+        println!("{:#}", capabilities.to_json());
+    } else {
+        println!("{capabilities}");
+    }
+}
+
+fn opcodes(command: &OpcodesCommand) {
+    let entries = gameboy::isadoc::entries();
+
+    let json = gameboy::isadoc::to_json(&entries);
+    let Some(ref output) = command.output else {
+        println!("{json:#}");
+        return;
+    };
+    if let Err(e) = std::fs::write(output, format!("{json:#}\n")) {
+        eprintln!("Error writing {output}: {e}");
+        std::process::exit(1);
+    }
+    let summary = &json["summary"];
+    println!(
+        "{output}: {} legal opcodes, cycle mismatches: {}",
+        summary["legal"], summary["cycle_mismatches"]
+    );
+}
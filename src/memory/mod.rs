@@ -1,12 +1,91 @@
+use crate::apu::Apu;
+use crate::bankaudit::BankAudit;
+use crate::bankstats::BankStats;
 use crate::cartridge::Cartridge;
+use crate::cgb::{SpeedSwitch, is_cgb_register};
+use crate::cheats::RomPatch;
+use crate::heatmap::HeatMap;
+use crate::interrupts::Interrupts;
+use crate::joypad::Joypad;
+use crate::lastwrite::WriteTracker;
+use crate::objects::ObjectPriority;
+use crate::ppu::Ppu;
+use crate::quirks::{Quirk, QuirkMonitor};
+use crate::savestate::{StateReader, StateWriter};
+use crate::serial::Serial;
 use crate::timer::Timer;
 
 const MEMORY_SIZE: usize = 0x10000; // 64KB
 
+/// What RAM contains after a hard reset. Real hardware powers up with
+/// semi-random contents; some games (accidentally) depend on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RamInit {
+    #[default]
+    Zeros,
+    Ones,
+    /// Deterministic pseudo-random bytes from the given seed
+    Random(u64),
+}
+
+impl RamInit {
+    pub fn fill(self, ram: &mut [u8]) {
+        match self {
+            RamInit::Zeros => ram.fill(0x00),
+            RamInit::Ones => ram.fill(0xFF),
+            RamInit::Random(seed) => {
+                // xorshift64; the seed is forced non-zero so the sequence never sticks
+                let mut state = seed | 1;
+                for byte in ram {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    *byte = state.to_le_bytes()[3];
+                }
+            }
+        }
+    }
+}
+
 pub struct Memory {
     pub data: Vec<u8>,
     pub cartridge: Option<Cartridge>,
     pub timer: Timer,
+    pub joypad: Joypad,
+    pub serial: Serial,
+    /// IF and IE
+    pub interrupts: Interrupts,
+    /// KEY1, only reachable in CGB mode
+    pub speed: SpeedSwitch,
+    /// LY and the STAT mode, advanced by `GameBoy::step`
+    pub ppu: Ppu,
+    /// Sound registers and channels, advanced by `GameBoy::step`
+    pub apu: Apu,
+    /// Whether CGB-only registers exist. Always off: only DMG mode is emulated.
+    // TODO: Set from the model once there is a CGB mode
+    pub(crate) cgb_mode: bool,
+    /// How overlapping objects are ordered: OPRI (0xFF6C) in CGB mode, the
+    /// DMG rule otherwise
+    pub object_priority: ObjectPriority,
+    /// Access tracking for the memory map overlay, off unless enabled
+    pub heat_map: Option<HeatMap>,
+    /// Cartridge bank usage, off unless enabled
+    pub bank_stats: Option<BankStats>,
+    /// Out-of-range ROM bank accesses, off unless enabled
+    pub bank_audit: Option<BankAudit>,
+    /// The last writer of each address, off unless enabled
+    pub write_tracker: Option<WriteTracker>,
+    pub quirks: QuirkMonitor,
+    /// Set by the PPU timing while it is drawing and VRAM is off limits
+    pub(crate) lcd_mode3: bool,
+    /// Cycles `bus_cycle` has run the timer for within the current
+    /// instruction, which `GameBoy::step` takes off the instruction's
+    pub(crate) timer_ahead: u8,
+    /// TIMA overflowed during `bus_cycle`, for `GameBoy::step` to request
+    /// the interrupt
+    pub(crate) timer_overflowed: bool,
+    /// Enabled Game Genie codes
+    pub(crate) rom_patches: Vec<RomPatch>,
 }
 
 #[allow(clippy::match_same_arms)] // Temporary whilst developing
@@ -16,32 +95,118 @@ impl Memory {
             data: vec![0; MEMORY_SIZE],
             cartridge: None,
             timer: Timer::default(),
+            joypad: Joypad::default(),
+            serial: Serial::default(),
+            interrupts: Interrupts::default(),
+            speed: SpeedSwitch::default(),
+            ppu: Ppu::default(),
+            apu: Apu::default(),
+            cgb_mode: false,
+            object_priority: ObjectPriority::default(),
+            heat_map: None,
+            bank_stats: None,
+            bank_audit: None,
+            write_tracker: None,
+            quirks: QuirkMonitor::default(),
+            lcd_mode3: false,
+            timer_ahead: 0,
+            timer_overflowed: false,
+            rom_patches: Vec::new(),
         }
     }
 
+    /// Fill VRAM, work RAM and HRAM according to `init`
+    pub fn init_ram(&mut self, init: RamInit) {
+        init.fill(&mut self.data[0x8000..=0x9FFF]);
+        init.fill(&mut self.data[0xC000..=0xDFFF]);
+        init.fill(&mut self.data[0xFF80..=0xFFFE]);
+    }
+
     /// Load a cartridge into memory
     pub fn load_cartridge(&mut self, cartridge: Cartridge) {
         self.cartridge = Some(cartridge);
     }
 
+    // TODO: Include in-flight OAM DMA once 0xFF46 transfers are emulated
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.data);
+        self.timer.save_state(state);
+        self.joypad.save_state(state);
+        self.serial.save_state(state);
+        self.interrupts.save_state(state);
+        self.speed.save_state(state);
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        state.write_u8(self.object_priority.opri());
+        state.write_bool(self.cartridge.is_some());
+        if let Some(ref cart) = self.cartridge {
+            cart.save_state(state);
+        }
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_into(&mut self.data)?;
+        self.timer.load_state(state)?;
+        self.joypad.load_state(state)?;
+        self.serial.load_state(state)?;
+        self.interrupts.load_state(state)?;
+        self.speed.load_state(state)?;
+        self.ppu.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.object_priority = ObjectPriority::from_opri(state.read_u8()?);
+        self.timer_ahead = 0;
+        self.timer_overflowed = false;
+        let has_cartridge = state.read_bool()?;
+        match (has_cartridge, &mut self.cartridge) {
+            (true, Some(cart)) => cart.load_state(state),
+            (false, None) => Ok(()),
+            _ => Err("Save state was made with a different cartridge setup".to_string()),
+        }
+    }
+
+    /// Read as the CPU does, recording the access in the heat map, bank
+    /// statistics and bank audit
     pub fn read_byte(&self, address: u16) -> u8 {
+        if let Some(ref heat_map) = self.heat_map {
+            heat_map.record_read(address);
+        }
+        if let (Some(stats), Some(cart)) = (&self.bank_stats, &self.cartridge) {
+            stats.record_read(address, cart.banks());
+        }
+        if let (Some(audit), Some(cart)) = (&self.bank_audit, &self.cartridge) {
+            audit.record_read(address, cart.banks().0);
+        }
+        self.check_quirks(address);
+        self.peek(address)
+    }
+
+    fn patch_rom(&self, address: u16, byte: u8) -> u8 {
+        self.rom_patches
+            .iter()
+            .fold(byte, |byte, patch| patch.apply(address, byte))
+    }
+
+    /// Read without side effects, for debuggers and tools
+    pub fn peek(&self, address: u16) -> u8 {
         match address {
             // Cartridge ROM Bank 0 (0x0000-0x3FFF)
             0x0000..=0x3FFF => {
-                if let Some(ref cart) = self.cartridge {
+                let byte = if let Some(ref cart) = self.cartridge {
                     cart.read_byte(address)
                 } else {
                     self.data[address as usize]
-                }
+                };
+                self.patch_rom(address, byte)
             }
 
             // Cartridge ROM Bank 1-N (0x4000-0x7FFF)
             0x4000..=0x7FFF => {
-                if let Some(ref cart) = self.cartridge {
+                let byte = if let Some(ref cart) = self.cartridge {
                     cart.read_byte(address)
                 } else {
                     self.data[address as usize]
-                }
+                };
+                self.patch_rom(address, byte)
             }
 
             // Video RAM (0x8000-0x9FFF)
@@ -56,20 +221,56 @@ impl Memory {
                 }
             }
 
+            // Joypad
+            0xFF00 => self.joypad.read_register(),
+
+            // Serial
+            0xFF01..=0xFF02 => self.serial.read_register(address),
+
             // Timer
             0xFF04..=0xFF07 => self.timer.read_register(address),
 
+            // Interrupt flags and enable (0xFF0F, 0xFFFF)
+            0xFF0F | 0xFFFF => self.interrupts.read_register(address),
+
+            // Sound channels 1 and 2, and the mixer
+            0xFF10..=0xFF19 | 0xFF24..=0xFF26 => self.apu.read_register(address),
+
+            // LCD status and current line
+            0xFF41 => self.ppu.read_stat(),
+            0xFF44 => self.ppu.ly(),
+            0xFF45 => self.ppu.lyc(),
+
+            // CGB-only registers, absent in DMG mode
+            _ if is_cgb_register(address) && !self.cgb_mode => 0xFF,
+            0xFF4D => self.speed.read_register(),
+            0xFF6C => self.object_priority.opri(),
+
             // Work RAM, Echo RAM, OAM, I/O, HRAM (0xC000-0xFFFF)
             0xC000..=0xFFFF => self.data[address as usize],
         }
     }
 
     pub fn write_byte(&mut self, address: u16, value: u8) {
+        if let Some(ref mut heat_map) = self.heat_map {
+            heat_map.record_write(address);
+        }
+        if let Some(ref mut tracker) = self.write_tracker {
+            tracker.record(address, value);
+        }
+        self.check_quirks(address);
+        if address < 0x8000 && self.cartridge.as_ref().is_some_and(|cart| !cart.has_mbc()) {
+            self.quirks.report(Quirk::RomWriteWithoutMbc, address);
+        }
         match address {
             // Cartridge ROM area (0x0000-0x7FFF) - MBC control writes
             0x0000..=0x7FFF => {
                 if let Some(ref mut cart) = self.cartridge {
+                    let before = cart.banks();
                     cart.write_byte(address, value);
+                    if let Some(ref mut stats) = self.bank_stats {
+                        stats.record_mapper_write(address, before, cart.banks());
+                    }
                 } else {
                     // No cartridge - allow writes for testing
                     self.data[address as usize] = value;
@@ -84,15 +285,40 @@ impl Memory {
             // External RAM (0xA000-0xBFFF)
             0xA000..=0xBFFF => {
                 if let Some(ref mut cart) = self.cartridge {
+                    if let Some(ref mut stats) = self.bank_stats {
+                        stats.record_ram_write(cart.banks().1);
+                    }
                     cart.write_byte(address, value);
                 } else {
                     self.data[address as usize] = value;
                 }
             }
 
+            // Joypad
+            0xFF00 => self.joypad.write_register(value),
+
+            // Serial
+            0xFF01..=0xFF02 => self.serial.write_register(address, value),
+
             // Timer
             0xFF04..=0xFF07 => self.timer.write_register(address, value),
 
+            // Interrupt flags and enable (0xFF0F, 0xFFFF)
+            0xFF0F | 0xFFFF => self.interrupts.write_register(address, value),
+
+            // Sound channels 1 and 2, and the mixer
+            0xFF10..=0xFF19 | 0xFF24..=0xFF26 => self.apu.write_register(address, value),
+
+            // LCD status; LY is read-only
+            0xFF41 => self.ppu.write_stat(value),
+            0xFF44 => {}
+            0xFF45 => self.ppu.write_lyc(value),
+
+            // CGB-only registers, absent in DMG mode
+            _ if is_cgb_register(address) && !self.cgb_mode => {}
+            0xFF4D => self.speed.write_register(value),
+            0xFF6C => self.object_priority = ObjectPriority::from_opri(value),
+
             // Work RAM, Echo RAM, OAM, I/O, HRAM (0xC000-0xFFFF)
             0xC000..=0xFFFF => {
                 self.data[address as usize] = value;
@@ -100,16 +326,46 @@ impl Memory {
         }
     }
 
-    pub fn read_word(&self, address: u16) -> u16 {
+    /// Report quirks shared by reads and writes
+    fn check_quirks(&self, address: u16) {
+        match address {
+            0x8000..=0x9FFF if self.lcd_mode3 => {
+                self.quirks.report(Quirk::VramAccessInMode3, address);
+            }
+            0xE000..=0xFDFF => self.quirks.report(Quirk::EchoRamAccess, address),
+            _ => {}
+        }
+    }
+
+    /// Read a little-endian word as the CPU does: two separate byte reads,
+    /// low byte first, an M-cycle apart (see `bus_cycle`). Each byte is
+    /// routed, banked and tracked on its own, so a word spanning two regions
+    /// (0x3FFF/0x4000, say) sees each region's rules, and 0xFFFF wraps round
+    /// to 0x0000.
+    pub fn read_word(&mut self, address: u16) -> u16 {
         let low = u16::from(self.read_byte(address));
+        self.bus_cycle();
         let high = u16::from(self.read_byte(address.wrapping_add(1)));
         (high << 8) | low
     }
 
+    /// Write a little-endian word as two separate byte writes, low byte
+    /// first (the order of `ld [n16], sp`), an M-cycle apart and with the
+    /// same per-byte routing as [`Memory::read_word`]
     pub fn write_word(&mut self, address: u16, value: u16) {
         self.write_byte(address, (value & 0xFF) as u8);
+        self.bus_cycle();
         self.write_byte(address.wrapping_add(1), (value >> 8) as u8);
     }
+
+    /// The M-cycle between the two bytes of a word access. The timer runs
+    /// through it, so the second byte sees DIV and TIMA four cycles on and a
+    /// TIMA overflow in between reloads from a TMA the first byte wrote. The
+    /// PPU, APU and serial port still catch up after the instruction.
+    fn bus_cycle(&mut self) {
+        self.timer_overflowed |= self.timer.tick(4);
+        self.timer_ahead = self.timer_ahead.saturating_add(4);
+    }
 }
 
 impl Default for Memory {
@@ -308,4 +564,169 @@ mod tests {
             assert_eq!(memory.read_byte(0xFF07), 0x05);
         }
     }
+
+    mod ram_init {
+        use super::*;
+
+        #[test]
+        fn ones_fills_work_ram_but_not_io() {
+            let mut memory = Memory::new();
+            memory.init_ram(RamInit::Ones);
+
+            assert_eq!(memory.read_byte(0xC000), 0xFF);
+            assert_eq!(memory.read_byte(0xDFFF), 0xFF);
+            assert_eq!(memory.read_byte(0xFF80), 0xFF);
+            assert_eq!(memory.read_byte(0xFF0F), 0x00, "IO registers are not RAM");
+            assert_eq!(memory.read_byte(0xFFFF), 0x00, "IE is not RAM");
+        }
+
+        #[test]
+        fn random_is_deterministic_per_seed() {
+            let mut a = [0; 64];
+            let mut b = [0; 64];
+            let mut c = [0; 64];
+            RamInit::Random(7).fill(&mut a);
+            RamInit::Random(7).fill(&mut b);
+            RamInit::Random(8).fill(&mut c);
+
+            assert_eq!(a, b);
+            assert_ne!(a, c);
+        }
+    }
+
+    mod word_access {
+        use super::*;
+
+        /// MBC1 cartridge with four ROM banks, each filled with its number
+        fn banked_memory() -> Memory {
+            let mut rom = vec![0; 0x10000];
+            for (bank, chunk) in rom.chunks_mut(0x4000).enumerate() {
+                chunk.fill(u8::try_from(bank).unwrap());
+            }
+            rom[0x0147] = 0x01; // MBC1
+            rom[0x0148] = 0x01; // 64KB
+            let mut memory = Memory::new();
+            memory.load_cartridge(Cartridge::from_rom(rom).unwrap());
+            memory
+        }
+
+        #[test]
+        fn read_spanning_banks_reads_each_bank() {
+            let mut memory = banked_memory();
+            assert_eq!(memory.read_word(0x3FFF), 0x0100);
+
+            memory.write_byte(0x2000, 0x03); // Switch to bank 3
+            assert_eq!(memory.read_word(0x3FFF), 0x0300);
+            assert_eq!(memory.read_word(0x7FFF), 0x0003, "VRAM follows the ROM");
+        }
+
+        #[test]
+        fn write_spanning_regions_follows_each_region() {
+            let mut memory = banked_memory();
+            memory.write_word(0x9FFF, 0xABCD);
+            assert_eq!(memory.read_byte(0x9FFF), 0xCD);
+            assert_eq!(
+                memory.read_byte(0xA000),
+                0xFF,
+                "Cartridge RAM is disabled, so the high byte is dropped"
+            );
+
+            memory.write_word(0x1FFF, 0x020A); // RAM enable, then bank select
+            assert_eq!(memory.read_byte(0x4000), 0x02);
+        }
+
+        #[test]
+        fn second_byte_is_an_m_cycle_later() {
+            let mut memory = Memory::new();
+            memory.write_byte(0xFF07, 0x05); // TIMA every 16 cycles
+            memory.timer.tick(12);
+            // DIV, then TIMA once the M-cycle between has clocked it
+            assert_eq!(memory.read_word(0xFF04), 0x0100);
+
+            // TMA, then TAC, with TIMA overflowing in between and reloading
+            // the TMA just written
+            memory.write_byte(0xFF05, 0xFF);
+            memory.timer.tick(12);
+            memory.write_word(0xFF06, 0x0542);
+            assert_eq!(memory.read_byte(0xFF05), 0x42);
+            assert!(memory.timer_overflowed);
+            assert_eq!(memory.timer_ahead, 8);
+        }
+
+        #[test]
+        fn bytes_are_accessed_low_first_and_wrap() {
+            let mut memory = Memory::new();
+            memory.heat_map = Some(HeatMap::new());
+            memory.write_word(0xFFFF, 0x1234);
+            assert_eq!(memory.interrupts.read_register(0xFFFF), 0x34);
+            assert_eq!(memory.data[0x0000], 0x12);
+            assert_eq!(memory.read_word(0xFFFF), 0x1234);
+
+            let heat_map = memory.heat_map.as_ref().unwrap();
+            assert_eq!(heat_map.heat(0xFFFF), (u8::MAX, u8::MAX, 0));
+            assert_eq!(heat_map.heat(0x0000), (u8::MAX, u8::MAX, 0));
+        }
+    }
+
+    mod joypad_register {
+        use super::*;
+
+        #[test]
+        fn p1_routes_to_joypad() {
+            let mut memory = Memory::new();
+
+            memory.write_byte(0xFF00, 0x20);
+            assert_eq!(
+                memory.read_byte(0xFF00),
+                0xEF,
+                "0xFF00 should read back select bits with unused bits high"
+            );
+        }
+
+        #[test]
+        fn p1_does_not_affect_adjacent_memory() {
+            let mut memory = Memory::new();
+
+            memory.write_byte(0xFF01, 0x42);
+            memory.write_byte(0xFF00, 0x00);
+            assert_eq!(memory.read_byte(0xFF01), 0x42);
+        }
+    }
+
+    mod cgb_registers {
+        use super::*;
+
+        #[test]
+        fn read_high_and_ignore_writes_in_dmg_mode() {
+            let mut memory = Memory::new();
+            for address in [0xFF4D, 0xFF4F, 0xFF55, 0xFF68, 0xFF69, 0xFF70] {
+                memory.write_byte(address, 0x00);
+                assert_eq!(memory.read_byte(address), 0xFF, "0x{address:04X}");
+            }
+            memory.write_byte(0xFF50, 0x00);
+            assert_eq!(memory.read_byte(0xFF50), 0x00, "Not a CGB register");
+        }
+
+        #[test]
+        fn key1_routes_to_the_speed_switch_in_cgb_mode() {
+            let mut memory = Memory::new();
+            memory.cgb_mode = true;
+            memory.write_byte(0xFF4D, 0x01);
+            assert_eq!(memory.read_byte(0xFF4D), 0x7F);
+        }
+
+        #[test]
+        fn opri_sets_object_priority_in_cgb_mode() {
+            let mut memory = Memory::new();
+            memory.write_byte(0xFF6C, 0x00);
+            assert_eq!(memory.object_priority, ObjectPriority::XCoordinate);
+
+            memory.cgb_mode = true;
+            memory.write_byte(0xFF6C, 0x00);
+            assert_eq!(memory.object_priority, ObjectPriority::OamIndex);
+            assert_eq!(memory.read_byte(0xFF6C), 0xFE);
+            memory.write_byte(0xFF6C, 0x01);
+            assert_eq!(memory.object_priority, ObjectPriority::XCoordinate);
+        }
+    }
 }
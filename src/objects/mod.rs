@@ -0,0 +1,129 @@
+/// Objects (sprites) the PPU can show on one scanline
+pub const MAX_PER_LINE: usize = 10;
+
+/// Which object wins where two overlap, set by OPRI (0xFF6C) on the CGB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ObjectPriority {
+    /// CGB games: the object earlier in OAM wins (OPRI bit 0 clear)
+    OamIndex,
+    /// DMG hardware, and CGB running DMG games: the object further left wins,
+    /// ties going to the earlier one in OAM (OPRI bit 0 set)
+    #[default]
+    XCoordinate,
+}
+
+impl ObjectPriority {
+    /// Decode a write to OPRI. The CGB boot ROM writes 1 before starting a
+    /// DMG game, which is how those games keep their DMG overlap rules.
+    pub fn from_opri(value: u8) -> Self {
+        if value & 0x01 == 0 {
+            ObjectPriority::OamIndex
+        } else {
+            ObjectPriority::XCoordinate
+        }
+    }
+
+    /// OPRI as read back; the unused bits read high
+    pub fn opri(self) -> u8 {
+        match self {
+            ObjectPriority::OamIndex => 0xFE,
+            ObjectPriority::XCoordinate => 0xFF,
+        }
+    }
+
+    /// Order `objects` (as selected by `objects_on_line`) from the one drawn
+    /// on top to the one drawn underneath
+    pub fn sort(self, objects: &mut [Object]) {
+        match self {
+            ObjectPriority::OamIndex => objects.sort_by_key(|object| object.index),
+            ObjectPriority::XCoordinate => objects.sort_by_key(|object| (object.x, object.index)),
+        }
+    }
+}
+
+/// One 4-byte OAM entry. Positions are as stored: `y` is the screen line
+/// plus 16 and `x` the screen column plus 8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Object {
+    /// Position in OAM (0-39)
+    pub index: u8,
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+}
+
+/// Every entry in `oam`, in OAM order
+pub fn oam_objects(oam: &[u8]) -> impl Iterator<Item = Object> + '_ {
+    oam.chunks_exact(4).zip(0..).map(|(entry, index)| Object {
+        index,
+        y: entry[0],
+        x: entry[1],
+        tile: entry[2],
+        attributes: entry[3],
+    })
+}
+
+/// The objects the OAM scan picks for line `ly`: the first ten in OAM order
+/// that cover it, whether or not they end up on screen horizontally.
+/// `height` is 8 or 16 (LCDC bit 2).
+pub fn objects_on_line(oam: &[u8], ly: u8, height: u8) -> Vec<Object> {
+    let line = u16::from(ly) + 16;
+    oam_objects(oam)
+        .filter(|object| {
+            (u16::from(object.y)..u16::from(object.y) + u16::from(height)).contains(&line)
+        })
+        .take(MAX_PER_LINE)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// OAM with the given (y, x) entries
+    fn oam(entries: &[(u8, u8)]) -> Vec<u8> {
+        let mut oam = vec![0; 0xA0];
+        for (index, &(y, x)) in entries.iter().enumerate() {
+            oam[index * 4] = y;
+            oam[index * 4 + 1] = x;
+            oam[index * 4 + 2] = u8::try_from(index).unwrap();
+        }
+        oam
+    }
+
+    fn indices(objects: &[Object]) -> Vec<u8> {
+        objects.iter().map(|object| object.index).collect()
+    }
+
+    #[test]
+    fn scan_keeps_the_first_ten_covering_the_line() {
+        let mut entries = vec![(16, 8); 12];
+        entries[0] = (40, 8); // Below line 0
+        entries[1] = (1, 8); // Only reaches line 0 when 16 tall
+        let oam = oam(&entries);
+
+        assert_eq!(
+            indices(&objects_on_line(&oam, 0, 8)),
+            (2..12).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            indices(&objects_on_line(&oam, 0, 16)),
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+        );
+    }
+
+    #[test]
+    fn opri_chooses_how_overlaps_resolve() {
+        let oam = oam(&[(16, 30), (16, 20), (16, 20), (16, 25)]);
+        let mut objects = objects_on_line(&oam, 0, 8);
+
+        ObjectPriority::from_opri(0x01).sort(&mut objects);
+        assert_eq!(indices(&objects), [1, 2, 3, 0]);
+        ObjectPriority::from_opri(0x00).sort(&mut objects);
+        assert_eq!(indices(&objects), [0, 1, 2, 3]);
+
+        assert_eq!(ObjectPriority::default(), ObjectPriority::XCoordinate);
+        assert_eq!(ObjectPriority::from_opri(0xFE).opri(), 0xFE);
+    }
+}
@@ -0,0 +1,112 @@
+use super::DmgPalette;
+
+/// A game the CGB boot ROM recognises when colorizing DMG games
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompatEntry {
+    /// Sum of the 16 title bytes (0x0134-0x0143)
+    pub checksum: u8,
+    /// Fourth title byte (0x0137), for titles whose checksums collide
+    pub fourth_letter: Option<u8>,
+    pub palette: &'static str,
+}
+
+/// What the boot ROM shows games it does not recognise: green and blue
+/// background, red objects
+pub const DEFAULT: &str = "right+a";
+
+/// Recognised games. Only Tetris so far: the boot ROM knows about 90
+/// titles, many with palettes that are not among the presets, and until
+/// they are added here those games get `DEFAULT` like an unknown title.
+pub const TABLE: [CompatEntry; 1] = [CompatEntry {
+    checksum: 0xDB, // TETRIS
+    fourth_letter: None,
+    palette: "down+a",
+}];
+
+/// Sum of the title bytes, as the boot ROM hashes them
+pub fn title_checksum(rom: &[u8]) -> u8 {
+    rom[0x0134..=0x0143]
+        .iter()
+        .fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+/// Whether the header names Nintendo as the licensee. Only Nintendo games
+/// are looked up; everything else gets the default palette.
+pub fn is_nintendo(rom: &[u8]) -> bool {
+    match rom[0x014B] {
+        0x01 => true,
+        0x33 => rom[0x0144..=0x0145] == *b"01",
+        _ => false,
+    }
+}
+
+/// Palette the CGB boot ROM picks for the DMG game `rom` when no button
+/// combination is held, from the title checksum in `table`
+pub fn select(rom: &[u8], table: &[CompatEntry]) -> DmgPalette {
+    let name = if rom.len() < 0x0150 || !is_nintendo(rom) {
+        DEFAULT
+    } else {
+        let checksum = title_checksum(rom);
+        table
+            .iter()
+            .find(|entry| {
+                entry.checksum == checksum
+                    && entry
+                        .fourth_letter
+                        .is_none_or(|letter| letter == rom[0x0137])
+            })
+            .map_or(DEFAULT, |entry| entry.palette)
+    };
+    DmgPalette::from_name(name).expect("compatibility palettes are presets")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(title: &[u8], old_licensee: u8, new_licensee: [u8; 2]) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+        rom[0x0144..=0x0145].copy_from_slice(&new_licensee);
+        rom[0x014B] = old_licensee;
+        rom
+    }
+
+    #[test]
+    fn nintendo_titles_are_looked_up_by_checksum() {
+        let tetris = rom(b"TETRIS", 0x01, [0, 0]);
+        assert_eq!(title_checksum(&tetris), 0xDB);
+        assert_eq!(
+            select(&tetris, &TABLE),
+            DmgPalette::from_name("down+a").unwrap()
+        );
+
+        let unlicensed = rom(b"TETRIS", 0x00, [0, 0]);
+        let default = DmgPalette::from_name(DEFAULT).unwrap();
+        assert_eq!(select(&unlicensed, &TABLE), default);
+        assert!(is_nintendo(&rom(b"TETRIS", 0x33, *b"01")));
+        assert!(!is_nintendo(&rom(b"TETRIS", 0x33, *b"08")));
+    }
+
+    #[test]
+    fn fourth_letter_separates_colliding_checksums() {
+        let table = [
+            CompatEntry {
+                checksum: title_checksum(&rom(b"ABCD", 0, [0, 0])),
+                fourth_letter: Some(b'D'),
+                palette: "up",
+            },
+            CompatEntry {
+                checksum: title_checksum(&rom(b"ABCD", 0, [0, 0])),
+                fourth_letter: Some(b'C'),
+                palette: "left",
+            },
+        ];
+        let left = DmgPalette::from_name("left").unwrap();
+        assert_eq!(select(&rom(b"ABDC", 0x01, [0, 0]), &table), left);
+        assert_eq!(
+            select(&rom(b"ABCD", 0x01, [0, 0]), &table),
+            DmgPalette::from_name("up").unwrap()
+        );
+    }
+}
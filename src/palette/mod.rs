@@ -0,0 +1,403 @@
+mod compat;
+
+use std::str::FromStr;
+
+/// An RGBA8 color
+pub type Rgba = [u8; 4];
+
+/// Layer a frame buffer pixel came from, stored in bits 2-3 of each pixel
+/// byte (bits 0-1 hold the 2-bit shade). DMG colorization picks a different
+/// palette per layer, just like the CGB boot ROM does.
+pub const SOURCE_BG: u8 = 0;
+pub const SOURCE_OBJ0: u8 = 1;
+pub const SOURCE_OBJ1: u8 = 2;
+
+/// Pack a shade and its source layer into a frame buffer pixel
+pub fn pixel(shade: u8, source: u8) -> u8 {
+    (shade & 0x03) | ((source & 0x03) << 2)
+}
+
+const fn rgb(hex: u32) -> Rgba {
+    let [_, r, g, b] = hex.to_be_bytes();
+    [r, g, b, 0xFF]
+}
+
+const fn ramp(colors: [u32; 4]) -> [Rgba; 4] {
+    [
+        rgb(colors[0]),
+        rgb(colors[1]),
+        rgb(colors[2]),
+        rgb(colors[3]),
+    ]
+}
+
+/// Colors used to display DMG shades, one 4-color ramp per layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmgPalette {
+    pub bg: [Rgba; 4],
+    pub obj0: [Rgba; 4],
+    pub obj1: [Rgba; 4],
+}
+
+const BROWN: [u32; 4] = [0xFF_FFFF, 0xFF_AD63, 0x84_3100, 0x00_0000];
+const RED: [u32; 4] = [0xFF_FFFF, 0xFF_8484, 0x94_3A3A, 0x00_0000];
+
+impl DmgPalette {
+    /// The same ramp for every layer
+    pub const fn uniform(colors: [u32; 4]) -> Self {
+        Self {
+            bg: ramp(colors),
+            obj0: ramp(colors),
+            obj1: ramp(colors),
+        }
+    }
+
+    pub const GRAYSCALE: Self = Self::uniform([0xFF_FFFF, 0xAA_AAAA, 0x55_5555, 0x00_0000]);
+
+    /// Pea-soup green of the original DMG screen
+    pub const DMG_GREEN: Self = Self::uniform([0x9B_BC0F, 0x8B_AC0F, 0x30_6230, 0x0F_380F]);
+
+    /// Built-in palettes, including the ones the CGB boot ROM offers when a
+    /// button combination is held during the logo.
+    pub const PRESETS: [(&'static str, Self); 14] = [
+        ("grayscale", Self::GRAYSCALE),
+        ("dmg", Self::DMG_GREEN),
+        ("up", Self::uniform(BROWN)),
+        ("up+a", Self::uniform(RED)),
+        (
+            "up+b",
+            Self::uniform([0xFF_E7C5, 0xCC_9C85, 0x84_6B29, 0x5B_3109]),
+        ),
+        (
+            "left",
+            Self {
+                bg: ramp([0xFF_FFFF, 0x65_A49B, 0x00_00FE, 0x00_0000]),
+                obj0: ramp(RED),
+                obj1: ramp(RED),
+            },
+        ),
+        (
+            "left+a",
+            Self {
+                bg: ramp([0xFF_FFFF, 0x8C_8CDE, 0x52_528C, 0x00_0000]),
+                obj0: ramp(RED),
+                obj1: ramp(BROWN),
+            },
+        ),
+        (
+            "left+b",
+            Self::uniform([0xFF_FFFF, 0xA5_A5A5, 0x52_5252, 0x00_0000]),
+        ),
+        (
+            "down",
+            Self::uniform([0xFF_FFA5, 0xFF_9494, 0x94_94FF, 0x00_0000]),
+        ),
+        (
+            "down+a",
+            Self::uniform([0xFF_FFFF, 0xFF_FF00, 0xFF_0000, 0x00_0000]),
+        ),
+        (
+            "down+b",
+            Self {
+                bg: ramp([0xFF_FFFF, 0xFF_FF00, 0x7B_4A00, 0x00_0000]),
+                obj0: ramp([0xFF_FFFF, 0x63_A5FF, 0x00_00FF, 0x00_0000]),
+                obj1: ramp([0xFF_FFFF, 0x7B_FF31, 0x00_8400, 0x00_0000]),
+            },
+        ),
+        (
+            "right",
+            Self::uniform([0xFF_FFFF, 0x52_FF00, 0xFF_4200, 0x00_0000]),
+        ),
+        (
+            "right+a",
+            Self {
+                bg: ramp([0xFF_FFFF, 0x7B_FF31, 0x00_63C5, 0x00_0000]),
+                obj0: ramp(RED),
+                obj1: ramp(RED),
+            },
+        ),
+        (
+            "right+b",
+            Self::uniform([0x00_0000, 0x00_8484, 0xFF_DE00, 0xFF_FFFF]),
+        ),
+    ];
+
+    /// The palette the CGB boot ROM gives the DMG game `rom`, chosen from a
+    /// hash of its title
+    pub fn cgb_compat(rom: &[u8]) -> Self {
+        compat::select(rom, &compat::TABLE)
+    }
+
+    /// Look up a built-in palette by name (case-insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        Self::PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    /// Parse a user palette file. Each line assigns four hex colors
+    /// (lightest first) to a layer; layers that are not given use `bg`:
+    ///
+    /// ```text
+    /// # comment
+    /// bg   = E0F8D0 88C070 346856 081820
+    /// obj0 = FFFFFF FF8484 943A3A 000000
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bg = None;
+        let mut obj0 = None;
+        let mut obj1 = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (layer, colors) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Line {}: expected `layer = colors`", number + 1))?;
+            let colors = parse_ramp(colors).map_err(|e| format!("Line {}: {e}", number + 1))?;
+            match layer.trim().to_ascii_lowercase().as_str() {
+                "bg" => bg = Some(colors),
+                "obj0" => obj0 = Some(colors),
+                "obj1" => obj1 = Some(colors),
+                other => return Err(format!("Line {}: unknown layer `{other}`", number + 1)),
+            }
+        }
+
+        let bg = bg.ok_or("Palette has no `bg` line")?;
+        Ok(Self {
+            bg,
+            obj0: obj0.unwrap_or(bg),
+            obj1: obj1.unwrap_or(bg),
+        })
+    }
+
+    /// Color for a frame buffer pixel produced with `pixel`
+    pub fn color(&self, pixel: u8) -> Rgba {
+        let ramp = match (pixel >> 2) & 0x03 {
+            SOURCE_OBJ0 => &self.obj0,
+            SOURCE_OBJ1 => &self.obj1,
+            _ => &self.bg,
+        };
+        ramp[usize::from(pixel & 0x03)]
+    }
+
+    /// Convert frame buffer pixels to packed RGBA8
+    pub fn to_rgba(&self, pixels: &[u8]) -> Vec<u8> {
+        pixels.iter().flat_map(|&pixel| self.color(pixel)).collect()
+    }
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        Self::GRAYSCALE
+    }
+}
+
+fn parse_ramp(text: &str) -> Result<[Rgba; 4], String> {
+    let colors = text
+        .split_whitespace()
+        .map(|hex| {
+            let hex = hex.trim_start_matches('#');
+            if hex.len() != 6 {
+                return Err(format!("`{hex}` is not an RRGGBB color"));
+            }
+            u32::from_str_radix(hex, 16)
+                .map(rgb)
+                .map_err(|_| format!("`{hex}` is not an RRGGBB color"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    colors
+        .try_into()
+        .map_err(|colors: Vec<Rgba>| format!("expected 4 colors, found {}", colors.len()))
+}
+
+/// How 15-bit CGB colors are turned into RGBA. The CGB LCD washes colors out
+/// and bleeds channels into each other, so games tuned on hardware look
+/// oversaturated when the raw values are shown on a modern screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorCorrection {
+    /// Scale each 5-bit channel straight to 8 bits
+    Raw,
+    /// Approximate the CGB LCD's channel bleed and reduced brightness
+    #[default]
+    Cgb,
+    /// Darker, gamma-adjusted output resembling the GBA screen
+    Gba,
+}
+
+impl FromStr for ColorCorrection {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "raw" => Ok(ColorCorrection::Raw),
+            "cgb" => Ok(ColorCorrection::Cgb),
+            "gba" => Ok(ColorCorrection::Gba),
+            _ => Err(format!("Unknown color correction: {name}")),
+        }
+    }
+}
+
+impl ColorCorrection {
+    /// Convert a CGB palette entry (`0bbbbbgggggrrrrr`) to RGBA
+    pub fn convert(self, color: u16) -> Rgba {
+        let r = color & 0x1F;
+        let g = (color >> 5) & 0x1F;
+        let b = (color >> 10) & 0x1F;
+        match self {
+            ColorCorrection::Raw => [expand5(r), expand5(g), expand5(b), 0xFF],
+            ColorCorrection::Cgb => {
+                // Each output channel mixes in some of the others; the sums
+                // top out at 960 so the brightest white is 0xF0, not 0xFF
+                let mix = |value: u16| u8::try_from(value.min(960) >> 2).unwrap_or(u8::MAX);
+                [
+                    mix(r * 26 + g * 4 + b * 2),
+                    mix(g * 24 + b * 8),
+                    mix(r * 6 + g * 4 + b * 22),
+                    0xFF,
+                ]
+            }
+            ColorCorrection::Gba => {
+                let linear = |value: u16| (f32::from(value) / 31.0).powf(GBA_GAMMA);
+                let (r, g, b) = (linear(r), linear(g), linear(b));
+                [
+                    encode(0.80 * r + 0.135 * g + 0.195 * b),
+                    encode(0.275 * r + 0.64 * g + 0.155 * b),
+                    encode(-0.075 * r + 0.225 * g + 0.65 * b),
+                    0xFF,
+                ]
+            }
+        }
+    }
+
+    /// Every 15-bit color converted up front, indexed by the raw palette entry
+    pub fn table(self) -> Vec<Rgba> {
+        (0..0x8000).map(|color| self.convert(color)).collect()
+    }
+}
+
+/// The GBA screen is darker than sRGB, so colors are linearized with a
+/// steeper curve than they are re-encoded with
+const GBA_GAMMA: f32 = 2.4;
+const DISPLAY_GAMMA: f32 = 2.2;
+
+fn expand5(value: u16) -> u8 {
+    u8::try_from((value << 3) | (value >> 2)).unwrap_or(u8::MAX)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn encode(linear: f32) -> u8 {
+    (linear.clamp(0.0, 1.0).powf(DISPLAY_GAMMA.recip()) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_found_by_name() {
+        assert_eq!(
+            DmgPalette::from_name("Grayscale"),
+            Some(DmgPalette::GRAYSCALE)
+        );
+        assert_eq!(
+            DmgPalette::from_name("UP+A").unwrap().bg[1],
+            [0xFF, 0x84, 0x84, 0xFF]
+        );
+        assert_eq!(DmgPalette::from_name("sepia"), None);
+    }
+
+    #[test]
+    fn color_uses_layer_of_pixel() {
+        let palette = DmgPalette::from_name("left+a").unwrap();
+
+        assert_eq!(palette.color(pixel(1, SOURCE_BG)), [0x8C, 0x8C, 0xDE, 0xFF]);
+        assert_eq!(
+            palette.color(pixel(1, SOURCE_OBJ0)),
+            [0xFF, 0x84, 0x84, 0xFF]
+        );
+        assert_eq!(
+            palette.color(pixel(1, SOURCE_OBJ1)),
+            [0xFF, 0xAD, 0x63, 0xFF]
+        );
+    }
+
+    #[test]
+    fn to_rgba_packs_four_bytes_per_pixel() {
+        let rgba = DmgPalette::GRAYSCALE.to_rgba(&[0, 3]);
+        assert_eq!(rgba, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn parse_user_palette() {
+        let palette = DmgPalette::parse(
+            "# Per-game palette\n\
+             bg = E0F8D0 88C070 346856 081820\n\
+             obj1 = #FFFFFF #FF8484 #943A3A #000000\n",
+        )
+        .unwrap();
+
+        assert_eq!(palette.bg[0], [0xE0, 0xF8, 0xD0, 0xFF]);
+        assert_eq!(palette.obj0, palette.bg, "Missing layers fall back to bg");
+        assert_eq!(palette.obj1[1], [0xFF, 0x84, 0x84, 0xFF]);
+    }
+
+    #[test]
+    fn parse_rejects_bad_input() {
+        assert!(DmgPalette::parse("").is_err());
+        assert!(DmgPalette::parse("bg = FFFFFF 000000").is_err());
+        assert!(DmgPalette::parse("bg = FFFFFF 000000 123456 GGGGGG").is_err());
+        assert!(DmgPalette::parse("window = FFFFFF 000000 123456 654321").is_err());
+    }
+
+    #[test]
+    fn raw_correction_scales_channels_to_full_range() {
+        assert_eq!(
+            ColorCorrection::Raw.convert(0x7FFF),
+            [0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(
+            ColorCorrection::Raw.convert(0x001F),
+            [0xFF, 0x00, 0x00, 0xFF]
+        );
+        assert_eq!(
+            ColorCorrection::Raw.convert(0x0000),
+            [0x00, 0x00, 0x00, 0xFF]
+        );
+    }
+
+    #[test]
+    fn cgb_correction_bleeds_channels_and_dims_white() {
+        let red = ColorCorrection::Cgb.convert(0x001F);
+        assert!(red[0] < 0xFF);
+        assert!(red[2] > 0x00, "Red bleeds into blue on the CGB LCD");
+        assert_eq!(
+            ColorCorrection::Cgb.convert(0x7FFF),
+            [0xF0, 0xF0, 0xF0, 0xFF]
+        );
+    }
+
+    #[test]
+    fn gba_correction_is_darker_than_raw() {
+        let raw = ColorCorrection::Raw.convert(0x3DEF);
+        let gba = ColorCorrection::Gba.convert(0x3DEF);
+        assert!(gba[..3].iter().zip(&raw[..3]).all(|(gba, raw)| gba < raw));
+        assert_eq!(
+            ColorCorrection::Gba.convert(0x0000),
+            [0x00, 0x00, 0x00, 0xFF]
+        );
+    }
+
+    #[test]
+    fn correction_table_matches_convert() {
+        let table = ColorCorrection::Cgb.table();
+        assert_eq!(table.len(), 0x8000);
+        assert_eq!(table[0x1234], ColorCorrection::Cgb.convert(0x1234));
+        assert_eq!("GBA".parse(), Ok(ColorCorrection::Gba));
+        assert!("vivid".parse::<ColorCorrection>().is_err());
+    }
+}
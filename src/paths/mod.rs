@@ -0,0 +1,184 @@
+use crate::cartridge::CartridgeHeader;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Per-game data directory holding saves, states, screenshots, cheats and config.
+///
+/// By default this lives under the user data directory
+/// (`$XDG_DATA_HOME/gameboy` or `~/.local/share/gameboy`) in a folder named
+/// after the ROM title and global checksum, so two ROMs with the same title
+/// never share saves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameDirectory {
+    root: PathBuf,
+}
+
+impl GameDirectory {
+    /// Directory for `header` under the default data root, or under `data_dir` if given
+    pub fn for_rom(header: &CartridgeHeader, data_dir: Option<&Path>) -> Self {
+        let base = data_dir.map_or_else(default_data_root, Path::to_path_buf);
+        Self {
+            root: base.join(game_folder_name(header)),
+        }
+    }
+
+    /// Use `root` directly as the game directory
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Battery-backed cartridge RAM
+    pub fn save_file(&self) -> PathBuf {
+        self.root.join("save.sav")
+    }
+
+    pub fn states_dir(&self) -> PathBuf {
+        self.root.join("states")
+    }
+
+    /// Save state file for a numbered slot
+    pub fn state_file(&self, slot: u8) -> PathBuf {
+        self.states_dir().join(format!("slot{slot}.state"))
+    }
+
+    /// Cached post-boot state for an exact ROM dump, hardware model and intro length
+    pub fn fast_boot_file(&self, model: &str, rom_hash: u32, frames: u32) -> PathBuf {
+        self.states_dir()
+            .join(format!("fastboot-{model}-{rom_hash:08X}-{frames}.state"))
+    }
+
+    pub fn screenshots_dir(&self) -> PathBuf {
+        self.root.join("screenshots")
+    }
+
+    pub fn cheats_file(&self) -> PathBuf {
+        self.root.join("cheats.txt")
+    }
+
+    /// User-supplied DMG colorization for this game
+    pub fn palette_file(&self) -> PathBuf {
+        self.root.join("palette.txt")
+    }
+
+    /// Named RAM locations to decode (see `rammap::RamMap`)
+    pub fn ram_map_file(&self) -> PathBuf {
+        self.root.join("ram_map.txt")
+    }
+
+    /// Cumulative emulated and wall-clock play time
+    pub fn play_time_file(&self) -> PathBuf {
+        self.root.join("playtime.json")
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.root.join("config.toml")
+    }
+
+    /// Create the directory tree if it does not exist yet
+    pub fn create(&self) -> io::Result<()> {
+        fs::create_dir_all(self.states_dir())?;
+        fs::create_dir_all(self.screenshots_dir())
+    }
+}
+
+fn default_data_root() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("gameboy");
+    }
+    if let Some(home) = env::var_os("HOME").filter(|home| !home.is_empty()) {
+        return PathBuf::from(home).join(".local/share/gameboy");
+    }
+    PathBuf::from("gameboy-data")
+}
+
+/// `TITLE-XXXX` with anything outside `[A-Za-z0-9_-]` replaced by `_`.
+/// Titles made entirely of padding or unprintable bytes become `UNTITLED`.
+fn game_folder_name(header: &CartridgeHeader) -> String {
+    let title: String = header
+        .title
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let title = title.trim_matches('_');
+    let title = if title.is_empty() { "UNTITLED" } else { title };
+    format!("{title}-{:04X}", header.global_checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::CartridgeType;
+
+    fn header(title: &str, global_checksum: u16) -> CartridgeHeader {
+        CartridgeHeader {
+            title: title.to_string(),
+            cartridge_type: CartridgeType::RomOnly,
+            rom_size: 0x8000,
+            ram_size: 0,
+            global_checksum,
+        }
+    }
+
+    #[test]
+    fn folder_name_uses_title_and_checksum() {
+        assert_eq!(game_folder_name(&header("TETRIS", 0x16BF)), "TETRIS-16BF");
+    }
+
+    #[test]
+    fn folder_name_sanitizes_title() {
+        assert_eq!(
+            game_folder_name(&header("POKEMON RED/1", 0x0001)),
+            "POKEMON_RED_1-0001"
+        );
+        assert_eq!(game_folder_name(&header("", 0xABCD)), "UNTITLED-ABCD");
+        assert_eq!(
+            game_folder_name(&header("\u{FFFD}\u{FFFD}", 0x0002)),
+            "UNTITLED-0002"
+        );
+    }
+
+    #[test]
+    fn override_directory_is_used_as_base() {
+        let dir = GameDirectory::for_rom(&header("TETRIS", 0x16BF), Some(Path::new("/tmp/gb")));
+        assert_eq!(dir.root(), Path::new("/tmp/gb/TETRIS-16BF"));
+        assert_eq!(dir.save_file(), Path::new("/tmp/gb/TETRIS-16BF/save.sav"));
+        assert_eq!(
+            dir.state_file(3),
+            Path::new("/tmp/gb/TETRIS-16BF/states/slot3.state")
+        );
+    }
+
+    #[test]
+    fn fast_boot_file_is_keyed_by_model_hash_and_frames() {
+        let dir = GameDirectory::at("/tmp/gb/TETRIS-16BF");
+        assert_eq!(
+            dir.fast_boot_file("dmg", 0x46DB_D8E7, 120),
+            Path::new("/tmp/gb/TETRIS-16BF/states/fastboot-dmg-46DBD8E7-120.state")
+        );
+    }
+
+    #[test]
+    fn create_builds_directory_tree() {
+        let root = env::temp_dir().join(format!("gameboy-paths-{}", std::process::id()));
+        let dir = GameDirectory::at(&root);
+
+        dir.create().unwrap();
+        assert!(dir.states_dir().is_dir());
+        assert!(dir.screenshots_dir().is_dir());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
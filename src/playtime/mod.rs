@@ -0,0 +1,120 @@
+use crate::gameboy::CYCLES_PER_FRAME;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// CPU clock of the original Game Boy
+pub const CYCLES_PER_SECOND: u64 = 4_194_304;
+
+/// Emulated time taken by `frames` frames (about 59.73 per second)
+pub fn emulated_time(frames: u64) -> Duration {
+    let cycles = frames * u64::from(CYCLES_PER_FRAME);
+    Duration::from_nanos(cycles * 1_000_000_000 / CYCLES_PER_SECOND)
+}
+
+/// Whole frames in `seconds` of emulated time
+pub fn emulated_frames(seconds: u64) -> u64 {
+    seconds * CYCLES_PER_SECOND / u64::from(CYCLES_PER_FRAME)
+}
+
+/// Cumulative play time for one game across sessions. Emulated time counts
+/// what the game itself experienced; wall-clock time also includes pauses,
+/// fast-forward and slow motion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayTime {
+    pub frames: u64,
+    pub wall_clock: Duration,
+}
+
+impl PlayTime {
+    pub fn emulated(&self) -> Duration {
+        emulated_time(self.frames)
+    }
+
+    /// Add one session
+    pub fn add(&mut self, frames: u64, wall_clock: Duration) {
+        self.frames += frames;
+        self.wall_clock += wall_clock;
+    }
+
+    /// Read the totals, starting from zero if the game was never played
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        let field = |name: &str| {
+            value[name]
+                .as_u64()
+                .ok_or_else(|| format!("{}: missing or invalid {name}", path.display()))
+        };
+        Ok(Self {
+            frames: field("frames")?,
+            wall_clock: Duration::from_millis(field("wall_clock_ms")?),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let value = json!({
+            "frames": self.frames,
+            "wall_clock_ms": u64::try_from(self.wall_clock.as_millis()).unwrap_or(u64::MAX),
+        });
+        fs::write(path, format!("{value:#}\n")).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+/// `H:MM:SS`, hours unbounded
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn one_emulated_second_is_about_60_frames() {
+        assert_eq!(emulated_time(0), Duration::ZERO);
+        assert_eq!(emulated_time(3600 * 60).as_secs(), 3616); // Slightly under 60 fps
+        assert_eq!(emulated_frames(60), 3583);
+        assert_eq!(
+            format_duration(Duration::from_secs(100 * 3600 + 62)),
+            "100:01:02"
+        );
+    }
+
+    #[test]
+    fn totals_accumulate_and_round_trip() {
+        let path = env::temp_dir().join(format!("gameboy-playtime-{}.json", std::process::id()));
+        assert_eq!(PlayTime::load(&path), Ok(PlayTime::default()));
+
+        let mut play_time = PlayTime::default();
+        play_time.add(600, Duration::from_secs(12));
+        play_time.add(60, Duration::from_secs(3));
+        play_time.save(&path).unwrap();
+        let loaded = PlayTime::load(&path);
+        fs::write(&path, "{}").unwrap();
+        let invalid = PlayTime::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            loaded,
+            Ok(PlayTime {
+                frames: 660,
+                wall_clock: Duration::from_secs(15)
+            })
+        );
+        assert!(invalid.is_err());
+    }
+}
@@ -0,0 +1,587 @@
+use super::{BGP, LCDC, Layer, Layers, Lcdc, OAM, OBP0, OBP1, SCX, SCY, WX, shade};
+use crate::memory::Memory;
+use crate::objects::{Object, ObjectPriority, objects_on_line};
+use crate::palette::{SOURCE_BG, SOURCE_OBJ0, SOURCE_OBJ1, pixel};
+use crate::savestate::{StateReader, StateWriter};
+
+/// Screen columns in a line
+const WIDTH: usize = 160;
+/// Dots the fetcher spends on each of its reads
+const STEP_DOTS: u8 = 2;
+/// Dots fetching an object's tile row stalls the line for, on top of any
+/// wait for the background fetcher
+const OBJECT_FETCH_DOTS: u8 = 6;
+
+/// Where the background fetcher is in fetching one row of a tile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Step {
+    #[default]
+    Tile,
+    DataLow,
+    DataHigh,
+    /// Holding the row until the background FIFO runs empty
+    Push,
+}
+
+/// One pixel waiting in the object FIFO; color 0 is transparent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ObjectPixel {
+    color: u8,
+    source: u8,
+    behind_background: bool,
+    /// OAM index of the object, for CGB-style overlap priority
+    index: u8,
+}
+
+/// Mode 3 of one line, a dot at a time, as the hardware draws it: a
+/// fetcher reads background or window tile rows into an 8-pixel FIFO, one
+/// pixel is shifted out to the screen per dot, and objects are fetched into
+/// a second FIFO as the line reaches them, stalling the background.
+///
+/// Mode 3 lasts until the 160th pixel is out, so its length varies like the
+/// hardware's: 172 dots, plus SCX % 8 dropped at the start of the line,
+/// 6 when the window starts, and 6 to 11 per object. Registers are read as
+/// the fetcher gets to them, so mid-line writes show up mid-line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Fifo {
+    ly: u8,
+    /// Pixels still to drop instead of drawing: SCX % 8 at the start of the
+    /// line, or the part of the window left of the screen
+    discard: u8,
+    step: Step,
+    /// Dots spent on the current step
+    step_dots: u8,
+    /// Tiles fetched so far, from SCX or the window's left edge
+    fetch_x: u8,
+    tile: u8,
+    low: u8,
+    high: u8,
+    /// The first tile of a line is fetched twice
+    first_fetch: bool,
+    /// Fetching the window rather than the background
+    window: bool,
+    /// Row of the window map being drawn (`Ppu::window_line`)
+    window_y: u8,
+    /// The background FIFO as two bit planes, next pixel out in bit 7
+    bg_low: u8,
+    bg_high: u8,
+    bg_len: u8,
+    /// Object pixels for the next eight columns
+    object_pixels: [ObjectPixel; 8],
+    /// What the OAM scan picked for this line
+    objects: Vec<Object>,
+    /// Bit per entry of `objects` already fetched
+    fetched: u16,
+    /// Dots left of an object fetch
+    stall: u8,
+    /// Bit per background (or window) tile column an object already waited
+    /// on the fetcher for
+    waited: u32,
+    /// The line so far, one frame buffer pixel per column
+    line: Vec<u8>,
+}
+
+impl Fifo {
+    /// Begin mode 3 of line `ly`, after the OAM scan
+    pub(crate) fn start_line(&mut self, memory: &Memory, ly: u8) {
+        let height = Lcdc(memory.peek(LCDC)).object_height();
+        let mut line = std::mem::take(&mut self.line);
+        line.clear();
+        *self = Self {
+            ly,
+            discard: memory.peek(SCX) % 8,
+            first_fetch: true,
+            objects: objects_on_line(&memory.data[OAM], ly, height),
+            line,
+            ..Self::default()
+        };
+    }
+
+    /// Whether all 160 pixels are out, ending mode 3
+    pub(crate) fn is_done(&self) -> bool {
+        self.line.len() == WIDTH
+    }
+
+    /// Whether the window started on this line
+    pub(crate) fn window_drawn(&self) -> bool {
+        self.window
+    }
+
+    pub(crate) fn line(&self) -> &[u8] {
+        &self.line
+    }
+
+    /// Run one dot. `window_y` is the window line to draw once WX is
+    /// reached, or `None` before WY has matched this frame.
+    pub(crate) fn tick(&mut self, memory: &Memory, layers: Layers, window_y: Option<u8>) {
+        if self.stall > 0 {
+            self.stall -= 1;
+            return;
+        }
+        let lcdc = Lcdc(memory.peek(LCDC));
+        let x = self.line.len();
+        if lcdc.objects_enabled()
+            && layers.is_visible(Layer::Objects)
+            && self.fetch_object(memory, lcdc, x)
+        {
+            return;
+        }
+
+        let wx = memory.peek(WX);
+        if let Some(window_y) = window_y
+            && !self.window
+            && lcdc.background_enabled()
+            && lcdc.window_enabled()
+            && layers.is_visible(Layer::Window)
+            && x + 7 >= usize::from(wx)
+        {
+            // The background pixels waiting are dropped and the fetcher
+            // starts over on the window's first tile
+            self.window = true;
+            self.window_y = window_y;
+            self.bg_len = 0;
+            self.step = Step::Tile;
+            self.step_dots = 0;
+            self.fetch_x = 0;
+            self.waited = 0;
+            if x == 0 {
+                self.discard = 7_u8.saturating_sub(wx);
+            }
+        }
+
+        self.fetch(memory, lcdc);
+        if self.bg_len == 0 {
+            return;
+        }
+        let color = ((self.bg_high >> 7) << 1) | (self.bg_low >> 7);
+        self.bg_low <<= 1;
+        self.bg_high <<= 1;
+        self.bg_len -= 1;
+        if self.discard > 0 {
+            self.discard -= 1;
+            return;
+        }
+        let object = self.object_pixels[0];
+        self.object_pixels.copy_within(1.., 0);
+        self.object_pixels[7] = ObjectPixel::default();
+        let out = self.mix(memory, lcdc, layers, color, object);
+        self.line.push(out);
+    }
+
+    /// Advance the background fetcher a dot. Each read lands on the second
+    /// dot of its step; the row is pushed as soon as the FIFO is empty.
+    fn fetch(&mut self, memory: &Memory, lcdc: Lcdc) {
+        if self.step == Step::Push {
+            if self.bg_len == 0 {
+                self.bg_low = self.low;
+                self.bg_high = self.high;
+                self.bg_len = 8;
+                self.fetch_x = self.fetch_x.wrapping_add(1);
+                self.step = Step::Tile;
+            }
+            return;
+        }
+        self.step_dots += 1;
+        if self.step_dots < STEP_DOTS {
+            return;
+        }
+        self.step_dots = 0;
+
+        let (map, column, y) = if self.window {
+            (lcdc.window_map(), self.fetch_x, self.window_y)
+        } else {
+            let column = (memory.peek(SCX) / 8).wrapping_add(self.fetch_x);
+            (
+                lcdc.background_map(),
+                column,
+                self.ly.wrapping_add(memory.peek(SCY)),
+            )
+        };
+        let row = lcdc.tile_address(self.tile) + u16::from(y % 8) * 2;
+        match self.step {
+            Step::Tile => {
+                self.tile = memory.peek(map + u16::from(y / 8) * 32 + u16::from(column % 32));
+                self.step = Step::DataLow;
+            }
+            Step::DataLow => {
+                self.low = memory.peek(row);
+                self.step = Step::DataHigh;
+            }
+            Step::DataHigh => {
+                self.high = memory.peek(row + 1);
+                if self.first_fetch {
+                    self.first_fetch = false;
+                    self.step = Step::Tile;
+                } else {
+                    self.step = Step::Push;
+                }
+            }
+            Step::Push => {}
+        }
+    }
+
+    /// Fetch the next object starting at column `x`, if there is one,
+    /// merging its row into the object FIFO and stalling for the fetch.
+    /// Objects hanging off the left edge all start at column 0, and are
+    /// fetched from the leftmost, then in OAM order like the rest.
+    fn fetch_object(&mut self, memory: &Memory, lcdc: Lcdc, x: usize) -> bool {
+        let Some(entry) = (0..self.objects.len())
+            .filter(|&entry| {
+                self.fetched & (1 << entry) == 0
+                    && usize::from(self.objects[entry].x).saturating_sub(8) == x
+            })
+            .min_by_key(|&entry| self.objects[entry].x)
+        else {
+            return false;
+        };
+        self.fetched |= 1 << entry;
+        let object = self.objects[entry];
+        self.stall = self.object_penalty(memory, object) - 1;
+
+        let height = lcdc.object_height();
+        let mut row = self.ly.wrapping_add(16).wrapping_sub(object.y);
+        if object.attributes & 0x40 != 0 {
+            row = (height - 1).wrapping_sub(row);
+        }
+        // 8x16 objects ignore bit 0 of the tile index
+        let tile = if height == 16 {
+            object.tile & 0xFE
+        } else {
+            object.tile
+        };
+        let address = 0x8000 + u16::from(tile) * 16 + u16::from(row) * 2;
+        let (low, high) = (memory.peek(address), memory.peek(address + 1));
+        let source = if object.attributes & 0x10 == 0 {
+            SOURCE_OBJ0
+        } else {
+            SOURCE_OBJ1
+        };
+
+        // Objects are fetched left to right, so on the DMG a pixel already
+        // in the FIFO belongs to the object further left, or the earlier one
+        // in OAM at the same X, and keeps its place
+        let skipped = x + 8 - usize::from(object.x);
+        for (slot, column) in (skipped..8).enumerate() {
+            let bit = if object.attributes & 0x20 == 0 {
+                7 - column
+            } else {
+                column
+            };
+            let color = (((high >> bit) & 0x01) << 1) | ((low >> bit) & 0x01);
+            let queued = &mut self.object_pixels[slot];
+            let wins = queued.color == 0
+                || (memory.object_priority == ObjectPriority::OamIndex
+                    && object.index < queued.index);
+            if color != 0 && wins {
+                *queued = ObjectPixel {
+                    color,
+                    source,
+                    behind_background: object.attributes & 0x80 != 0,
+                    index: object.index,
+                };
+            }
+        }
+        true
+    }
+
+    /// Dots `object` holds up the line for: the fetch itself, plus waiting
+    /// for the fetcher to finish the tile under the object's leftmost pixel,
+    /// the first time an object lands on that tile. An object at X 0 always
+    /// costs 11.
+    fn object_penalty(&mut self, memory: &Memory, object: Object) -> u8 {
+        if object.x == 0 {
+            return 11;
+        }
+        let under = if self.window {
+            object.x.wrapping_sub(1).wrapping_sub(memory.peek(WX))
+        } else {
+            object.x.wrapping_sub(8).wrapping_add(memory.peek(SCX))
+        };
+        let tile = 1 << (under / 8);
+        let mut penalty = OBJECT_FETCH_DOTS;
+        if self.waited & tile == 0 {
+            self.waited |= tile;
+            penalty += (7 - under % 8).saturating_sub(2);
+        }
+        penalty
+    }
+
+    /// The frame buffer pixel for background `color` with `object` over it
+    fn mix(
+        &self,
+        memory: &Memory,
+        lcdc: Lcdc,
+        layers: Layers,
+        color: u8,
+        object: ObjectPixel,
+    ) -> u8 {
+        // Off or hidden, the background is blank and hides no objects
+        let shown =
+            lcdc.background_enabled() && (self.window || layers.is_visible(Layer::Background));
+        let color = if shown { color } else { 0 };
+        if object.color != 0
+            && lcdc.objects_enabled()
+            && layers.is_visible(Layer::Objects)
+            && !(object.behind_background && color != 0)
+        {
+            let palette = memory.peek(if object.source == SOURCE_OBJ0 {
+                OBP0
+            } else {
+                OBP1
+            });
+            return pixel(shade(palette, object.color), object.source);
+        }
+        if shown {
+            pixel(shade(memory.peek(BGP), color), SOURCE_BG)
+        } else {
+            pixel(0, SOURCE_BG)
+        }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.ly);
+        state.write_u8(self.discard);
+        state.write_u8(self.step as u8);
+        state.write_u8(self.step_dots);
+        state.write_u8(self.fetch_x);
+        state.write_u8(self.tile);
+        state.write_u8(self.low);
+        state.write_u8(self.high);
+        state.write_bool(self.first_fetch);
+        state.write_bool(self.window);
+        state.write_u8(self.window_y);
+        state.write_u8(self.bg_low);
+        state.write_u8(self.bg_high);
+        state.write_u8(self.bg_len);
+        for pixel in &self.object_pixels {
+            state.write_u8(pixel.color);
+            state.write_u8(pixel.source);
+            state.write_bool(pixel.behind_background);
+            state.write_u8(pixel.index);
+        }
+        let objects: Vec<u8> = self
+            .objects
+            .iter()
+            .flat_map(|object| {
+                [
+                    object.index,
+                    object.y,
+                    object.x,
+                    object.tile,
+                    object.attributes,
+                ]
+            })
+            .collect();
+        state.write_bytes(&objects);
+        state.write_u16(self.fetched);
+        state.write_u8(self.stall);
+        state.write_u32(self.waited);
+        state.write_bytes(&self.line);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.ly = state.read_u8()?;
+        self.discard = state.read_u8()?;
+        self.step = match state.read_u8()? {
+            0 => Step::Tile,
+            1 => Step::DataLow,
+            2 => Step::DataHigh,
+            3 => Step::Push,
+            step => return Err(format!("Invalid fetcher step: {step}")),
+        };
+        self.step_dots = state.read_u8()?;
+        self.fetch_x = state.read_u8()?;
+        self.tile = state.read_u8()?;
+        self.low = state.read_u8()?;
+        self.high = state.read_u8()?;
+        self.first_fetch = state.read_bool()?;
+        self.window = state.read_bool()?;
+        self.window_y = state.read_u8()?;
+        self.bg_low = state.read_u8()?;
+        self.bg_high = state.read_u8()?;
+        self.bg_len = state.read_u8()?;
+        for pixel in &mut self.object_pixels {
+            pixel.color = state.read_u8()?;
+            pixel.source = state.read_u8()?;
+            pixel.behind_background = state.read_bool()?;
+            pixel.index = state.read_u8()?;
+        }
+        let objects = state.read_bytes()?;
+        self.objects = objects
+            .chunks_exact(5)
+            .map(|entry| Object {
+                index: entry[0],
+                y: entry[1],
+                x: entry[2],
+                tile: entry[3],
+                attributes: entry[4],
+            })
+            .collect();
+        self.fetched = state.read_u16()?;
+        self.stall = state.read_u8()?;
+        self.waited = state.read_u32()?;
+        self.line = state.read_bytes()?;
+        if self.line.len() > WIDTH || self.objects.len() > 16 {
+            return Err("Invalid pixel FIFO state".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu::{Ppu, WY};
+
+    /// Memory with the LCD, background and objects on
+    fn memory() -> Memory {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, 0x93);
+        memory.write_byte(BGP, 0xE4);
+        memory
+    }
+
+    /// Dots line 0 spends in mode 3
+    fn mode3_dots(memory: &Memory) -> u32 {
+        let mut ppu = Ppu::default();
+        ppu.tick(79, memory, Layers::default());
+        let mut dots = 0;
+        loop {
+            ppu.tick(1, memory, Layers::default());
+            if ppu.mode() != 3 {
+                return dots;
+            }
+            dots += 1;
+        }
+    }
+
+    fn place_object(memory: &mut Memory, slot: usize, x: u8) {
+        let entry = 0xFE00 + slot * 4;
+        memory.data[entry..entry + 4].copy_from_slice(&[16, x, 0, 0]);
+    }
+
+    #[test]
+    fn fine_scroll_lengthens_mode_3() {
+        let mut memory = memory();
+        assert_eq!(mode3_dots(&memory), 172);
+        memory.write_byte(SCX, 3);
+        assert_eq!(mode3_dots(&memory), 175);
+        memory.write_byte(SCX, 8);
+        assert_eq!(mode3_dots(&memory), 172, "Whole tiles cost nothing");
+    }
+
+    #[test]
+    fn objects_stall_by_where_they_sit_on_the_tile() {
+        let mut memory = memory();
+        place_object(&mut memory, 0, 8);
+        assert_eq!(mode3_dots(&memory), 172 + 11);
+        place_object(&mut memory, 1, 12);
+        assert_eq!(mode3_dots(&memory), 172 + 11 + 6, "Same tile: no wait");
+
+        let mut memory = self::memory();
+        place_object(&mut memory, 0, 13);
+        assert_eq!(mode3_dots(&memory), 172 + 6);
+        place_object(&mut memory, 0, 0);
+        assert_eq!(mode3_dots(&memory), 172 + 11, "Off the left edge");
+        place_object(&mut memory, 0, 168);
+        assert_eq!(mode3_dots(&memory), 172, "Off the right edge");
+
+        place_object(&mut memory, 0, 8);
+        memory.write_byte(LCDC, 0x91);
+        assert_eq!(mode3_dots(&memory), 172, "Objects off");
+    }
+
+    #[test]
+    fn window_restarts_the_fetcher() {
+        let mut memory = memory();
+        memory.write_byte(LCDC, 0xB3);
+        memory.write_byte(WX, 50);
+        assert_eq!(mode3_dots(&memory), 172 + 6);
+        memory.write_byte(WY, 1);
+        assert_eq!(mode3_dots(&memory), 172, "Below line 0");
+    }
+
+    /// Line 0 as drawn by a PPU from the start of the frame
+    fn render(memory: &Memory) -> Vec<u8> {
+        let mut ppu = Ppu::default();
+        while ppu.tick(1, memory, Layers::default()).line_finished != Some(0) {}
+        ppu.line().to_vec()
+    }
+
+    /// Memory with tile 1 solid color 3 for objects, OBP0 and OBP1 both
+    /// showing it as shade 3 so the frame buffer's source bits tell them
+    /// apart
+    fn object_memory() -> Memory {
+        let mut memory = memory();
+        memory.write_byte(0x8010, 0xFF);
+        memory.write_byte(0x8011, 0xFF);
+        memory.write_byte(OBP0, 0xFF);
+        memory.write_byte(OBP1, 0xFF);
+        memory
+    }
+
+    fn place(memory: &mut Memory, slot: usize, x: u8, palette: u8) {
+        let entry = 0xFE00 + slot * 4;
+        memory.data[entry..entry + 4].copy_from_slice(&[16, x, 1, palette << 4]);
+    }
+
+    #[test]
+    fn only_ten_objects_per_line_even_off_screen() {
+        let mut memory = object_memory();
+        for slot in 0..10 {
+            place(&mut memory, slot, 0, 0); // Off the left edge, but counted
+        }
+        place(&mut memory, 10, 40, 1);
+        assert!(
+            render(&memory)
+                .iter()
+                .all(|&out| out == pixel(0, SOURCE_BG))
+        );
+
+        place(&mut memory, 0, 40, 1);
+        let line = render(&memory);
+        assert_eq!(line[32..40], [pixel(3, SOURCE_OBJ1); 8]);
+    }
+
+    #[test]
+    fn lower_x_wins_then_lower_oam_index() {
+        let mut memory = object_memory();
+        place(&mut memory, 0, 12, 0);
+        place(&mut memory, 1, 8, 1);
+        let line = render(&memory);
+        assert_eq!(line[..8], [pixel(3, SOURCE_OBJ1); 8]);
+        assert_eq!(line[8..12], [pixel(3, SOURCE_OBJ0); 4]);
+
+        // Both hanging off the left edge, fetched together at column 0
+        place(&mut memory, 0, 6, 0);
+        place(&mut memory, 1, 5, 1);
+        assert_eq!(render(&memory)[..5], [pixel(3, SOURCE_OBJ1); 5]);
+
+        place(&mut memory, 1, 6, 1);
+        assert_eq!(render(&memory)[..6], [pixel(3, SOURCE_OBJ0); 6]);
+
+        // CGB priority: OAM order alone
+        place(&mut memory, 0, 12, 0);
+        place(&mut memory, 1, 8, 1);
+        memory.object_priority = ObjectPriority::OamIndex;
+        assert_eq!(render(&memory)[4..8], [pixel(3, SOURCE_OBJ0); 4]);
+    }
+
+    #[test]
+    fn mid_line_writes_change_the_rest_of_the_line() {
+        let mut memory = memory();
+        memory.write_byte(0x8000, 0xFF); // Tile 0 row 0: color 3
+        memory.write_byte(0x8001, 0xFF);
+        let mut ppu = Ppu::default();
+        ppu.tick(80 + 60, &memory, Layers::default());
+        memory.write_byte(SCY, 1); // Onto row 1, color 0
+
+        let mut events = ppu.tick(1, &memory, Layers::default());
+        while events.line_finished.is_none() {
+            events = ppu.tick(1, &memory, Layers::default());
+        }
+        let line = ppu.line();
+        assert!(line[..40].iter().all(|&pixel| pixel == 3));
+        assert!(line[64..].iter().all(|&pixel| pixel == 0));
+    }
+}
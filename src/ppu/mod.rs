@@ -0,0 +1,324 @@
+mod fifo;
+mod timing;
+
+use std::ops::Range;
+use std::str::FromStr;
+
+pub use timing::{DOTS_PER_LINE, LINES_PER_FRAME, MODE3_DOTS, Ppu, PpuEvents, VBLANK_LINE};
+
+const LCDC: u16 = 0xFF40;
+const SCY: u16 = 0xFF42;
+const SCX: u16 = 0xFF43;
+const BGP: u16 = 0xFF47;
+const OBP0: u16 = 0xFF48;
+const OBP1: u16 = 0xFF49;
+const WY: u16 = 0xFF4A;
+const WX: u16 = 0xFF4B;
+const OAM: Range<usize> = 0xFE00..0xFEA0;
+
+/// What the PPU composites into each line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Background,
+    Window,
+    Objects,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 3] = [Layer::Background, Layer::Window, Layer::Objects];
+
+    /// Lowercase name, as accepted by `from_str`
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Background => "background",
+            Layer::Window => "window",
+            Layer::Objects => "objects",
+        }
+    }
+}
+
+impl FromStr for Layer {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Layer::ALL
+            .into_iter()
+            .find(|layer| layer.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!("Unknown layer: {name} (expected background, window or objects)")
+            })
+    }
+}
+
+/// Debug switches for hiding layers, for diagnosing rendering bugs and
+/// ripping sprites. A hidden layer is skipped as though its LCDC enable bit
+/// were clear, but the game still sees LCDC as it wrote it. Hidden objects
+/// and window are not fetched either, so mode 3 is shorter without them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layers {
+    background: bool,
+    window: bool,
+    objects: bool,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            background: true,
+            window: true,
+            objects: true,
+        }
+    }
+}
+
+impl Layers {
+    pub fn is_visible(self, layer: Layer) -> bool {
+        match layer {
+            Layer::Background => self.background,
+            Layer::Window => self.window,
+            Layer::Objects => self.objects,
+        }
+    }
+
+    pub fn set_visible(&mut self, layer: Layer, visible: bool) {
+        match layer {
+            Layer::Background => self.background = visible,
+            Layer::Window => self.window = visible,
+            Layer::Objects => self.objects = visible,
+        }
+    }
+
+    /// Flip a layer, returning whether it is now visible
+    pub fn toggle(&mut self, layer: Layer) -> bool {
+        let visible = !self.is_visible(layer);
+        self.set_visible(layer, visible);
+        visible
+    }
+}
+
+/// LCDC (0xFF40), bit by bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lcdc(pub u8);
+
+impl Lcdc {
+    /// Bit 7. Off, the PPU stops at LY 0 in mode 0 and the screen is blank.
+    pub fn lcd_enabled(self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// Bit 6: the tile map the window reads
+    pub fn window_map(self) -> u16 {
+        if self.0 & 0x40 == 0 { 0x9800 } else { 0x9C00 }
+    }
+
+    /// Bit 5
+    pub fn window_enabled(self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    /// Start of background and window tile `index` in VRAM. Bit 4 picks
+    /// unsigned indices from 0x8000, otherwise indices are signed from
+    /// 0x9000. Objects always use 0x8000.
+    pub fn tile_address(self, index: u8) -> u16 {
+        if self.0 & 0x10 == 0 {
+            0x9000_u16.wrapping_add_signed(i16::from(index.cast_signed()) * 16)
+        } else {
+            0x8000 + u16::from(index) * 16
+        }
+    }
+
+    /// Bit 3: the tile map the background reads
+    pub fn background_map(self) -> u16 {
+        if self.0 & 0x08 == 0 { 0x9800 } else { 0x9C00 }
+    }
+
+    /// Bit 2: objects are 8x8 or 8x16
+    pub fn object_height(self) -> u8 {
+        if self.0 & 0x04 == 0 { 8 } else { 16 }
+    }
+
+    /// Bit 1
+    pub fn objects_enabled(self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    /// Bit 0. On the DMG, clearing it blanks the window as well as the
+    /// background; objects are still drawn.
+    pub fn background_enabled(self) -> bool {
+        self.0 & 0x01 != 0
+    }
+}
+
+/// Shade (0 = lightest) a 2-bit color index maps to through a DMG palette
+/// register such as BGP
+pub fn shade(palette: u8, color: u8) -> u8 {
+    (palette >> ((color & 0x03) * 2)) & 0x03
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Memory;
+    use crate::palette::{SOURCE_BG, SOURCE_OBJ0, SOURCE_OBJ1, pixel};
+
+    /// Memory with the LCD and background on and BGP the identity palette
+    fn memory(lcdc: u8) -> Memory {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, 0x81 | lcdc);
+        memory.write_byte(BGP, 0xE4);
+        memory
+    }
+
+    /// Line `ly` as drawn by a PPU running from the start of a frame
+    fn render(memory: &Memory, layers: Layers, ly: u8) -> Vec<u8> {
+        let mut ppu = Ppu::default();
+        while ppu.tick(1, memory, layers).line_finished != Some(ly) {}
+        ppu.line().to_vec()
+    }
+
+    fn shades(line: &[u8]) -> Vec<u8> {
+        line.iter().map(|pixel| pixel & 0x03).collect()
+    }
+
+    #[test]
+    fn draws_tiles_from_the_map() {
+        let mut memory = memory(0x10);
+        // Tile 1: top row colors 3, 2, 1, 0, 0, 0, 0, 3
+        memory.write_byte(0x8010, 0b1010_0001);
+        memory.write_byte(0x8011, 0b1100_0001);
+        memory.write_byte(0x9801, 0x01); // Second tile of the first map row
+
+        let line = render(&memory, Layers::default(), 0);
+        assert_eq!(
+            shades(&line[..16]),
+            [0, 0, 0, 0, 0, 0, 0, 0, 3, 2, 1, 0, 0, 0, 0, 3]
+        );
+        assert!(line.iter().all(|&pixel| pixel >> 2 == SOURCE_BG));
+
+        memory.write_byte(BGP, 0x1B); // Reversed
+        let line = render(&memory, Layers::default(), 0);
+        assert_eq!(shades(&line[8..12]), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn scrolls_and_wraps_around_the_map() {
+        let mut memory = memory(0x10);
+        memory.write_byte(0x8010, 0xFF); // Tile 1 row 0: color 1
+        memory.write_byte(0x9800 + 31 * 32 + 31, 0x01); // Bottom right of the map
+        memory.write_byte(SCX, 252);
+        memory.write_byte(SCY, 248);
+
+        let line = render(&memory, Layers::default(), 0);
+        assert_eq!(shades(&line[..6]), [1, 1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn signed_tile_data_and_second_map() {
+        let mut memory = memory(0x08);
+        memory.write_byte(0x8FF0, 0xFF); // Tile -1 from 0x9000
+        memory.write_byte(0x8FF1, 0xFF);
+        memory.write_byte(0x9C00, 0xFF);
+
+        let line = render(&memory, Layers::default(), 0);
+        assert_eq!(shades(&line[..9]), [3, 3, 3, 3, 3, 3, 3, 3, 0]);
+
+        memory.write_byte(LCDC, 0x80); // Background off
+        let line = render(&memory, Layers::default(), 0);
+        assert!(line.iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn hidden_background_renders_blank() {
+        let mut memory = memory(0x10);
+        memory.write_byte(BGP, 0xFF); // Every color black
+
+        let mut layers = Layers::default();
+        let line = render(&memory, layers, 0);
+        assert!(line.iter().all(|&pixel| pixel == 3));
+
+        assert!(!layers.toggle("Background".parse().unwrap()));
+        let line = render(&memory, layers, 0);
+        assert!(line.iter().all(|&pixel| pixel == 0));
+        assert!(layers.is_visible(Layer::Objects));
+    }
+
+    #[test]
+    fn window_starts_at_wx_and_counts_its_own_lines() {
+        let mut memory = memory(0x10 | 0x20 | 0x40); // Window on, map at 0x9C00
+        memory.write_byte(0x8010, 0xFF); // Tile 1 row 0: color 1
+        memory.write_byte(0x8013, 0xFF); // Tile 1 row 1: color 2
+        memory.write_byte(0x9C00, 0x01);
+        memory.write_byte(WX, 7 + 4);
+        memory.write_byte(WY, 10);
+
+        let line = render(&memory, Layers::default(), 9);
+        assert!(line.iter().all(|&pixel| pixel == 0), "Above WY");
+
+        // The first window line shows the window's row 0 whatever LY is
+        let line = render(&memory, Layers::default(), 10);
+        assert_eq!(
+            shades(&line[..14]),
+            [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0]
+        );
+        let line = render(&memory, Layers::default(), 11);
+        assert_eq!(shades(&line[3..5]), [0, 2]);
+
+        memory.write_byte(WX, 3); // Four columns cut off at the left
+        let line = render(&memory, Layers::default(), 11);
+        assert_eq!(shades(&line[..6]), [2, 2, 2, 2, 0, 0]);
+
+        memory.write_byte(LCDC, 0xF0); // Background off takes the window too
+        let line = render(&memory, Layers::default(), 11);
+        assert!(line.iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn objects_draw_over_the_background_by_attributes() {
+        let mut memory = memory(0x10 | 0x02);
+        memory.write_byte(OBP0, 0xE4);
+        memory.write_byte(OBP1, 0x1B); // Reversed
+        memory.write_byte(0x8010, 0xF0); // Tile 1 row 0: color 1 on the left half
+        memory.write_byte(0x8020, 0xFF); // Tile 2 row 0: color 3
+        memory.write_byte(0x8021, 0xFF);
+        memory.write_byte(0x9800, 0x02); // Background color 3 under columns 0-7
+        let oam = &mut memory.data[OAM];
+        oam[..4].copy_from_slice(&[16, 8, 1, 0x80]); // Behind the background
+        oam[4..8].copy_from_slice(&[16, 16, 1, 0x20 | 0x10]); // X flipped, OBP1
+
+        let line = render(&memory, Layers::default(), 0);
+        assert!(line[..8].iter().all(|&out| out == pixel(3, SOURCE_BG)));
+        assert_eq!(line[11], pixel(0, SOURCE_BG), "Transparent half");
+        assert_eq!(line[12], pixel(2, SOURCE_OBJ1));
+
+        // Clearing the background leaves the first object nothing to hide
+        // behind; hiding objects leaves only the background
+        memory.data[0x9800] = 0x00;
+        let line = render(&memory, Layers::default(), 0);
+        assert_eq!(line[0], pixel(1, SOURCE_OBJ0));
+        let mut layers = Layers::default();
+        layers.set_visible(Layer::Objects, false);
+        let line = render(&memory, layers, 0);
+        assert_eq!(line[0], pixel(0, SOURCE_BG));
+    }
+
+    #[test]
+    fn tall_objects_span_two_tiles_and_flip_vertically() {
+        let mut memory = memory(0x10 | 0x02 | 0x04);
+        memory.write_byte(OBP0, 0xE4);
+        memory.write_byte(0x8020, 0xFF); // Tile 2 row 0: color 1
+        memory.write_byte(0x803F, 0xFF); // Tile 3 row 7: color 2
+        memory.data[0xFE00..0xFE04].copy_from_slice(&[16, 8, 0x03, 0x00]);
+
+        let line = render(&memory, Layers::default(), 0);
+        assert_eq!(shades(&line[..1]), [1], "Bit 0 of the tile ignored");
+        let line = render(&memory, Layers::default(), 15);
+        assert_eq!(shades(&line[..1]), [2]);
+
+        memory.data[0xFE03] = 0x40;
+        let line = render(&memory, Layers::default(), 0);
+        assert_eq!(shades(&line[..1]), [2]);
+        memory.write_byte(LCDC, 0x93); // 8x8 again: line 15 is below it
+        let line = render(&memory, Layers::default(), 15);
+        assert_eq!(shades(&line[..1]), [0]);
+    }
+}
@@ -0,0 +1,402 @@
+use super::fifo::Fifo;
+use super::{LCDC, Layers, Lcdc, WY};
+use crate::interrupts::{Interrupt, Interrupts};
+use crate::memory::Memory;
+use crate::savestate::{StateReader, StateWriter};
+use crate::tick::Tickable;
+use std::ops::Range;
+
+/// Dots (T-cycles) in one scanline, visible or not
+pub const DOTS_PER_LINE: u16 = 456;
+/// Lines per frame, including the ten V-Blank lines
+pub const LINES_PER_FRAME: u8 = 154;
+/// First V-Blank line
+pub const VBLANK_LINE: u8 = 144;
+
+/// Dots of each visible line spent in mode 3 (drawing), at its shortest
+pub const MODE3_DOTS: Range<u16> = 80..252;
+
+/// What happened during one `Ppu::tick`
+#[allow(clippy::struct_excessive_bools)] // Independent events, any of which can coincide
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PpuEvents {
+    /// A visible line was finished, ready to draw
+    pub line_finished: Option<u8>,
+    /// Line 144 began
+    pub vblank: bool,
+    /// Line 0's OAM scan began: LY wrapped to 0, or the LCD was switched on
+    pub frame_started: bool,
+    /// The STAT interrupt line rose
+    pub stat: bool,
+    /// LCDC bit 7 was cleared, blanking the screen
+    pub lcd_off: bool,
+}
+
+/// The PPU's position in the frame: LY (0xFF44) and the mode shown in the
+/// low bits of STAT (0xFF41). 0 = H-Blank, 1 = V-Blank, 2 = OAM scan,
+/// 3 = drawing.
+///
+/// STAT bit 2 is set while LY equals LYC (0xFF45). Bits 3-6 select which of
+/// mode 0, 1, 2 and LY=LYC drive the STAT interrupt line; like the
+/// hardware, the interrupt is requested only when the line rises, so a
+/// second source becoming true while another already holds it high is lost.
+///
+/// Mode 3 draws the line through a pixel FIFO (see `fifo::Fifo`), and
+/// lasts as long as that takes, so H-Blank shrinks to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ppu {
+    enabled: bool,
+    ly: u8,
+    /// Dots into the current line
+    dot: u16,
+    mode: u8,
+    /// STAT bits 3-6, the interrupt sources the game selected
+    stat_select: u8,
+    lyc: u8,
+    /// Whether the STAT interrupt line was high after the last dot
+    stat_line: bool,
+    /// Lines of the window drawn so far this frame
+    window_line: u8,
+    /// LY has matched WY at the start of a line this frame
+    wy_reached: bool,
+    fifo: Fifo,
+}
+
+impl Ppu {
+    /// Current LY; 0 while the LCD is off
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    /// Current mode; 0 while the LCD is off
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    /// Dots into the current line; 0 while the LCD is off
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    /// Whether LY, the dot and the mode are a position `tick` could have
+    /// reached. Mode 3 and H-Blank can't be told apart by the dot alone, as
+    /// mode 3 lasts as long as the FIFO takes.
+    pub(crate) fn position_is_valid(&self) -> bool {
+        if !self.enabled {
+            return self.ly == 0 && self.dot == 0 && self.mode == 0;
+        }
+        let mode_is_valid = match (self.ly, self.mode) {
+            (VBLANK_LINE.., mode) => mode == 1,
+            (_, 2) => self.dot < MODE3_DOTS.start,
+            (_, 0 | 3) => self.dot >= MODE3_DOTS.start,
+            _ => false,
+        };
+        self.ly < LINES_PER_FRAME && self.dot < DOTS_PER_LINE && mode_is_valid
+    }
+
+    /// Start the current line again, or the frame if LY is past its end, so
+    /// the position is valid again
+    pub(crate) fn restart_line(&mut self) {
+        if !self.enabled || self.ly >= LINES_PER_FRAME {
+            self.ly = 0;
+            self.window_line = 0;
+            self.wy_reached = false;
+        }
+        self.dot = 0;
+        self.mode = match self.ly {
+            _ if !self.enabled => 0,
+            VBLANK_LINE.. => 1,
+            _ => 2,
+        };
+        self.fifo = Fifo::default();
+    }
+
+    pub fn read_stat(&self) -> u8 {
+        0x80 | self.stat_select | u8::from(self.ly == self.lyc) << 2 | self.mode
+    }
+
+    pub fn lyc(&self) -> u8 {
+        self.lyc
+    }
+
+    /// The window's own line counter: the row of the window map the next
+    /// window line shows. It stands still while the window is off, so a
+    /// window switched off and on mid-frame carries on where it left off.
+    pub fn window_line(&self) -> u8 {
+        self.window_line
+    }
+
+    /// Pixels of the current line drawn so far; all 160 from the end of
+    /// mode 3 until the next line's mode 3
+    pub fn line(&self) -> &[u8] {
+        self.fifo.line()
+    }
+
+    /// Takes effect from the next dot
+    pub fn write_lyc(&mut self, value: u8) {
+        self.lyc = value;
+    }
+
+    /// Whether any selected STAT interrupt source holds
+    fn stat_sources(&self) -> bool {
+        let mode_source = match self.mode {
+            0 => 0x08,
+            1 => 0x10,
+            2 => 0x20,
+            _ => 0,
+        };
+        self.stat_select & mode_source != 0 || (self.stat_select & 0x40 != 0 && self.ly == self.lyc)
+    }
+
+    /// The mode bits are read-only
+    pub fn write_stat(&mut self, value: u8) {
+        self.stat_select = value & 0x78;
+    }
+
+    /// Advance `cycles` dots, one at a time, drawing from VRAM, OAM and the
+    /// registers in `memory` during mode 3. Turning the LCD off (LCDC bit 7)
+    /// stops the PPU at LY 0 in mode 0, and turning it back on starts a
+    /// frame from line 0.
+    pub fn tick(&mut self, cycles: u32, memory: &Memory, layers: Layers) -> PpuEvents {
+        let mut events = PpuEvents::default();
+        if !Lcdc(memory.peek(LCDC)).lcd_enabled() {
+            if self.enabled {
+                self.enabled = false;
+                self.ly = 0;
+                self.dot = 0;
+                self.mode = 0;
+                self.stat_line = false;
+                self.window_line = 0;
+                self.wy_reached = false;
+                events.lcd_off = true;
+            }
+            return events;
+        }
+        if !self.enabled {
+            self.enabled = true;
+            self.mode = 2;
+            self.wy_reached = memory.peek(WY) == 0;
+            events.frame_started = true;
+        }
+
+        for _ in 0..cycles {
+            self.dot += 1;
+            if self.ly < VBLANK_LINE {
+                if self.dot == MODE3_DOTS.start {
+                    self.mode = 3;
+                    self.fifo.start_line(memory, self.ly);
+                }
+                if self.mode == 3 {
+                    if self.fifo.is_done() {
+                        self.mode = 0;
+                    } else {
+                        let window_y = self.wy_reached.then_some(self.window_line);
+                        self.fifo.tick(memory, layers, window_y);
+                    }
+                }
+            }
+            if self.dot == DOTS_PER_LINE {
+                self.dot = 0;
+                if self.ly < VBLANK_LINE {
+                    events.line_finished = Some(self.ly);
+                    if self.fifo.window_drawn() {
+                        self.window_line = self.window_line.wrapping_add(1);
+                    }
+                }
+                self.ly += 1;
+                if self.ly == LINES_PER_FRAME {
+                    self.ly = 0;
+                    self.window_line = 0;
+                    self.wy_reached = false;
+                    events.frame_started = true;
+                }
+                if self.ly == VBLANK_LINE {
+                    self.mode = 1;
+                    events.vblank = true;
+                } else if self.ly < VBLANK_LINE {
+                    self.mode = 2;
+                    self.wy_reached |= memory.peek(WY) == self.ly;
+                }
+            }
+
+            let stat_line = self.stat_sources();
+            events.stat |= stat_line && !self.stat_line;
+            self.stat_line = stat_line;
+        }
+        events
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u8(self.ly);
+        state.write_u16(self.dot);
+        state.write_u8(self.mode);
+        state.write_u8(self.stat_select);
+        state.write_u8(self.lyc);
+        state.write_bool(self.stat_line);
+        state.write_u8(self.window_line);
+        state.write_bool(self.wy_reached);
+        self.fifo.save_state(state);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.enabled = state.read_bool()?;
+        self.ly = state.read_u8()?;
+        self.dot = state.read_u16()?;
+        self.mode = state.read_u8()?;
+        self.stat_select = state.read_u8()?;
+        self.lyc = state.read_u8()?;
+        self.stat_line = state.read_bool()?;
+        self.window_line = state.read_u8()?;
+        self.wy_reached = state.read_bool()?;
+        self.fifo.load_state(state)
+    }
+}
+
+/// Draws every layer. Each finished line is in `line` until the next
+/// line's mode 3 starts.
+impl Tickable for Ppu {
+    fn advance(&mut self, cycles: u32, memory: &Memory, interrupts: &mut Interrupts) {
+        let events = self.tick(cycles, memory, Layers::default());
+        if events.vblank {
+            interrupts.request(Interrupt::VBlank);
+        }
+        if events.stat {
+            interrupts.request(Interrupt::LcdStat);
+        }
+    }
+
+    /// Until the next mode change or line. Mode 3 ends when the FIFO is
+    /// done, so until its shortest end and then one dot at a time. `None`
+    /// while the LCD is off, until LCDC turns it on.
+    fn next_event(&self) -> Option<u32> {
+        if !self.enabled {
+            return None;
+        }
+        let dots = match self.mode {
+            2 => MODE3_DOTS.start - self.dot,
+            3 => MODE3_DOTS.end.saturating_sub(self.dot).max(1),
+            _ => DOTS_PER_LINE - self.dot,
+        };
+        Some(u32::from(dots))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory with LCDC bit 7 set or clear
+    fn lcd(on: bool) -> Memory {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, if on { 0x91 } else { 0x11 });
+        memory
+    }
+
+    #[test]
+    fn modes_follow_the_dots_of_a_line() {
+        let on = lcd(true);
+        let mut ppu = Ppu::default();
+        ppu.write_lyc(5);
+        ppu.write_stat(0xFF);
+        assert_eq!(ppu.read_stat(), 0xF8, "LCD off: mode 0");
+
+        ppu.tick(79, &on, Layers::default());
+        assert_eq!(ppu.mode(), 2);
+        ppu.tick(1, &on, Layers::default());
+        assert_eq!(ppu.read_stat(), 0xFB);
+        ppu.tick(172, &on, Layers::default());
+        assert_eq!(ppu.mode(), 0);
+
+        let events = ppu.tick(204, &on, Layers::default());
+        assert_eq!(events.line_finished, Some(0));
+        assert_eq!((ppu.ly(), ppu.mode()), (1, 2));
+    }
+
+    #[test]
+    fn vblank_lines_then_wrap_to_line_zero() {
+        let on = lcd(true);
+        let mut ppu = Ppu::default();
+        let dots = u32::from(DOTS_PER_LINE);
+        ppu.tick(dots * 143, &on, Layers::default());
+        let events = ppu.tick(dots, &on, Layers::default());
+        assert_eq!(events.line_finished, Some(143));
+        assert!(events.vblank);
+        assert_eq!((ppu.ly(), ppu.mode()), (144, 1));
+
+        let events = ppu.tick(dots * 9, &on, Layers::default());
+        assert_eq!(events, PpuEvents::default(), "V-Blank lines are not drawn");
+        assert_eq!((ppu.ly(), ppu.mode()), (153, 1));
+        let events = ppu.tick(dots, &on, Layers::default());
+        assert!(events.frame_started);
+        assert_eq!((ppu.ly(), ppu.mode()), (0, 2));
+
+        let off = lcd(false);
+        let events = ppu.tick(500, &off, Layers::default());
+        assert!(events.lcd_off);
+        assert_eq!((ppu.ly(), ppu.mode(), ppu.window_line()), (0, 0, 0));
+        assert!(
+            !ppu.tick(1, &off, Layers::default()).lcd_off,
+            "Only when switched off"
+        );
+    }
+
+    #[test]
+    fn restarting_the_line_makes_a_bad_position_valid() {
+        let on = lcd(true);
+        let mut ppu = Ppu::default();
+        assert!(ppu.position_is_valid(), "LCD off");
+        ppu.tick(u32::from(DOTS_PER_LINE) * 150 + 10, &on, Layers::default());
+        assert!(ppu.position_is_valid());
+
+        ppu.mode = 3; // Drawing during V-Blank
+        assert!(!ppu.position_is_valid());
+        ppu.restart_line();
+        assert_eq!((ppu.ly(), ppu.dot(), ppu.mode()), (150, 0, 1));
+
+        ppu.ly = 200;
+        ppu.restart_line();
+        assert_eq!((ppu.ly(), ppu.dot(), ppu.mode()), (0, 0, 2));
+        assert!(ppu.position_is_valid());
+    }
+
+    #[test]
+    fn lyc_match_sets_the_flag_and_raises_stat() {
+        let on = lcd(true);
+        let mut ppu = Ppu::default();
+        let dots = u32::from(DOTS_PER_LINE);
+        ppu.write_lyc(2);
+        ppu.write_stat(0x40);
+
+        let events = ppu.tick(dots * 2 - 1, &on, Layers::default());
+        assert!(!events.stat);
+        assert_eq!(ppu.read_stat() & 0x04, 0);
+        let events = ppu.tick(1, &on, Layers::default());
+        assert!(events.stat);
+        assert_eq!(ppu.read_stat() & 0x07, 0x06, "LY=LYC in mode 2");
+        assert!(
+            !ppu.tick(dots - 1, &on, Layers::default()).stat,
+            "Only on the rising edge"
+        );
+    }
+
+    #[test]
+    fn mode_sources_share_one_line() {
+        let on = lcd(true);
+        let mut ppu = Ppu::default();
+        ppu.write_stat(0x28); // Modes 0 and 2
+        let rises = |ppu: &mut Ppu, dots: u32| {
+            (0..dots)
+                .filter(|_| ppu.tick(1, &on, Layers::default()).stat)
+                .count()
+        };
+
+        assert_eq!(rises(&mut ppu, 1), 1, "OAM scan of line 0");
+        assert_eq!(rises(&mut ppu, 455), 1, "H-Blank");
+        // H-Blank runs straight into the next OAM scan without a gap
+        assert_eq!(rises(&mut ppu, 1), 0);
+
+        ppu.write_stat(0x10); // V-Blank
+        assert_eq!(rises(&mut ppu, u32::from(DOTS_PER_LINE) * 154), 1);
+    }
+}
@@ -0,0 +1,234 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Edge-case behavior that real hardware tolerates (or chokes on) but that
+/// usually points at a bug in the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quirk {
+    /// Write to 0x0000-0x7FFF on a cartridge without a memory bank controller
+    RomWriteWithoutMbc,
+    /// VRAM read or write while the PPU is drawing (mode 3)
+    VramAccessInMode3,
+    /// Read or write in 0xE000-0xFDFF
+    EchoRamAccess,
+    /// One of the 11 unused opcodes, which lock up the CPU
+    IllegalOpcode,
+}
+
+impl Quirk {
+    pub const ALL: [Quirk; 4] = [
+        Quirk::RomWriteWithoutMbc,
+        Quirk::VramAccessInMode3,
+        Quirk::EchoRamAccess,
+        Quirk::IllegalOpcode,
+    ];
+
+    /// Name used on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Quirk::RomWriteWithoutMbc => "rom-write",
+            Quirk::VramAccessInMode3 => "vram-mode3",
+            Quirk::EchoRamAccess => "echo-ram",
+            Quirk::IllegalOpcode => "illegal-opcode",
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Quirk::RomWriteWithoutMbc => 0,
+            Quirk::VramAccessInMode3 => 1,
+            Quirk::EchoRamAccess => 2,
+            Quirk::IllegalOpcode => 3,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Quirk::RomWriteWithoutMbc => "ROM write without an MBC",
+            Quirk::VramAccessInMode3 => "VRAM access during mode 3",
+            Quirk::EchoRamAccess => "Echo RAM access",
+            Quirk::IllegalOpcode => "Illegal opcode",
+        }
+    }
+}
+
+impl FromStr for Quirk {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Quirk::ALL
+            .into_iter()
+            .find(|quirk| quirk.name() == s)
+            .ok_or_else(|| format!("Unknown quirk: {s}"))
+    }
+}
+
+/// What to do when a quirk happens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuirkAction {
+    #[default]
+    Silent,
+    /// Print a warning the first time it happens at each address
+    Warn,
+    /// Stop emulation so a debugger (or the user) can inspect the state
+    Trap,
+}
+
+impl FromStr for QuirkAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "silent" => Ok(QuirkAction::Silent),
+            "warn" => Ok(QuirkAction::Warn),
+            "trap" => Ok(QuirkAction::Trap),
+            _ => Err(format!(
+                "Unknown quirk action: {s} (expected silent, warn or trap)"
+            )),
+        }
+    }
+}
+
+/// The action for each quirk. The default keeps everything silent, as
+/// players expect; developers can turn the emulator into a strict validator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuirkPolicy {
+    actions: [QuirkAction; Quirk::ALL.len()],
+}
+
+impl QuirkPolicy {
+    /// The same action for every quirk
+    pub fn all(action: QuirkAction) -> Self {
+        Self {
+            actions: [action; Quirk::ALL.len()],
+        }
+    }
+
+    pub fn action(&self, quirk: Quirk) -> QuirkAction {
+        self.actions[quirk.index()]
+    }
+
+    pub fn set(&mut self, quirk: Quirk, action: QuirkAction) {
+        self.actions[quirk.index()] = action;
+    }
+}
+
+impl FromStr for QuirkPolicy {
+    type Err = String;
+
+    /// Comma-separated list of `action` (applies to every quirk) or
+    /// `quirk=action`, applied left to right, e.g. `warn,illegal-opcode=trap`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = QuirkPolicy::default();
+        for item in s.split(',').map(str::trim) {
+            match item.split_once('=') {
+                Some((quirk, action)) => policy.set(quirk.parse()?, action.parse()?),
+                None => policy = QuirkPolicy::all(item.parse()?),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// One occurrence of a quirk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuirkEvent {
+    pub quirk: Quirk,
+    /// Memory address accessed, or the opcode's address for illegal opcodes
+    pub address: u16,
+}
+
+impl fmt::Display for QuirkEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:04X}", self.quirk.description(), self.address)
+    }
+}
+
+/// Applies the policy to quirks as the emulator reports them.
+///
+/// Reports come in through `&Memory` reads, hence the cells.
+#[derive(Debug, Default)]
+pub struct QuirkMonitor {
+    pub policy: QuirkPolicy,
+    warned: RefCell<HashSet<QuirkEvent>>,
+    trap: Cell<Option<QuirkEvent>>,
+}
+
+impl QuirkMonitor {
+    pub fn report(&self, quirk: Quirk, address: u16) {
+        let event = QuirkEvent { quirk, address };
+        match self.policy.action(quirk) {
+            QuirkAction::Silent => {}
+            QuirkAction::Warn => {
+                if self.warned.borrow_mut().insert(event) {
+                    eprintln!("Warning: {event}");
+                }
+            }
+            QuirkAction::Trap => {
+                // Keep the first event; later ones in the same instruction are fallout
+                if self.trap.get().is_none() {
+                    self.trap.set(Some(event));
+                }
+            }
+        }
+    }
+
+    /// Whether a trap is pending
+    pub fn trapped(&self) -> bool {
+        self.trap.get().is_some()
+    }
+
+    /// Clear and return the pending trap
+    pub fn take_trap(&self) -> Option<QuirkEvent> {
+        self.trap.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_policy_specs() {
+        assert_eq!("silent".parse(), Ok(QuirkPolicy::default()));
+
+        let policy: QuirkPolicy = "warn,illegal-opcode=trap".parse().unwrap();
+        assert_eq!(policy.action(Quirk::EchoRamAccess), QuirkAction::Warn);
+        assert_eq!(policy.action(Quirk::IllegalOpcode), QuirkAction::Trap);
+
+        assert!("loud".parse::<QuirkPolicy>().is_err());
+        assert!("echo=warn".parse::<QuirkPolicy>().is_err());
+    }
+
+    #[test]
+    fn trap_keeps_first_event_until_taken() {
+        let monitor = QuirkMonitor {
+            policy: QuirkPolicy::all(QuirkAction::Trap),
+            ..QuirkMonitor::default()
+        };
+        monitor.report(Quirk::EchoRamAccess, 0xE000);
+        monitor.report(Quirk::IllegalOpcode, 0x0150);
+
+        assert!(monitor.trapped());
+        assert_eq!(
+            monitor.take_trap(),
+            Some(QuirkEvent {
+                quirk: Quirk::EchoRamAccess,
+                address: 0xE000
+            })
+        );
+        assert!(!monitor.trapped());
+    }
+
+    #[test]
+    fn silent_and_warn_never_trap() {
+        let mut monitor = QuirkMonitor::default();
+        monitor.report(Quirk::IllegalOpcode, 0x0150);
+        monitor.policy = QuirkPolicy::all(QuirkAction::Warn);
+        monitor.report(Quirk::IllegalOpcode, 0x0150);
+
+        assert!(!monitor.trapped());
+    }
+}
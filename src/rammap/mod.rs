@@ -0,0 +1,244 @@
+use crate::memory::Memory;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How the bytes of a RAM map field are decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    U8,
+    I8,
+    /// Little-endian, the CPU's own byte order
+    U16,
+    /// Big-endian, as some games store scores
+    U16Be,
+    /// Two decimal digits in one byte
+    Bcd8,
+    /// Four decimal digits, most significant byte first
+    Bcd16,
+    /// Any nonzero byte is true
+    Bool,
+}
+
+impl FieldType {
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldType::U8 => "u8",
+            FieldType::I8 => "i8",
+            FieldType::U16 => "u16",
+            FieldType::U16Be => "u16be",
+            FieldType::Bcd8 => "bcd8",
+            FieldType::Bcd16 => "bcd16",
+            FieldType::Bool => "bool",
+        }
+    }
+
+    /// Bytes the field occupies
+    pub fn size(self) -> u16 {
+        match self {
+            FieldType::U8 | FieldType::I8 | FieldType::Bcd8 | FieldType::Bool => 1,
+            FieldType::U16 | FieldType::U16Be | FieldType::Bcd16 => 2,
+        }
+    }
+
+    fn decode(self, bytes: [u8; 2]) -> i64 {
+        let bcd = |byte: u8| i64::from(byte >> 4) * 10 + i64::from(byte & 0x0F);
+        match self {
+            FieldType::U8 => i64::from(bytes[0]),
+            FieldType::I8 => i64::from(bytes[0].cast_signed()),
+            FieldType::U16 => i64::from(u16::from_le_bytes(bytes)),
+            FieldType::U16Be => i64::from(u16::from_be_bytes(bytes)),
+            FieldType::Bcd8 => bcd(bytes[0]),
+            FieldType::Bcd16 => bcd(bytes[0]) * 100 + bcd(bytes[1]),
+            FieldType::Bool => i64::from(bytes[0] != 0),
+        }
+    }
+}
+
+impl FromStr for FieldType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "u8" => Ok(FieldType::U8),
+            "i8" => Ok(FieldType::I8),
+            "u16" => Ok(FieldType::U16),
+            "u16be" => Ok(FieldType::U16Be),
+            "bcd8" => Ok(FieldType::Bcd8),
+            "bcd16" => Ok(FieldType::Bcd16),
+            "bool" => Ok(FieldType::Bool),
+            _ => Err(format!(
+                "Unknown field type: {s} (expected u8, i8, u16, u16be, bcd8, bcd16 or bool)"
+            )),
+        }
+    }
+}
+
+/// One named value in a game's RAM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RamField {
+    pub address: u16,
+    pub kind: FieldType,
+    pub name: String,
+}
+
+impl RamField {
+    /// Decode the field's current value without side effects
+    pub fn read(&self, memory: &Memory) -> i64 {
+        let bytes = [
+            memory.peek(self.address),
+            memory.peek(self.address.wrapping_add(1)),
+        ];
+        self.kind.decode(bytes)
+    }
+
+    /// `value` as shown to people: true/false for flags, decimal otherwise
+    pub fn format(&self, value: i64) -> String {
+        match self.kind {
+            FieldType::Bool => (value != 0).to_string(),
+            _ => value.to_string(),
+        }
+    }
+}
+
+/// Per-game map of named RAM locations (player HP, coordinates, ...), in the
+/// spirit of the maps on datacrystal.
+///
+/// The file has one field per line: a hex address (`C0A0`, `0xC0A0` or
+/// `$C0A0`), a type and a name. Blank lines and `#` comments are ignored.
+///
+/// ```text
+/// # Player
+/// C0A0  u8     hp
+/// C0A2  u16    x
+/// D010  bcd16  gold
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RamMap {
+    fields: Vec<RamField>,
+}
+
+impl RamMap {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut fields: Vec<RamField> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {message}", index + 1);
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [address, kind, name] = parts[..] else {
+                return Err(error("expected: address type name".to_string()));
+            };
+            let digits = address
+                .trim_start_matches("0x")
+                .trim_start_matches("0X")
+                .trim_start_matches('$');
+            let address = u16::from_str_radix(digits, 16)
+                .map_err(|_| error(format!("invalid address: {address}")))?;
+            let kind = kind.parse().map_err(error)?;
+            if fields.iter().any(|field| field.name == name) {
+                return Err(error(format!("duplicate name: {name}")));
+            }
+            fields.push(RamField {
+                address,
+                kind,
+                name: name.to_string(),
+            });
+        }
+        Ok(Self { fields })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Fields in file order
+    pub fn fields(&self) -> &[RamField] {
+        &self.fields
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RamField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Every field with its current value
+    pub fn read_all<'a>(&'a self, memory: &Memory) -> Vec<(&'a RamField, i64)> {
+        self.fields
+            .iter()
+            .map(|field| (field, field.read(memory)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_decodes_fields() {
+        let map = RamMap::parse(
+            "# Player\n\
+             C000 u8 hp\n\
+             0xC001 i8 speed   # signed\n\
+             \n\
+             $C002 u16 x\n\
+             C002 u16be x_be\n\
+             C004 bcd16 gold\n\
+             C006 bool poisoned\n",
+        )
+        .unwrap();
+        let mut memory = Memory::default();
+        for (offset, value) in [0x63, 0xFE, 0x34, 0x12, 0x12, 0x34, 0x00]
+            .iter()
+            .enumerate()
+        {
+            memory.write_byte(0xC000 + u16::try_from(offset).unwrap(), *value);
+        }
+
+        let values: Vec<(&str, i64)> = map
+            .read_all(&memory)
+            .into_iter()
+            .map(|(field, value)| (field.name.as_str(), value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("hp", 99),
+                ("speed", -2),
+                ("x", 0x1234),
+                ("x_be", 0x3412),
+                ("gold", 1234),
+                ("poisoned", 0),
+            ]
+        );
+        let poisoned = map.get("poisoned").unwrap();
+        assert_eq!(poisoned.format(0), "false");
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(
+            RamMap::parse("C000 u8 hp\nC001 u8").unwrap_err(),
+            "line 2: expected: address type name"
+        );
+        assert!(
+            RamMap::parse("G000 u8 hp")
+                .unwrap_err()
+                .contains("invalid address")
+        );
+        assert!(
+            RamMap::parse("C000 u32 hp")
+                .unwrap_err()
+                .contains("Unknown field type")
+        );
+        assert!(
+            RamMap::parse("C000 u8 hp\nC001 u8 hp")
+                .unwrap_err()
+                .contains("duplicate")
+        );
+    }
+}
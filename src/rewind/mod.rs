@@ -0,0 +1,186 @@
+use crate::audio;
+use crate::gameboy::GameBoy;
+use crate::savestate::SaveState;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// What rewinding sounds like
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RewindAudio {
+    /// Silence, as long as the audio being rewound over
+    #[default]
+    Mute,
+    /// The audio being rewound over, played backwards
+    Reverse,
+}
+
+impl FromStr for RewindAudio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mute" => Ok(RewindAudio::Mute),
+            "reverse" => Ok(RewindAudio::Reverse),
+            _ => Err(format!(
+                "Unknown rewind audio mode: {s} (expected mute or reverse)"
+            )),
+        }
+    }
+}
+
+/// A snapshot and what happened after it
+struct Entry {
+    state: SaveState,
+    /// Frames run since the snapshot
+    frames: u32,
+    /// Samples those frames produced
+    audio: Vec<audio::Frame>,
+}
+
+/// Recent save states for the rewind hotkey, taken every `interval` frames,
+/// each with the audio played since it so rewinding can be heard.
+///
+/// The frontend calls `record_frame` after every frame it runs and
+/// `step_back` once per frame while rewind is held. Each step jumps back one
+/// snapshot and returns the samples to play in place of that stretch: silence
+/// or the same samples reversed, always as many as were recorded, so
+/// audio-synced pacing rewinds at the speed the game was played.
+pub struct Rewind {
+    entries: VecDeque<Entry>,
+    /// Snapshots kept; the oldest is dropped beyond this
+    capacity: usize,
+    interval: u32,
+    pub audio: RewindAudio,
+}
+
+impl Rewind {
+    pub fn new(capacity: usize, interval: u32, audio: RewindAudio) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            interval: interval.max(1),
+            audio,
+        }
+    }
+
+    /// Snapshots held
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget everything, e.g. after loading a state or resetting
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// A frame has finished, producing `samples`
+    pub fn record_frame(&mut self, gb: &GameBoy, samples: &[audio::Frame]) {
+        if let Some(entry) = self.entries.back_mut()
+            && entry.frames < self.interval
+        {
+            entry.frames += 1;
+            entry.audio.extend_from_slice(samples);
+            if entry.frames < self.interval {
+                return;
+            }
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            state: gb.save_state(),
+            frames: 0,
+            audio: Vec::new(),
+        });
+    }
+
+    /// Restore the previous snapshot, returning the samples to play for the
+    /// stretch rewound over, or `None` once the oldest one is reached.
+    /// A snapshot taken this very frame is skipped, since restoring it would
+    /// not move.
+    pub fn step_back(&mut self, gb: &mut GameBoy) -> Result<Option<Vec<audio::Frame>>, String> {
+        if self.entries.back().is_some_and(|entry| entry.frames == 0) {
+            if self.entries.len() == 1 {
+                return Ok(None);
+            }
+            self.entries.pop_back();
+        }
+        let Some(mut entry) = self.entries.pop_back() else {
+            return Ok(None);
+        };
+        gb.load_state(&entry.state)?;
+
+        match self.audio {
+            RewindAudio::Mute => entry.audio.fill([0.0; 2]),
+            RewindAudio::Reverse => entry.audio.reverse(),
+        }
+        let audio = entry.audio;
+        // Recording carries on from the restored state
+        self.entries.push_back(Entry {
+            state: entry.state,
+            frames: 0,
+            audio: Vec::new(),
+        });
+        Ok(Some(audio))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)] // Samples are copied, never computed
+mod tests {
+    use super::*;
+
+    /// Run one frame, counting frames in work RAM, and record it with one
+    /// sample holding the count
+    fn run_frame(gb: &mut GameBoy, rewind: &mut Rewind) {
+        let count = gb.memory.peek(0xC000) + 1;
+        gb.memory.write_byte(0xC000, count);
+        gb.finish_frame();
+        rewind.record_frame(gb, &[[f32::from(count); 2]]);
+    }
+
+    #[test]
+    fn steps_back_a_snapshot_at_a_time_with_reversed_audio() {
+        let mut gb = GameBoy::new();
+        let mut rewind = Rewind::new(3, 2, RewindAudio::Reverse);
+        for _ in 0..7 {
+            run_frame(&mut gb, &mut rewind);
+        }
+        // Snapshots after frames 1, 3, 5 and 7, the oldest dropped
+        assert_eq!(rewind.len(), 3);
+
+        let audio = rewind.step_back(&mut gb).unwrap().unwrap();
+        assert_eq!(gb.memory.peek(0xC000), 5);
+        assert_eq!(audio, [[7.0; 2], [6.0; 2]]);
+
+        run_frame(&mut gb, &mut rewind);
+        let audio = rewind.step_back(&mut gb).unwrap().unwrap();
+        assert_eq!(gb.memory.peek(0xC000), 5, "Back to the restored state");
+        assert_eq!(audio, [[6.0; 2]]);
+
+        let audio = rewind.step_back(&mut gb).unwrap().unwrap();
+        assert_eq!(gb.memory.peek(0xC000), 3);
+        assert_eq!(audio.len(), 2);
+        assert_eq!(rewind.step_back(&mut gb), Ok(None), "Oldest snapshot");
+        assert_eq!(gb.memory.peek(0xC000), 3);
+    }
+
+    #[test]
+    fn mute_keeps_the_length_of_the_audio() {
+        let mut gb = GameBoy::new();
+        let mut rewind = Rewind::new(4, 3, RewindAudio::Mute);
+        for _ in 0..4 {
+            run_frame(&mut gb, &mut rewind);
+        }
+
+        let audio = rewind.step_back(&mut gb).unwrap().unwrap();
+        assert_eq!(audio, [[0.0; 2]; 3]);
+        assert_eq!(gb.memory.peek(0xC000), 1);
+        assert_eq!("reverse".parse(), Ok(RewindAudio::Reverse));
+    }
+}
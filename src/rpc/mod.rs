@@ -0,0 +1,685 @@
+use crate::gameboy::{GameBoy, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::image::write_png;
+use crate::joypad::Button;
+use serde_json::{Value, json};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long a connected client gets to send its request and take the answer
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request body accepted
+const MAX_BODY: usize = 64 * 1024;
+
+/// Largest request line and headers accepted
+const MAX_HEADERS: usize = 8 * 1024;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// JSON-RPC 2.0 over HTTP for controlling a running emulator from scripts,
+/// trainers and test orchestrators. Each request is one `POST` whose body is
+/// a JSON-RPC call:
+///
+/// ```text
+/// curl -d '{"jsonrpc":"2.0","id":1,"method":"peek","params":{"address":49152,"length":4}}' localhost:8765
+/// ```
+///
+/// Methods:
+/// - `status` - frame count, paused, halted, PC and LY
+/// - `pause`, `resume` - stop and restart emulation between frames
+/// - `step {count = 1}` - run instructions, e.g. while paused, stopping
+///   mid-frame; the frame so far is handed to the frame sinks
+/// - `peek {address, length = 1}` - read bytes, without side effects
+/// - `poke {address, value}` or `poke {address, values}` - write bytes as the
+///   CPU would (writes to ROM reach the MBC)
+/// - `screenshot {path}` - write the current frame as a PNG; mid-frame,
+///   the lines drawn so far with the current line marked (`partial_frame`)
+/// - `track_writes {enabled = true}` - start or stop recording who last
+///   wrote each address
+/// - `last_write {address}` - the last write to `address` since tracking
+///   started: `{value, pc, cycle, frame}`, with `pc` null for writes from
+///   outside the CPU, or null if nothing wrote it
+/// - `press {button, frames?}` - hold a button, for `frames` frames if given
+/// - `release {button}` - let go of a button
+/// - `quit` - end the session
+///
+/// The listener never blocks emulation: the frontend calls `poll` between
+/// frames, which reads whatever clients have sent without waiting for more.
+/// A request that arrives in pieces is kept until it is whole and answered
+/// by the `poll` that completes it; a client that takes longer than
+/// `CLIENT_TIMEOUT` is dropped.
+pub struct RpcServer {
+    listener: TcpListener,
+    /// Accepted connections not yet answered and closed
+    connections: Vec<Connection>,
+    paused: bool,
+    quit: bool,
+    /// Buttons pressed for a limited time, with the frame they are let go
+    timed: Vec<(Button, u64)>,
+}
+
+impl RpcServer {
+    /// Listen on `address`, e.g. "127.0.0.1:8765"
+    pub fn bind(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            connections: Vec::new(),
+            paused: false,
+            quit: false,
+            timed: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Whether a client has called `quit`
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    /// Release timed presses that have run out, read what clients have sent
+    /// and answer every request that is complete. Call between frames.
+    pub fn poll(&mut self, gb: &mut GameBoy) {
+        let frame = gb.frame_count();
+        let (expired, held): (Vec<_>, Vec<_>) =
+            self.timed.drain(..).partition(|&(_, until)| until <= frame);
+        self.timed = held;
+        for (button, _) in expired {
+            release(gb, button);
+        }
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => match stream.set_nonblocking(true) {
+                    Ok(()) => self.connections.push(Connection::new(stream)),
+                    Err(e) => eprintln!("RPC connection error: {e}"),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("RPC accept error: {e}");
+                    break;
+                }
+            }
+        }
+
+        let mut connections = std::mem::take(&mut self.connections);
+        connections.retain_mut(|connection| match self.serve(gb, connection) {
+            Ok(false) => false,
+            Ok(true) if connection.accepted.elapsed() < CLIENT_TIMEOUT => true,
+            Ok(true) => {
+                eprintln!("RPC connection error: client timed out");
+                false
+            }
+            Err(e) => {
+                eprintln!("RPC connection error: {e}");
+                false
+            }
+        });
+        self.connections = connections;
+    }
+
+    /// Read what has arrived on `connection`, answer its request once it is
+    /// whole and send as much of the answer as the socket takes. Returns
+    /// whether the connection is still waiting on either side.
+    fn serve(&mut self, gb: &mut GameBoy, connection: &mut Connection) -> io::Result<bool> {
+        if connection.response.is_none() {
+            let closed = connection.receive()?;
+            let Some(request) = parse_request(&connection.request) else {
+                return Ok(!closed);
+            };
+            connection.response = Some(self.respond(gb, request));
+        }
+
+        let Some(response) = connection.response.as_mut() else {
+            return Ok(false);
+        };
+        while !response.is_empty() {
+            match connection.stream.write(response) {
+                Ok(0) => return Ok(false),
+                Ok(sent) => {
+                    response.drain(..sent);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(true),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(false)
+    }
+
+    /// The HTTP response to a whole request
+    fn respond(&mut self, gb: &mut GameBoy, request: Request) -> Vec<u8> {
+        let (status, content_type, body) = match request {
+            Request::Post(body) => {
+                let response = self.handle(gb, &String::from_utf8_lossy(body));
+                ("200 OK", "application/json", response)
+            }
+            Request::NotPost => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "Send JSON-RPC requests with POST\n".to_string(),
+            ),
+            Request::TooLarge => (
+                "413 Content Too Large",
+                "text/plain",
+                "Request too large\n".to_string(),
+            ),
+        };
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .into_bytes()
+    }
+
+    /// Answer one JSON-RPC request, given and returned as JSON text
+    pub fn handle(&mut self, gb: &mut GameBoy, request: &str) -> String {
+        let request: Value = match serde_json::from_str(request) {
+            Ok(request) => request,
+            Err(e) => return error_response(&Value::Null, PARSE_ERROR, &e.to_string()),
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return error_response(&id, INVALID_REQUEST, "Missing method");
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        match self.call(gb, method, &params) {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string(),
+            Err((code, message)) => error_response(&id, code, &message),
+        }
+    }
+
+    fn call(
+        &mut self,
+        gb: &mut GameBoy,
+        method: &str,
+        params: &Value,
+    ) -> Result<Value, (i64, String)> {
+        match method {
+            "status" => Ok(json!({
+                "frame": gb.frame_count(),
+                "paused": self.paused,
+                "halted": gb.stopped(),
+                "pc": gb.cpu.pc,
+                "ly": gb.memory.ppu.ly(),
+            })),
+            "pause" | "resume" => {
+                self.paused = method == "pause";
+                Ok(json!({"paused": self.paused}))
+            }
+            "step" => step(gb, params),
+            "peek" => {
+                let address = address_param(params)?;
+                let length = match params.get("length") {
+                    None => 1,
+                    Some(length) => length
+                        .as_u64()
+                        .and_then(|length| u16::try_from(length).ok())
+                        .ok_or_else(|| invalid_params("length must be 0-65535"))?,
+                };
+                let bytes: Vec<u8> = (0..length)
+                    .map(|offset| gb.memory.peek(address.wrapping_add(offset)))
+                    .collect();
+                Ok(json!(bytes))
+            }
+            "poke" => {
+                let address = address_param(params)?;
+                let values = match (params.get("value"), params.get("values")) {
+                    (Some(value), None) => vec![byte_param(value)?],
+                    (None, Some(Value::Array(values))) => {
+                        values.iter().map(byte_param).collect::<Result<_, _>>()?
+                    }
+                    _ => return Err(invalid_params("expected value or values")),
+                };
+                for (offset, value) in (0..=u16::MAX).zip(values) {
+                    gb.memory.write_byte(address.wrapping_add(offset), value);
+                }
+                Ok(Value::Null)
+            }
+            "track_writes" => track_writes(gb, params),
+            "last_write" => last_write(gb, params),
+            "screenshot" => {
+                let path = params
+                    .get("path")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid_params("missing path"))?;
+                write_png(
+                    Path::new(path),
+                    SCREEN_WIDTH,
+                    SCREEN_HEIGHT,
+                    gb.partial_frame().rgba(),
+                )
+                .map_err(|e| (INVALID_PARAMS, format!("{path}: {e}")))?;
+                Ok(json!({"path": path}))
+            }
+            "press" => {
+                let button = button_param(params)?;
+                let frames = match params.get("frames") {
+                    None => None,
+                    Some(frames) => Some(
+                        frames
+                            .as_u64()
+                            .ok_or_else(|| invalid_params("frames must be a whole number"))?,
+                    ),
+                };
+                let mut buttons = gb.memory.joypad.buttons();
+                buttons.press(button);
+                gb.set_buttons(buttons);
+                self.timed.retain(|&(timed, _)| timed != button);
+                if let Some(frames) = frames {
+                    self.timed.push((button, gb.frame_count() + frames));
+                }
+                Ok(Value::Null)
+            }
+            "release" => {
+                let button = button_param(params)?;
+                self.timed.retain(|&(timed, _)| timed != button);
+                release(gb, button);
+                Ok(Value::Null)
+            }
+            "quit" => {
+                self.quit = true;
+                Ok(Value::Null)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {method}"))),
+        }
+    }
+}
+
+/// Turn last-writer tracking on, or off with `enabled: false`
+fn track_writes(gb: &mut GameBoy, params: &Value) -> Result<Value, (i64, String)> {
+    let enabled = match params.get("enabled") {
+        None => true,
+        Some(enabled) => enabled
+            .as_bool()
+            .ok_or_else(|| invalid_params("enabled must be true or false"))?,
+    };
+    gb.set_write_tracking(enabled);
+    Ok(json!({"enabled": enabled}))
+}
+
+fn last_write(gb: &GameBoy, params: &Value) -> Result<Value, (i64, String)> {
+    let address = address_param(params)?;
+    let write = gb.last_write(address).map_err(|e| (INVALID_REQUEST, e))?;
+    Ok(write.map_or(Value::Null, |write| {
+        json!({
+            "value": write.value,
+            "pc": write.pc,
+            "cycle": write.cycle,
+            "frame": write.frame,
+        })
+    }))
+}
+
+/// Run `count` instructions, stopping early if the CPU stops, and show
+/// the frame so far
+fn step(gb: &mut GameBoy, params: &Value) -> Result<Value, (i64, String)> {
+    let count = match params.get("count") {
+        None => 1,
+        Some(count) => count
+            .as_u64()
+            .ok_or_else(|| invalid_params("count must be a whole number"))?,
+    };
+    for _ in 0..count {
+        if gb.stopped() {
+            break;
+        }
+        gb.step();
+    }
+    gb.present_partial_frame();
+    Ok(json!({"pc": gb.cpu.pc, "ly": gb.memory.ppu.ly()}))
+}
+
+fn release(gb: &mut GameBoy, button: Button) {
+    let mut buttons = gb.memory.joypad.buttons();
+    buttons.release(button);
+    gb.set_buttons(buttons);
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}).to_string()
+}
+
+fn invalid_params(message: &str) -> (i64, String) {
+    (INVALID_PARAMS, message.to_string())
+}
+
+fn address_param(params: &Value) -> Result<u16, (i64, String)> {
+    params
+        .get("address")
+        .and_then(Value::as_u64)
+        .and_then(|address| u16::try_from(address).ok())
+        .ok_or_else(|| invalid_params("address must be 0-65535"))
+}
+
+fn byte_param(value: &Value) -> Result<u8, (i64, String)> {
+    value
+        .as_u64()
+        .and_then(|value| u8::try_from(value).ok())
+        .ok_or_else(|| invalid_params("values must be 0-255"))
+}
+
+fn button_param(params: &Value) -> Result<Button, (i64, String)> {
+    params
+        .get("button")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid_params("missing button"))?
+        .parse()
+        .map_err(|e| (INVALID_PARAMS, e))
+}
+
+/// A client connection from accept until its answer is sent
+struct Connection {
+    stream: TcpStream,
+    accepted: Instant,
+    /// What the client has sent so far
+    request: Vec<u8>,
+    /// The answer once the request is whole, less what has been sent
+    response: Option<Vec<u8>>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            accepted: Instant::now(),
+            request: Vec::new(),
+            response: None,
+        }
+    }
+
+    /// Read everything the client has sent so far, without waiting for more.
+    /// Returns whether the client has closed its side.
+    fn receive(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(true),
+                Ok(read) => self.request.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// A whole HTTP request
+#[derive(Clone, Copy)]
+enum Request<'a> {
+    /// A `POST` and its body
+    Post(&'a [u8]),
+    NotPost,
+    /// Headers or body over the limits
+    TooLarge,
+}
+
+/// The request in `received`, or `None` until all of it has arrived
+fn parse_request(received: &[u8]) -> Option<Request<'_>> {
+    let Some((head, body_start)) = split_headers(received) else {
+        return (received.len() > MAX_HEADERS).then_some(Request::TooLarge);
+    };
+    if head.len() > MAX_HEADERS {
+        return Some(Request::TooLarge);
+    }
+
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.lines();
+    if !lines.next().is_some_and(|line| line.starts_with("POST ")) {
+        return Some(Request::NotPost);
+    }
+    let content_length = lines
+        .filter_map(|header| header.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .map_or(0, |(_, value)| value.trim().parse().unwrap_or(0));
+    if content_length > MAX_BODY {
+        return Some(Request::TooLarge);
+    }
+    received
+        .get(body_start..body_start + content_length)
+        .map(Request::Post)
+}
+
+/// The request line and headers, and where the body starts, once the blank
+/// line ending the headers has arrived
+fn split_headers(received: &[u8]) -> Option<(&[u8], usize)> {
+    let mut line_start = 0;
+    for (i, &byte) in received.iter().enumerate() {
+        if byte != b'\n' {
+            continue;
+        }
+        if matches!(&received[line_start..i], b"" | b"\r") {
+            return Some((&received[..line_start], i + 1));
+        }
+        line_start = i + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(server: &mut RpcServer, gb: &mut GameBoy, method: &str, params: &Value) -> Value {
+        let request = json!({"jsonrpc": "2.0", "id": 7, "method": method, "params": params});
+        let response: Value =
+            serde_json::from_str(&server.handle(gb, &request.to_string())).unwrap();
+        assert_eq!(response["id"], 7);
+        response
+    }
+
+    #[test]
+    fn peek_poke_and_pause() {
+        let mut server = RpcServer::bind("127.0.0.1:0").unwrap();
+        let mut gb = GameBoy::new();
+
+        call(
+            &mut server,
+            &mut gb,
+            "poke",
+            &json!({"address": 0xC000, "values": [1, 2, 3]}),
+        );
+        let response = call(
+            &mut server,
+            &mut gb,
+            "peek",
+            &json!({"address": 0xC001, "length": 2}),
+        );
+        assert_eq!(response["result"], json!([2, 3]));
+
+        call(&mut server, &mut gb, "pause", &Value::Null);
+        assert!(server.is_paused());
+        let status = call(&mut server, &mut gb, "status", &Value::Null);
+        assert_eq!(status["result"]["paused"], true);
+
+        let error = call(
+            &mut server,
+            &mut gb,
+            "poke",
+            &json!({"address": 0xC000, "value": 256}),
+        );
+        assert_eq!(error["error"]["code"], INVALID_PARAMS);
+        let error = call(&mut server, &mut gb, "explode", &Value::Null);
+        assert_eq!(error["error"]["code"], METHOD_NOT_FOUND);
+        let error: Value = serde_json::from_str(&server.handle(&mut gb, "{")).unwrap();
+        assert_eq!(error["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn last_write_names_the_writing_instruction() {
+        let mut server = RpcServer::bind("127.0.0.1:0").unwrap();
+        let mut gb = GameBoy::new();
+        // LD A,0x42; LD (0xC123),A
+        for (address, byte) in (0..).zip([0x3E, 0x42, 0xEA, 0x23, 0xC1]) {
+            gb.memory.write_byte(address, byte);
+        }
+        gb.cpu.pc = 0x0000;
+        let address = json!({"address": 0xC123});
+        let error = call(&mut server, &mut gb, "last_write", &address);
+        assert_eq!(error["error"]["message"], "Write tracking is off");
+
+        call(&mut server, &mut gb, "track_writes", &Value::Null);
+        assert_eq!(
+            call(&mut server, &mut gb, "last_write", &address)["result"],
+            Value::Null
+        );
+        call(&mut server, &mut gb, "step", &json!({"count": 2}));
+        let write = call(&mut server, &mut gb, "last_write", &address);
+        assert_eq!(
+            write["result"],
+            json!({"value": 0x42, "pc": 0x0002, "cycle": 8, "frame": 0})
+        );
+
+        call(
+            &mut server,
+            &mut gb,
+            "poke",
+            &json!({"address": 0xC123, "value": 1}),
+        );
+        let write = call(&mut server, &mut gb, "last_write", &address);
+        assert_eq!(write["result"]["pc"], Value::Null);
+        assert_eq!(write["result"]["cycle"], 24);
+    }
+
+    #[test]
+    fn step_stops_mid_frame_for_a_partial_screenshot() {
+        let mut server = RpcServer::bind("127.0.0.1:0").unwrap();
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000; // NOPs
+        gb.memory.write_byte(0xFF40, 0x91);
+
+        let response = call(&mut server, &mut gb, "step", &json!({"count": 1500}));
+        assert_eq!(response["result"]["pc"], 1500);
+        assert_eq!(response["result"]["ly"], 13, "1500 NOPs are 6000 dots");
+        assert_eq!(gb.partial_frame().progress().unwrap().ly, 13);
+
+        let path = std::env::temp_dir().join(format!("gameboy-rpc-{}.png", std::process::id()));
+        call(
+            &mut server,
+            &mut gb,
+            "screenshot",
+            &json!({"path": path.to_str().unwrap()}),
+        );
+        assert!(path.exists());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn timed_press_is_released_after_its_frames() {
+        let mut server = RpcServer::bind("127.0.0.1:0").unwrap();
+        let mut gb = GameBoy::new();
+
+        call(
+            &mut server,
+            &mut gb,
+            "press",
+            &json!({"button": "start", "frames": 2}),
+        );
+        call(&mut server, &mut gb, "press", &json!({"button": "a"}));
+        gb.finish_frame();
+        server.poll(&mut gb);
+        assert!(gb.memory.joypad.buttons().is_pressed(Button::Start));
+
+        gb.finish_frame();
+        server.poll(&mut gb);
+        assert!(!gb.memory.joypad.buttons().is_pressed(Button::Start));
+        assert!(
+            gb.memory.joypad.buttons().is_pressed(Button::A),
+            "Held until released"
+        );
+    }
+
+    #[test]
+    fn answers_http_posts() {
+        let mut server = RpcServer::bind("127.0.0.1:0").unwrap();
+        let mut gb = GameBoy::new();
+        gb.memory.write_byte(0xC000, 0x42);
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"peek","params":{"address":49152}}"#;
+        let mut client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        write!(
+            client,
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        server.poll(&mut gb);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(r#""result":[66]}"#), "{response}");
+    }
+
+    #[test]
+    fn request_sent_in_pieces_does_not_hold_up_polls() {
+        let mut server = RpcServer::bind("127.0.0.1:0").unwrap();
+        let mut gb = GameBoy::new();
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"status"}"#;
+        let mut client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        write!(
+            client,
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        let started = Instant::now();
+        server.poll(&mut gb);
+        server.poll(&mut gb);
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(server.connections.len(), 1, "Still waiting for the body");
+
+        client.write_all(body.as_bytes()).unwrap();
+        for _ in 0..100 {
+            server.poll(&mut gb);
+            if server.connections.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.contains(r#""paused":false"#), "{response}");
+    }
+
+    #[test]
+    fn parses_only_whole_requests() {
+        assert!(parse_request(b"POST / HTTP/1.1\r\nContent-Le").is_none());
+        assert!(parse_request(b"POST / HTTP/1.1\r\ncontent-length: 4\r\n\r\n{}").is_none());
+        assert!(matches!(
+            parse_request(b"POST / HTTP/1.1\ncontent-length: 2\n\n{}"),
+            Some(Request::Post(b"{}"))
+        ));
+        assert!(matches!(
+            parse_request(b"GET / HTTP/1.1\r\n\r\n"),
+            Some(Request::NotPost)
+        ));
+        let too_long = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert!(matches!(
+            parse_request(too_long.as_bytes()),
+            Some(Request::TooLarge)
+        ));
+        assert!(matches!(
+            parse_request(&[b'x'; MAX_HEADERS + 1]),
+            Some(Request::TooLarge)
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::playtime::CYCLES_PER_SECOND;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where a cartridge real-time clock gets the time from, so casual play
+/// can follow the host's clock while replays and tests stay deterministic
+pub trait ClockSource: Send {
+    /// Seconds since the UNIX epoch, `cycles` CPU cycles after power on
+    fn now(&self, cycles: u64) -> u64;
+}
+
+/// The host's wall clock, so in-game time passes while the emulator is
+/// closed, as it does with a cartridge battery
+pub struct SystemClock;
+
+impl ClockSource for SystemClock {
+    fn now(&self, _cycles: u64) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs())
+    }
+}
+
+/// Time counted from emulated cycles, `start` at power on: the same input
+/// always sees the same time, however fast the emulator runs
+pub struct EmulatedClock {
+    pub start: u64,
+}
+
+impl ClockSource for EmulatedClock {
+    fn now(&self, cycles: u64) -> u64 {
+        self.start + cycles / CYCLES_PER_SECOND
+    }
+}
+
+/// Stopped at one time, for tests
+pub struct FixedClock(pub u64);
+
+impl ClockSource for FixedClock {
+    fn now(&self, _cycles: u64) -> u64 {
+        self.0
+    }
+}
+
+/// A clock source as chosen on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Clock {
+    #[default]
+    System,
+    /// `EmulatedClock` starting at this UNIX time
+    Emulated(u64),
+    Fixed(u64),
+}
+
+impl Clock {
+    pub fn source(self) -> Box<dyn ClockSource> {
+        match self {
+            Clock::System => Box::new(SystemClock),
+            Clock::Emulated(start) => Box::new(EmulatedClock { start }),
+            Clock::Fixed(time) => Box::new(FixedClock(time)),
+        }
+    }
+}
+
+impl FromStr for Clock {
+    type Err = String;
+
+    /// `system`, `emulated` (from the epoch), `emulated:SECONDS` or
+    /// `fixed:SECONDS`, in seconds since the UNIX epoch
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, time) = s.split_once(':').unwrap_or((s, ""));
+        let seconds = || {
+            time.parse::<u64>()
+                .map_err(|_| format!("Invalid clock time in {s}: expected seconds"))
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "system" if time.is_empty() => Ok(Clock::System),
+            "emulated" if time.is_empty() => Ok(Clock::Emulated(0)),
+            "emulated" => Ok(Clock::Emulated(seconds()?)),
+            "fixed" => Ok(Clock::Fixed(seconds()?)),
+            _ => Err(format!(
+                "Unknown clock: {s} (expected system, emulated[:SECONDS] or fixed:SECONDS)"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emulated_clock_follows_cycles() {
+        let clock = Clock::Emulated(1_000).source();
+        assert_eq!(clock.now(0), 1_000);
+        assert_eq!(clock.now(CYCLES_PER_SECOND * 90 + 1), 1_090);
+        assert_eq!(Clock::Fixed(5).source().now(CYCLES_PER_SECOND), 5);
+        assert!(SystemClock.now(0) > 1_600_000_000);
+    }
+
+    #[test]
+    fn parses_clocks() {
+        assert_eq!("system".parse(), Ok(Clock::System));
+        assert_eq!("Emulated".parse(), Ok(Clock::Emulated(0)));
+        assert_eq!("emulated:60".parse(), Ok(Clock::Emulated(60)));
+        assert_eq!("fixed:1700000000".parse(), Ok(Clock::Fixed(1_700_000_000)));
+        assert!("fixed".parse::<Clock>().is_err());
+        assert!("system:5".parse::<Clock>().is_err());
+        assert!("sundial".parse::<Clock>().is_err());
+    }
+}
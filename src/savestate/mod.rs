@@ -0,0 +1,347 @@
+use crate::playtime;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"GBSS";
+/// Format of the states written; states in any other format are rejected
+pub const VERSION: u8 = 17;
+
+/// Size of the downscaled screen stored with each state (half the LCD)
+pub const THUMBNAIL_WIDTH: usize = 80;
+pub const THUMBNAIL_HEIGHT: usize = 72;
+
+/// Descriptive block at the start of every save state, readable without
+/// loading the state so a picker can list slots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateMetadata {
+    pub title: String,
+    /// CRC-32 of the ROM the state was made with (see `GameBoy::rom_hash`)
+    pub rom_hash: Option<u32>,
+    /// Seconds since the UNIX epoch when the state was saved
+    pub timestamp: u64,
+    /// Frames emulated since power on
+    pub frames: u64,
+    /// `THUMBNAIL_WIDTH` x `THUMBNAIL_HEIGHT` RGBA8 pixels
+    pub thumbnail: Vec<u8>,
+}
+
+impl StateMetadata {
+    /// Emulated time played, derived from the frame count
+    pub fn play_time(&self) -> Duration {
+        playtime::emulated_time(self.frames)
+    }
+
+    pub(crate) fn write(&self, state: &mut StateWriter) {
+        state.write_bytes(self.title.as_bytes());
+        state.write_bool(self.rom_hash.is_some());
+        state.write_u32(self.rom_hash.unwrap_or(0));
+        state.write_u64(self.timestamp);
+        state.write_u64(self.frames);
+        state.write_bytes(&self.thumbnail);
+    }
+
+    pub(crate) fn read(state: &mut StateReader) -> Result<Self, String> {
+        let title = String::from_utf8(state.read_bytes()?)
+            .map_err(|_| "Save state title is not UTF-8".to_string())?;
+        let has_hash = state.read_bool()?;
+        let hash = state.read_u32()?;
+        Ok(Self {
+            title,
+            rom_hash: has_hash.then_some(hash),
+            timestamp: state.read_u64()?,
+            frames: state.read_u64()?,
+            thumbnail: state.read_bytes()?,
+        })
+    }
+}
+
+/// Serialized emulator state. Produced by `GameBoy::save_state` and consumed
+/// by `GameBoy::load_state`; the ROM itself is never included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    data: Vec<u8>,
+}
+
+impl SaveState {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+        if data.len() < MAGIC.len() + 1 || &data[..MAGIC.len()] != MAGIC {
+            return Err("Not a save state".to_string());
+        }
+        if data[MAGIC.len()] != VERSION {
+            return Err(format!(
+                "Unsupported save state version: {}",
+                data[MAGIC.len()]
+            ));
+        }
+        Ok(Self { data })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Read the metadata block without restoring anything
+    pub fn metadata(&self) -> Result<StateMetadata, String> {
+        StateMetadata::read(&mut self.reader())
+    }
+
+    pub(crate) fn reader(&self) -> StateReader<'_> {
+        StateReader {
+            data: &self.data,
+            position: MAGIC.len() + 1,
+        }
+    }
+}
+
+/// How `GameBoy::load_state` treats a state that breaks the machine's
+/// invariants, as a corrupt or hand-edited state might
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StateCheck {
+    /// Load states as they are
+    #[default]
+    Off,
+    /// Reject a state with any problem, leaving the machine as it was
+    Refuse,
+    /// Fix what can be fixed, with a warning for each, and reject the rest
+    Repair,
+}
+
+impl FromStr for StateCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(StateCheck::Off),
+            "refuse" => Ok(StateCheck::Refuse),
+            "repair" => Ok(StateCheck::Repair),
+            _ => Err(format!(
+                "Unknown state check: {s} (expected off, refuse or repair)"
+            )),
+        }
+    }
+}
+
+/// An invariant a loaded state breaks (see `GameBoy::state_problems`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateProblem {
+    /// PC in memory code cannot run from: 0xFEA0-0xFF7F, or external RAM
+    /// on a cartridge without any
+    UnmappedPc(u16),
+    /// SP outside work RAM, high RAM and external RAM, so pushes would go
+    /// nowhere
+    StrayStack(u16),
+    /// LY, the dot within the line and the PPU mode disagree
+    PpuPosition { ly: u8, dot: u16, mode: u8 },
+    /// Mapped ROM bank past the end of the ROM
+    RomBank { bank: usize, banks: usize },
+    /// Mapped RAM bank past the end of external RAM
+    RamBank { bank: usize, banks: usize },
+}
+
+impl StateProblem {
+    /// Whether `Repair` can fix it. The PPU restarts its line and banks
+    /// wrap to the cartridge's size, as the mapper masks bank numbers on
+    /// hardware; nothing says where PC or SP should have been.
+    pub fn repairable(self) -> bool {
+        !matches!(
+            self,
+            StateProblem::UnmappedPc(_) | StateProblem::StrayStack(_)
+        )
+    }
+}
+
+impl fmt::Display for StateProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateProblem::UnmappedPc(pc) => write!(f, "PC {pc:04X} is not in runnable memory"),
+            StateProblem::StrayStack(sp) => write!(f, "SP {sp:04X} is not in RAM"),
+            StateProblem::PpuPosition { ly, dot, mode } => {
+                write!(f, "PPU in mode {mode} at LY {ly}, dot {dot}")
+            }
+            StateProblem::RomBank { bank, banks } => {
+                write!(f, "ROM bank {bank} mapped, ROM has {banks}")
+            }
+            StateProblem::RamBank { bank, banks } => {
+                write!(f, "RAM bank {bank} mapped, cartridge has {banks}")
+            }
+        }
+    }
+}
+
+/// Appends little-endian fields to a save state
+pub(crate) struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        Self { data }
+    }
+
+    pub fn finish(self) -> SaveState {
+        SaveState { data: self.data }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(u8::from(value));
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Length-prefixed byte block
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(u32::try_from(bytes.len()).expect("state block over 4GB"));
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+/// Reads fields back in the order `StateWriter` wrote them
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl StateReader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.position + len;
+        if end > self.data.len() {
+            return Err("Save state is truncated".to_string());
+        }
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.read_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// Read a byte block that must be exactly `into.len()` long
+    pub fn read_into(&mut self, into: &mut [u8]) -> Result<(), String> {
+        let bytes = self.read_bytes()?;
+        if bytes.len() != into.len() {
+            return Err(format!(
+                "Save state block is {} bytes, expected {}",
+                bytes.len(),
+                into.len()
+            ));
+        }
+        into.copy_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_round_trip() {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0x12);
+        writer.write_bool(true);
+        writer.write_u16(0x3456);
+        writer.write_u32(0x789A_BCDE);
+        writer.write_u64(0x0102_0304_0506_0708);
+        writer.write_bytes(&[1, 2, 3]);
+        let state = writer.finish();
+
+        let mut reader = state.reader();
+        assert_eq!(reader.read_u8(), Ok(0x12));
+        assert_eq!(reader.read_bool(), Ok(true));
+        assert_eq!(reader.read_u16(), Ok(0x3456));
+        assert_eq!(reader.read_u32(), Ok(0x789A_BCDE));
+        assert_eq!(reader.read_u64(), Ok(0x0102_0304_0506_0708));
+        assert_eq!(reader.read_bytes(), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn truncated_state_is_an_error() {
+        let state = SaveState::from_bytes(b"GBSS\x11\x34".to_vec()).unwrap();
+        assert!(state.reader().read_u16().is_err());
+    }
+
+    #[test]
+    fn parses_state_checks() {
+        assert_eq!("repair".parse(), Ok(StateCheck::Repair));
+        assert!("strict".parse::<StateCheck>().is_err());
+        assert!(StateProblem::RomBank { bank: 9, banks: 4 }.repairable());
+        assert!(!StateProblem::StrayStack(0x8000).repairable());
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_version() {
+        assert!(SaveState::from_bytes(b"NOPE\x01".to_vec()).is_err());
+        assert!(SaveState::from_bytes(b"GBSS\x63".to_vec()).is_err());
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let metadata = StateMetadata {
+            title: "TETRIS".to_string(),
+            rom_hash: Some(0x46DB_D8E7),
+            timestamp: 1_700_000_000,
+            frames: 3600,
+            thumbnail: vec![0xFF; THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 4],
+        };
+        let mut writer = StateWriter::new();
+        metadata.write(&mut writer);
+        writer.write_u8(0x99);
+        let state = writer.finish();
+
+        assert_eq!(state.metadata(), Ok(metadata.clone()));
+        assert_eq!(metadata.play_time().as_secs(), 60);
+    }
+
+    #[test]
+    fn read_into_checks_length() {
+        let mut writer = StateWriter::new();
+        writer.write_bytes(&[1, 2]);
+        let state = writer.finish();
+
+        let mut buffer = [0; 3];
+        assert!(state.reader().read_into(&mut buffer).is_err());
+    }
+}
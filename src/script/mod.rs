@@ -0,0 +1,421 @@
+use crate::gameboy::GameBoy;
+use crate::joypad::{Button, InputMacro};
+use crate::ppu::Layer;
+use crate::rammap::RamMap;
+use rhai::{Engine, EvalAltResult, INT, Map};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Run a Rhai script that drives `gb` headlessly, returning the machine once
+/// the script finishes so callers can inspect the final state.
+///
+/// Functions available to scripts:
+/// - `frames(n)` / `step(n)` - run `n` frames / instructions
+/// - `seek(frame)` - run to the end of frame number `frame`, presenting only that one
+/// - `press(name)`, `release(name)`, `release_all()` - hold buttons ("a", "start", "up", ...)
+/// - `peek(addr)`, `poke(addr, value)` - read / write memory
+/// - `register(name)` - read "a".."l", "af".."hl", "sp" or "pc"
+/// - `io(name)`, `set_io(name, value)` - read / write an IO register by name ("lcdc", "scx", ...)
+/// - `frame_count()` - frames run since power on
+/// - `turbo(name, hz)` - auto-fire a button while held (`hz` 0 turns it off)
+/// - `record_macro()`, `stop_macro(name)`, `define_macro(name, text)`, `play_macro(name)` -
+///   record, bind and replay input macros (text format as in `joypad::InputMacro`)
+/// - `cheat(name, enabled)` - toggle a cheat from the game's cheat list
+/// - `layer(name, visible)` - show or hide "background", "window" or "objects"
+/// - `ram(name)`, `ram_fields()` - decode RAM map fields (see `run_script_with_ram_map`)
+/// - `assert(condition, message)`, `assert_memory(addr, expected)` - fail the script
+pub fn run_script(gb: GameBoy, source: &str) -> Result<GameBoy, String> {
+    run_script_with_ram_map(gb, source, RamMap::default())
+}
+
+/// `run_script` with the game's RAM map available to the script through
+/// `ram(name)`, which decodes one field, and `ram_fields()`, which returns a
+/// map of every field name to its current value
+pub fn run_script_with_ram_map(
+    gb: GameBoy,
+    source: &str,
+    ram_map: RamMap,
+) -> Result<GameBoy, String> {
+    let gb = Rc::new(RefCell::new(gb));
+    let mut engine = Engine::new();
+    register_api(&mut engine, &gb);
+    register_ram_map(&mut engine, &gb, ram_map);
+
+    let result = engine.run(source);
+    drop(engine);
+
+    let gb = Rc::try_unwrap(gb)
+        .map_err(|_| "Script engine still holds the emulator".to_string())?
+        .into_inner();
+    result.map(|()| gb).map_err(|e| e.to_string())
+}
+
+fn register_api(engine: &mut Engine, gb: &Rc<RefCell<GameBoy>>) {
+    let handle = Rc::clone(gb);
+    engine.register_fn("frames", move |count: INT| {
+        let mut gb = handle.borrow_mut();
+        for _ in 0..count {
+            gb.finish_frame();
+        }
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("step", move |count: INT| {
+        let mut gb = handle.borrow_mut();
+        for _ in 0..count {
+            gb.step();
+        }
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("seek", move |frame: INT| -> ScriptResult<INT> {
+        let frame = u64::try_from(frame).map_err(|_| format!("Invalid frame: {frame}"))?;
+        let reached = handle.borrow_mut().seek(frame)?;
+        Ok(INT::try_from(reached).unwrap_or(INT::MAX))
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("press", move |name: &str| -> ScriptResult<()> {
+        let mut gb = handle.borrow_mut();
+        let mut buttons = gb.memory.joypad.buttons();
+        buttons.press(name.parse::<Button>()?);
+        gb.set_buttons(buttons);
+        Ok(())
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("release", move |name: &str| -> ScriptResult<()> {
+        let mut gb = handle.borrow_mut();
+        let mut buttons = gb.memory.joypad.buttons();
+        buttons.release(name.parse::<Button>()?);
+        gb.set_buttons(buttons);
+        Ok(())
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("release_all", move || {
+        handle
+            .borrow_mut()
+            .set_buttons(crate::joypad::Buttons::new());
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("peek", move |address: INT| -> ScriptResult<INT> {
+        Ok(INT::from(handle.borrow().memory.peek(to_address(address)?)))
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn(
+        "poke",
+        move |address: INT, value: INT| -> ScriptResult<()> {
+            let value = u8::try_from(value).map_err(|_| format!("Value out of range: {value}"))?;
+            handle
+                .borrow_mut()
+                .memory
+                .write_byte(to_address(address)?, value);
+            Ok(())
+        },
+    );
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("register", move |name: &str| -> ScriptResult<INT> {
+        let gb = handle.borrow();
+        let registers = &gb.cpu.registers;
+        let value = match name.to_ascii_lowercase().as_str() {
+            "a" => u16::from(registers.a),
+            "f" => u16::from(registers.f.to_u8()),
+            "b" => u16::from(registers.b),
+            "c" => u16::from(registers.c),
+            "d" => u16::from(registers.d),
+            "e" => u16::from(registers.e),
+            "h" => u16::from(registers.h),
+            "l" => u16::from(registers.l),
+            "af" => registers.af(),
+            "bc" => registers.bc(),
+            "de" => registers.de(),
+            "hl" => registers.hl(),
+            "sp" => gb.cpu.sp,
+            "pc" => gb.cpu.pc,
+            _ => return Err(format!("Unknown register: {name}").into()),
+        };
+        Ok(INT::from(value))
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("frame_count", move || -> INT {
+        INT::try_from(handle.borrow().frame_count()).unwrap_or(INT::MAX)
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn(
+        "cheat",
+        move |name: &str, enabled: bool| -> ScriptResult<()> {
+            Ok(handle.borrow_mut().set_cheat_enabled(name, enabled)?)
+        },
+    );
+
+    register_io(engine, gb);
+    register_input(engine, gb);
+    register_layers(engine, gb);
+    register_assertions(engine, gb);
+}
+
+fn register_io(engine: &mut Engine, gb: &Rc<RefCell<GameBoy>>) {
+    let handle = Rc::clone(gb);
+    engine.register_fn("io", move |name: &str| -> ScriptResult<INT> {
+        Ok(INT::from(handle.borrow_mut().io().get(name)?))
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn(
+        "set_io",
+        move |name: &str, value: INT| -> ScriptResult<()> {
+            let value = u8::try_from(value).map_err(|_| format!("Value out of range: {value}"))?;
+            Ok(handle.borrow_mut().io().set(name, value)?)
+        },
+    );
+}
+
+fn register_layers(engine: &mut Engine, gb: &Rc<RefCell<GameBoy>>) {
+    let handle = Rc::clone(gb);
+    engine.register_fn(
+        "layer",
+        move |name: &str, visible: bool| -> ScriptResult<()> {
+            let layer = name.parse::<Layer>()?;
+            handle.borrow_mut().set_layer_visible(layer, visible);
+            Ok(())
+        },
+    );
+}
+
+fn register_input(engine: &mut Engine, gb: &Rc<RefCell<GameBoy>>) {
+    let handle = Rc::clone(gb);
+    engine.register_fn("turbo", move |name: &str, hz: f64| -> ScriptResult<()> {
+        let button = name.parse::<Button>()?;
+        handle.borrow_mut().input_mut().set_turbo(button, Some(hz));
+        Ok(())
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("record_macro", move || {
+        handle.borrow_mut().input_mut().start_recording();
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("stop_macro", move |name: &str| -> ScriptResult<INT> {
+        let mut gb = handle.borrow_mut();
+        let recorded = gb
+            .input_mut()
+            .stop_recording(name)
+            .ok_or("stop_macro() called without record_macro()")?;
+        Ok(INT::try_from(recorded.len()).unwrap_or(INT::MAX))
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn(
+        "define_macro",
+        move |name: &str, text: &str| -> ScriptResult<()> {
+            let input = text.parse::<InputMacro>()?;
+            handle.borrow_mut().input_mut().add_macro(name, input);
+            Ok(())
+        },
+    );
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("play_macro", move |name: &str| -> ScriptResult<()> {
+        Ok(handle.borrow_mut().input_mut().play(name)?)
+    });
+}
+
+fn register_assertions(engine: &mut Engine, gb: &Rc<RefCell<GameBoy>>) {
+    engine.register_fn(
+        "assert",
+        |condition: bool, message: &str| -> ScriptResult<()> {
+            if condition {
+                Ok(())
+            } else {
+                Err(format!("Assertion failed: {message}").into())
+            }
+        },
+    );
+
+    let handle = Rc::clone(gb);
+    engine.register_fn(
+        "assert_memory",
+        move |address: INT, expected: INT| -> ScriptResult<()> {
+            let actual = INT::from(handle.borrow().memory.peek(to_address(address)?));
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Assertion failed: memory[0x{address:04X}] is 0x{actual:02X}, expected 0x{expected:02X}"
+                )
+                .into())
+            }
+        },
+    );
+}
+
+fn register_ram_map(engine: &mut Engine, gb: &Rc<RefCell<GameBoy>>, ram_map: RamMap) {
+    let ram_map = Rc::new(ram_map);
+
+    let handle = Rc::clone(gb);
+    let map = Rc::clone(&ram_map);
+    engine.register_fn("ram", move |name: &str| -> ScriptResult<INT> {
+        let field = map
+            .get(name)
+            .ok_or_else(|| format!("Unknown RAM map field: {name}"))?;
+        Ok(field.read(&handle.borrow().memory))
+    });
+
+    let handle = Rc::clone(gb);
+    engine.register_fn("ram_fields", move || -> Map {
+        ram_map
+            .read_all(&handle.borrow().memory)
+            .into_iter()
+            .map(|(field, value)| (field.name.as_str().into(), value.into()))
+            .collect()
+    });
+}
+
+fn to_address(address: INT) -> ScriptResult<u16> {
+    u16::try_from(address).map_err(|_| format!("Address out of range: {address}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheats::CheatList;
+
+    fn nop_gameboy() -> GameBoy {
+        let mut gb = GameBoy::new();
+        gb.cpu.pc = 0x0000;
+        gb
+    }
+
+    #[test]
+    fn script_runs_frames_and_reads_memory() {
+        let mut gb = run_script(
+            nop_gameboy(),
+            r#"
+                poke(0xC000, 0x42);
+                frames(2);
+                assert_memory(0xC000, 0x42);
+                assert(frame_count() == 2, `frame count was ${frame_count()}`);
+                set_io("scx", 8);
+                assert(io("IF") >= 0xE0, "Unused IF bits read as 1");
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(gb.frame_count(), 2);
+        assert_eq!(gb.io().scx(), 8);
+    }
+
+    #[test]
+    fn script_toggles_cheats() {
+        let mut gb = nop_gameboy();
+        gb.set_cheats(
+            CheatList::parse(
+                "off 0142C0C0 Lives\n\
+                 off 01F-FFF  Patch",
+            )
+            .unwrap(),
+        );
+        let gb = run_script(
+            gb,
+            r#"
+                frames(1);
+                assert_memory(0xC0C0, 0x00);
+                cheat("Lives", true);
+                cheat("Patch", true);
+                frames(1);
+                assert_memory(0xC0C0, 0x42);
+                assert_memory(0x0FFF, 0x01);
+            "#,
+        )
+        .unwrap();
+        assert!(gb.cheats().get("Lives").unwrap().enabled);
+    }
+
+    #[test]
+    fn script_presses_buttons() {
+        let gb = run_script(
+            nop_gameboy(),
+            r#"press("start"); press("a"); release("a");"#,
+        )
+        .unwrap();
+
+        let buttons = gb.memory.joypad.buttons();
+        assert!(buttons.is_pressed(Button::Start));
+        assert!(!buttons.is_pressed(Button::A));
+    }
+
+    #[test]
+    fn script_reads_registers() {
+        run_script(
+            nop_gameboy(),
+            r#"step(3); assert(register("pc") == 3, "pc");"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn failed_assertion_is_an_error() {
+        let error = run_script(nop_gameboy(), "assert_memory(0xC000, 0x01);")
+            .err()
+            .unwrap();
+        assert!(
+            error.contains("memory[0xC000] is 0x00, expected 0x01"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn script_reads_ram_map_fields() {
+        let ram_map = RamMap::parse("C000 u8 hp\nC001 bool poisoned").unwrap();
+        run_script_with_ram_map(
+            nop_gameboy(),
+            r#"
+                poke(0xC000, 42);
+                assert(ram("hp") == 42, "hp");
+                let fields = ram_fields();
+                assert(fields.hp == 42 && fields.poisoned == 0, `${fields}`);
+            "#,
+            ram_map,
+        )
+        .unwrap();
+        assert!(run_script(nop_gameboy(), r#"ram("hp");"#).is_err());
+    }
+
+    #[test]
+    fn script_plays_macros_and_turbo() {
+        let gb = run_script(
+            nop_gameboy(),
+            r#"
+                define_macro("walk", "right x2\n-\n");
+                play_macro("walk");
+                turbo("a", 30.0);
+                record_macro();
+                press("a");
+                frames(1);
+                assert(stop_macro("mash") == 1, "recorded frames");
+            "#,
+        )
+        .unwrap();
+
+        let buttons = gb.memory.joypad.buttons();
+        assert!(buttons.is_pressed(Button::Right), "Macro still playing");
+        assert!(!buttons.is_pressed(Button::A), "Turbo released A");
+        assert!(gb.input().get_macro("mash").is_some());
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert!(run_script(nop_gameboy(), r#"press("turbo");"#).is_err());
+        assert!(run_script(nop_gameboy(), "peek(0x10000);").is_err());
+        assert!(run_script(nop_gameboy(), "poke(0xC000, 256);").is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use super::SerialDevice;
+use serialport::SerialPort;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// A real Game Boy on the end of a USB link cable adapter.
+///
+/// Speaks the byte-exchange protocol of the common Arduino/Pico adapters:
+/// every byte written to the port is shifted out over the link cable and the
+/// byte shifted in comes back.
+pub struct LinkCable {
+    port: Box<dyn SerialPort>,
+    internal_clock: bool,
+    pending: bool,
+}
+
+impl LinkCable {
+    pub const BAUD_RATE: u32 = 115_200;
+
+    /// How long an internal clock transfer may wait for the reply. The
+    /// real transfer takes 0.5ms; anything slower means nothing is connected.
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    /// Open the adapter at `path`, e.g. `/dev/ttyACM0` or `COM3`
+    pub fn open(path: &str) -> Result<Self, String> {
+        let port = serialport::new(path, Self::BAUD_RATE)
+            .timeout(Self::TIMEOUT)
+            .open()
+            .map_err(|e| format!("{path}: {e}"))?;
+        Ok(Self {
+            port,
+            internal_clock: false,
+            pending: false,
+        })
+    }
+}
+
+impl SerialDevice for LinkCable {
+    fn start(&mut self, byte: u8, internal_clock: bool) {
+        // Drop the reply to an abandoned transfer so replies stay in step
+        self.port.clear(serialport::ClearBuffer::Input).ok();
+        self.internal_clock = internal_clock;
+        self.pending = self.port.write_all(&[byte]).is_ok();
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        if !self.pending {
+            return None;
+        }
+        // The other Game Boy clocks the transfer whenever it likes, so don't
+        // block emulation waiting for it
+        if !self.internal_clock && self.port.bytes_to_read().unwrap_or(0) == 0 {
+            return None;
+        }
+
+        self.pending = false;
+        let mut byte = [0];
+        self.port.read_exact(&mut byte).ok().map(|()| byte[0])
+    }
+}
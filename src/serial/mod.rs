@@ -0,0 +1,240 @@
+mod link_cable;
+
+pub use link_cable::LinkCable;
+
+use crate::interrupts::{Interrupt, Interrupts};
+use crate::memory::Memory;
+use crate::savestate::{StateReader, StateWriter};
+use crate::tick::{self, Tickable};
+
+/// CPU cycles to shift out one byte with the internal clock (8 bits at 8192 Hz)
+const TRANSFER_CYCLES: u16 = 4096;
+
+/// Whatever is on the other end of the link cable
+pub trait SerialDevice: Send {
+    /// The game started a transfer of `byte`. With `internal_clock` this Game
+    /// Boy drives the clock; otherwise the other side does, whenever it likes.
+    fn start(&mut self, byte: u8, internal_clock: bool);
+
+    /// The byte shifted in for the transfer started last, once the other side
+    /// has provided it
+    fn receive(&mut self) -> Option<u8>;
+}
+
+/// Serial port registers SB (0xFF01) and SC (0xFF02)
+pub struct Serial {
+    sb: u8,
+    sc: u8, // byte format S--- ---C; S = Transfer in progress, C = Internal clock
+    transfer_cycles: u16, // Cycles left in an internal clock transfer
+    device: Option<Box<dyn SerialDevice>>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            sb: 0,
+            sc: 0,
+            transfer_cycles: 0,
+            device: None,
+        }
+    }
+
+    /// Plug in (or with `None`, unplug) the link cable
+    pub fn connect(&mut self, device: Option<Box<dyn SerialDevice>>) {
+        self.device = device;
+    }
+
+    /// Unplug the link cable, handing back whatever was connected
+    pub fn take_device(&mut self) -> Option<Box<dyn SerialDevice>> {
+        self.device.take()
+    }
+
+    /// Advance a transfer in progress. Returns true when it completes and the
+    /// serial interrupt should be requested.
+    pub fn tick(&mut self, cycles: u8) -> bool {
+        if self.sc & 0x80 == 0 {
+            return false;
+        }
+
+        let received = if self.is_internal_clock() {
+            self.transfer_cycles = self.transfer_cycles.saturating_sub(u16::from(cycles));
+            if self.transfer_cycles > 0 {
+                return false;
+            }
+            // With nothing connected the data line floats high
+            Some(
+                self.device
+                    .as_mut()
+                    .and_then(|device| device.receive())
+                    .unwrap_or(0xFF),
+            )
+        } else {
+            self.device.as_mut().and_then(|device| device.receive())
+        };
+
+        match received {
+            Some(byte) => {
+                self.sb = byte;
+                self.sc &= 0x7F;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Read SB or SC; any other address reads 0xFF
+    pub fn read_register(&self, address: u16) -> u8 {
+        // 0xFF01 = SB, 0xFF02 = SC
+        match address {
+            0xFF01 => self.sb,
+            0xFF02 => self.sc | 0x7E, // Unused bits read high
+            _ => 0xFF,
+        }
+    }
+
+    /// Write SB, or SC, starting a transfer if bit 7 is set; any other
+    /// address is ignored
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        // 0xFF01 = SB, 0xFF02 = SC
+        match address {
+            0xFF01 => self.sb = value,
+            0xFF02 => {
+                self.sc = value & 0x81;
+                if self.sc & 0x80 != 0 {
+                    self.transfer_cycles = TRANSFER_CYCLES;
+                    let internal_clock = self.is_internal_clock();
+                    if let Some(ref mut device) = self.device {
+                        device.start(self.sb, internal_clock);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.sb);
+        state.write_u8(self.sc);
+        state.write_u16(self.transfer_cycles);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.sb = state.read_u8()?;
+        self.sc = state.read_u8()?;
+        self.transfer_cycles = state.read_u16()?;
+        Ok(())
+    }
+
+    fn is_internal_clock(&self) -> bool {
+        self.sc & 0x01 != 0
+    }
+}
+
+impl Tickable for Serial {
+    fn advance(&mut self, cycles: u32, _memory: &Memory, interrupts: &mut Interrupts) {
+        for step in tick::in_steps(cycles, u8::MAX) {
+            if self.tick(step) {
+                interrupts.request(Interrupt::Serial);
+            }
+        }
+    }
+
+    /// Until an internal clock transfer completes. One on the other side's
+    /// clock completes on the first `advance` after its byte arrives.
+    fn next_event(&self) -> Option<u32> {
+        (self.sc & 0x80 != 0 && self.is_internal_clock())
+            .then(|| u32::from(self.transfer_cycles.max(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    type Sent = Arc<Mutex<Vec<(u8, bool)>>>;
+
+    /// Replies with a fixed byte, optionally only once `ready` is set
+    struct Peer {
+        reply: u8,
+        ready: Arc<AtomicBool>,
+        sent: Sent,
+    }
+
+    impl SerialDevice for Peer {
+        fn start(&mut self, byte: u8, internal_clock: bool) {
+            self.sent.lock().unwrap().push((byte, internal_clock));
+        }
+
+        fn receive(&mut self) -> Option<u8> {
+            self.ready.load(Ordering::Relaxed).then_some(self.reply)
+        }
+    }
+
+    fn connect(serial: &mut Serial, ready: bool) -> (Arc<AtomicBool>, Sent) {
+        let ready = Arc::new(AtomicBool::new(ready));
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        serial.connect(Some(Box::new(Peer {
+            reply: 0x5A,
+            ready: Arc::clone(&ready),
+            sent: Arc::clone(&sent),
+        })));
+        (ready, sent)
+    }
+
+    fn run(serial: &mut Serial, cycles: u16) -> bool {
+        let mut interrupted = false;
+        for _ in 0..cycles / 4 {
+            interrupted |= serial.tick(4);
+        }
+        interrupted
+    }
+
+    #[test]
+    fn internal_clock_transfer_takes_4096_cycles() {
+        let mut serial = Serial::new();
+        let (_, sent) = connect(&mut serial, true);
+        serial.write_register(0xFF01, 0xA5);
+        serial.write_register(0xFF02, 0x81);
+
+        assert!(!run(&mut serial, TRANSFER_CYCLES - 4));
+        assert_eq!(serial.read_register(0xFF02), 0xFF);
+        assert!(serial.tick(4));
+
+        assert_eq!(serial.read_register(0xFF01), 0x5A);
+        assert_eq!(serial.read_register(0xFF02), 0x7F);
+        assert_eq!(*sent.lock().unwrap(), vec![(0xA5, true)]);
+    }
+
+    #[test]
+    fn internal_clock_without_cable_shifts_in_ones() {
+        let mut serial = Serial::new();
+        serial.write_register(0xFF01, 0x12);
+        serial.write_register(0xFF02, 0x81);
+
+        assert!(run(&mut serial, TRANSFER_CYCLES));
+        assert_eq!(serial.read_register(0xFF01), 0xFF);
+    }
+
+    #[test]
+    fn external_clock_waits_for_the_other_side() {
+        let mut serial = Serial::new();
+        let (ready, sent) = connect(&mut serial, false);
+        serial.write_register(0xFF01, 0x33);
+        serial.write_register(0xFF02, 0x80);
+
+        assert!(!run(&mut serial, TRANSFER_CYCLES * 4));
+        ready.store(true, Ordering::Relaxed);
+        assert!(serial.tick(4));
+
+        assert_eq!(serial.read_register(0xFF01), 0x5A);
+        assert_eq!(*sent.lock().unwrap(), vec![(0x33, false)]);
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs;
+use std::path::Path;
+
+/// Source language of a post-process shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Wgsl,
+    Glsl,
+}
+
+/// Post-process fragment shader applied to the scaled output by a GPU frontend.
+///
+/// User shaders only provide the fragment stage; the frontend draws a
+/// full-screen triangle and binds the emulated frame for them:
+///
+/// - WGSL: define `fn fs_main(in: VertexOutput) -> @location(0) vec4<f32>`.
+///   `frame`, `frame_sampler` and `screen` (output and source size in pixels)
+///   come from `WGSL_PRELUDE`, which is prepended to the user source.
+/// - GLSL (450): write a complete `void main()` reading `in vec2 uv` at
+///   location 0 and writing `out vec4 color` at location 0, with
+///   `frame` (binding 0), `frame_sampler` (binding 1) and the `Screen`
+///   uniform block (binding 2) declared in set 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostShader {
+    pub name: String,
+    pub language: ShaderLanguage,
+    pub source: String,
+}
+
+/// Declarations shared by every WGSL post-process shader
+pub const WGSL_PRELUDE: &str = r"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+struct Screen {
+    output_size: vec2<f32>,
+    source_size: vec2<f32>,
+};
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var frame_sampler: sampler;
+@group(0) @binding(2) var<uniform> screen: Screen;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+";
+
+const SCANLINES: &str = r"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(frame, frame_sampler, in.uv);
+    // Darken the lower part of every emulated line
+    let row = fract(in.uv.y * screen.source_size.y);
+    let shade = select(1.0, 0.7, row > 0.6);
+    return vec4<f32>(color.rgb * shade, 1.0);
+}
+";
+
+const LCD_GRID: &str = r"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(frame, frame_sampler, in.uv);
+    // Thin gaps between LCD cells in both directions
+    let cell = fract(in.uv * screen.source_size);
+    let edge = step(vec2<f32>(0.85), cell);
+    let gap = max(edge.x, edge.y);
+    return vec4<f32>(mix(color.rgb, color.rgb * 0.8, gap), 1.0);
+}
+";
+
+const CURVATURE: &str = r"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Barrel distortion around the screen center, black outside the tube
+    let centered = in.uv * 2.0 - 1.0;
+    let warped = centered * (1.0 + 0.08 * dot(centered, centered));
+    let uv = warped * 0.5 + 0.5;
+    let color = textureSample(frame, frame_sampler, clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)));
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), vec4<f32>(color.rgb, 1.0), inside);
+}
+";
+
+const SMOOTH: &str = r"
+fn texel(position: vec2<f32>) -> vec3<f32> {
+    let size = vec2<i32>(screen.source_size);
+    return textureLoad(frame, clamp(vec2<i32>(position), vec2<i32>(0), size - 1), 0).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Blend the four nearest pixels by distance, softening the pixel edges
+    let position = in.uv * screen.source_size - 0.5;
+    let corner = floor(position);
+    let weight = position - corner;
+    let top = mix(texel(corner), texel(corner + vec2<f32>(1.0, 0.0)), weight.x);
+    let bottom = mix(
+        texel(corner + vec2<f32>(0.0, 1.0)),
+        texel(corner + vec2<f32>(1.0, 1.0)),
+        weight.x,
+    );
+    return vec4<f32>(mix(top, bottom, weight.y), 1.0);
+}
+";
+
+impl PostShader {
+    /// Names accepted by `preset`
+    pub const PRESETS: [&'static str; 4] = ["scanlines", "lcd-grid", "curvature", "smooth"];
+
+    /// Built-in WGSL shader by name (case-insensitive)
+    pub fn preset(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let source = match name.as_str() {
+            "scanlines" => SCANLINES,
+            "lcd-grid" => LCD_GRID,
+            "curvature" => CURVATURE,
+            "smooth" => SMOOTH,
+            _ => return None,
+        };
+        Some(Self {
+            name,
+            language: ShaderLanguage::Wgsl,
+            source: source.to_string(),
+        })
+    }
+
+    /// Read a user shader, picking the language from the file extension
+    /// (`.wgsl`, or `.glsl` / `.frag` for GLSL)
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let language = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("wgsl") => ShaderLanguage::Wgsl,
+            Some("glsl" | "frag") => ShaderLanguage::Glsl,
+            _ => {
+                return Err(format!(
+                    "{}: expected a .wgsl, .glsl or .frag shader",
+                    path.display()
+                ));
+            }
+        };
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        Self::from_source(name, language, source)
+    }
+
+    /// Resolve a built-in preset name or load a shader file
+    pub fn from_name_or_path(name_or_path: &str) -> Result<Self, String> {
+        Self::preset(name_or_path).map_or_else(|| Self::load(Path::new(name_or_path)), Ok)
+    }
+
+    /// Wrap shader source after checking it defines the expected entry point
+    pub fn from_source(
+        name: String,
+        language: ShaderLanguage,
+        source: String,
+    ) -> Result<Self, String> {
+        let entry_point = match language {
+            ShaderLanguage::Wgsl => "fn fs_main",
+            ShaderLanguage::Glsl => "void main",
+        };
+        if !source.contains(entry_point) {
+            return Err(format!("Shader `{name}` does not define `{entry_point}`"));
+        }
+        Ok(Self {
+            name,
+            language,
+            source,
+        })
+    }
+
+    /// Complete module for the frontend to compile. WGSL gets the shared
+    /// prelude; GLSL is passed through since it declares its own interface.
+    pub fn module_source(&self) -> String {
+        match self.language {
+            ShaderLanguage::Wgsl => format!("{WGSL_PRELUDE}{}", self.source),
+            ShaderLanguage::Glsl => self.source.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn presets_are_wgsl_with_entry_point() {
+        for name in PostShader::PRESETS {
+            let shader = PostShader::preset(name).unwrap();
+            assert_eq!(shader.language, ShaderLanguage::Wgsl);
+            assert!(shader.module_source().contains("fn vs_main"));
+            assert!(shader.source.contains("fn fs_main"), "{name}");
+        }
+        assert_eq!(PostShader::preset("Scanlines").unwrap().name, "scanlines");
+        assert!(PostShader::preset("bloom").is_none());
+    }
+
+    #[test]
+    fn load_picks_language_from_extension() {
+        let path = env::temp_dir().join(format!("gameboy-shader-{}.frag", std::process::id()));
+        fs::write(&path, "#version 450\nvoid main() {}\n").unwrap();
+
+        let shader = PostShader::from_name_or_path(path.to_str().unwrap()).unwrap();
+        assert_eq!(shader.language, ShaderLanguage::Glsl);
+        assert_eq!(shader.module_source(), shader.source);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_entry_point_is_an_error() {
+        let error = PostShader::from_source(
+            "empty".to_string(),
+            ShaderLanguage::Wgsl,
+            "// nothing here".to_string(),
+        )
+        .unwrap_err();
+        assert!(error.contains("fn fs_main"), "{error}");
+    }
+
+    #[test]
+    fn unknown_extension_is_an_error() {
+        assert!(PostShader::load(Path::new("crt.hlsl")).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::gameboy::GameBoy;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU8, AtomicU16, AtomicUsize, Ordering, fence};
+
+const LY_ADDRESS: u16 = 0xFF44;
+
+/// A plain copy of the emulator state a UI overlay is interested in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ly: u8,
+    pub ram_start: u16,
+    pub ram: Vec<u8>,
+}
+
+/// One half of the double buffer. Every field is atomic so slots can be
+/// shared between threads without locks or unsafe code.
+struct Slot {
+    // Odd while the emulation thread is writing into this slot
+    sequence: AtomicUsize,
+    registers: [AtomicU8; 8], // A, F, B, C, D, E, H, L
+    sp: AtomicU16,
+    pc: AtomicU16,
+    ly: AtomicU8,
+    ram: Vec<AtomicU8>,
+}
+
+impl Slot {
+    fn new(ram_len: usize) -> Self {
+        Self {
+            sequence: AtomicUsize::new(0),
+            registers: Default::default(),
+            sp: AtomicU16::new(0),
+            pc: AtomicU16::new(0),
+            ly: AtomicU8::new(0),
+            ram: (0..ram_len).map(|_| AtomicU8::new(0)).collect(),
+        }
+    }
+}
+
+/// Double-buffered snapshot that the emulation thread publishes into and a
+/// UI thread reads from without ever pausing emulation.
+///
+/// The writer always fills the slot that is not currently being presented and
+/// then flips the front index. Readers copy the front slot and only retry in
+/// the rare case that the writer lapped them mid-read.
+pub struct SnapshotBuffer {
+    front: AtomicUsize,
+    slots: [Slot; 2],
+    ram_range: RangeInclusive<u16>,
+}
+
+impl SnapshotBuffer {
+    /// Create a buffer that captures registers, LY and the given RAM range
+    pub fn new(ram_range: RangeInclusive<u16>) -> Self {
+        let ram_len = ram_range.clone().count();
+        Self {
+            front: AtomicUsize::new(0),
+            slots: [Slot::new(ram_len), Slot::new(ram_len)],
+            ram_range,
+        }
+    }
+
+    /// Copy the current emulator state into the back buffer and present it.
+    /// Intended to be called from the emulation thread, e.g. once per frame.
+    pub fn publish(&self, gb: &GameBoy) {
+        let back = 1 - self.front.load(Ordering::Acquire);
+        let slot = &self.slots[back];
+
+        slot.sequence.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let registers = &gb.cpu.registers;
+        let values = [
+            registers.a,
+            registers.f.to_u8(),
+            registers.b,
+            registers.c,
+            registers.d,
+            registers.e,
+            registers.h,
+            registers.l,
+        ];
+        for (register, value) in slot.registers.iter().zip(values) {
+            register.store(value, Ordering::Relaxed);
+        }
+        slot.sp.store(gb.cpu.sp, Ordering::Relaxed);
+        slot.pc.store(gb.cpu.pc, Ordering::Relaxed);
+        slot.ly.store(gb.memory.peek(LY_ADDRESS), Ordering::Relaxed);
+        for (byte, address) in slot.ram.iter().zip(self.ram_range.clone()) {
+            byte.store(gb.memory.peek(address), Ordering::Relaxed);
+        }
+
+        slot.sequence.fetch_add(1, Ordering::Release);
+        self.front.store(back, Ordering::Release);
+    }
+
+    /// Read the most recently published snapshot. Safe to call from any thread.
+    pub fn read(&self) -> Snapshot {
+        loop {
+            let slot = &self.slots[self.front.load(Ordering::Acquire)];
+            let before = slot.sequence.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                continue; // Writer lapped us and is filling this slot
+            }
+
+            let registers: Vec<u8> = slot
+                .registers
+                .iter()
+                .map(|register| register.load(Ordering::Relaxed))
+                .collect();
+            let snapshot = Snapshot {
+                a: registers[0],
+                f: registers[1],
+                b: registers[2],
+                c: registers[3],
+                d: registers[4],
+                e: registers[5],
+                h: registers[6],
+                l: registers[7],
+                sp: slot.sp.load(Ordering::Relaxed),
+                pc: slot.pc.load(Ordering::Relaxed),
+                ly: slot.ly.load(Ordering::Relaxed),
+                ram_start: *self.ram_range.start(),
+                ram: slot
+                    .ram
+                    .iter()
+                    .map(|byte| byte.load(Ordering::Relaxed))
+                    .collect(),
+            };
+
+            fence(Ordering::Acquire);
+            if slot.sequence.load(Ordering::Relaxed) == before {
+                return snapshot;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Memory;
+    use crate::ppu::Layers;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn read_before_publish_is_zeroed() {
+        let buffer = SnapshotBuffer::new(0xC000..=0xC003);
+        let snapshot = buffer.read();
+
+        assert_eq!(snapshot.pc, 0);
+        assert_eq!(snapshot.ram_start, 0xC000);
+        assert_eq!(snapshot.ram, vec![0; 4]);
+    }
+
+    #[test]
+    fn publish_captures_registers_ly_and_ram() {
+        let mut gb = GameBoy::new();
+        gb.cpu.registers.a = 0x12;
+        gb.cpu.registers.l = 0x34;
+        gb.cpu.sp = 0xDFF0;
+        gb.cpu.pc = 0x0150;
+        let mut lcd = Memory::new();
+        lcd.data[0xFF40] = 0x91;
+        gb.memory.ppu.tick(456 * 0x90, &lcd, Layers::default()); // LY 0x90
+        gb.memory.write_byte(0xC000, 0xAA);
+        gb.memory.write_byte(0xC001, 0xBB);
+
+        let buffer = SnapshotBuffer::new(0xC000..=0xC001);
+        buffer.publish(&gb);
+        let snapshot = buffer.read();
+
+        assert_eq!(snapshot.a, 0x12);
+        assert_eq!(snapshot.l, 0x34);
+        assert_eq!(snapshot.sp, 0xDFF0);
+        assert_eq!(snapshot.pc, 0x0150);
+        assert_eq!(snapshot.ly, 0x90);
+        assert_eq!(snapshot.ram, vec![0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn later_publish_replaces_earlier() {
+        let mut gb = GameBoy::new();
+        let buffer = SnapshotBuffer::new(0xC000..=0xC000);
+
+        gb.cpu.pc = 0x1000;
+        buffer.publish(&gb);
+        gb.cpu.pc = 0x2000;
+        buffer.publish(&gb);
+
+        assert_eq!(buffer.read().pc, 0x2000);
+    }
+
+    #[test]
+    fn reader_thread_never_sees_torn_snapshot() {
+        let buffer = Arc::new(SnapshotBuffer::new(0xC000..=0xC00F));
+
+        let reader = {
+            let buffer = Arc::clone(&buffer);
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    let snapshot = buffer.read();
+                    // Every publish writes the same value to A and all RAM bytes
+                    assert!(snapshot.ram.iter().all(|&byte| byte == snapshot.a));
+                }
+            })
+        };
+
+        let mut gb = GameBoy::new();
+        for value in 0..=255u8 {
+            gb.cpu.registers.a = value;
+            for address in 0xC000..=0xC00F {
+                gb.memory.write_byte(address, value);
+            }
+            buffer.publish(&gb);
+        }
+
+        reader.join().unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+use crate::GameBoy;
+use crate::cartridge::{self, Cartridge};
+use crate::harness::panic_message;
+use crate::joypad::Buttons;
+use crate::quirks::{Quirk, QuirkAction, QuirkEvent, QuirkPolicy};
+use crate::throttle::Throttle;
+use serde_json::{Value, json};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// PC staying within this many bytes counts as staying put
+const STUCK_PC_RANGE: u16 = 16;
+
+/// How long each ROM is soaked, in emulated frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoakLimits {
+    pub frames: u64,
+    /// Frames with an unchanged picture and PC stuck in one place before a
+    /// ROM counts as hung
+    pub hang_frames: u64,
+    /// Percent of one CPU each ROM may use (see `Throttle`), or all of it
+    pub max_host_cpu: Option<u8>,
+}
+
+/// How a ROM fared under random input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoakOutcome {
+    /// Ran for the whole time
+    Survived,
+    /// The ROM could not be read or has no valid header
+    LoadError(String),
+    /// The emulator panicked, with the panic message
+    Panicked(String),
+    IllegalOpcode(QuirkEvent),
+    /// The CPU went to sleep at this address with nothing to wake it (see
+    /// `GameBoy::asleep`)
+    Halted(u16),
+    /// The picture stopped changing with PC stuck around this address
+    Hung(u16),
+}
+
+impl SoakOutcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SoakOutcome::Survived => "OK",
+            SoakOutcome::LoadError(_) => "ERROR",
+            SoakOutcome::Panicked(_) => "PANIC",
+            SoakOutcome::IllegalOpcode(_) => "ILLEGAL",
+            SoakOutcome::Halted(_) => "HALT",
+            SoakOutcome::Hung(_) => "HANG",
+        }
+    }
+
+    pub fn detail(&self) -> String {
+        match self {
+            SoakOutcome::Survived => String::new(),
+            SoakOutcome::LoadError(message) | SoakOutcome::Panicked(message) => message.clone(),
+            SoakOutcome::IllegalOpcode(event) => event.to_string(),
+            SoakOutcome::Halted(pc) => format!("CPU halted at 0x{pc:04X}"),
+            SoakOutcome::Hung(pc) => format!("stuck around 0x{pc:04X}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoakResult {
+    pub rom: PathBuf,
+    pub outcome: SoakOutcome,
+    /// Frames run before the outcome
+    pub frames: u64,
+}
+
+impl SoakResult {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "rom": self.rom.display().to_string(),
+            "result": self.outcome.name(),
+            "detail": self.outcome.detail(),
+            "frames": self.frames,
+        })
+    }
+}
+
+/// Button presses from a seeded xorshift64, so a soak run can be repeated
+/// exactly. Buttons are held for a few frames at a time, as a player would,
+/// so games see presses as well as releases.
+#[derive(Debug, Clone)]
+pub struct RandomInput {
+    state: u64,
+    buttons: Buttons,
+    /// Frames until the buttons change
+    hold: u8,
+}
+
+impl RandomInput {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            // Forced non-zero so the sequence never sticks
+            state: seed | 1,
+            buttons: Buttons::new(),
+            hold: 0,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// The buttons to hold for the next frame
+    pub fn next_frame(&mut self) -> Buttons {
+        if self.hold == 0 {
+            let [bits, hold, ..] = self.next().to_le_bytes();
+            self.buttons = Buttons::from_bits(bits);
+            self.hold = hold % 16;
+        } else {
+            self.hold -= 1;
+        }
+        self.buttons
+    }
+}
+
+/// Boot `rom` and feed it random input from `seed` for `limits.frames`
+/// frames, stopping at the first panic, illegal opcode, halt or hang.
+/// Returns the outcome and the frames run.
+pub fn soak_rom(rom: Vec<u8>, seed: u64, limits: SoakLimits) -> (SoakOutcome, u64) {
+    let cartridge = match Cartridge::from_rom(rom) {
+        Ok(cartridge) => cartridge,
+        Err(e) => return (SoakOutcome::LoadError(e), 0),
+    };
+    let mut game = GameBoy::new();
+    game.memory.load_cartridge(cartridge);
+    let mut policy = QuirkPolicy::default();
+    policy.set(Quirk::IllegalOpcode, QuirkAction::Trap);
+    game.set_quirk_policy(policy);
+    game.set_throttle(limits.max_host_cpu.map(Throttle::new));
+    game.power_on();
+
+    let mut input = RandomInput::new(seed);
+    let mut picture = 0;
+    let mut stuck_at = game.cpu.pc;
+    let mut still_frames = 0;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        while game.frame_count() < limits.frames {
+            game.set_buttons(input.next_frame());
+            game.finish_frame();
+            if let Some(event) = game.take_quirk_trap() {
+                return Some(SoakOutcome::IllegalOpcode(event));
+            }
+            if game.asleep() {
+                return Some(SoakOutcome::Halted(game.cpu.pc));
+            }
+
+            let hash = cartridge::crc32(game.frame_buffer());
+            if hash == picture && game.cpu.pc.abs_diff(stuck_at) < STUCK_PC_RANGE {
+                still_frames += 1;
+                if still_frames >= limits.hang_frames {
+                    return Some(SoakOutcome::Hung(stuck_at));
+                }
+            } else {
+                picture = hash;
+                stuck_at = game.cpu.pc;
+                still_frames = 0;
+            }
+        }
+        None
+    }));
+    let outcome = match result {
+        Ok(outcome) => outcome.unwrap_or(SoakOutcome::Survived),
+        Err(payload) => SoakOutcome::Panicked(panic_message(&*payload)),
+    };
+    (outcome, game.frame_count())
+}
+
+/// Soak every ROM on `jobs` worker threads, each with the same input
+/// sequence from `seed`. Results come back in `roms` order.
+///
+/// # Panics
+///
+/// Never in practice: emulator panics are caught and reported.
+pub fn soak_all(roms: &[PathBuf], seed: u64, limits: SoakLimits, jobs: usize) -> Vec<SoakResult> {
+    let next = AtomicUsize::new(0);
+    let finished = Mutex::new(Vec::with_capacity(roms.len()));
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| {
+                while let Some(path) = roms.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let (outcome, frames) = match fs::read(path) {
+                        Ok(rom) => soak_rom(rom, seed, limits),
+                        Err(e) => (SoakOutcome::LoadError(e.to_string()), 0),
+                    };
+                    finished.lock().unwrap().push(SoakResult {
+                        rom: path.clone(),
+                        outcome,
+                        frames,
+                    });
+                }
+            });
+        }
+    });
+
+    let mut results = finished.into_inner().unwrap();
+    results.sort_by_key(|result| roms.iter().position(|rom| *rom == result.rom));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::test_rom;
+
+    const LIMITS: SoakLimits = SoakLimits {
+        frames: 30,
+        hang_frames: 10,
+        max_host_cpu: None,
+    };
+
+    #[test]
+    fn random_input_is_repeatable_and_held() {
+        let frames = |seed| {
+            let mut input = RandomInput::new(seed);
+            (0..64).map(|_| input.next_frame()).collect::<Vec<_>>()
+        };
+        assert_eq!(frames(7), frames(7));
+        assert_ne!(frames(7), frames(8));
+        let changes = frames(7)
+            .windows(2)
+            .filter(|pair| pair[0] != pair[1])
+            .count();
+        assert!((1..32).contains(&changes), "{changes} changes in 64 frames");
+    }
+
+    #[test]
+    fn finds_illegal_opcodes_halts_and_hangs() {
+        let (outcome, frames) = soak_rom(test_rom(&[0x00, 0xD3]), 1, LIMITS);
+        assert_eq!(outcome.name(), "ILLEGAL");
+        assert_eq!(outcome.detail(), "Illegal opcode at 0x0101");
+        assert_eq!(frames, 0, "Trapped before the first frame ended");
+
+        let (outcome, _) = soak_rom(test_rom(&[0x76]), 1, LIMITS);
+        assert_eq!(outcome, SoakOutcome::Halted(0x0101));
+
+        // JR -2, with the LCD off so the picture never changes
+        let (outcome, frames) = soak_rom(test_rom(&[0x18, 0xFE]), 1, LIMITS);
+        assert_eq!(outcome, SoakOutcome::Hung(0x0100));
+        assert!(frames <= 12, "{frames}");
+    }
+
+    #[test]
+    fn reports_each_rom_in_order() {
+        let dir = std::env::temp_dir().join(format!("gameboy-soak-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // LD A,1; ADD HL,BC; JR -2
+        fs::write(dir.join("a.gb"), test_rom(&[0x3E, 0x01, 0x09, 0x18, 0xFE])).unwrap();
+        fs::write(dir.join("b.gb"), test_rom(&[0x76])).unwrap();
+        let roms = vec![dir.join("a.gb"), dir.join("b.gb"), dir.join("missing.gb")];
+
+        let results = soak_all(&roms, 1, LIMITS, 2);
+        fs::remove_dir_all(&dir).unwrap();
+        let names: Vec<&str> = results.iter().map(|result| result.outcome.name()).collect();
+        assert_eq!(names, ["HANG", "HALT", "ERROR"]);
+        assert_eq!(results[0].to_json()["detail"], "stuck around 0x0103");
+        assert_eq!(results[1].to_json()["detail"], "CPU halted at 0x0101");
+    }
+}
@@ -0,0 +1,81 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Shortest sleep worth making; smaller debts carry over to the next frame
+const MIN_SLEEP: Duration = Duration::from_millis(2);
+
+/// Holds a headless run to a share of one host CPU by sleeping between
+/// frames, so long batch jobs (soak tests, scripted rollouts) can run on a
+/// shared machine without cgroup tooling. Each stretch of work earns a
+/// proportional rest: at 25%, 1 ms of emulation is followed by 3 ms asleep.
+///
+/// Only for runs that would otherwise go flat out; real-time frontends are
+/// idle most of each frame already.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    /// 1 to 100
+    percent: u8,
+    /// When the current stretch of work began
+    busy_since: Instant,
+    /// Rest earned but not yet taken
+    owed: Duration,
+}
+
+impl Throttle {
+    /// Use at most `percent` of one CPU, taken as 1 to 100
+    pub fn new(percent: u8) -> Self {
+        Self {
+            percent: percent.clamp(1, 100),
+            busy_since: Instant::now(),
+            owed: Duration::ZERO,
+        }
+    }
+
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    /// Rest owed for `busy` time of work
+    pub fn rest_for(&self, busy: Duration) -> Duration {
+        busy * u32::from(100 - self.percent) / u32::from(self.percent)
+    }
+
+    /// Call between frames: sleeps off the work done since the last call
+    /// once the rest owed is worth a sleep
+    pub fn pause(&mut self) {
+        let now = Instant::now();
+        self.owed += self.rest_for(now - self.busy_since);
+        if self.owed >= MIN_SLEEP {
+            thread::sleep(self.owed);
+            // Oversleeping pays ahead
+            self.owed = self.owed.saturating_sub(now.elapsed());
+        }
+        self.busy_since = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rest_is_proportional_to_work() {
+        let busy = Duration::from_millis(10);
+        assert_eq!(Throttle::new(50).rest_for(busy), busy);
+        assert_eq!(Throttle::new(25).rest_for(busy), busy * 3);
+        assert_eq!(Throttle::new(100).rest_for(busy), Duration::ZERO);
+        assert_eq!(Throttle::new(0).percent(), 1);
+        assert_eq!(Throttle::new(200).percent(), 100);
+    }
+
+    #[test]
+    fn pause_sleeps_off_the_work() {
+        let mut throttle = Throttle::new(50);
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(20) {
+            std::hint::spin_loop();
+        }
+        throttle.pause();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::interrupts::Interrupts;
+use crate::memory::Memory;
+
+/// Hardware on the CPU's clock that can be driven on its own: the timer,
+/// PPU, APU and serial port. `GameBoy::step` advances them together after
+/// each instruction; a co-simulation environment (a hardware-in-the-loop
+/// rig, an RTL model of one chip) can instead take them out of `Memory`
+/// and run each from its own master clock.
+///
+/// A driver asks each component for its `next_event`, advances every one
+/// to the earliest, services what happened, and repeats. Advancing further
+/// in one go is allowed, but what happens in between is only seen at the
+/// end: the PPU's finished lines, for one, are overwritten.
+pub trait Tickable {
+    /// Run for `cycles` T-cycles. The PPU draws from `memory` (LCDC, VRAM,
+    /// OAM and the scroll and palette registers); the others ignore it.
+    /// Interrupts are requested in `interrupts`.
+    fn advance(&mut self, cycles: u32, memory: &Memory, interrupts: &mut Interrupts);
+
+    /// T-cycles, at least 1, until the component next does something a
+    /// driver has to see: requests an interrupt, finishes a PPU line or
+    /// clocks the APU's frame sequencer. `None` while only outside input (a
+    /// register write, the link partner) can start anything.
+    fn next_event(&self) -> Option<u32>;
+}
+
+/// `cycles` in pieces of at most `step`, for components whose `tick` only
+/// handles a few cycles at a time
+pub(crate) fn in_steps(cycles: u32, step: u8) -> impl Iterator<Item = u8> {
+    let full = cycles / u32::from(step);
+    let rest = u8::try_from(cycles % u32::from(step)).unwrap_or_default();
+    (0..full)
+        .map(move |_| step)
+        .chain((rest > 0).then_some(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apu::Apu;
+    use crate::interrupts::Interrupt;
+    use crate::ppu::Ppu;
+    use crate::serial::Serial;
+    use crate::timer::Timer;
+
+    #[test]
+    fn splits_cycles_into_steps() {
+        assert_eq!(in_steps(37, 16).collect::<Vec<_>>(), [16, 16, 5]);
+        assert_eq!(in_steps(32, 16).collect::<Vec<_>>(), [16, 16]);
+        assert_eq!(in_steps(0, 16).count(), 0);
+    }
+
+    /// Advance every component to the earliest next event until `done`,
+    /// returning the master clock
+    fn run(
+        components: &mut [&mut dyn Tickable],
+        memory: &Memory,
+        interrupts: &mut Interrupts,
+        done: impl Fn(&Interrupts) -> bool,
+    ) -> u64 {
+        let mut clock = 0;
+        while !done(interrupts) {
+            let cycles = components
+                .iter()
+                .filter_map(|component| component.next_event())
+                .min()
+                .expect("something is scheduled");
+            for component in components.iter_mut() {
+                component.advance(cycles, memory, interrupts);
+            }
+            clock += u64::from(cycles);
+        }
+        clock
+    }
+
+    #[test]
+    fn drives_components_from_an_outside_clock() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xFF40, 0x91); // LCD on
+        let mut timer = Timer::new();
+        timer.write_register(0xFF05, 0xF0);
+        timer.write_register(0xFF07, 0x05); // Every 16 cycles
+        let mut ppu = Ppu::default();
+        let mut apu = Apu::default();
+        let mut serial = Serial::new();
+        assert_eq!(serial.next_event(), None);
+        serial.write_register(0xFF02, 0x81);
+
+        let mut interrupts = Interrupts::default();
+        let mut components: [&mut dyn Tickable; 4] = [&mut timer, &mut ppu, &mut apu, &mut serial];
+        let clock = run(&mut components, &memory, &mut interrupts, |interrupts| {
+            interrupts.is_requested(Interrupt::Timer)
+        });
+        assert_eq!(clock, 16 * 16);
+
+        let clock = run(&mut components, &memory, &mut interrupts, |interrupts| {
+            interrupts.is_requested(Interrupt::Serial)
+        });
+        assert_eq!(clock, 4096 - 16 * 16);
+
+        let clock = run(&mut components, &memory, &mut interrupts, |interrupts| {
+            interrupts.is_requested(Interrupt::VBlank)
+        });
+        assert_eq!(clock, 144 * 456 - 4096);
+        assert_eq!((ppu.ly(), ppu.dot()), (144, 0));
+        assert_eq!(serial.read_register(0xFF01), 0xFF);
+    }
+}
@@ -1,3 +1,8 @@
+use crate::interrupts::{Interrupt, Interrupts};
+use crate::memory::Memory;
+use crate::savestate::{StateReader, StateWriter};
+use crate::tick::{self, Tickable};
+
 pub struct Timer {
     div_counter: u16,
     tima: u8,
@@ -47,6 +52,7 @@ impl Timer {
         interrupted
     }
 
+    /// Read DIV, TIMA, TMA or TAC; any other address reads 0xFF
     pub fn read_register(&self, address: u16) -> u8 {
         // 0xFF04 = DIV, 0xFF05 = TIMA, 0xFF06 = TMA, 0xFF07 = TAC
         match address {
@@ -54,21 +60,49 @@ impl Timer {
             0xFF05 => self.tima,
             0xFF06 => self.tma,
             0xFF07 => self.tac,
-            _ => panic!("Read from none timer register in the timer {address:4x}"),
+            _ => 0xFF,
         }
     }
 
+    /// Write DIV (clearing it), TIMA, TMA or TAC; any other address is
+    /// ignored
     pub fn write_register(&mut self, address: u16, value: u8) {
         // 0xFF04 = DIV, 0xFF05 = TIMA, 0xFF06 = TMA, 0xFF07 = TAC
         match address {
-            0xFF04 => self.div_counter = 0,
+            0xFF04 => self.reset_div(),
             0xFF05 => self.tima = value,
             0xFF06 => self.tma = value,
             0xFF07 => self.tac = value,
-            _ => panic!("Write to none timer register in the timer {address:4x}"),
+            _ => {}
         }
     }
 
+    /// Clear DIV, as a write to 0xFF04 or entering STOP does.
+    ///
+    /// TODO: TIMA is clocked by its own counter rather than by DIV's bits, so
+    /// the extra TIMA tick from a falling edge here (and CGB speed switching,
+    /// once there is a CGB mode) needs DIV and TIMA to share one system counter
+    pub fn reset_div(&mut self) {
+        self.div_counter = 0;
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.div_counter);
+        state.write_u8(self.tima);
+        state.write_u8(self.tma);
+        state.write_u8(self.tac);
+        state.write_u16(self.tima_counter);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.div_counter = state.read_u16()?;
+        self.tima = state.read_u8()?;
+        self.tma = state.read_u8()?;
+        self.tac = state.read_u8()?;
+        self.tima_counter = state.read_u16()?;
+        Ok(())
+    }
+
     fn is_timer_enabled(&self) -> bool {
         self.tac & 4 != 0 // 4 = 0b0000100
     }
@@ -78,9 +112,31 @@ impl Timer {
             0 => 1024,
             1 => 16,
             2 => 64,
-            3 => 256,
-            _ => panic!("Bottom 2 nibbles was greater than 3?"),
+            _ => 256, // 3
+        }
+    }
+}
+
+/// TIMA is clocked at most every 16 cycles, so `tick` runs 16 at a time
+impl Tickable for Timer {
+    fn advance(&mut self, cycles: u32, _memory: &Memory, interrupts: &mut Interrupts) {
+        for step in tick::in_steps(cycles, 16) {
+            if self.tick(step) {
+                interrupts.request(Interrupt::Timer);
+            }
+        }
+    }
+
+    /// Until TIMA overflows; DIV wrapping is no event
+    fn next_event(&self) -> Option<u32> {
+        if !self.is_timer_enabled() {
+            return None;
         }
+        let frequency = u32::from(self.get_tima_frequency());
+        let to_increment = frequency
+            .saturating_sub(u32::from(self.tima_counter))
+            .max(1);
+        Some(to_increment + u32::from(0xFF - self.tima) * frequency)
     }
 }
 
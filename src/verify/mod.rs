@@ -0,0 +1,433 @@
+use crate::cartridge::{self, CartridgeType, crc32};
+use serde_json::json;
+
+pub use crate::cartridge::NINTENDO_LOGO;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    /// Unusual, but hardware (or most emulators) will cope
+    Warn,
+    /// Will not boot or will misbehave on hardware
+    Fail,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+}
+
+/// Result of `verify_rom`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub title: String,
+    pub crc32: u32,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// True if no check failed (warnings are allowed)
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.status != Status::Fail)
+    }
+
+    pub fn check(&self, name: &str) -> Option<&Check> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+
+    /// Machine-readable form for ROM-management scripts
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "title": self.title,
+            "crc32": format!("{:08X}", self.crc32),
+            "passed": self.passed(),
+            "checks": self.checks.iter().map(|check| json!({
+                "name": check.name,
+                "status": check.status.name(),
+                "message": check.message,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Run every header and layout check on a ROM image. Unlike
+/// `CartridgeHeader::from_rom` this never gives up early, so a broken ROM
+/// still gets a full report.
+pub fn verify_rom(rom: &[u8]) -> Report {
+    let mut report = Report {
+        title: String::new(),
+        crc32: crc32(rom),
+        checks: Vec::new(),
+    };
+    if rom.len() < 0x0150 {
+        report.checks.push(Check {
+            name: "header",
+            status: Status::Fail,
+            message: format!("ROM is {} bytes, too small to hold a header", rom.len()),
+        });
+        return report;
+    }
+    // The last title byte doubles as the CGB flag, so stop at the first NUL
+    let title = &rom[0x0134..=0x0143];
+    let end = title
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(title.len());
+    report.title = String::from_utf8_lossy(&title[..end]).into_owned();
+
+    report.checks.extend([
+        check_entry_point(rom),
+        check_logo(rom),
+        check_header_checksum(rom),
+        check_global_checksum(rom),
+        check_rom_size(rom),
+        check_ram_size(rom),
+        check_mapper(rom),
+    ]);
+    report
+}
+
+fn pass(name: &'static str, message: impl Into<String>) -> Check {
+    Check {
+        name,
+        status: Status::Pass,
+        message: message.into(),
+    }
+}
+
+fn warn(name: &'static str, message: impl Into<String>) -> Check {
+    Check {
+        name,
+        status: Status::Warn,
+        message: message.into(),
+    }
+}
+
+fn fail(name: &'static str, message: impl Into<String>) -> Check {
+    Check {
+        name,
+        status: Status::Fail,
+        message: message.into(),
+    }
+}
+
+/// The 4 bytes at 0x0100 should jump over the header into bank 0 or 1
+fn check_entry_point(rom: &[u8]) -> Check {
+    let code = [rom[0x0100], rom[0x0101], rom[0x0102], rom[0x0103]];
+    let target = match code {
+        [0x00, 0xC3, low, high] | [0xC3, low, high, _] => u16::from_le_bytes([low, high]),
+        [0x00, 0x18, offset, _] => 0x0104_u16.wrapping_add_signed(i16::from(offset.cast_signed())),
+        [0x18, offset, _, _] => 0x0102_u16.wrapping_add_signed(i16::from(offset.cast_signed())),
+        [a, b, c, d] => {
+            return warn(
+                "entry_point",
+                format!("Unusual entry point code {a:02X} {b:02X} {c:02X} {d:02X}"),
+            );
+        }
+    };
+    if (0x0104..0x0150).contains(&target) {
+        fail(
+            "entry_point",
+            format!("Entry point jumps into the header (0x{target:04X})"),
+        )
+    } else if target >= 0x8000 {
+        fail(
+            "entry_point",
+            format!("Entry point jumps outside ROM (0x{target:04X})"),
+        )
+    } else {
+        pass("entry_point", format!("Jumps to 0x{target:04X}"))
+    }
+}
+
+fn check_logo(rom: &[u8]) -> Check {
+    if rom[0x0104..0x0134] == NINTENDO_LOGO {
+        pass("logo", "Logo matches")
+    } else {
+        fail("logo", "Logo bytes differ; the boot ROM will lock up")
+    }
+}
+
+fn check_header_checksum(rom: &[u8]) -> Check {
+    let computed = cartridge::header_checksum(rom);
+    let stored = rom[0x014D];
+    if computed == stored {
+        pass("header_checksum", format!("0x{stored:02X}"))
+    } else {
+        fail(
+            "header_checksum",
+            format!("Stored 0x{stored:02X}, computed 0x{computed:02X}; the boot ROM will lock up"),
+        )
+    }
+}
+
+/// Not checked by hardware, but a mismatch usually means a bad dump or a hack
+fn check_global_checksum(rom: &[u8]) -> Check {
+    let stored = u16::from_be_bytes([rom[0x014E], rom[0x014F]]);
+    let computed = cartridge::global_checksum(rom);
+    if computed == stored {
+        pass("global_checksum", format!("0x{stored:04X}"))
+    } else {
+        warn(
+            "global_checksum",
+            format!("Stored 0x{stored:04X}, computed 0x{computed:04X}"),
+        )
+    }
+}
+
+fn header_rom_size(code: u8) -> Option<usize> {
+    (code <= 0x08).then(|| 0x8000 << code)
+}
+
+fn header_ram_size(code: u8) -> Option<usize> {
+    match code {
+        0x00 => Some(0),
+        0x01 => Some(0x800),
+        0x02 => Some(0x2000),
+        0x03 => Some(0x8000),
+        0x04 => Some(0x2_0000),
+        0x05 => Some(0x1_0000),
+        _ => None,
+    }
+}
+
+fn check_rom_size(rom: &[u8]) -> Check {
+    let Some(expected) = header_rom_size(rom[0x0148]) else {
+        return fail(
+            "rom_size",
+            format!("Invalid ROM size code 0x{:02X}", rom[0x0148]),
+        );
+    };
+    match rom.len() {
+        len if len == expected => pass("rom_size", format!("{} KB", len / 1024)),
+        len if len > expected => warn(
+            "rom_size",
+            format!(
+                "File is {} KB but header declares {} KB (overdump?)",
+                len / 1024,
+                expected / 1024
+            ),
+        ),
+        len => fail(
+            "rom_size",
+            format!(
+                "File is {} KB but header declares {} KB (truncated?)",
+                len / 1024,
+                expected / 1024
+            ),
+        ),
+    }
+}
+
+fn check_ram_size(rom: &[u8]) -> Check {
+    match header_ram_size(rom[0x0149]) {
+        Some(0x800) => warn("ram_size", "2 KB RAM code is unused by licensed games"),
+        Some(size) => pass("ram_size", format!("{} KB", size / 1024)),
+        None => fail(
+            "ram_size",
+            format!("Invalid RAM size code 0x{:02X}", rom[0x0149]),
+        ),
+    }
+}
+
+/// Mapper name and the largest ROM / RAM it can address
+fn mapper_limits(cartridge_type: u8) -> Option<(&'static str, usize, usize)> {
+    match cartridge_type {
+        0x00 => Some(("ROM only", 0x8000, 0)),
+        0x08 | 0x09 => Some(("ROM+RAM", 0x8000, 0x2000)),
+        0x01..=0x03 => Some(("MBC1", 0x20_0000, 0x8000)),
+        // MBC2 has 512x4 bits of built-in RAM; the header should declare none
+        0x05 | 0x06 => Some(("MBC2", 0x4_0000, 0)),
+        0x0F..=0x13 => Some(("MBC3", 0x20_0000, 0x8000)),
+        0x19..=0x1E => Some(("MBC5", 0x80_0000, 0x2_0000)),
+        _ => None,
+    }
+}
+
+#[allow(clippy::similar_names)]
+fn check_mapper(rom: &[u8]) -> Check {
+    let code = rom[0x0147];
+    let Some((mapper, max_rom, max_ram)) = mapper_limits(code) else {
+        return warn(
+            "mapper",
+            format!("Cartridge type 0x{code:02X} is not checked"),
+        );
+    };
+    let rom_size = header_rom_size(rom[0x0148]).unwrap_or(rom.len());
+    let ram_size = header_ram_size(rom[0x0149]).unwrap_or(0);
+
+    if rom_size > max_rom {
+        return fail(
+            "mapper",
+            format!(
+                "{mapper} addresses at most {} KB of ROM, header declares {} KB",
+                max_rom / 1024,
+                rom_size / 1024
+            ),
+        );
+    }
+    if ram_size > max_ram {
+        return fail(
+            "mapper",
+            format!(
+                "{mapper} addresses at most {} KB of RAM, header declares {} KB",
+                max_ram / 1024,
+                ram_size / 1024
+            ),
+        );
+    }
+    // MBC1's 2-bit secondary register selects either the upper ROM bank bits
+    // or the RAM bank, so large ROMs and banked RAM cannot be combined
+    if mapper == "MBC1" && rom_size > 0x8_0000 && ram_size > 0x2000 {
+        return fail(
+            "mapper",
+            "MBC1 cannot bank both more than 512 KB of ROM and more than 8 KB of RAM",
+        );
+    }
+    if let CartridgeType::Unknown(_) = CartridgeType::from(code) {
+        return warn(
+            "mapper",
+            format!("{mapper} (0x{code:02X}) is not supported by this emulator yet"),
+        );
+    }
+    pass("mapper", format!("{mapper} (0x{code:02X})"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::fix_checksums;
+
+    /// 32 KB ROM-only image with a valid header and checksums
+    fn valid_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x0134..0x0138].copy_from_slice(b"TEST");
+        fix_checksums(&mut rom);
+        rom
+    }
+
+    #[test]
+    fn valid_rom_passes_every_check() {
+        let report = verify_rom(&valid_rom());
+        assert_eq!(report.title, "TEST");
+        assert!(
+            report
+                .checks
+                .iter()
+                .all(|check| check.status == Status::Pass),
+            "{:?}",
+            report.checks
+        );
+    }
+
+    #[test]
+    fn bad_logo_and_header_checksum_fail() {
+        let mut rom = valid_rom();
+        rom[0x0110] ^= 0xFF;
+        let report = verify_rom(&rom);
+
+        assert!(!report.passed());
+        assert_eq!(report.check("logo").unwrap().status, Status::Fail);
+        assert_eq!(
+            report.check("header_checksum").unwrap().status,
+            Status::Pass
+        );
+
+        rom[0x014D] ^= 0x01;
+        let report = verify_rom(&rom);
+        assert_eq!(
+            report.check("header_checksum").unwrap().status,
+            Status::Fail
+        );
+        assert_eq!(
+            report.check("global_checksum").unwrap().status,
+            Status::Warn
+        );
+    }
+
+    #[test]
+    fn entry_point_into_header_fails() {
+        let mut rom = valid_rom();
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x20, 0x01]);
+        assert_eq!(
+            verify_rom(&rom).check("entry_point").unwrap().status,
+            Status::Fail
+        );
+
+        rom[0x0100..0x0104].copy_from_slice(&[0x18, 0x4E, 0x00, 0x00]); // JR +0x4E
+        assert_eq!(
+            verify_rom(&rom).check("entry_point").unwrap().message,
+            "Jumps to 0x0150"
+        );
+    }
+
+    #[test]
+    fn size_mismatch_is_reported() {
+        let mut rom = valid_rom();
+        rom[0x0148] = 0x01; // Declares 64 KB
+        fix_checksums(&mut rom);
+        assert_eq!(
+            verify_rom(&rom).check("rom_size").unwrap().status,
+            Status::Fail
+        );
+
+        rom.resize(0x1_8000, 0);
+        assert_eq!(
+            verify_rom(&rom).check("rom_size").unwrap().status,
+            Status::Warn
+        );
+    }
+
+    #[test]
+    fn mbc1_size_limits_fail() {
+        let mut rom = valid_rom();
+        rom[0x0147] = 0x03;
+        rom[0x0148] = 0x07; // 4 MB
+        assert_eq!(
+            verify_rom(&rom).check("mapper").unwrap().status,
+            Status::Fail
+        );
+
+        rom[0x0148] = 0x05; // 1 MB with 32 KB RAM
+        rom[0x0149] = 0x03;
+        assert_eq!(
+            verify_rom(&rom).check("mapper").unwrap().status,
+            Status::Fail
+        );
+
+        rom[0x0149] = 0x02;
+        assert_eq!(
+            verify_rom(&rom).check("mapper").unwrap().status,
+            Status::Pass
+        );
+    }
+
+    #[test]
+    fn tiny_rom_fails_without_panicking() {
+        let report = verify_rom(&[0; 0x100]);
+        assert!(!report.passed());
+        assert_eq!(report.checks.len(), 1);
+    }
+
+    #[test]
+    fn json_lists_checks() {
+        let json = verify_rom(&valid_rom()).to_json();
+        assert_eq!(json["passed"], true);
+        assert_eq!(json["checks"][1]["name"], "logo");
+        assert_eq!(json["checks"][1]["status"], "pass");
+    }
+}
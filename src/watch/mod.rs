@@ -0,0 +1,164 @@
+use crate::gameboy::GameBoy;
+use crate::playtime::{emulated_time, format_duration};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Set by the SIGUSR1 handler, cleared by `take_dump_request`
+static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Periodic status lines for long headless runs (soak tests, training), so
+/// they can be followed from a log without attaching a debugger.
+///
+/// The run loop calls `poll` after every frame; once `interval` has passed
+/// it returns a line with the frame count, emulated time, speed relative to
+/// real time since the last line, and the state hash.
+pub struct Watch {
+    interval: Duration,
+    last_report: Instant,
+    last_frame: u64,
+}
+
+impl Watch {
+    pub fn new(interval: Duration, gb: &GameBoy, now: Instant) -> Self {
+        Self {
+            interval,
+            last_report: now,
+            last_frame: gb.frame_count(),
+        }
+    }
+
+    /// The status line, if one is due
+    pub fn poll(&mut self, gb: &GameBoy, now: Instant) -> Option<String> {
+        if now.duration_since(self.last_report) < self.interval {
+            return None;
+        }
+        Some(self.report(gb, now))
+    }
+
+    /// The status line now, starting a new interval
+    pub fn report(&mut self, gb: &GameBoy, now: Instant) -> String {
+        let frames = gb.frame_count();
+        let emulated = emulated_time(frames - self.last_frame);
+        let wall_clock = now.duration_since(self.last_report);
+        let speed = if wall_clock.is_zero() {
+            0.0
+        } else {
+            emulated.as_secs_f64() / wall_clock.as_secs_f64()
+        };
+        self.last_report = now;
+        self.last_frame = frames;
+        status_line(gb, speed)
+    }
+}
+
+/// One status line: frames, emulated time, `speed` as a multiple of real
+/// time, and the state hash
+pub fn status_line(gb: &GameBoy, speed: f64) -> String {
+    let frames = gb.frame_count();
+    format!(
+        "frame {frames}  emulated {}  speed {speed:.1}x  state {:08X}",
+        format_duration(emulated_time(frames)),
+        gb.state_hash()
+    )
+}
+
+/// Make SIGUSR1 request a state dump (see `take_dump_request`) instead of
+/// ending the process. Only Unix has the signal.
+#[cfg(unix)]
+pub fn install_dump_signal() -> io::Result<()> {
+    extern "C" fn on_sigusr1(_: libc::c_int) {
+        DUMP_REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    let handler = on_sigusr1 as extern "C" fn(libc::c_int);
+    // The handler only stores to an atomic, which is async-signal-safe
+    let previous = unsafe { libc::signal(libc::SIGUSR1, handler as libc::sighandler_t) };
+    if previous == libc::SIG_ERR {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install_dump_signal() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SIGUSR1 state dumps need a Unix system",
+    ))
+}
+
+/// Whether a dump was requested since the last call
+pub fn take_dump_request() -> bool {
+    DUMP_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Save the state to `dump-frameN.state` in `dir`, returning the path
+pub fn dump_state(gb: &GameBoy, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("dump-frame{}.state", gb.frame_count()));
+    fs::write(&path, gb.save_state().as_bytes())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::savestate::SaveState;
+
+    #[test]
+    fn reports_once_per_interval_with_speed() {
+        let mut gb = GameBoy::new();
+        let start = Instant::now();
+        let mut watch = Watch::new(Duration::from_secs(10), &gb, start);
+        for _ in 0..120 {
+            gb.finish_frame();
+        }
+
+        assert_eq!(watch.poll(&gb, start + Duration::from_secs(9)), None);
+        let line = watch.poll(&gb, start + Duration::from_secs(10)).unwrap();
+        // 120 frames are just over two seconds of emulated time
+        assert!(line.starts_with("frame 120  emulated 0:00:02  speed 0.2x  state "));
+        assert!(line.ends_with(&format!("{:08X}", gb.state_hash())));
+        assert_eq!(watch.poll(&gb, start + Duration::from_secs(15)), None);
+    }
+
+    #[test]
+    fn state_hash_follows_the_machine_not_the_clock() {
+        let mut gb = GameBoy::new();
+        let hash = gb.state_hash();
+        assert_eq!(gb.state_hash(), hash);
+        gb.finish_frame();
+        assert_ne!(gb.state_hash(), hash);
+
+        let mut other = GameBoy::new();
+        other.finish_frame();
+        assert_eq!(other.state_hash(), gb.state_hash());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sigusr1_requests_a_dump() {
+        install_dump_signal().unwrap();
+        assert!(!take_dump_request());
+        unsafe { libc::raise(libc::SIGUSR1) };
+        assert!(take_dump_request());
+        assert!(!take_dump_request(), "Taken once");
+    }
+
+    #[test]
+    fn dumps_a_loadable_state_named_by_frame() {
+        let dir = std::env::temp_dir().join(format!("gameboy-watch-{}", std::process::id()));
+        let mut gb = GameBoy::new();
+        gb.finish_frame();
+
+        let path = dump_state(&gb, &dir).unwrap();
+        let state = SaveState::from_bytes(fs::read(&path).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(path, dir.join("dump-frame1.state"));
+        assert_eq!(state.unwrap().metadata().unwrap().frames, 1);
+    }
+}